@@ -178,7 +178,9 @@ fn get_progress_bar_text(terminal_width: u16, pos: usize, total: usize, pb_style
     text
 }
 
-fn get_bytes_text(byte_count: usize, total_bytes: usize) -> String {
+/// Gets the byte count as kilobytes or megabytes (ex. "1.52KB") depending on
+/// the total bytes, so the text is in the same unit while the count increases.
+pub fn get_bytes_text(byte_count: usize, total_bytes: usize) -> String {
     let bytes_to_kb = 1_000;
     let bytes_to_mb = 1_000_000;
     return if total_bytes < bytes_to_mb {
@@ -0,0 +1,111 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+type CancelledCallback = Box<dyn FnOnce() + Send>;
+
+/// Signals that the result of a request is no longer needed.
+///
+/// Clones share the same state, so cancelling one cancels them all.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<CancellationTokenState>);
+
+#[derive(Default)]
+struct CancellationTokenState {
+    is_cancelled: AtomicBool,
+    next_callback_id: AtomicUsize,
+    callbacks: Mutex<Vec<(usize, CancelledCallback)>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Cancels the request.
+    pub fn cancel(&self) {
+        // the callbacks are run while holding the lock so none run after their guard is dropped
+        let mut callbacks = self.0.callbacks.lock().unwrap();
+        self.0.is_cancelled.store(true, Ordering::SeqCst);
+        for (_, callback) in callbacks.drain(..) {
+            callback();
+        }
+    }
+
+    /// Gets if the request was cancelled.
+    ///
+    /// Long running work should check this periodically and stop early when it returns true.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Runs the callback on the cancelling thread when the request is cancelled or immediately
+    /// when it already was. The callback is removed when the returned guard is dropped.
+    ///
+    /// This is useful for interrupting work that can't poll the token. The callback should
+    /// be quick and must not use the token.
+    pub fn on_cancelled(&self, callback: impl FnOnce() + Send + 'static) -> CancelledCallbackGuard {
+        let id = self.0.next_callback_id.fetch_add(1, Ordering::SeqCst);
+        {
+            let mut callbacks = self.0.callbacks.lock().unwrap();
+            if !self.is_cancelled() {
+                callbacks.push((id, Box::new(callback)));
+                return CancelledCallbackGuard { token: self.clone(), id };
+            }
+        }
+        callback();
+        CancelledCallbackGuard { token: self.clone(), id }
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken").field("is_cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+/// Removes a callback added with `CancellationToken::on_cancelled` when dropped.
+pub struct CancelledCallbackGuard {
+    token: CancellationToken,
+    id: usize,
+}
+
+impl Drop for CancelledCallbackGuard {
+    fn drop(&mut self) {
+        let mut callbacks = self.token.0.callbacks.lock().unwrap();
+        callbacks.retain(|(id, _)| *id != self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_cancel_clones() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert_eq!(clone.is_cancelled(), false);
+        token.cancel();
+        assert_eq!(clone.is_cancelled(), true);
+        assert_eq!(CancellationToken::new().is_cancelled(), false);
+    }
+
+    #[test]
+    fn it_should_run_callbacks_when_cancelled() {
+        let token = CancellationToken::new();
+        let count = Arc::new(AtomicUsize::new(0));
+        let create_callback = || {
+            let count = count.clone();
+            move || { count.fetch_add(1, Ordering::SeqCst); }
+        };
+        let _guard = token.on_cancelled(create_callback());
+        drop(token.on_cancelled(create_callback()));
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        token.clone().cancel();
+        // only the callback whose guard wasn't dropped runs
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        // runs immediately once cancelled
+        let _guard = token.on_cancelled(create_callback());
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+}
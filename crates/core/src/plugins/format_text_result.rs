@@ -0,0 +1,56 @@
+use serde::{Serialize, Deserialize};
+
+/// The result of formatting a file's text.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatTextResult {
+    /// The formatted text.
+    pub text: String,
+    /// Problems found while formatting that should be surfaced to the user.
+    #[serde(default = "Vec::new")]
+    pub diagnostics: Vec<FormatDiagnostic>,
+}
+
+/// A problem found by a plugin while formatting a file.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatDiagnostic {
+    /// How severe the problem is.
+    pub severity: FormatDiagnosticSeverity,
+    /// The message to display to the user.
+    pub message: String,
+    /// The byte range in the original file text the problem occurred at.
+    #[serde(default)]
+    pub range: Option<FormatDiagnosticRange>,
+}
+
+/// The byte range of a format diagnostic.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatDiagnosticRange {
+    /// The start byte index.
+    pub start: usize,
+    /// The end byte index.
+    pub end: usize,
+}
+
+/// The severity of a format diagnostic.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum FormatDiagnosticSeverity {
+    /// The CLI will report the file as having an error.
+    Error,
+    Warning,
+    Info,
+}
+
+impl FormatDiagnosticSeverity {
+    /// Gets the display text of the severity (ex. "Warning").
+    pub fn as_display_str(&self) -> &'static str {
+        match self {
+            FormatDiagnosticSeverity::Error => "Error",
+            FormatDiagnosticSeverity::Warning => "Warning",
+            FormatDiagnosticSeverity::Info => "Info",
+        }
+    }
+}
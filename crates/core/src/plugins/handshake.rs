@@ -0,0 +1,105 @@
+use serde::{Serialize, Deserialize};
+
+/// Information the host provides to a plugin when it starts communicating with it.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HostInfo {
+    /// The version of the CLI (ex. "0.15.0").
+    pub cli_version: String,
+    /// The optional features the host supports.
+    pub capabilities: PluginCapabilities,
+}
+
+/// The plugin's response to the host's information.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginHandshake {
+    /// The optional features both the host and plugin support.
+    pub capabilities: PluginCapabilities,
+    /// The minimum version of the CLI the plugin requires (ex. "0.15.0").
+    #[serde(default)]
+    pub minimum_cli_version: Option<String>,
+}
+
+/// Optional features of the plugin system.
+///
+/// New features should be added here instead of incrementing the plugin schema
+/// version. Unknown properties are ignored when deserializing, so older hosts and
+/// plugins will treat a new feature as unsupported.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginCapabilities {
+    /// Whether format diagnostics may be sent along with the formatted text.
+    #[serde(default)]
+    pub diagnostics: bool,
+    /// Whether process plugin messages may be sent with ids after the handshake
+    /// so that many requests can be in flight at once.
+    #[serde(default)]
+    pub multiplexed: bool,
+    /// Whether process plugins may be sent messages to cancel in flight format requests.
+    ///
+    /// Requires the multiplexed capability.
+    #[serde(default)]
+    pub cancellation: bool,
+    /// Whether Wasm plugins may send text to the host in a single call by pointer and
+    /// length instead of in chunks through the plugin's memory buffer.
+    #[serde(default)]
+    pub direct_transfer: bool,
+    /// Whether plugins may provide the language id or media type of the text
+    /// they ask the host to format along with the file path.
+    #[serde(default)]
+    pub host_format_language: bool,
+}
+
+impl PluginCapabilities {
+    /// The capabilities when nothing is supported. This is what's used when
+    /// communicating with a host or plugin that doesn't do a handshake.
+    pub const NONE: PluginCapabilities = PluginCapabilities {
+        diagnostics: false,
+        multiplexed: false,
+        cancellation: false,
+        direct_transfer: false,
+        host_format_language: false,
+    };
+
+    /// The capabilities this version of dprint-core supports for all plugins.
+    ///
+    /// The multiplexed and cancellation capabilities are opted into by process plugins that use
+    /// `handle_process_stdio_messages_concurrently`. The direct transfer capability is
+    /// only used by Wasm plugins.
+    pub const SUPPORTED: PluginCapabilities = PluginCapabilities {
+        diagnostics: true,
+        multiplexed: false,
+        cancellation: false,
+        direct_transfer: false,
+        host_format_language: true,
+    };
+
+    /// Gets the capabilities supported by both.
+    pub fn intersect(&self, other: &PluginCapabilities) -> PluginCapabilities {
+        PluginCapabilities {
+            diagnostics: self.diagnostics && other.diagnostics,
+            multiplexed: self.multiplexed && other.multiplexed,
+            cancellation: self.cancellation && other.cancellation,
+            direct_transfer: self.direct_transfer && other.direct_transfer,
+            host_format_language: self.host_format_language && other.host_format_language,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_intersect_capabilities() {
+        assert_eq!(PluginCapabilities::SUPPORTED.intersect(&PluginCapabilities::NONE), PluginCapabilities::NONE);
+        assert_eq!(PluginCapabilities::SUPPORTED.intersect(&PluginCapabilities::SUPPORTED), PluginCapabilities::SUPPORTED);
+    }
+
+    #[test]
+    fn it_should_treat_unknown_and_missing_capabilities_as_unsupported() {
+        let capabilities: PluginCapabilities = serde_json::from_str(r#"{ "someFutureCapability": true }"#).unwrap();
+        assert_eq!(capabilities, PluginCapabilities::NONE);
+    }
+}
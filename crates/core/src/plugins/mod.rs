@@ -1,10 +1,22 @@
 mod plugin_info;
 mod plugin_handler;
+mod format_text_result;
+mod handshake;
+mod cancellation_token;
+#[cfg(any(feature = "process", feature = "native"))]
+mod plugin_config_state;
 
 #[cfg(feature = "process")]
 pub mod process;
 #[cfg(feature = "wasm")]
 pub mod wasm;
+#[cfg(feature = "native")]
+pub mod native;
 
 pub use plugin_info::*;
 pub use plugin_handler::*;
+pub use format_text_result::*;
+pub use handshake::*;
+pub use cancellation_token::*;
+#[cfg(any(feature = "process", feature = "native"))]
+pub(crate) use plugin_config_state::*;
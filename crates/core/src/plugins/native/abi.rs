@@ -0,0 +1,188 @@
+use std::ffi::c_void;
+
+/// The version of the native plugin C ABI.
+///
+/// This is incremented when there are any breaking changes to the exported
+/// functions or the types below. Optional features should be added as plugin
+/// capabilities instead.
+pub const NATIVE_PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Names of the functions a native plugin exports.
+pub mod export_names {
+    pub const ABI_VERSION: &[u8] = b"dprint_native_plugin_abi_version";
+    pub const CREATE: &[u8] = b"dprint_native_plugin_create";
+    pub const DESTROY: &[u8] = b"dprint_native_plugin_destroy";
+    pub const FREE_BUFFER: &[u8] = b"dprint_native_plugin_free_buffer";
+    pub const HANDSHAKE: &[u8] = b"dprint_native_plugin_handshake";
+    pub const GET_PLUGIN_INFO: &[u8] = b"dprint_native_plugin_get_plugin_info";
+    pub const GET_LICENSE_TEXT: &[u8] = b"dprint_native_plugin_get_license_text";
+    pub const SET_GLOBAL_CONFIG: &[u8] = b"dprint_native_plugin_set_global_config";
+    pub const SET_PLUGIN_CONFIG: &[u8] = b"dprint_native_plugin_set_plugin_config";
+    pub const GET_RESOLVED_CONFIG: &[u8] = b"dprint_native_plugin_get_resolved_config";
+    pub const GET_CONFIG_DIAGNOSTICS: &[u8] = b"dprint_native_plugin_get_config_diagnostics";
+    pub const FORMAT_TEXT: &[u8] = b"dprint_native_plugin_format_text";
+}
+
+/// Bytes borrowed from the caller for the duration of a call.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct NativeSlice {
+    pub data: *const u8,
+    pub len: usize,
+}
+
+impl NativeSlice {
+    pub fn new(bytes: &[u8]) -> NativeSlice {
+        NativeSlice {
+            data: bytes.as_ptr(),
+            len: bytes.len(),
+        }
+    }
+
+    /// Gets the bytes of the slice.
+    ///
+    /// # Safety
+    ///
+    /// The data must be valid for the provided lifetime.
+    pub unsafe fn as_bytes<'a>(&self) -> &'a [u8] {
+        if self.data.is_null() || self.len == 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(self.data, self.len)
+        }
+    }
+}
+
+/// Bytes owned by the side that allocated them.
+///
+/// The receiver copies the bytes, then gives the buffer back to be freed by
+/// the allocating side (ex. using the plugin's `dprint_native_plugin_free_buffer`).
+#[repr(C)]
+pub struct NativeBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+    pub capacity: usize,
+}
+
+impl NativeBuffer {
+    pub fn empty() -> NativeBuffer {
+        NativeBuffer::from_vec(Vec::new())
+    }
+
+    pub fn from_vec(bytes: Vec<u8>) -> NativeBuffer {
+        let mut bytes = std::mem::ManuallyDrop::new(bytes);
+        NativeBuffer {
+            data: bytes.as_mut_ptr(),
+            len: bytes.len(),
+            capacity: bytes.capacity(),
+        }
+    }
+
+    /// Gets the bytes of the buffer.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must not have been freed.
+    pub unsafe fn as_bytes(&self) -> &[u8] {
+        if self.data.is_null() || self.len == 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(self.data, self.len)
+        }
+    }
+
+    /// Takes ownership of the buffer's bytes.
+    ///
+    /// # Safety
+    ///
+    /// Only call this on the side that created the buffer.
+    pub unsafe fn into_vec(self) -> Vec<u8> {
+        Vec::from_raw_parts(self.data, self.len, self.capacity)
+    }
+}
+
+/// Functions the host provides to the plugin while formatting.
+#[repr(C)]
+pub struct NativeHost {
+    pub context: *mut c_void,
+    /// Formats the text using another plugin. Returns a `NativeFormatResult`.
+    ///
+    /// The output is the formatted text or error message and must be freed with `free_buffer`.
+    /// The override config is JSON or empty for no override.
+    pub format: unsafe extern "C" fn(
+        context: *mut c_void,
+        file_path: NativeSlice,
+        file_text: NativeSlice,
+        override_config: NativeSlice,
+        output: *mut NativeBuffer,
+    ) -> u32,
+    /// Frees a buffer allocated by the host.
+    pub free_buffer: unsafe extern "C" fn(buffer: NativeBuffer),
+    /// Same as `format`, but with the language id or media type of the text, which is empty when not known.
+    ///
+    /// This is only provided by hosts that negotiated the host format language capability.
+    pub format_with_language: unsafe extern "C" fn(
+        context: *mut c_void,
+        file_path: NativeSlice,
+        language: NativeSlice,
+        file_text: NativeSlice,
+        override_config: NativeSlice,
+        output: *mut NativeBuffer,
+    ) -> u32,
+}
+
+/// The result of a native plugin call other than formatting.
+#[derive(Debug)]
+pub enum NativeResult {
+    /// The output has the JSON or text result.
+    Success = 0,
+    /// The output has the error message.
+    Error = 1,
+}
+
+// todo: generate with a macro
+impl From<u32> for NativeResult {
+    fn from(orig: u32) -> Self {
+        match orig {
+            0 => NativeResult::Success,
+            1 => NativeResult::Error,
+            _ => unreachable!("Unexpected native result: {}", orig),
+        }
+    }
+}
+
+/// The result of formatting with a native plugin or the host.
+#[derive(Debug)]
+pub enum NativeFormatResult {
+    NoChange = 0,
+    /// The output has the formatted text.
+    Change = 1,
+    /// The output has the error message.
+    Error = 2,
+}
+
+// todo: generate with a macro
+impl From<u32> for NativeFormatResult {
+    fn from(orig: u32) -> Self {
+        match orig {
+            0 => NativeFormatResult::NoChange,
+            1 => NativeFormatResult::Change,
+            2 => NativeFormatResult::Error,
+            _ => unreachable!("Unexpected native format result: {}", orig),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_roundtrip_buffers() {
+        let buffer = NativeBuffer::from_vec(b"testing".to_vec());
+        assert_eq!(unsafe { buffer.as_bytes() }, b"testing");
+        assert_eq!(unsafe { buffer.into_vec() }, b"testing".to_vec());
+        assert_eq!(unsafe { NativeBuffer::empty().as_bytes() }.len(), 0);
+        assert_eq!(unsafe { NativeSlice::new(&[]).as_bytes() }.len(), 0);
+    }
+}
@@ -0,0 +1,204 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+
+use crate::configuration::ConfigKeyMap;
+use crate::types::ErrBox;
+use crate::plugins::{PluginHandler, HostInfo, PluginHandshake, PluginCapabilities, FormatTextResult, PluginConfigState};
+use super::{NativeSlice, NativeBuffer, NativeHost, NativeResult, NativeFormatResult};
+
+/// The state of a plugin created by the host through the native plugin C ABI.
+///
+/// This is what the functions generated by `generate_native_plugin_code!` forward to.
+pub struct NativePluginInstance<THandler: PluginHandler<TConfiguration>, TConfiguration: Clone + Serialize> {
+    handler: THandler,
+    config_state: PluginConfigState<TConfiguration>,
+    capabilities: PluginCapabilities,
+}
+
+impl<THandler: PluginHandler<TConfiguration>, TConfiguration: Clone + Serialize> NativePluginInstance<THandler, TConfiguration> {
+    pub fn new(handler: THandler) -> Self {
+        NativePluginInstance {
+            handler,
+            config_state: PluginConfigState::new(),
+            capabilities: PluginCapabilities::NONE,
+        }
+    }
+
+    pub fn handshake(&mut self, host_info: &[u8]) -> Result<Vec<u8>, ErrBox> {
+        let host_info: HostInfo = serde_json::from_slice(host_info)?;
+        self.capabilities = PluginCapabilities::SUPPORTED.intersect(&host_info.capabilities);
+        let handshake = PluginHandshake {
+            capabilities: self.capabilities,
+            minimum_cli_version: self.handler.get_minimum_cli_version(),
+        };
+        Ok(serde_json::to_vec(&handshake)?)
+    }
+
+    pub fn get_plugin_info(&mut self) -> Result<Vec<u8>, ErrBox> {
+        Ok(serde_json::to_vec(&self.handler.get_plugin_info())?)
+    }
+
+    pub fn get_license_text(&mut self) -> Result<Vec<u8>, ErrBox> {
+        Ok(self.handler.get_license_text().into_bytes())
+    }
+
+    pub fn set_global_config(&mut self, global_config: &[u8]) -> Result<Vec<u8>, ErrBox> {
+        self.config_state.set_global_config(serde_json::from_slice(global_config)?);
+        Ok(Vec::new())
+    }
+
+    pub fn set_plugin_config(&mut self, plugin_config: &[u8]) -> Result<Vec<u8>, ErrBox> {
+        self.config_state.set_plugin_config(serde_json::from_slice(plugin_config)?);
+        Ok(Vec::new())
+    }
+
+    pub fn get_resolved_config(&mut self) -> Result<Vec<u8>, ErrBox> {
+        self.config_state.get_resolved_config_bytes(&mut self.handler)
+    }
+
+    pub fn get_config_diagnostics(&mut self) -> Result<Vec<u8>, ErrBox> {
+        self.config_state.get_config_diagnostics_bytes(&mut self.handler)
+    }
+
+    /// Formats the text, using the provided host to format embedded text with other plugins.
+    pub fn format_text(&mut self, file_path: &Path, file_text: &str, override_config: &[u8], host: &NativeHost) -> Result<FormatTextResult, ErrBox> {
+        let override_config: ConfigKeyMap = if override_config.is_empty() {
+            ConfigKeyMap::new()
+        } else {
+            serde_json::from_slice(override_config)?
+        };
+        let config = self.config_state.get_format_config(&mut self.handler, override_config)?;
+        let capabilities = self.capabilities;
+        let mut result = self.handler.format_text_with_diagnostics(
+            file_path,
+            file_text,
+            &config,
+            |file_path, language, file_text, override_config| format_with_host(host, &capabilities, file_path, language, file_text, override_config),
+        )?;
+
+        // only provide diagnostics to hosts that said they support them
+        if !self.capabilities.diagnostics {
+            result.diagnostics.clear();
+        }
+
+        Ok(result)
+    }
+}
+
+fn format_with_host(
+    host: &NativeHost,
+    capabilities: &PluginCapabilities,
+    file_path: &Path,
+    language: Option<&str>,
+    file_text: String,
+    override_config: &ConfigKeyMap,
+) -> Result<String, ErrBox> {
+    let file_path = file_path.to_string_lossy();
+    let override_config = if override_config.is_empty() {
+        Vec::new()
+    } else {
+        serde_json::to_vec(override_config)?
+    };
+    let mut output = NativeBuffer::empty();
+    let result = unsafe {
+        // hosts that don't support the language don't provide this function
+        if capabilities.host_format_language {
+            (host.format_with_language)(
+                host.context,
+                NativeSlice::new(file_path.as_bytes()),
+                NativeSlice::new(language.unwrap_or("").as_bytes()),
+                NativeSlice::new(file_text.as_bytes()),
+                NativeSlice::new(&override_config),
+                &mut output,
+            )
+        } else {
+            (host.format)(
+                host.context,
+                NativeSlice::new(file_path.as_bytes()),
+                NativeSlice::new(file_text.as_bytes()),
+                NativeSlice::new(&override_config),
+                &mut output,
+            )
+        }
+    };
+    // the host allocated the output, so copy it then give it back to be freed
+    let output_text = String::from_utf8_lossy(unsafe { output.as_bytes() }).to_string();
+    unsafe { (host.free_buffer)(output) };
+
+    match result.into() {
+        NativeFormatResult::NoChange => Ok(file_text),
+        NativeFormatResult::Change => Ok(output_text),
+        NativeFormatResult::Error => err!("{}", output_text),
+    }
+}
+
+/// Runs a native plugin call, writing its result or error message to the output.
+///
+/// Panics are caught because unwinding across the C ABI is undefined behaviour.
+///
+/// # Safety
+///
+/// The output must be writable.
+pub unsafe fn run_native_plugin_call(output: *mut NativeBuffer, action: impl FnOnce() -> Result<Vec<u8>, ErrBox>) -> u32 {
+    let (result, bytes) = match catch_unwind(AssertUnwindSafe(action)) {
+        Ok(Ok(bytes)) => (NativeResult::Success, bytes),
+        Ok(Err(err)) => (NativeResult::Error, err.to_string().into_bytes()),
+        Err(panic) => (NativeResult::Error, get_panic_message(panic).into_bytes()),
+    };
+    output.write(NativeBuffer::from_vec(bytes));
+    result as u32
+}
+
+/// Formats with the native plugin instance, writing the formatted text or error message to the
+/// output and any diagnostics as JSON to the diagnostics output.
+///
+/// # Safety
+///
+/// The slices must be valid for the duration of the call and the outputs must be writable.
+pub unsafe fn run_native_plugin_format<THandler: PluginHandler<TConfiguration>, TConfiguration: Clone + Serialize>(
+    instance: &mut NativePluginInstance<THandler, TConfiguration>,
+    file_path: NativeSlice,
+    file_text: NativeSlice,
+    override_config: NativeSlice,
+    host: &NativeHost,
+    output: *mut NativeBuffer,
+    diagnostics_output: *mut NativeBuffer,
+) -> u32 {
+    let result = catch_unwind(AssertUnwindSafe(|| -> Result<(String, FormatTextResult), ErrBox> {
+        let file_path = PathBuf::from(String::from_utf8(file_path.as_bytes().to_vec())?);
+        let file_text = String::from_utf8(file_text.as_bytes().to_vec())?;
+        let result = instance.format_text(&file_path, &file_text, override_config.as_bytes(), host)?;
+        Ok((file_text, result))
+    }));
+    let (result, bytes, diagnostics) = match result {
+        Ok(Ok((file_text, result))) => {
+            let diagnostics = if result.diagnostics.is_empty() {
+                Vec::new()
+            } else {
+                serde_json::to_vec(&result.diagnostics).unwrap()
+            };
+            if result.text == file_text {
+                (NativeFormatResult::NoChange, Vec::new(), diagnostics)
+            } else {
+                (NativeFormatResult::Change, result.text.into_bytes(), diagnostics)
+            }
+        }
+        Ok(Err(err)) => (NativeFormatResult::Error, err.to_string().into_bytes(), Vec::new()),
+        Err(panic) => (NativeFormatResult::Error, get_panic_message(panic).into_bytes(), Vec::new()),
+    };
+    output.write(NativeBuffer::from_vec(bytes));
+    diagnostics_output.write(NativeBuffer::from_vec(diagnostics));
+    result as u32
+}
+
+fn get_panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    let message = if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic.".to_string()
+    };
+    format!("The plugin panicked: {}", message)
+}
@@ -0,0 +1,125 @@
+/// Exports the native plugin C ABI for a `PluginHandler`.
+///
+/// Provide the handler type, its configuration type, and an expression that creates
+/// a handler. The host creates one instance per thread it formats on.
+///
+/// ```ignore
+/// dprint_core::generate_native_plugin_code!(MyPluginHandler, Configuration, MyPluginHandler::new());
+/// ```
+#[macro_export]
+macro_rules! generate_native_plugin_code {
+    ($handler:ty, $configuration:ty, $handler_creation:expr) => {
+        type DprintNativePluginInstance = dprint_core::plugins::native::NativePluginInstance<$handler, $configuration>;
+
+        #[no_mangle]
+        pub extern "C" fn dprint_native_plugin_abi_version() -> u32 {
+            dprint_core::plugins::native::NATIVE_PLUGIN_ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn dprint_native_plugin_create() -> *mut std::ffi::c_void {
+            let instance: DprintNativePluginInstance = dprint_core::plugins::native::NativePluginInstance::new($handler_creation);
+            Box::into_raw(Box::new(instance)) as *mut std::ffi::c_void
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_destroy(instance: *mut std::ffi::c_void) {
+            if !instance.is_null() {
+                drop(Box::from_raw(instance as *mut DprintNativePluginInstance));
+            }
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_free_buffer(buffer: dprint_core::plugins::native::NativeBuffer) {
+            drop(buffer.into_vec());
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_handshake(
+            instance: *mut std::ffi::c_void,
+            host_info: dprint_core::plugins::native::NativeSlice,
+            output: *mut dprint_core::plugins::native::NativeBuffer,
+        ) -> u32 {
+            let instance = &mut *(instance as *mut DprintNativePluginInstance);
+            dprint_core::plugins::native::run_native_plugin_call(output, || instance.handshake(host_info.as_bytes()))
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_get_plugin_info(
+            instance: *mut std::ffi::c_void,
+            output: *mut dprint_core::plugins::native::NativeBuffer,
+        ) -> u32 {
+            let instance = &mut *(instance as *mut DprintNativePluginInstance);
+            dprint_core::plugins::native::run_native_plugin_call(output, || instance.get_plugin_info())
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_get_license_text(
+            instance: *mut std::ffi::c_void,
+            output: *mut dprint_core::plugins::native::NativeBuffer,
+        ) -> u32 {
+            let instance = &mut *(instance as *mut DprintNativePluginInstance);
+            dprint_core::plugins::native::run_native_plugin_call(output, || instance.get_license_text())
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_set_global_config(
+            instance: *mut std::ffi::c_void,
+            global_config: dprint_core::plugins::native::NativeSlice,
+            output: *mut dprint_core::plugins::native::NativeBuffer,
+        ) -> u32 {
+            let instance = &mut *(instance as *mut DprintNativePluginInstance);
+            dprint_core::plugins::native::run_native_plugin_call(output, || instance.set_global_config(global_config.as_bytes()))
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_set_plugin_config(
+            instance: *mut std::ffi::c_void,
+            plugin_config: dprint_core::plugins::native::NativeSlice,
+            output: *mut dprint_core::plugins::native::NativeBuffer,
+        ) -> u32 {
+            let instance = &mut *(instance as *mut DprintNativePluginInstance);
+            dprint_core::plugins::native::run_native_plugin_call(output, || instance.set_plugin_config(plugin_config.as_bytes()))
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_get_resolved_config(
+            instance: *mut std::ffi::c_void,
+            output: *mut dprint_core::plugins::native::NativeBuffer,
+        ) -> u32 {
+            let instance = &mut *(instance as *mut DprintNativePluginInstance);
+            dprint_core::plugins::native::run_native_plugin_call(output, || instance.get_resolved_config())
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_get_config_diagnostics(
+            instance: *mut std::ffi::c_void,
+            output: *mut dprint_core::plugins::native::NativeBuffer,
+        ) -> u32 {
+            let instance = &mut *(instance as *mut DprintNativePluginInstance);
+            dprint_core::plugins::native::run_native_plugin_call(output, || instance.get_config_diagnostics())
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn dprint_native_plugin_format_text(
+            instance: *mut std::ffi::c_void,
+            file_path: dprint_core::plugins::native::NativeSlice,
+            file_text: dprint_core::plugins::native::NativeSlice,
+            override_config: dprint_core::plugins::native::NativeSlice,
+            host: *const dprint_core::plugins::native::NativeHost,
+            output: *mut dprint_core::plugins::native::NativeBuffer,
+            diagnostics_output: *mut dprint_core::plugins::native::NativeBuffer,
+        ) -> u32 {
+            let instance = &mut *(instance as *mut DprintNativePluginInstance);
+            dprint_core::plugins::native::run_native_plugin_format(
+                instance,
+                file_path,
+                file_text,
+                override_config,
+                &*host,
+                output,
+                diagnostics_output,
+            )
+        }
+    }
+}
@@ -0,0 +1,6 @@
+mod abi;
+mod instance;
+mod macros;
+
+pub use abi::*;
+pub use instance::*;
@@ -0,0 +1,91 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use serde::Serialize;
+
+use crate::configuration::{GlobalConfiguration, ResolveConfigurationResult, ConfigKeyMap};
+use crate::types::ErrBox;
+use super::PluginHandler;
+
+/// The configuration a host provided to a plugin along with the lazily resolved result.
+///
+/// This is shared by the plugin implementations that resolve the configuration on the
+/// plugin's side of a host boundary (ex. process and native plugins).
+pub(crate) struct PluginConfigState<TConfiguration: Clone + Serialize> {
+    pub global_config: Option<GlobalConfiguration>,
+    pub config: Option<ConfigKeyMap>,
+    resolved_config_result: Option<ResolveConfigurationResult<TConfiguration>>,
+}
+
+impl<TConfiguration: Clone + Serialize> PluginConfigState<TConfiguration> {
+    pub fn new() -> Self {
+        PluginConfigState {
+            global_config: None,
+            config: None,
+            resolved_config_result: None,
+        }
+    }
+
+    pub fn set_global_config(&mut self, global_config: GlobalConfiguration) {
+        self.global_config = Some(global_config);
+        self.clear_resolved_config();
+    }
+
+    pub fn set_plugin_config(&mut self, config: ConfigKeyMap) {
+        self.config = Some(config);
+        self.clear_resolved_config();
+    }
+
+    /// Clears the resolved configuration so it's resolved again when next used.
+    pub fn clear_resolved_config(&mut self) {
+        self.resolved_config_result.take();
+    }
+
+    /// Gets the resolved configuration serialized as JSON.
+    pub fn get_resolved_config_bytes<THandler: PluginHandler<TConfiguration>>(&mut self, handler: &mut THandler) -> Result<Vec<u8>, ErrBox> {
+        Ok(serde_json::to_vec(&self.get_resolved_config_result(handler)?.config)?)
+    }
+
+    /// Gets the diagnostics of resolving the configuration serialized as JSON.
+    pub fn get_config_diagnostics_bytes<THandler: PluginHandler<TConfiguration>>(&mut self, handler: &mut THandler) -> Result<Vec<u8>, ErrBox> {
+        Ok(serde_json::to_vec(&self.get_resolved_config_result(handler)?.diagnostics)?)
+    }
+
+    /// Gets the configuration to format a file with, which is only resolved again when there's override configuration.
+    pub fn get_format_config<THandler: PluginHandler<TConfiguration>>(
+        &mut self,
+        handler: &mut THandler,
+        override_config: ConfigKeyMap,
+    ) -> Result<Cow<'_, TConfiguration>, ErrBox> {
+        Ok(if override_config.is_empty() {
+            Cow::Borrowed(&self.get_resolved_config_result(handler)?.config)
+        } else {
+            Cow::Owned(self.create_resolved_config_result(handler, override_config)?.config)
+        })
+    }
+
+    fn get_resolved_config_result<THandler: PluginHandler<TConfiguration>>(
+        &mut self,
+        handler: &mut THandler,
+    ) -> Result<&ResolveConfigurationResult<TConfiguration>, ErrBox> {
+        if self.resolved_config_result.is_none() {
+            self.resolved_config_result = Some(self.create_resolved_config_result(handler, HashMap::new())?);
+        }
+
+        Ok(self.resolved_config_result.as_ref().unwrap())
+    }
+
+    fn create_resolved_config_result<THandler: PluginHandler<TConfiguration>>(
+        &self,
+        handler: &mut THandler,
+        override_config: ConfigKeyMap,
+    ) -> Result<ResolveConfigurationResult<TConfiguration>, ErrBox> {
+        let mut plugin_config = self.config.as_ref().ok_or("Expected plugin config to be set at this point")?.clone();
+        for (key, value) in override_config {
+            plugin_config.insert(key, value);
+        }
+        Ok(handler.resolve_config(
+            plugin_config,
+            self.global_config.as_ref().ok_or("Expected global config to be set at this point.")?,
+        ))
+    }
+}
@@ -1,5 +1,5 @@
 use crate::configuration::{ConfigKeyMap, GlobalConfiguration, ResolveConfigurationResult};
-use crate::plugins::PluginInfo;
+use crate::plugins::{PluginInfo, FormatTextResult, CancellationToken};
 use std::path::Path;
 use serde::Serialize;
 use crate::types::ErrBox;
@@ -12,12 +12,44 @@ pub trait PluginHandler<TConfiguration : Clone + Serialize> {
     fn get_plugin_info(&mut self) -> PluginInfo;
     /// Gets the plugin's license text.
     fn get_license_text(&mut self) -> String;
+    /// Gets the minimum version of the CLI the plugin requires (ex. "0.15.0").
+    fn get_minimum_cli_version(&mut self) -> Option<String> {
+        None
+    }
+    /// Provides the token of the format request that's about to start.
+    ///
+    /// The token is cancelled when the host no longer needs the result (ex. the file changed in the
+    /// editor). Override this to store it and check `is_cancelled()` periodically while formatting
+    /// in order to stop early. Only process plugins that negotiate the cancellation capability
+    /// receive cancelled tokens.
+    fn set_cancellation_token(&mut self, _token: CancellationToken) {
+    }
     /// Formats the provided file text based on the provided file path and configuration.
+    ///
+    /// Embedded text may be formatted by another plugin using `format_with_host`. Provide the
+    /// language id (ex. `typescript`) or media type (ex. `text/css`) of the text when known, which
+    /// is resolved using the `languages` declared in each plugin's `PluginInfo` before the file path.
     fn format_text(
         &mut self,
         file_path: &Path,
         file_text: &str,
         config: &TConfiguration,
-        format_with_host: impl FnMut(&Path, String, &ConfigKeyMap) -> Result<String, ErrBox>,
+        format_with_host: impl FnMut(&Path, Option<&str>, String, &ConfigKeyMap) -> Result<String, ErrBox>,
     ) -> Result<String, ErrBox>;
+    /// Formats the provided file text and provides any diagnostics found while formatting.
+    ///
+    /// Override this instead of `format_text` in order to surface warnings to the user.
+    /// Diagnostic ranges are byte indexes into the provided file text.
+    fn format_text_with_diagnostics(
+        &mut self,
+        file_path: &Path,
+        file_text: &str,
+        config: &TConfiguration,
+        format_with_host: impl FnMut(&Path, Option<&str>, String, &ConfigKeyMap) -> Result<String, ErrBox>,
+    ) -> Result<FormatTextResult, ErrBox> {
+        Ok(FormatTextResult {
+            text: self.format_text(file_path, file_text, config, format_with_host)?,
+            diagnostics: Vec::new(),
+        })
+    }
 }
@@ -15,6 +15,14 @@ pub struct PluginInfo {
     /// The file names this plugin should format.
     #[serde(default = "Vec::new")]
     pub file_names: Vec<String>,
+    /// The interpreter names (ex. `node` in `#!/usr/bin/env node`) of files
+    /// without an extension that this plugin should format.
+    #[serde(default = "Vec::new")]
+    pub interpreters: Vec<String>,
+    /// The language ids (ex. `typescript`) and media types (ex. `text/css`) of embedded
+    /// code this plugin should format when another plugin formats it with the host.
+    #[serde(default = "Vec::new")]
+    pub languages: Vec<String>,
     /// A url the user can go to in order to get help information about the plugin.
     pub help_url: String,
     /// Schema url for the plugin configuration.
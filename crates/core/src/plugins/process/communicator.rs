@@ -3,13 +3,17 @@ use std::process::{Child, Command, Stdio, ChildStdin, ChildStdout};
 
 use crate::configuration::{ConfigKeyMap, GlobalConfiguration, ConfigurationDiagnostic};
 use crate::types::ErrBox;
-use crate::plugins::PluginInfo;
-use super::{StdIoReaderWriter, StdIoMessenger, FormatResult, MessageKind, PLUGIN_SCHEMA_VERSION, HostFormatResult, ResponseKind};
+use crate::plugins::{PluginInfo, FormatTextResult, HostInfo, PluginHandshake, PluginCapabilities};
+use super::{MultiplexedProcessPluginCommunicator, StdIoReaderWriter, StdIoMessenger, FormatResult, MessageKind, PLUGIN_SCHEMA_VERSION, MINIMUM_PLUGIN_SCHEMA_VERSION,
+    HostFormatResult, ResponseKind, get_host_format_language};
 
 /// Communicates with a process plugin.
 pub struct ProcessPluginCommunicator {
-    child: Child,
-    messenger: StdIoMessenger<ChildStdout, ChildStdin>,
+    // these are only None once converted to a multiplexed communicator
+    child: Option<Child>,
+    messenger: Option<StdIoMessenger<ChildStdout, ChildStdin>>,
+    plugin_schema_version: u32,
+    capabilities: PluginCapabilities,
 }
 
 impl Drop for ProcessPluginCommunicator {
@@ -70,24 +74,54 @@ impl ProcessPluginCommunicator {
 
         let messenger = StdIoMessenger::new(StdIoReaderWriter::new(child.stdout.take().unwrap(), child.stdin.take().unwrap()));
         let mut communicator = ProcessPluginCommunicator {
-            child,
-            messenger,
+            child: Some(child),
+            messenger: Some(messenger),
+            plugin_schema_version: 0,
+            capabilities: PluginCapabilities::NONE,
         };
 
-        communicator.verify_plugin_schema_version()?;
+        communicator.plugin_schema_version = communicator.verify_plugin_schema_version()?;
 
         Ok(communicator)
     }
 
     fn kill(&mut self) -> Result<(), ErrBox> {
-        // attempt to exit nicely
-        let _ignore = self.messenger.send_message(MessageKind::Close as u32, Vec::new());
+        if let Some(child) = self.child.as_mut() {
+            // attempt to exit nicely
+            if !self.capabilities.multiplexed {
+                if let Some(messenger) = self.messenger.as_mut() {
+                    let _ignore = messenger.send_message(MessageKind::Close as u32, Vec::new());
+                }
+            }
 
-        // now ensure kill
-        self.child.kill()?;
+            // now ensure kill
+            child.kill()?;
+        }
         Ok(())
     }
 
+    fn messenger(&mut self) -> &mut StdIoMessenger<ChildStdout, ChildStdin> {
+        if self.is_multiplexed() {
+            panic!("Programming error: The communicator must be converted with into_multiplexed() after negotiating the multiplexed capability.");
+        }
+        self.messenger.as_mut().unwrap()
+    }
+
+    /// Gets if the multiplexed capability was negotiated in the handshake.
+    pub fn is_multiplexed(&self) -> bool {
+        self.capabilities.multiplexed
+    }
+
+    /// Converts the communicator to one that may have many requests in flight at once.
+    ///
+    /// This must be done after negotiating the multiplexed capability in the handshake.
+    pub fn into_multiplexed(mut self) -> MultiplexedProcessPluginCommunicator {
+        assert!(self.is_multiplexed(), "Expected the multiplexed capability to be negotiated.");
+        let child = self.child.take().unwrap();
+        let (reader, writer) = self.messenger.take().unwrap().into_inner();
+        MultiplexedProcessPluginCommunicator::new(child, reader, writer, self.plugin_schema_version, self.capabilities)
+    }
+
     pub fn set_global_config(&mut self, global_config: &GlobalConfiguration) -> Result<(), ErrBox> {
         let json = serde_json::to_vec(global_config)?;
         self.send_data(MessageKind::SetGlobalConfig, &json)?;
@@ -100,6 +134,25 @@ impl ProcessPluginCommunicator {
         Ok(())
     }
 
+    /// Tells the plugin about the host and gets the capabilities both support.
+    ///
+    /// Plugins that don't support the handshake are treated as supporting no capabilities.
+    /// When the multiplexed capability is negotiated, the communicator must then be
+    /// converted using `into_multiplexed()`.
+    pub fn handshake(&mut self, host_info: &HostInfo) -> Result<PluginHandshake, ErrBox> {
+        if self.plugin_schema_version < 4 {
+            return Ok(PluginHandshake::default());
+        }
+
+        let json = serde_json::to_vec(host_info)?;
+        self.messenger().send_message(MessageKind::Handshake as u32, vec![json.into()])?;
+        self.messenger().read_response()?;
+        let response = self.messenger().read_single_part_message()?;
+        let handshake: PluginHandshake = serde_json::from_slice(&response)?;
+        self.capabilities = handshake.capabilities;
+        Ok(handshake)
+    }
+
     pub fn get_plugin_info(&mut self) -> Result<PluginInfo, ErrBox> {
         let response = self.get_bytes(MessageKind::GetPluginInfo)?;
         Ok(serde_json::from_slice(&response)?)
@@ -123,11 +176,11 @@ impl ProcessPluginCommunicator {
         file_path: &Path,
         file_text: &str,
         override_config: &ConfigKeyMap,
-        format_with_host: impl Fn(PathBuf, String, ConfigKeyMap) -> Result<Option<String>, ErrBox>,
-    ) -> Result<String, ErrBox> {
+        format_with_host: impl Fn(PathBuf, Option<String>, String, ConfigKeyMap) -> Result<Option<String>, ErrBox>,
+    ) -> Result<FormatTextResult, ErrBox> {
         let override_config = serde_json::to_vec(override_config)?;
         // send message
-        self.messenger.send_message(
+        self.messenger().send_message(
             MessageKind::FormatText as u32,
             vec![
                 file_path.into(),
@@ -137,37 +190,62 @@ impl ProcessPluginCommunicator {
         )?;
 
         loop {
-            self.messenger.read_response()?;
-            let format_result = self.messenger.read_code()?;
+            self.messenger().read_response()?;
+            let format_result = self.messenger().read_code()?;
             match format_result.into() {
                 FormatResult::NoChange => {
-                    self.messenger.read_zero_part_message()?;
-                    break Ok(String::from(file_text))
+                    self.messenger().read_zero_part_message()?;
+                    break Ok(FormatTextResult {
+                        text: String::from(file_text),
+                        diagnostics: Vec::new(),
+                    })
                 },
                 FormatResult::Change => {
-                    break Ok(self.messenger.read_single_part_string_message()?)
+                    break Ok(FormatTextResult {
+                        text: self.messenger().read_single_part_string_message()?,
+                        diagnostics: Vec::new(),
+                    })
+                },
+                FormatResult::NoChangeWithDiagnostics => {
+                    let diagnostics = self.messenger().read_single_part_message()?;
+                    break Ok(FormatTextResult {
+                        text: String::from(file_text),
+                        diagnostics: serde_json::from_slice(&diagnostics)?,
+                    })
+                },
+                FormatResult::ChangeWithDiagnostics => {
+                    let mut message_parts = self.messenger().read_multi_part_message(2)?;
+                    let text = message_parts.take_string()?;
+                    let diagnostics = serde_json::from_slice(&message_parts.take_part()?)?;
+                    break Ok(FormatTextResult {
+                        text,
+                        diagnostics,
+                    })
                 },
                 FormatResult::RequestTextFormat => {
-                    let mut message_parts = self.messenger.read_multi_part_message(3)?;
+                    // plugins only send the language when the capability was negotiated
+                    let has_language = self.capabilities.host_format_language;
+                    let mut message_parts = self.messenger().read_multi_part_message(if has_language { 4 } else { 3 })?;
                     let file_path = message_parts.take_path_buf()?;
                     let file_text = message_parts.take_string()?;
                     let override_config = serde_json::from_slice(&message_parts.take_part()?)?;
+                    let language = if has_language { get_host_format_language(message_parts.take_string()?) } else { None };
 
-                    match format_with_host(file_path, file_text, override_config) {
+                    match format_with_host(file_path, language, file_text, override_config) {
                         Ok(Some(formatted_text)) => {
-                            self.messenger.send_message(
+                            self.messenger().send_message(
                                 HostFormatResult::Change as u32,
                                 vec![formatted_text.as_str().into()]
                             )?;
                         },
                         Ok(None) => {
-                            self.messenger.send_message(
+                            self.messenger().send_message(
                                 HostFormatResult::NoChange as u32,
                                 vec![]
                             )?;
                         }
                         Err(err) => {
-                            self.messenger.send_message(
+                            self.messenger().send_message(
                                 HostFormatResult::Error as u32,
                                 vec![err.to_string().as_str().into()]
                             )?;
@@ -183,7 +261,7 @@ impl ProcessPluginCommunicator {
     pub fn is_process_alive(&mut self) -> bool {
         let result = self.get_plugin_schema_version();
         if let Ok(plugin_schema_version) = result {
-            plugin_schema_version == PLUGIN_SCHEMA_VERSION
+            plugin_schema_version == self.plugin_schema_version
         } else {
             false
         }
@@ -204,19 +282,28 @@ impl ProcessPluginCommunicator {
         }
     }
 
-    fn verify_plugin_schema_version(&mut self) -> Result<(), ErrBox> {
+    fn verify_plugin_schema_version(&mut self) -> Result<u32, ErrBox> {
         let plugin_schema_version = self.get_plugin_schema_version()?;
-        if plugin_schema_version != PLUGIN_SCHEMA_VERSION {
+        if plugin_schema_version > PLUGIN_SCHEMA_VERSION {
             return err!(
                 concat!(
-                    "The plugin schema version was {}, but expected {}. ",
-                    "This may indicate you are using an old version of the dprint CLI or plugin and should upgrade."
+                    "The plugin schema version was {}, but expected {} or less. ",
+                    "This may indicate you are using an old version of the dprint CLI and should upgrade."
                 ),
                 plugin_schema_version, PLUGIN_SCHEMA_VERSION
             );
         }
+        if plugin_schema_version < MINIMUM_PLUGIN_SCHEMA_VERSION {
+            return err!(
+                concat!(
+                    "The plugin schema version was {}, but expected {} or greater. ",
+                    "This may indicate you are using an old version of the plugin and should upgrade."
+                ),
+                plugin_schema_version, MINIMUM_PLUGIN_SCHEMA_VERSION
+            );
+        }
 
-        Ok(())
+        Ok(plugin_schema_version)
     }
 
     fn get_string(&mut self, message_kind: MessageKind) -> Result<String, ErrBox> {
@@ -225,21 +312,21 @@ impl ProcessPluginCommunicator {
     }
 
     fn get_bytes(&mut self, message_kind: MessageKind) -> Result<Vec<u8>, ErrBox> {
-        self.messenger.send_message(message_kind as u32, Vec::new())?;
-        self.messenger.read_response()?;
-        self.messenger.read_single_part_message()
+        self.messenger().send_message(message_kind as u32, Vec::new())?;
+        self.messenger().read_response()?;
+        self.messenger().read_single_part_message()
     }
 
     fn get_u32(&mut self, message_kind: MessageKind) -> Result<u32, ErrBox> {
-        self.messenger.send_message(message_kind as u32, Vec::new())?;
-        self.messenger.read_response()?;
-        self.messenger.read_single_part_u32_message()
+        self.messenger().send_message(message_kind as u32, Vec::new())?;
+        self.messenger().read_response()?;
+        self.messenger().read_single_part_u32_message()
     }
 
     fn send_data(&mut self, message_kind: MessageKind, data: &[u8]) -> Result<(), ErrBox> {
-        self.messenger.send_message(message_kind as u32, vec![data.into()])?;
-        self.messenger.read_response()?;
-        self.messenger.read_zero_part_message()
+        self.messenger().send_message(message_kind as u32, vec![data.into()])?;
+        self.messenger().read_response()?;
+        self.messenger().read_zero_part_message()
     }
 }
 
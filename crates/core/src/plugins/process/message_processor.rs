@@ -1,42 +1,66 @@
-use std::collections::HashMap;
-use std::io::{Read, Write};
+use std::io::{Read, Write, Stdin, Stdout};
 use std::path::Path;
-use std::borrow::Cow;
 use serde::{Serialize};
 
-use crate::configuration::{GlobalConfiguration, ResolveConfigurationResult, ConfigKeyMap};
+use crate::configuration::ConfigKeyMap;
 use crate::types::ErrBox;
-use crate::plugins::PluginHandler;
+use crate::plugins::{PluginHandler, HostInfo, PluginHandshake, PluginCapabilities, FormatTextResult, PluginConfigState};
 use super::{MessageKind, StdIoMessenger, FormatResult, HostFormatResult, StdIoReaderWriter, PLUGIN_SCHEMA_VERSION,
     MessagePart, ResponseKind};
 
-struct MessageProcessorState<TConfiguration: Clone + Serialize> {
-    global_config: Option<GlobalConfiguration>,
-    config: Option<ConfigKeyMap>,
-    resolved_config_result: Option<ResolveConfigurationResult<TConfiguration>>,
+pub(super) struct MessageProcessorState<TConfiguration: Clone + Serialize> {
+    pub config_state: PluginConfigState<TConfiguration>,
+    /// The capabilities negotiated with the host.
+    pub capabilities: PluginCapabilities,
+    /// The capabilities the plugin supports.
+    pub supported_capabilities: PluginCapabilities,
+}
+
+impl<TConfiguration: Clone + Serialize> MessageProcessorState<TConfiguration> {
+    pub fn new(supported_capabilities: PluginCapabilities) -> Self {
+        MessageProcessorState {
+            config_state: PluginConfigState::new(),
+            capabilities: PluginCapabilities::NONE,
+            supported_capabilities,
+        }
+    }
 }
 
 /// Handles the process' messages based on the provided handler.
 pub fn handle_process_stdio_messages<THandler: PluginHandler<TConfiguration>, TConfiguration: Clone + Serialize>(
     mut handler: THandler
 ) -> Result<(), ErrBox> {
+    let mut messenger = create_stdio_messenger();
+    let mut state = MessageProcessorState::new(PluginCapabilities::SUPPORTED);
+    handle_request_response_messages(&mut messenger, &mut handler, &mut state)?;
+    Ok(())
+}
+
+pub(super) fn create_stdio_messenger() -> StdIoMessenger<Stdin, Stdout> {
     let stdin = std::io::stdin();
     let stdout = std::io::stdout();
     let reader_writer = StdIoReaderWriter::new(stdin, stdout);
-    let mut messenger = StdIoMessenger::new(reader_writer);
-    let mut state = MessageProcessorState {
-        global_config: None,
-        config: None,
-        resolved_config_result: None,
-    };
+    StdIoMessenger::new(reader_writer)
+}
 
+/// Handles messages one at a time until the host closes the plugin or the
+/// multiplexed capability is negotiated, in which case this returns true.
+pub(super) fn handle_request_response_messages<TRead: Read, TWrite: Write, TConfiguration: Clone + Serialize, THandler: PluginHandler<TConfiguration>>(
+    messenger: &mut StdIoMessenger<TRead, TWrite>,
+    handler: &mut THandler,
+    state: &mut MessageProcessorState<TConfiguration>,
+) -> Result<bool, ErrBox> {
     loop {
         let message_kind = messenger.read_code()?.into();
 
-        match handle_message_kind(message_kind, &mut messenger, &mut handler, &mut state) {
+        match handle_message_kind(message_kind, messenger, handler, state) {
             Err(err) => messenger.send_error_response(&err.to_string())?,
-            Ok(true) => {},
-            Ok(false) => return Ok(()),
+            Ok(true) => {
+                if state.capabilities.multiplexed {
+                    return Ok(true);
+                }
+            },
+            Ok(false) => return Ok(false),
         }
     }
 }
@@ -56,6 +80,16 @@ fn handle_message_kind<TRead: Read, TWrite: Write, TConfiguration: Clone + Seria
             messenger.read_zero_part_message()?;
             messenger.send_response(vec![PLUGIN_SCHEMA_VERSION.into()])?
         },
+        MessageKind::Handshake => {
+            let message_data = messenger.read_single_part_message()?;
+            let host_info: HostInfo = serde_json::from_slice(&message_data)?;
+            state.capabilities = state.supported_capabilities.intersect(&host_info.capabilities);
+            let handshake = PluginHandshake {
+                capabilities: state.capabilities,
+                minimum_cli_version: handler.get_minimum_cli_version(),
+            };
+            messenger.send_response(vec![serde_json::to_vec(&handshake)?.into()])?
+        },
         MessageKind::GetPluginInfo => {
             messenger.read_zero_part_message()?;
             messenger.send_response(vec![serde_json::to_vec(&handler.get_plugin_info())?.into()])?
@@ -66,112 +100,112 @@ fn handle_message_kind<TRead: Read, TWrite: Write, TConfiguration: Clone + Seria
         },
         MessageKind::SetGlobalConfig => {
             let message_data = messenger.read_single_part_message()?;
-            state.global_config = Some(serde_json::from_slice(&message_data)?);
-            state.resolved_config_result.take();
+            state.config_state.set_global_config(serde_json::from_slice(&message_data)?);
             messenger.send_response(Vec::new())?;
         },
         MessageKind::SetPluginConfig => {
             let message_data = messenger.read_single_part_message()?;
-            let plugin_config = serde_json::from_slice(&message_data)?;
-            state.resolved_config_result.take();
-            state.config = Some(plugin_config);
+            state.config_state.set_plugin_config(serde_json::from_slice(&message_data)?);
             messenger.send_response(Vec::new())?;
         },
         MessageKind::GetResolvedConfig => {
             messenger.read_zero_part_message()?;
-            ensure_resolved_config(handler, state)?;
-            let resolved_config = get_resolved_config_result(state)?;
-            messenger.send_response(vec![serde_json::to_vec(&resolved_config.config)?.into()])?
+            messenger.send_response(vec![state.config_state.get_resolved_config_bytes(handler)?.into()])?
         },
         MessageKind::GetConfigDiagnostics => {
             messenger.read_zero_part_message()?;
-            ensure_resolved_config(handler, state)?;
-            let resolved_config = get_resolved_config_result(state)?;
-            messenger.send_response(vec![serde_json::to_vec(&resolved_config.diagnostics)?.into()])?
+            messenger.send_response(vec![state.config_state.get_config_diagnostics_bytes(handler)?.into()])?
+        },
+        MessageKind::CancelFormat => {
+            messenger.read_single_part_message()?;
+            return err!("Cancelling format requests is only supported in the multiplexed mode.");
         },
         MessageKind::FormatText => {
             let mut parts = messenger.read_multi_part_message(3)?;
-            ensure_resolved_config(handler, state)?;
             let file_path = parts.take_path_buf()?;
             let file_text = parts.take_string()?;
             let override_config: ConfigKeyMap = serde_json::from_slice(&parts.take_part()?)?;
-            let config = if !override_config.is_empty() {
-                Cow::Owned(create_resolved_config_result(handler, state, override_config)?.config)
-            } else {
-                Cow::Borrowed(&get_resolved_config_result(state)?.config)
-            };
+            let config = state.config_state.get_format_config(handler, override_config)?;
 
-            let formatted_text = handler.format_text(
+            let capabilities = state.capabilities;
+            let result = handler.format_text_with_diagnostics(
                 &file_path,
                 &file_text,
                 &config,
-                |file_path, file_text, override_config| {
-                    format_with_host(messenger, file_path, file_text, override_config)
+                |file_path, language, file_text, override_config| {
+                    format_with_host(messenger, &capabilities, file_path, language, file_text, override_config)
                 }
             )?;
-
-            if formatted_text == file_text {
-                messenger.send_response(vec![(FormatResult::NoChange as u32).into()])?;
-            } else {
-                messenger.send_response(
-                    vec![
-                        (FormatResult::Change as u32).into(),
-                        formatted_text.into(),
-                    ]
-                )?;
-            }
+            messenger.send_response(get_format_response_parts(result, &file_text, &state.capabilities)?)?;
         },
     }
 
     Ok(true)
 }
 
-fn ensure_resolved_config<TConfiguration: Clone + Serialize, THandler: PluginHandler<TConfiguration>>(
-    handler: &mut THandler,
-    state: &mut MessageProcessorState<TConfiguration>,
-) -> Result<(), ErrBox> {
-    if state.resolved_config_result.is_none() {
-        state.resolved_config_result = Some(create_resolved_config_result(handler, state, HashMap::new())?);
-    }
-
-    Ok(())
+/// Gets the message parts of the response to a format request.
+pub(super) fn get_format_response_parts(result: FormatTextResult, file_text: &str, capabilities: &PluginCapabilities) -> Result<Vec<MessagePart<'static>>, ErrBox> {
+    let has_change = result.text != file_text;
+
+    // only send diagnostics to hosts that said they support them
+    Ok(if result.diagnostics.is_empty() || !capabilities.diagnostics {
+        if has_change {
+            vec![
+                (FormatResult::Change as u32).into(),
+                result.text.into(),
+            ]
+        } else {
+            vec![(FormatResult::NoChange as u32).into()]
+        }
+    } else {
+        let diagnostics = serde_json::to_vec(&result.diagnostics)?;
+        if has_change {
+            vec![
+                (FormatResult::ChangeWithDiagnostics as u32).into(),
+                result.text.into(),
+                diagnostics.into(),
+            ]
+        } else {
+            vec![
+                (FormatResult::NoChangeWithDiagnostics as u32).into(),
+                diagnostics.into(),
+            ]
+        }
+    })
 }
 
-fn create_resolved_config_result<TConfiguration: Clone + Serialize, THandler: PluginHandler<TConfiguration>>(
-    handler: &mut THandler,
-    state: &MessageProcessorState<TConfiguration>,
-    override_config: ConfigKeyMap,
-) -> Result<ResolveConfigurationResult<TConfiguration>, ErrBox> {
-    let mut plugin_config = state.config.as_ref().ok_or("Expected plugin config to be set at this point")?.clone();
-    for (key, value) in override_config {
-        plugin_config.insert(key, value);
+/// Gets the message parts of a request for the host to format text.
+///
+/// The language is only sent to hosts that said they support it.
+pub(super) fn get_host_format_request_parts<'a>(
+    capabilities: &PluginCapabilities,
+    file_path: &'a Path,
+    language: Option<&'a str>,
+    file_text: &'a str,
+    override_config: &ConfigKeyMap,
+) -> Result<Vec<MessagePart<'a>>, ErrBox> {
+    let mut message_parts = vec![
+        (FormatResult::RequestTextFormat as u32).into(),
+        file_path.into(),
+        file_text.into(),
+        serde_json::to_vec(&override_config)?.into(),
+    ];
+    if capabilities.host_format_language {
+        // an empty language means it's not known
+        message_parts.push(language.unwrap_or("").into());
     }
-    Ok(handler.resolve_config(
-        plugin_config,
-        state.global_config.as_ref().ok_or("Expected global config to be set at this point.")?,
-    ))
-}
-
-fn get_resolved_config_result<TConfiguration: Clone + Serialize>(
-    state: &MessageProcessorState<TConfiguration>,
-) -> Result<&ResolveConfigurationResult<TConfiguration>, ErrBox> {
-    Ok(state.resolved_config_result.as_ref().ok_or("Expected the config to be resolved at this point.")?)
+    Ok(message_parts)
 }
 
 fn format_with_host<TRead: Read, TWrite: Write>(
     messenger: &mut StdIoMessenger<TRead, TWrite>,
+    capabilities: &PluginCapabilities,
     file_path: &Path,
+    language: Option<&str>,
     file_text: String,
     override_config: &ConfigKeyMap,
 ) -> Result<String, ErrBox> {
-    messenger.send_response(
-        vec![
-            (FormatResult::RequestTextFormat as u32).into(),
-            file_path.into(),
-            file_text.as_str().into(),
-            (&serde_json::to_vec(&override_config)?).into(),
-        ]
-    )?;
+    messenger.send_response(get_host_format_request_parts(capabilities, file_path, language, &file_text, override_config)?)?;
 
     let format_result = messenger.read_code()?.into();
     match format_result {
@@ -8,6 +8,18 @@ pub struct ReadMessageParts {
 }
 
 impl ReadMessageParts {
+    pub(super) fn new(parts: Vec<Vec<u8>>) -> Self {
+        ReadMessageParts { parts }
+    }
+
+    pub fn take_u32(&mut self) -> Result<u32, ErrBox> {
+        let message_data = self.take_part()?;
+        if message_data.len() != 4 {
+            return err!("Expected a 4 byte message part, but found {} bytes.", message_data.len());
+        }
+        Ok(u32::from_be_bytes([message_data[0], message_data[1], message_data[2], message_data[3]]))
+    }
+
     pub fn take_path_buf(&mut self) -> Result<PathBuf, ErrBox> {
         let message_data = self.take_part()?;
         Ok(PathBuf::from(String::from_utf8(message_data)?))
@@ -39,6 +51,11 @@ impl<TRead: Read, TWrite: Write> StdIoMessenger<TRead, TWrite> {
         }
     }
 
+    /// Gets the underlying reader and writer.
+    pub fn into_inner(self) -> (TRead, TWrite) {
+        self.reader_writer.into_inner()
+    }
+
     pub fn read_code(&mut self) -> Result<u32, ErrBox> {
         self.reader_writer.read_u32()
     }
@@ -1,6 +1,9 @@
 mod communicator;
 mod message_processor;
 mod messenger;
+mod multiplexed_communicator;
+mod multiplexed_message_processor;
+mod multiplexed_messenger;
 mod parent_process_checker;
 mod stdio_reader_writer;
 mod shared_types;
@@ -8,6 +11,9 @@ mod shared_types;
 pub use communicator::*;
 pub use messenger::*;
 pub use message_processor::*;
+pub use multiplexed_communicator::*;
+pub use multiplexed_message_processor::*;
+pub use multiplexed_messenger::*;
 pub use parent_process_checker::*;
 pub use stdio_reader_writer::*;
 use shared_types::*;
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use crate::configuration::{ConfigKeyMap, GlobalConfiguration, ConfigurationDiagnostic};
+use crate::types::ErrBox;
+use crate::plugins::{PluginInfo, FormatTextResult, PluginCapabilities, CancellationToken};
+use super::{FormatResult, MessageKind, HostFormatResult, ResponseKind, MessagePart, ReadMessageParts,
+    MultiplexedMessage, read_multiplexed_message, write_multiplexed_message, get_host_format_language};
+
+type PendingRequests = Arc<Mutex<HashMap<u32, Sender<MultiplexedMessage>>>>;
+
+/// Communicates with a process plugin that may handle many requests at once.
+///
+/// Create this using `ProcessPluginCommunicator::into_multiplexed()`.
+pub struct MultiplexedProcessPluginCommunicator {
+    child: Mutex<Child>,
+    writer: Mutex<ChildStdin>,
+    pending_requests: PendingRequests,
+    is_closed: Arc<AtomicBool>,
+    next_id: AtomicU32,
+    plugin_schema_version: u32,
+    capabilities: PluginCapabilities,
+}
+
+impl Drop for MultiplexedProcessPluginCommunicator {
+    fn drop(&mut self) {
+        let _ignore = self.kill();
+    }
+}
+
+impl MultiplexedProcessPluginCommunicator {
+    pub(super) fn new(
+        child: Child,
+        mut reader: ChildStdout,
+        writer: ChildStdin,
+        plugin_schema_version: u32,
+        capabilities: PluginCapabilities,
+    ) -> Self {
+        let pending_requests: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let is_closed = Arc::new(AtomicBool::new(false));
+
+        // route the responses to the threads waiting on them
+        std::thread::spawn({
+            let pending_requests = pending_requests.clone();
+            let is_closed = is_closed.clone();
+            move || {
+                loop {
+                    match read_multiplexed_message(&mut reader) {
+                        Ok(message) => {
+                            let pending_requests = pending_requests.lock().unwrap();
+                            if let Some(sender) = pending_requests.get(&message.id) {
+                                let _ignore = sender.send(message);
+                            }
+                        },
+                        Err(_) => {
+                            // the process exited, so drop the senders to error any waiting requests
+                            is_closed.store(true, Ordering::SeqCst);
+                            pending_requests.lock().unwrap().clear();
+                            return;
+                        },
+                    }
+                }
+            }
+        });
+
+        MultiplexedProcessPluginCommunicator {
+            child: Mutex::new(child),
+            writer: Mutex::new(writer),
+            pending_requests,
+            is_closed,
+            next_id: AtomicU32::new(1),
+            plugin_schema_version,
+            capabilities,
+        }
+    }
+
+    fn kill(&self) -> Result<(), ErrBox> {
+        // attempt to exit nicely
+        let _ignore = self.send_message(0, MessageKind::Close as u32, Vec::new());
+
+        // now ensure kill
+        self.child.lock().unwrap().kill()?;
+        Ok(())
+    }
+
+    pub fn set_global_config(&self, global_config: &GlobalConfiguration) -> Result<(), ErrBox> {
+        let json = serde_json::to_vec(global_config)?;
+        self.request(MessageKind::SetGlobalConfig, vec![json.into()])?;
+        Ok(())
+    }
+
+    pub fn set_plugin_config(&self, plugin_config: &ConfigKeyMap) -> Result<(), ErrBox> {
+        let json = serde_json::to_vec(plugin_config)?;
+        self.request(MessageKind::SetPluginConfig, vec![json.into()])?;
+        Ok(())
+    }
+
+    pub fn get_plugin_info(&self) -> Result<PluginInfo, ErrBox> {
+        let mut response = self.request(MessageKind::GetPluginInfo, Vec::new())?;
+        Ok(serde_json::from_slice(&response.take_part()?)?)
+    }
+
+    pub fn get_license_text(&self) -> Result<String, ErrBox> {
+        self.request(MessageKind::GetLicenseText, Vec::new())?.take_string()
+    }
+
+    pub fn get_resolved_config(&self) -> Result<String, ErrBox> {
+        self.request(MessageKind::GetResolvedConfig, Vec::new())?.take_string()
+    }
+
+    pub fn get_config_diagnostics(&self) -> Result<Vec<ConfigurationDiagnostic>, ErrBox> {
+        let mut response = self.request(MessageKind::GetConfigDiagnostics, Vec::new())?;
+        Ok(serde_json::from_slice(&response.take_part()?)?)
+    }
+
+    pub fn format_text(
+        &self,
+        file_path: &Path,
+        file_text: &str,
+        override_config: &ConfigKeyMap,
+        cancellation_token: &CancellationToken,
+        format_with_host: impl Fn(PathBuf, Option<String>, String, ConfigKeyMap) -> Result<Option<String>, ErrBox>,
+    ) -> Result<FormatTextResult, ErrBox> {
+        if cancellation_token.is_cancelled() {
+            return err!("The format request was cancelled.");
+        }
+
+        let override_config = serde_json::to_vec(override_config)?;
+        let (id, receiver) = self.start_request(
+            MessageKind::FormatText,
+            vec![
+                file_path.into(),
+                file_text.into(),
+                (&override_config).into(),
+            ]
+        )?;
+        let result = self.handle_format_text_responses(id, &receiver, file_text, cancellation_token, format_with_host);
+        self.pending_requests.lock().unwrap().remove(&id);
+        result
+    }
+
+    fn handle_format_text_responses(
+        &self,
+        id: u32,
+        receiver: &Receiver<MultiplexedMessage>,
+        file_text: &str,
+        cancellation_token: &CancellationToken,
+        format_with_host: impl Fn(PathBuf, Option<String>, String, ConfigKeyMap) -> Result<Option<String>, ErrBox>,
+    ) -> Result<FormatTextResult, ErrBox> {
+        loop {
+            let mut message_parts = self.receive_format_response(id, receiver, cancellation_token)?;
+            match message_parts.take_u32()?.into() {
+                FormatResult::NoChange => {
+                    break Ok(FormatTextResult {
+                        text: String::from(file_text),
+                        diagnostics: Vec::new(),
+                    })
+                },
+                FormatResult::Change => {
+                    break Ok(FormatTextResult {
+                        text: message_parts.take_string()?,
+                        diagnostics: Vec::new(),
+                    })
+                },
+                FormatResult::NoChangeWithDiagnostics => {
+                    break Ok(FormatTextResult {
+                        text: String::from(file_text),
+                        diagnostics: serde_json::from_slice(&message_parts.take_part()?)?,
+                    })
+                },
+                FormatResult::ChangeWithDiagnostics => {
+                    let text = message_parts.take_string()?;
+                    let diagnostics = serde_json::from_slice(&message_parts.take_part()?)?;
+                    break Ok(FormatTextResult {
+                        text,
+                        diagnostics,
+                    })
+                },
+                FormatResult::RequestTextFormat => {
+                    let file_path = message_parts.take_path_buf()?;
+                    let file_text = message_parts.take_string()?;
+                    let override_config = serde_json::from_slice(&message_parts.take_part()?)?;
+                    let language = if self.capabilities.host_format_language {
+                        get_host_format_language(message_parts.take_string()?)
+                    } else {
+                        None
+                    };
+
+                    // respond using the id of the format request so the plugin can correlate it
+                    match format_with_host(file_path, language, file_text, override_config) {
+                        Ok(Some(formatted_text)) => {
+                            self.send_message(id, HostFormatResult::Change as u32, vec![formatted_text.as_str().into()])?;
+                        },
+                        Ok(None) => {
+                            self.send_message(id, HostFormatResult::NoChange as u32, vec![])?;
+                        }
+                        Err(err) => {
+                            self.send_message(id, HostFormatResult::Error as u32, vec![err.to_string().as_str().into()])?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Gets if the process is known to have exited.
+    pub fn has_exited(&self) -> bool {
+        self.is_closed.load(Ordering::SeqCst)
+    }
+
+    /// Checks if the process is functioning.
+    /// Only use this after an error has occurred to tell if the process should be recreated.
+    pub fn is_process_alive(&self) -> bool {
+        if self.has_exited() {
+            return false;
+        }
+        let result = self.request(MessageKind::GetPluginSchemaVersion, Vec::new()).and_then(|mut response| response.take_u32());
+        if let Ok(plugin_schema_version) = result {
+            plugin_schema_version == self.plugin_schema_version
+        } else {
+            false
+        }
+    }
+
+    fn request(&self, message_kind: MessageKind, message_parts: Vec<MessagePart>) -> Result<ReadMessageParts, ErrBox> {
+        let (id, receiver) = self.start_request(message_kind, message_parts)?;
+        let result = self.receive_response(&receiver);
+        self.pending_requests.lock().unwrap().remove(&id);
+        result
+    }
+
+    fn start_request(&self, message_kind: MessageKind, message_parts: Vec<MessagePart>) -> Result<(u32, Receiver<MultiplexedMessage>), ErrBox> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = channel();
+        self.pending_requests.lock().unwrap().insert(id, sender);
+        if let Err(err) = self.send_message(id, message_kind as u32, message_parts) {
+            self.pending_requests.lock().unwrap().remove(&id);
+            return Err(err);
+        }
+        Ok((id, receiver))
+    }
+
+    fn receive_response(&self, receiver: &Receiver<MultiplexedMessage>) -> Result<ReadMessageParts, ErrBox> {
+        match receiver.recv() {
+            Ok(message) => get_message_response_parts(message),
+            Err(_) => err!("The process plugin exited before responding."),
+        }
+    }
+
+    fn receive_format_response(
+        &self,
+        id: u32,
+        receiver: &Receiver<MultiplexedMessage>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<ReadMessageParts, ErrBox> {
+        // poll the token while waiting so the request can be abandoned
+        let message = loop {
+            match receiver.recv_timeout(Duration::from_millis(25)) {
+                Ok(message) => break message,
+                Err(RecvTimeoutError::Timeout) => {
+                    if cancellation_token.is_cancelled() {
+                        if self.capabilities.cancellation {
+                            self.send_message(0, MessageKind::CancelFormat as u32, vec![id.into()])?;
+                        }
+                        return err!("The format request was cancelled.");
+                    }
+                },
+                Err(RecvTimeoutError::Disconnected) => return err!("The process plugin exited before responding."),
+            }
+        };
+        get_message_response_parts(message)
+    }
+
+    fn send_message(&self, id: u32, code: u32, message_parts: Vec<MessagePart>) -> Result<(), ErrBox> {
+        if self.is_closed.load(Ordering::SeqCst) {
+            return err!("The process plugin has exited.");
+        }
+        let mut writer = self.writer.lock().unwrap();
+        write_multiplexed_message(&mut *writer, id, code, message_parts)
+    }
+}
+
+fn get_message_response_parts(mut message: MultiplexedMessage) -> Result<ReadMessageParts, ErrBox> {
+    match message.code.into() {
+        ResponseKind::Success => Ok(message.parts),
+        ResponseKind::Error => err!("{}", message.parts.take_string()?),
+    }
+}
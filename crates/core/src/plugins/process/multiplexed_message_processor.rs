@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::io::{Stdin, Stdout};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use serde::Serialize;
+
+use crate::configuration::{ConfigKeyMap, GlobalConfiguration};
+use crate::types::ErrBox;
+use crate::plugins::{PluginHandler, PluginCapabilities, CancellationToken};
+use super::{MessageKind, HostFormatResult, ResponseKind, MessagePart, PLUGIN_SCHEMA_VERSION,
+    MultiplexedMessage, read_multiplexed_message, write_multiplexed_message};
+use super::message_processor::{MessageProcessorState, create_stdio_messenger, handle_request_response_messages,
+    get_format_response_parts, get_host_format_request_parts};
+
+type SharedWriter = Arc<Mutex<Stdout>>;
+type HostFormatSenders = Arc<Mutex<HashMap<u32, Sender<MultiplexedMessage>>>>;
+type CancellationTokens = Arc<Mutex<HashMap<u32, CancellationToken>>>;
+
+/// Handles the process' messages, formatting many files at once on separate threads
+/// when the host supports the multiplexed protocol mode.
+///
+/// A handler is created for each formatting thread, so one process can be used
+/// by the host instead of starting a process for each formatting thread.
+pub fn handle_process_stdio_messages_concurrently<
+    THandler: PluginHandler<TConfiguration> + Send + 'static,
+    TConfiguration: Clone + Serialize + 'static,
+>(
+    create_handler: impl Fn() -> THandler + Send + Sync + 'static,
+) -> Result<(), ErrBox> {
+    let mut handler = create_handler();
+    let mut messenger = create_stdio_messenger();
+    let mut state = MessageProcessorState::new(PluginCapabilities {
+        multiplexed: true,
+        cancellation: true,
+        ..PluginCapabilities::SUPPORTED
+    });
+
+    // start with the request/response mode until the handshake says otherwise
+    if handle_request_response_messages(&mut messenger, &mut handler, &mut state)? {
+        let (reader, writer) = messenger.into_inner();
+        handle_multiplexed_messages(reader, writer, handler, state, create_handler)
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct SharedConfig {
+    global_config: Option<GlobalConfiguration>,
+    config: Option<ConfigKeyMap>,
+    /// Incremented whenever the configuration changes so the formatting threads know to resolve it again.
+    version: usize,
+}
+
+struct FormatJob {
+    message: MultiplexedMessage,
+    host_format_receiver: Receiver<MultiplexedMessage>,
+    cancellation_token: CancellationToken,
+}
+
+fn handle_multiplexed_messages<
+    THandler: PluginHandler<TConfiguration> + Send + 'static,
+    TConfiguration: Clone + Serialize + 'static,
+>(
+    mut reader: Stdin,
+    writer: Stdout,
+    mut handler: THandler,
+    mut state: MessageProcessorState<TConfiguration>,
+    create_handler: impl Fn() -> THandler + Send + Sync + 'static,
+) -> Result<(), ErrBox> {
+    let writer: SharedWriter = Arc::new(Mutex::new(writer));
+    let create_handler = Arc::new(create_handler);
+    let shared_config = Arc::new(RwLock::new(SharedConfig {
+        global_config: state.config_state.global_config.clone(),
+        config: state.config_state.config.clone(),
+        version: 0,
+    }));
+    let host_format_senders: HostFormatSenders = Arc::new(Mutex::new(HashMap::new()));
+    let cancellation_tokens: CancellationTokens = Arc::new(Mutex::new(HashMap::new()));
+    let (job_sender, job_receiver) = channel::<FormatJob>();
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+    let idle_thread_count = Arc::new(AtomicUsize::new(0));
+
+    loop {
+        let mut message = read_multiplexed_message(&mut reader)?;
+
+        // route responses of host format requests to the thread that's formatting
+        if let Some(sender) = host_format_senders.lock().unwrap().get(&message.id) {
+            let _ignore = sender.send(message);
+            continue;
+        }
+
+        match message.code.into() {
+            MessageKind::Close => return Ok(()),
+            MessageKind::CancelFormat => {
+                let format_id = message.parts.take_u32()?;
+                if let Some(token) = cancellation_tokens.lock().unwrap().remove(&format_id) {
+                    token.cancel();
+                }
+                // the host won't respond to any more host format requests
+                host_format_senders.lock().unwrap().remove(&format_id);
+            },
+            MessageKind::FormatText => {
+                let (sender, receiver) = channel();
+                let cancellation_token = CancellationToken::new();
+                host_format_senders.lock().unwrap().insert(message.id, sender);
+                cancellation_tokens.lock().unwrap().insert(message.id, cancellation_token.clone());
+                if idle_thread_count.load(Ordering::SeqCst) == 0 {
+                    start_format_thread(FormatThreadContext {
+                        create_handler: create_handler.clone(),
+                        capabilities: state.capabilities,
+                        shared_config: shared_config.clone(),
+                        job_receiver: job_receiver.clone(),
+                        idle_thread_count: idle_thread_count.clone(),
+                        host_format_senders: host_format_senders.clone(),
+                        cancellation_tokens: cancellation_tokens.clone(),
+                        writer: writer.clone(),
+                    });
+                }
+                let job = FormatJob {
+                    message,
+                    host_format_receiver: receiver,
+                    cancellation_token,
+                };
+                if job_sender.send(job).is_err() {
+                    return err!("The formatting threads exited.");
+                }
+            },
+            message_kind => {
+                let id = message.id;
+                let is_config_change = matches!(message_kind, MessageKind::SetGlobalConfig | MessageKind::SetPluginConfig);
+                let result = handle_message_kind(message_kind, &mut message, &mut handler, &mut state);
+                if is_config_change {
+                    let mut shared_config = shared_config.write().unwrap();
+                    shared_config.global_config = state.config_state.global_config.clone();
+                    shared_config.config = state.config_state.config.clone();
+                    shared_config.version += 1;
+                }
+                send_response(&writer, id, result)?;
+            }
+        }
+    }
+}
+
+fn handle_message_kind<TConfiguration: Clone + Serialize, THandler: PluginHandler<TConfiguration>>(
+    message_kind: MessageKind,
+    message: &mut MultiplexedMessage,
+    handler: &mut THandler,
+    state: &mut MessageProcessorState<TConfiguration>,
+) -> Result<Vec<MessagePart<'static>>, ErrBox> {
+    Ok(match message_kind {
+        MessageKind::GetPluginSchemaVersion => vec![PLUGIN_SCHEMA_VERSION.into()],
+        MessageKind::GetPluginInfo => vec![serde_json::to_vec(&handler.get_plugin_info())?.into()],
+        MessageKind::GetLicenseText => vec![handler.get_license_text().into()],
+        MessageKind::SetGlobalConfig => {
+            state.config_state.set_global_config(serde_json::from_slice(&message.parts.take_part()?)?);
+            Vec::new()
+        },
+        MessageKind::SetPluginConfig => {
+            state.config_state.set_plugin_config(serde_json::from_slice(&message.parts.take_part()?)?);
+            Vec::new()
+        },
+        MessageKind::GetResolvedConfig => vec![state.config_state.get_resolved_config_bytes(handler)?.into()],
+        MessageKind::GetConfigDiagnostics => vec![state.config_state.get_config_diagnostics_bytes(handler)?.into()],
+        MessageKind::Handshake => return err!("The handshake was already done."),
+        MessageKind::Close | MessageKind::CancelFormat | MessageKind::FormatText => unreachable!(),
+    })
+}
+
+/// The state a formatting thread shares with the thread reading the messages.
+struct FormatThreadContext<TCreateHandler> {
+    create_handler: Arc<TCreateHandler>,
+    capabilities: PluginCapabilities,
+    shared_config: Arc<RwLock<SharedConfig>>,
+    job_receiver: Arc<Mutex<Receiver<FormatJob>>>,
+    idle_thread_count: Arc<AtomicUsize>,
+    host_format_senders: HostFormatSenders,
+    cancellation_tokens: CancellationTokens,
+    writer: SharedWriter,
+}
+
+fn start_format_thread<
+    THandler: PluginHandler<TConfiguration> + Send + 'static,
+    TConfiguration: Clone + Serialize + 'static,
+    TCreateHandler: Fn() -> THandler + Send + Sync + 'static,
+>(context: FormatThreadContext<TCreateHandler>) {
+    std::thread::spawn(move || {
+        let FormatThreadContext {
+            create_handler,
+            capabilities,
+            shared_config,
+            job_receiver,
+            idle_thread_count,
+            host_format_senders,
+            cancellation_tokens,
+            writer,
+        } = context;
+        let mut handler = create_handler();
+        let mut state = MessageProcessorState::new(capabilities);
+        state.capabilities = capabilities;
+        let mut config_version = None;
+
+        loop {
+            idle_thread_count.fetch_add(1, Ordering::SeqCst);
+            let job = job_receiver.lock().unwrap().recv();
+            idle_thread_count.fetch_sub(1, Ordering::SeqCst);
+            let FormatJob { mut message, host_format_receiver, cancellation_token } = match job {
+                Ok(job) => job,
+                Err(_) => return, // main thread exited
+            };
+
+            // resolve the configuration again if it changed
+            {
+                let shared_config = shared_config.read().unwrap();
+                if config_version != Some(shared_config.version) {
+                    state.config_state.global_config = shared_config.global_config.clone();
+                    state.config_state.config = shared_config.config.clone();
+                    state.config_state.clear_resolved_config();
+                    config_version = Some(shared_config.version);
+                }
+            }
+
+            let id = message.id;
+            handler.set_cancellation_token(cancellation_token);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                format_text(&mut message, &host_format_receiver, &mut handler, &mut state, &writer)
+            }));
+            host_format_senders.lock().unwrap().remove(&id);
+            cancellation_tokens.lock().unwrap().remove(&id);
+            let result = match result {
+                Ok(result) => result,
+                Err(_) => {
+                    // don't reuse a handler that panicked
+                    handler = create_handler();
+                    state.config_state.clear_resolved_config();
+                    Err(err_obj!("The plugin panicked while formatting.").into())
+                }
+            };
+            if send_response(&writer, id, result).is_err() {
+                return; // host exited
+            }
+        }
+    });
+}
+
+fn format_text<TConfiguration: Clone + Serialize, THandler: PluginHandler<TConfiguration>>(
+    message: &mut MultiplexedMessage,
+    host_format_receiver: &Receiver<MultiplexedMessage>,
+    handler: &mut THandler,
+    state: &mut MessageProcessorState<TConfiguration>,
+    writer: &SharedWriter,
+) -> Result<Vec<MessagePart<'static>>, ErrBox> {
+    let file_path = message.parts.take_path_buf()?;
+    let file_text = message.parts.take_string()?;
+    let override_config: ConfigKeyMap = serde_json::from_slice(&message.parts.take_part()?)?;
+    let config = state.config_state.get_format_config(handler, override_config)?;
+
+    let id = message.id;
+    let capabilities = state.capabilities;
+    let result = handler.format_text_with_diagnostics(
+        &file_path,
+        &file_text,
+        &config,
+        |file_path, language, file_text, override_config| {
+            let message_parts = get_host_format_request_parts(&capabilities, file_path, language, &file_text, override_config)?;
+            send_message(writer, id, ResponseKind::Success as u32, message_parts)?;
+            receive_host_format_response(host_format_receiver, file_text)
+        }
+    )?;
+    get_format_response_parts(result, &file_text, &state.capabilities)
+}
+
+fn receive_host_format_response(host_format_receiver: &Receiver<MultiplexedMessage>, file_text: String) -> Result<String, ErrBox> {
+    let mut message = match host_format_receiver.recv() {
+        Ok(message) => message,
+        Err(_) => return err!("The host exited before responding."),
+    };
+    match message.code.into() {
+        HostFormatResult::Change => message.parts.take_string(),
+        HostFormatResult::NoChange => Ok(file_text),
+        HostFormatResult::Error => err!("{}", message.parts.take_string()?),
+    }
+}
+
+fn send_response(writer: &SharedWriter, id: u32, result: Result<Vec<MessagePart>, ErrBox>) -> Result<(), ErrBox> {
+    match result {
+        Ok(message_parts) => send_message(writer, id, ResponseKind::Success as u32, message_parts),
+        Err(err) => send_message(writer, id, ResponseKind::Error as u32, vec![err.to_string().into()]),
+    }
+}
+
+fn send_message(writer: &SharedWriter, id: u32, code: u32, message_parts: Vec<MessagePart>) -> Result<(), ErrBox> {
+    let mut writer = writer.lock().unwrap();
+    write_multiplexed_message(&mut *writer, id, code, message_parts)
+}
@@ -0,0 +1,106 @@
+use std::io::{Read, Write};
+use super::{MessagePart, ReadMessageParts};
+use crate::types::ErrBox;
+
+const SUCCESS_BYTES: &[u8; 4] = &[255, 255, 255, 255];
+
+/// A message sent in the multiplexed protocol mode.
+///
+/// The mode is switched to after the handshake when both the host and plugin support it.
+/// Messages are sent as the id, code, part count, then each part's length and data followed by
+/// the success bytes. Unlike the request/response mode, there's no acknowledgement between chunks
+/// of data so that the messages of many requests may be interleaved.
+///
+/// Requests from the host get a new id and the plugin responds using the same id. When the plugin
+/// requests the host to format text while formatting, it does so using the id of the format request
+/// and the host responds with that id as well.
+pub struct MultiplexedMessage {
+    pub id: u32,
+    pub code: u32,
+    pub parts: ReadMessageParts,
+}
+
+/// Reads a multiplexed message from the reader.
+pub fn read_multiplexed_message(reader: &mut impl Read) -> Result<MultiplexedMessage, ErrBox> {
+    let id = read_u32(reader)?;
+    let code = read_u32(reader)?;
+    let part_count = read_u32(reader)?;
+    let mut parts = Vec::with_capacity(part_count as usize);
+    for _ in 0..part_count {
+        let length = read_u32(reader)? as usize;
+        let mut data = vec![0u8; length];
+        reader.read_exact(&mut data)?;
+        parts.push(data);
+    }
+
+    let mut success_bytes = [0u8; 4];
+    reader.read_exact(&mut success_bytes)?;
+    if &success_bytes != SUCCESS_BYTES {
+        panic!("Catastrophic error reading from process. Did not receive the success bytes at end of message. Found: {:?}", success_bytes)
+    }
+
+    Ok(MultiplexedMessage {
+        id,
+        code,
+        parts: ReadMessageParts::new(parts),
+    })
+}
+
+/// Writes a multiplexed message to the writer.
+///
+/// The entire message is written before flushing, so callers should hold
+/// a lock on the writer while calling this to prevent interleaving bytes.
+pub fn write_multiplexed_message(writer: &mut impl Write, id: u32, code: u32, message_parts: Vec<MessagePart>) -> Result<(), ErrBox> {
+    writer.write_all(&id.to_be_bytes())?;
+    writer.write_all(&code.to_be_bytes())?;
+    writer.write_all(&(message_parts.len() as u32).to_be_bytes())?;
+    for message_part in message_parts {
+        match message_part {
+            MessagePart::Number(value) => {
+                writer.write_all(&4u32.to_be_bytes())?;
+                writer.write_all(&value.to_be_bytes())?;
+            },
+            MessagePart::VariableData(value) => {
+                writer.write_all(&(value.len() as u32).to_be_bytes())?;
+                writer.write_all(&value)?;
+            },
+        }
+    }
+    writer.write_all(SUCCESS_BYTES)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, ErrBox> {
+    let mut int_buf: [u8; 4] = [0; 4];
+    reader.read_exact(&mut int_buf)?;
+    Ok(u32::from_be_bytes(int_buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_write_and_read_messages() {
+        let mut bytes = Vec::new();
+        let large_text = "a".repeat(5000);
+        write_multiplexed_message(&mut bytes, 1, 7, vec![large_text.as_str().into(), 5.into()]).unwrap();
+        write_multiplexed_message(&mut bytes, 2, 0, vec![]).unwrap();
+
+        let mut reader = bytes.as_slice();
+        let mut message = read_multiplexed_message(&mut reader).unwrap();
+        assert_eq!(message.id, 1);
+        assert_eq!(message.code, 7);
+        assert_eq!(message.parts.take_string().unwrap(), large_text);
+        assert_eq!(message.parts.take_u32().unwrap(), 5);
+        assert_eq!(message.parts.take_part().is_err(), true);
+
+        let mut message = read_multiplexed_message(&mut reader).unwrap();
+        assert_eq!(message.id, 2);
+        assert_eq!(message.code, 0);
+        assert_eq!(message.parts.take_part().is_err(), true);
+        assert_eq!(reader.len(), 0);
+    }
+}
@@ -2,7 +2,12 @@ use std::path::Path;
 use std::borrow::Cow;
 
 /// The process plugin schema version.
-pub const PLUGIN_SCHEMA_VERSION: u32 = 3;
+pub const PLUGIN_SCHEMA_VERSION: u32 = 4;
+/// The oldest process plugin schema version the host can communicate with.
+///
+/// Schema version 3 plugins don't support the handshake, so they're
+/// communicated with as if they support no optional capabilities.
+pub const MINIMUM_PLUGIN_SCHEMA_VERSION: u32 = 3;
 
 /// Kinds of messages that process plugins must handle.
 #[derive(Debug)]
@@ -17,6 +22,12 @@ pub enum MessageKind {
     /// Returns a format result part, then a file text part.
     FormatText = 7,
     Close = 8,
+    /// Sends the host information and returns the plugin handshake (schema version 4+).
+    Handshake = 9,
+    /// Cancels the format request with the id in the first part. No response is sent.
+    ///
+    /// Only sent in the multiplexed mode when the cancellation capability was negotiated.
+    CancelFormat = 10,
 }
 
 // todo: generate with a macro
@@ -32,6 +43,8 @@ impl From<u32> for MessageKind {
             6 => MessageKind::GetConfigDiagnostics,
             7 => MessageKind::FormatText,
             8 => MessageKind::Close,
+            9 => MessageKind::Handshake,
+            10 => MessageKind::CancelFormat,
             _ => unreachable!("Unexpected message kind: {}", kind),
         }
     }
@@ -61,6 +74,10 @@ pub enum FormatResult {
     NoChange = 0,
     Change = 1,
     RequestTextFormat = 2,
+    /// Followed by a diagnostics part. Only sent when there are diagnostics.
+    NoChangeWithDiagnostics = 3,
+    /// Followed by a file text part, then a diagnostics part. Only sent when there are diagnostics.
+    ChangeWithDiagnostics = 4,
 }
 
 // todo: generate with a macro
@@ -70,6 +87,8 @@ impl From<u32> for FormatResult {
             0 => FormatResult::NoChange,
             1 => FormatResult::Change,
             2 => FormatResult::RequestTextFormat,
+            3 => FormatResult::NoChangeWithDiagnostics,
+            4 => FormatResult::ChangeWithDiagnostics,
             _ => unreachable!("Unexpected format result: {}", orig),
         }
     }
@@ -153,3 +172,12 @@ impl<'a> From<u32> for MessagePart<'a> {
         MessagePart::Number(value)
     }
 }
+
+/// Gets the language of a host format request, which is empty when not known.
+pub fn get_host_format_language(language: String) -> Option<String> {
+    if language.is_empty() {
+        None
+    } else {
+        Some(language)
+    }
+}
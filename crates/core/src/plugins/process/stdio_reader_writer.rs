@@ -19,6 +19,11 @@ impl<TRead: Read, TWrite: Write> StdIoReaderWriter<TRead, TWrite> {
         }
     }
 
+    /// Gets the underlying reader and writer.
+    pub fn into_inner(self) -> (TRead, TWrite) {
+        (self.reader, self.writer)
+    }
+
     /// Send a u32 value.
     pub fn send_u32(&mut self, value: u32) -> Result<(), ErrBox> {
         self.writer.write_all(&value.to_be_bytes())?;
@@ -2,7 +2,8 @@
 /// when there are any breaking changes.
 pub const PLUGIN_SYSTEM_SCHEMA_VERSION: u32 = 3;
 
-#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+// wasm32-wasi plugins may use this as well since the host provides a sandboxed WASI environment
+#[cfg(target_arch = "wasm32")]
 pub mod macros {
     #[macro_export]
     macro_rules! generate_plugin_code {
@@ -31,7 +32,12 @@ pub mod macros {
 
             // HOST FORMATTING
 
-            fn format_with_host(file_path: &std::path::Path, file_text: String, override_config: &dprint_core::configuration::ConfigKeyMap) -> Result<String, ErrBox> {
+            fn format_with_host(
+                file_path: &std::path::Path,
+                language: Option<&str>,
+                file_text: String,
+                override_config: &dprint_core::configuration::ConfigKeyMap,
+            ) -> Result<String, ErrBox> {
                 #[link(wasm_import_module = "dprint")]
                 extern "C" {
                     fn host_clear_bytes(length: u32);
@@ -56,7 +62,15 @@ pub mod macros {
                     unsafe { host_take_override_config(); }
                 }
 
-                send_string_to_host(file_path.to_string_lossy().to_string());
+                let file_path = file_path.to_string_lossy().to_string();
+                // hosts that support the language take it before the file path, separated by
+                // a nul character, so plugins don't need a new import that older hosts lack
+                match language {
+                    Some(language) if unsafe { CAPABILITIES.get().host_format_language } => {
+                        send_string_to_host(format!("{}\0{}", language, file_path));
+                    }
+                    _ => send_string_to_host(file_path),
+                }
                 unsafe { host_take_file_path(); }
                 send_string_to_host(file_text.clone());
 
@@ -78,6 +92,15 @@ pub mod macros {
                 };
 
                 fn send_string_to_host(text: String) {
+                    if unsafe { CAPABILITIES.get().direct_transfer } {
+                        // the host reads the whole text from this plugin's memory at once
+                        unsafe {
+                            host_clear_bytes(text.len() as u32);
+                            host_read_buffer(text.as_ptr() as u32, text.len() as u32);
+                        }
+                        return;
+                    }
+
                     let mut index = 0;
                     let length = set_shared_bytes_str(text);
                     unsafe { host_clear_bytes(length as u32); }
@@ -90,6 +113,13 @@ pub mod macros {
                 }
 
                 fn get_string_from_host(length: u32) -> String {
+                    if unsafe { CAPABILITIES.get().direct_transfer } {
+                        // the host writes the whole text to this plugin's memory at once
+                        let mut bytes = vec![0; length as usize];
+                        unsafe { host_write_buffer(bytes.as_mut_ptr() as u32, 0, length); }
+                        return String::from_utf8(bytes).unwrap();
+                    }
+
                     let mut index: u32 = 0;
                     clear_shared_bytes(length as usize);
                     while index < length {
@@ -108,6 +138,7 @@ pub mod macros {
             static FILE_PATH: StaticCell<Option<std::path::PathBuf>> = StaticCell::new(None);
             static FORMATTED_TEXT: StaticCell<Option<String>> = StaticCell::new(None);
             static ERROR_TEXT: StaticCell<Option<String>> = StaticCell::new(None);
+            static FORMAT_DIAGNOSTICS: StaticCell<Vec<dprint_core::plugins::FormatDiagnostic>> = StaticCell::new(Vec::new());
 
             #[no_mangle]
             pub fn set_override_config() {
@@ -135,13 +166,17 @@ pub mod macros {
                 let file_path = unsafe { FILE_PATH.get().take().expect("Expected the file path to be set.") };
                 let file_text = take_string_from_shared_bytes();
 
-                let formatted_text = unsafe { WASM_PLUGIN.get().format_text(&file_path, &file_text, &config, format_with_host) };
-                match formatted_text {
-                    Ok(formatted_text) => {
-                        if formatted_text == file_text {
+                let format_result = unsafe { WASM_PLUGIN.get().format_text_with_diagnostics(&file_path, &file_text, &config, format_with_host) };
+                match format_result {
+                    Ok(format_result) => {
+                        // only provide diagnostics to hosts that said they support them
+                        if unsafe { CAPABILITIES.get().diagnostics } {
+                            FORMAT_DIAGNOSTICS.replace(format_result.diagnostics);
+                        }
+                        if format_result.text == file_text {
                             0 // no change
                         } else {
-                            unsafe { FORMATTED_TEXT.get().replace(formatted_text) };
+                            unsafe { FORMATTED_TEXT.get().replace(format_result.text) };
                             1 // change
                         }
                     },
@@ -158,12 +193,46 @@ pub mod macros {
                 set_shared_bytes_str(formatted_text)
             }
 
+            /// Gets the diagnostics of the last successful format as JSON or
+            /// returns 0 when there are none.
+            #[no_mangle]
+            pub fn get_format_diagnostics() -> usize {
+                let diagnostics = FORMAT_DIAGNOSTICS.replace(Vec::new());
+                if diagnostics.is_empty() {
+                    0
+                } else {
+                    set_shared_bytes(serde_json::to_vec(&diagnostics).unwrap())
+                }
+            }
+
             #[no_mangle]
             pub fn get_error_text() -> usize {
                 let error_text = unsafe { ERROR_TEXT.get().take().expect("Expected to have error text.") };
                 set_shared_bytes_str(error_text)
             }
 
+            // HANDSHAKE
+
+            static CAPABILITIES: StaticCell<dprint_core::plugins::PluginCapabilities> = StaticCell::new(dprint_core::plugins::PluginCapabilities::NONE);
+
+            /// Takes the host info from the shared bytes and responds with the plugin handshake.
+            #[no_mangle]
+            pub fn handshake() -> usize {
+                let bytes = take_from_shared_bytes();
+                let host_info: dprint_core::plugins::HostInfo = serde_json::from_slice(&bytes).unwrap();
+                let supported_capabilities = dprint_core::plugins::PluginCapabilities {
+                    direct_transfer: true,
+                    ..dprint_core::plugins::PluginCapabilities::SUPPORTED
+                };
+                let capabilities = supported_capabilities.intersect(&host_info.capabilities);
+                CAPABILITIES.replace(capabilities);
+                let handshake = dprint_core::plugins::PluginHandshake {
+                    capabilities,
+                    minimum_cli_version: unsafe { WASM_PLUGIN.get().get_minimum_cli_version() },
+                };
+                set_shared_bytes(serde_json::to_vec(&handshake).unwrap())
+            }
+
             // INFORMATION & CONFIGURATION
 
             static RESOLVE_CONFIGURATION_RESULT: StaticCell<Option<dprint_core::configuration::ResolveConfigurationResult<Configuration>>> = StaticCell::new(None);
@@ -291,6 +360,21 @@ pub mod macros {
                 SHARED_BYTES.replace(Vec::with_capacity(capacity));
             }
 
+            /// Allocates the shared bytes with the provided length so the host can
+            /// write directly to them instead of going through the memory buffer.
+            #[no_mangle]
+            pub fn allocate_shared_bytes(length: usize) -> *mut u8 {
+                SHARED_BYTES.replace(vec![0; length]);
+                unsafe { SHARED_BYTES.get().as_mut_ptr() }
+            }
+
+            /// Gets a pointer to the shared bytes so the host can read
+            /// directly from them instead of going through the memory buffer.
+            #[no_mangle]
+            pub fn get_shared_bytes_ptr() -> *const u8 {
+                unsafe { SHARED_BYTES.get().as_ptr() }
+            }
+
             fn take_string_from_shared_bytes() -> String {
                 String::from_utf8(take_from_shared_bytes()).unwrap()
             }
@@ -38,95 +38,99 @@ impl<TEnvironment> Cache<TEnvironment> where TEnvironment : Environment {
     }
 
     pub fn create_cache_item<'b>(&self, options: CreateCacheItemOptions<'b>) -> Result<CacheItem, ErrBox> {
-        let file_name = self.get_file_name_from_key(&options.key, &options.extension);
-        let cache_item = CacheItem {
-            file_name,
-            created_time: self.environment.get_time_secs(),
-            meta_data: options.meta_data,
-        };
-
-        if let Some(bytes) = options.bytes {
-            let file_path = self.resolve_cache_item_file_path(&cache_item);
-            self.environment.write_file_bytes(&file_path, bytes)?;
-        }
+        self.update_manifest(|manifest| {
+            // get the file name from the latest manifest so it's unique among other processes
+            let file_name = get_file_name_from_key(manifest, &options.key, &options.extension);
+            let cache_item = CacheItem {
+                file_name,
+                created_time: self.environment.get_time_secs(),
+                meta_data: options.meta_data,
+            };
 
-        self.cache_manifest.write().add_item(options.key, cache_item.clone());
-        self.save_manifest()?;
+            if let Some(bytes) = options.bytes {
+                let file_path = self.resolve_cache_item_file_path(&cache_item);
+                self.environment.write_file_bytes(&file_path, bytes)?;
+            }
 
-        Ok(cache_item)
+            manifest.add_item(options.key, cache_item.clone());
+            Ok(cache_item)
+        })
     }
 
-    #[allow(dead_code)]
     pub fn forget_item(&self, key: &str) -> Result<(), ErrBox> {
-        if let Some(item) = self.cache_manifest.write().remove_item(key) {
+        if self.cache_manifest.read().get_item(key).is_none() {
+            return Ok(());
+        }
+
+        if let Some(item) = self.update_manifest(|manifest| Ok(manifest.remove_item(key)))? {
             let cache_file = self.cache_dir_path.join(&item.file_name);
             match self.environment.remove_file(&cache_file) {
                 _ => {}, // do nothing on success or failure
             }
-        } else {
-            return Ok(());
         }
 
-        // do this outside to prevent a borrow while mutably borrowing
-        self.save_manifest()?;
-
         Ok(())
     }
 
-    fn get_file_name_from_key(&self, key: &str, extension: &str) -> String {
-        return self.get_unique_file_name(&get_starting_file_name(key), extension);
-
-        fn get_starting_file_name(key: &str) -> String {
-            // try to get some kind of readable file name based on the key
-            let mut file_name = Vec::new();
-            for c in key.chars().rev() {
-                if c.is_alphanumeric() || c == '-' || c == '.' {
-                    file_name.push(c);
-                } else if !file_name.is_empty() {
-                    break;
-                }
-            }
-            file_name.reverse();
-
-            let file_name = file_name.into_iter().collect::<String>();
-            let standard_name = "temp-cache-item";
-            let file_name = PathBuf::from(if file_name.is_empty() { String::from(standard_name) } else { file_name });
-            match file_name.file_stem() {
-                Some(file_stem) => file_stem.to_str().unwrap_or(standard_name).to_string(),
-                None => standard_name.to_string()
-            }
-        }
+    /// Applies the change to the manifest on the file system, then
+    /// stores the result that may include changes from other processes.
+    fn update_manifest<TResult>(&self, action: impl FnOnce(&mut CacheManifest) -> Result<TResult, ErrBox>) -> Result<TResult, ErrBox> {
+        let mut cache_manifest = self.cache_manifest.write();
+        let (updated_manifest, result) = update_manifest(&self.environment, action)?;
+        *cache_manifest = updated_manifest;
+        Ok(result)
     }
+}
 
-    fn get_unique_file_name(&self, prefix: &str, extension: &str) -> String {
-        let mut index = 1;
-        loop {
-            let file_name_with_ext = if index == 1 {
-                get_file_name_with_ext(prefix, extension)
-            } else {
-                get_file_name_with_ext(&format!("{}_{}", prefix, index), extension)
-            };
-            if self.has_file_name_cache_item(&file_name_with_ext) {
-                index += 1;
-            } else {
-                return file_name_with_ext;
+fn get_file_name_from_key(manifest: &CacheManifest, key: &str, extension: &str) -> String {
+    return get_unique_file_name(manifest, &get_starting_file_name(key), extension);
+
+    fn get_starting_file_name(key: &str) -> String {
+        // try to get some kind of readable file name based on the key
+        let mut file_name = Vec::new();
+        for c in key.chars().rev() {
+            if c.is_alphanumeric() || c == '-' || c == '.' {
+                file_name.push(c);
+            } else if !file_name.is_empty() {
+                break;
             }
         }
-
-        fn get_file_name_with_ext(file_name: &str, extension: &str) -> String {
-            format!("{}.{}", file_name, extension)
+        file_name.reverse();
+
+        let file_name = file_name.into_iter().collect::<String>();
+        let standard_name = "temp-cache-item";
+        let file_name = PathBuf::from(if file_name.is_empty() { String::from(standard_name) } else { file_name });
+        match file_name.file_stem() {
+            Some(file_stem) => file_stem.to_str().unwrap_or(standard_name).to_string(),
+            None => standard_name.to_string()
         }
     }
+}
 
-    fn has_file_name_cache_item(&self, file_name: &str) -> bool {
-        self.cache_manifest.read().items().filter(|u| u.file_name == file_name).next().is_some()
+fn get_unique_file_name(manifest: &CacheManifest, prefix: &str, extension: &str) -> String {
+    let mut index = 1;
+    loop {
+        let file_name_with_ext = if index == 1 {
+            get_file_name_with_ext(prefix, extension)
+        } else {
+            get_file_name_with_ext(&format!("{}_{}", prefix, index), extension)
+        };
+        if has_file_name_cache_item(manifest, &file_name_with_ext) {
+            index += 1;
+        } else {
+            return file_name_with_ext;
+        }
     }
 
-    fn save_manifest(&self) -> Result<(), ErrBox> {
-        write_manifest(&self.cache_manifest.read(), &self.environment)
+    fn get_file_name_with_ext(file_name: &str, extension: &str) -> String {
+        format!("{}.{}", file_name, extension)
     }
 }
 
+fn has_file_name_cache_item(manifest: &CacheManifest, file_name: &str) -> bool {
+    manifest.items().filter(|u| u.file_name == file_name).next().is_some()
+}
+
 #[cfg(test)]
 mod test {
     use crate::environment::TestEnvironment;
@@ -171,6 +175,32 @@ mod test {
         assert_eq!(cache_item2.file_name, "test_2.test");
     }
 
+    #[test]
+    fn it_should_keep_items_cached_by_other_processes() {
+        let environment = TestEnvironment::new();
+        let cache = Cache::new(environment.clone());
+
+        // another process creates an item after this one read the manifest
+        environment.write_file(
+            &environment.get_cache_dir().join("cache-manifest.json"),
+            r#"{ "other/test": { "fileName": "test.test", "createdTime": 123 } }"#
+        ).unwrap();
+
+        let cache_item = cache.create_cache_item(CreateCacheItemOptions {
+            key: String::from("prefix/test"),
+            extension: "test",
+            bytes: Some("t".as_bytes()),
+            meta_data: None,
+        }).unwrap();
+        // should not reuse the other process' file name
+        assert_eq!(cache_item.file_name, "test_2.test");
+        assert_eq!(cache.get_cache_item("other/test").unwrap().file_name, "test.test");
+
+        let manifest_text = environment.read_file(&environment.get_cache_dir().join("cache-manifest.json")).unwrap();
+        assert!(manifest_text.contains("other/test"));
+        assert!(manifest_text.contains("prefix/test"));
+    }
+
     #[test]
     fn it_should_delete_key_from_manifest_when_no_file() {
         let environment = TestEnvironment::new();
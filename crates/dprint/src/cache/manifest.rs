@@ -5,6 +5,7 @@ use std::path::PathBuf;
 use dprint_core::types::ErrBox;
 
 use crate::environment::Environment;
+use crate::utils::atomic_write_file;
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct CacheManifest(HashMap<String, CacheItem>);
@@ -47,18 +48,44 @@ pub fn read_manifest(environment: &impl Environment) -> CacheManifest {
         Ok(text) => match serde_json::from_str(&text) {
             Ok(manifest) => manifest,
             Err(err) => {
-                environment.log_error(&format!("Resetting cache manifest. Message: {}", err));
-                CacheManifest::new()
+                environment.log_error(&format!("Recovering cache manifest. Message: {}", err));
+                recover_manifest(&text)
             }
         },
         Err(_) => CacheManifest::new(),
     }
 }
 
+/// Gets the items that can still be deserialized from a manifest that failed to deserialize.
+fn recover_manifest(text: &str) -> CacheManifest {
+    let mut manifest = CacheManifest::new();
+    if let Ok(serde_json::Value::Object(items)) = serde_json::from_str::<serde_json::Value>(text) {
+        for (key, value) in items {
+            if let Ok(item) = serde_json::from_value::<CacheItem>(value) {
+                manifest.add_item(key, item);
+            }
+        }
+    }
+    manifest
+}
+
 pub fn write_manifest(manifest: &CacheManifest, environment: &impl Environment) -> Result<(), ErrBox> {
     let file_path = get_manifest_file_path(environment);
     let serialized_manifest = serde_json::to_string(&manifest)?;
-    environment.write_file(&file_path, &serialized_manifest)
+    atomic_write_file(&file_path, &serialized_manifest, environment)
+}
+
+/// Re-reads the manifest while holding a lock on it, applies the change, then writes it
+/// back so that changes made by other dprint processes since it was last read aren't lost.
+pub fn update_manifest<TEnvironment: Environment, TResult>(
+    environment: &TEnvironment,
+    action: impl FnOnce(&mut CacheManifest) -> Result<TResult, ErrBox>,
+) -> Result<(CacheManifest, TResult), ErrBox> {
+    let _file_lock = environment.lock_file(&get_manifest_lock_file_path(environment))?;
+    let mut manifest = read_manifest(environment);
+    let result = action(&mut manifest)?;
+    write_manifest(&manifest, environment)?;
+    Ok((manifest, result))
 }
 
 fn get_manifest_file_path(environment: &impl Environment) -> PathBuf {
@@ -66,6 +93,11 @@ fn get_manifest_file_path(environment: &impl Environment) -> PathBuf {
     cache_dir.join("cache-manifest.json")
 }
 
+fn get_manifest_lock_file_path(environment: &impl Environment) -> PathBuf {
+    let cache_dir = environment.get_cache_dir();
+    cache_dir.join("cache-manifest.json.lock")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -114,10 +146,28 @@ mod test {
 
         assert_eq!(read_manifest(&environment), CacheManifest::new());
         assert_eq!(environment.take_logged_errors(), vec![
-            String::from("Resetting cache manifest. Message: key must be a string at line 1 column 10")
+            String::from("Recovering cache manifest. Message: key must be a string at line 1 column 10")
         ]);
     }
 
+    #[test]
+    fn it_should_recover_readable_items_of_manifest() {
+        let environment = TestEnvironment::new();
+        environment.write_file(
+            &environment.get_cache_dir().join("cache-manifest.json"),
+            r#"{ "a": { "fileName": "b", "createdTime": 123 }, "c": { "fileName": 5 } }"#
+        ).unwrap();
+
+        let mut expected_manifest = CacheManifest::new();
+        expected_manifest.add_item(String::from("a"), CacheItem {
+            file_name: String::from("b"),
+            created_time: 123,
+            meta_data: None,
+        });
+        assert_eq!(read_manifest(&environment), expected_manifest);
+        assert_eq!(environment.take_logged_errors().len(), 1);
+    }
+
     #[test]
     fn it_should_deal_with_non_existent_manifest() {
         let environment = TestEnvironment::new();
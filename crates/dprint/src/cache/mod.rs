@@ -2,4 +2,3 @@ mod cache;
 mod manifest;
 
 pub use cache::*;
-pub use manifest::*;
@@ -7,12 +7,17 @@ pub struct CliArgs {
     pub verbose: bool,
     pub plugins: Vec<String>,
     pub config: Option<String>,
+    pub frozen: bool,
+    pub cache_dir: Option<PathBuf>,
     // It depends on the command whether these will exist... it
     // was just a lot easier to store these on a global object.
     pub incremental: bool,
     pub file_patterns: Vec<String>,
     pub exclude_file_patterns: Vec<String>,
     pub allow_node_modules: bool,
+    pub use_gitignore: bool,
+    pub diff_context_lines: Option<usize>,
+    pub reporter: Reporter,
 }
 
 impl CliArgs {
@@ -23,14 +28,27 @@ impl CliArgs {
         }
     }
 
+    /// Gets if the sub command formats files, which is cancelled when the process is interrupted.
+    pub fn is_cancellable(&self) -> bool {
+        match self.sub_command {
+            SubCommand::Check | SubCommand::Fmt | SubCommand::OutputFormatTimes | SubCommand::StdInFmt(..) => true,
+            _ => false
+        }
+    }
+
     fn new_with_sub_command(sub_command: SubCommand) -> CliArgs {
         CliArgs {
             sub_command,
             verbose: false,
             config: None,
+            frozen: false,
+            cache_dir: None,
             plugins: Vec::new(),
             incremental: false,
             allow_node_modules: false,
+            use_gitignore: false,
+            diff_context_lines: None,
+            reporter: Reporter::Default,
             file_patterns: Vec::new(),
             exclude_file_patterns: Vec::new(),
         }
@@ -46,6 +64,14 @@ pub enum SubCommand {
     OutputFilePaths,
     OutputResolvedConfig,
     OutputFormatTimes,
+    ConfigValidate,
+    PluginsExport(PathBuf),
+    PluginsImport(PathBuf),
+    PluginsList,
+    PluginsOutdated,
+    PluginsUpdate,
+    PluginsRemove(String),
+    PluginsPrune(u64),
     Version,
     License,
     Help(String),
@@ -56,6 +82,15 @@ pub enum SubCommand {
     Hidden(HiddenSubCommand),
 }
 
+/// How the diagnostics plugins report while formatting are output.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Reporter {
+    /// Displays each diagnostic as text with the line and column of its range.
+    Default,
+    /// Outputs each diagnostic as a JSON object on its own line of stdout.
+    Json,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct EditorServiceSubCommand {
     pub parent_pid: u32,
@@ -117,6 +152,30 @@ pub fn parse_args<TStdInReader: StdInReader>(args: Vec<String>, std_in_reader: &
         ("output-file-paths", _) => SubCommand::OutputFilePaths,
         ("output-resolved-config", _) => SubCommand::OutputResolvedConfig,
         ("output-format-times", _) => SubCommand::OutputFormatTimes,
+        ("config", Some(matches)) => {
+            match matches.subcommand() {
+                ("validate", _) => SubCommand::ConfigValidate,
+                _ => unreachable!(),
+            }
+        },
+        ("plugins", Some(matches)) => {
+            match matches.subcommand() {
+                ("export", Some(matches)) => SubCommand::PluginsExport(PathBuf::from(matches.value_of("archive").unwrap())),
+                ("import", Some(matches)) => SubCommand::PluginsImport(PathBuf::from(matches.value_of("archive").unwrap())),
+                ("list", _) => SubCommand::PluginsList,
+                ("outdated", _) => SubCommand::PluginsOutdated,
+                ("update", _) => SubCommand::PluginsUpdate,
+                ("remove", Some(matches)) => SubCommand::PluginsRemove(matches.value_of("name").map(String::from).unwrap()),
+                ("prune", Some(matches)) => {
+                    let unused_days = matches.value_of("unused-days").unwrap();
+                    match unused_days.parse::<u64>() {
+                        Ok(unused_days) => SubCommand::PluginsPrune(unused_days),
+                        Err(_) => return err!("Expected --unused-days to be a number of days, but found: {}", unused_days),
+                    }
+                },
+                _ => unreachable!(),
+            }
+        },
         ("version", _) => SubCommand::Version,
         ("license", _) => SubCommand::License,
         ("editor-info", _) => SubCommand::EditorInfo,
@@ -139,14 +198,30 @@ pub fn parse_args<TStdInReader: StdInReader>(args: Vec<String>, std_in_reader: &
         (_, Some(matches)) => Some(matches),
         _ => None,
     };
+    let diff_context_lines = match sub_command_matches.map(|m| m.value_of("diff-context-lines")).flatten() {
+        Some(diff_context_lines) => match diff_context_lines.parse::<usize>() {
+            Ok(diff_context_lines) => Some(diff_context_lines),
+            Err(_) => return err!("Expected --diff-context-lines to be a number of lines, but found: {}", diff_context_lines),
+        },
+        None => None,
+    };
+    let reporter = match sub_command_matches.map(|m| m.value_of("reporter")).flatten() {
+        Some("json") => Reporter::Json,
+        _ => Reporter::Default,
+    };
 
     Ok(CliArgs {
         sub_command,
         verbose: matches.is_present("verbose"),
         config: matches.value_of("config").map(String::from),
         plugins: values_to_vec(matches.values_of("plugins")),
+        frozen: matches.is_present("frozen"),
+        cache_dir: matches.value_of("cache-dir").map(PathBuf::from),
         incremental: sub_command_matches.map(|m| m.is_present("incremental")).unwrap_or(false),
         allow_node_modules: sub_command_matches.map(|m| m.is_present("allow-node-modules")).unwrap_or(false),
+        use_gitignore: sub_command_matches.map(|m| m.is_present("use-gitignore")).unwrap_or(false),
+        diff_context_lines,
+        reporter,
         file_patterns: sub_command_matches.map(|m| values_to_vec(m.values_of("files"))).unwrap_or(Vec::new()),
         exclude_file_patterns: sub_command_matches.map(|m| values_to_vec(m.values_of("excludes"))).unwrap_or(Vec::new()),
     })
@@ -229,6 +304,7 @@ EXAMPLES:
                 .about("Formats the source files and writes the result to the file system.")
                 .add_resolve_file_path_args()
                 .add_incremental_arg()
+                .add_reporter_arg()
                 .arg(
                     Arg::with_name("stdin")
                         .long("stdin")
@@ -243,6 +319,14 @@ EXAMPLES:
                 .about("Checks for any files that haven't been formatted.")
                 .add_resolve_file_path_args()
                 .add_incremental_arg()
+                .add_reporter_arg()
+                .arg(
+                    Arg::with_name("diff-context-lines")
+                        .long("diff-context-lines")
+                        .value_name("LINES")
+                        .help("Number of unchanged lines to show before and after each difference. Defaults to 2.")
+                        .takes_value(true)
+                )
         )
         .subcommand(
             SubCommand::with_name("output-file-paths")
@@ -258,6 +342,74 @@ EXAMPLES:
                 .about("Prints the amount of time it takes to format each file. Use this for debugging.")
                 .add_resolve_file_path_args()
         )
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("Commands for working with the configuration file.")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("validate")
+                        .about("Checks the configuration of every plugin, including plugins that have no files to format.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("plugins")
+                .about("Commands for working with plugins.")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("export")
+                        .about("Writes the remote plugins and remote configuration files to an archive for use without internet access.")
+                        .arg(
+                            Arg::with_name("archive")
+                                .help("File path of the archive to write.")
+                                .takes_value(true)
+                                .required(true)
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("import")
+                        .about("Installs the plugins and configuration files of an archive created by `dprint plugins export` into the cache.")
+                        .arg(
+                            Arg::with_name("archive")
+                                .help("File path of the archive to import.")
+                                .takes_value(true)
+                                .required(true)
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("Lists the plugins stored in the cache.")
+                )
+                .subcommand(
+                    SubCommand::with_name("outdated")
+                        .about("Lists the configured plugins that have a newer version available.")
+                )
+                .subcommand(
+                    SubCommand::with_name("update")
+                        .about("Downloads the configured plugins again and records their checksums in dprint.lock, creating it if necessary.")
+                )
+                .subcommand(
+                    SubCommand::with_name("remove")
+                        .about("Removes every cached version of a plugin from the cache.")
+                        .arg(
+                            Arg::with_name("name")
+                                .help("Name of the plugin to remove (ex. dprint-plugin-typescript).")
+                                .takes_value(true)
+                                .required(true)
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("prune")
+                        .about("Removes the cached plugins that haven't been used recently.")
+                        .arg(
+                            Arg::with_name("unused-days")
+                                .long("unused-days")
+                                .help("Removes plugins that haven't been used in this number of days.")
+                                .value_name("DAYS")
+                                .takes_value(true)
+                                .default_value("30")
+                        )
+                )
+        )
         .subcommand(
             SubCommand::with_name("clear-cache")
                 .about("Deletes the plugin cache directory.")
@@ -297,6 +449,21 @@ EXAMPLES:
                 .takes_value(true)
                 .multiple(true),
         )
+        .arg(
+            Arg::with_name("frozen")
+                .long("frozen")
+                .help("Errors if plugins would resolve differently than what is recorded in dprint.lock.")
+                .global(true)
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("cache-dir")
+                .long("cache-dir")
+                .help("Directory to store cached plugins and remote configuration files in. Defaults to the DPRINT_CACHE_DIR environment variable or the system cache directory.")
+                .value_name("path")
+                .global(true)
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("verbose")
                 .long("verbose")
@@ -336,6 +503,7 @@ EXAMPLES:
 trait ClapExtensions {
     fn add_resolve_file_path_args(self) -> Self;
     fn add_incremental_arg(self) -> Self;
+    fn add_reporter_arg(self) -> Self;
 }
 
 impl<'a, 'b> ClapExtensions for clap::App<'a, 'b> {
@@ -361,6 +529,12 @@ impl<'a, 'b> ClapExtensions for clap::App<'a, 'b> {
                 .help("Allows traversing node module directories (unstable - This flag will be renamed to be non-node specific in the future).")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("use-gitignore")
+                .long("use-gitignore")
+                .help("Skips files ignored by .gitignore files and .git/info/exclude. This may alternatively be specified in the configuration file.")
+                .takes_value(false),
+        )
     }
 
     fn add_incremental_arg(self) -> Self {
@@ -372,4 +546,16 @@ impl<'a, 'b> ClapExtensions for clap::App<'a, 'b> {
                 .takes_value(false),
         )
     }
+
+    fn add_reporter_arg(self) -> Self {
+        use clap::Arg;
+        self.arg(
+            Arg::with_name("reporter")
+                .long("reporter")
+                .value_name("REPORTER")
+                .help("How to output the diagnostics of plugins. Use `json` to output each one as a JSON object on its own line of stdout.")
+                .possible_values(&["default", "json"])
+                .takes_value(true),
+        )
+    }
 }
@@ -3,12 +3,13 @@ use std::collections::HashMap;
 use crossterm::style::Stylize;
 use dprint_core::configuration::ConfigKeyValue;
 use dprint_core::types::ErrBox;
+use url::Url;
 
 use crate::cache::Cache;
 use crate::configuration::{ConfigMap, ConfigMapValue, deserialize_config};
 use crate::cli::CliArgs;
 use crate::environment::Environment;
-use crate::plugins::{PluginSourceReference, parse_plugin_source_reference};
+use crate::plugins::{DEFAULT_MAX_HOST_FORMAT_DEPTH, PluginSignaturePolicy, PluginSourceReference, parse_plugin_source_reference, verify_public_key_text};
 use crate::utils::{ResolvedPath, resolve_url_or_file_path, PathSource};
 
 use super::resolve_main_config_path;
@@ -22,6 +23,17 @@ pub struct ResolvedConfig {
     pub excludes: Vec<String>,
     pub plugins: Vec<PluginSourceReference>,
     pub incremental: bool,
+    /// Where to store the incremental data instead of the cache directory.
+    pub incremental_cache_path: Option<PathBuf>,
+    pub use_gitignore: bool,
+    /// Interpreter names in shebangs mapped to the file extension of the language.
+    pub interpreters: HashMap<String, String>,
+    /// Number of nested host format requests allowed between plugins.
+    pub max_host_format_depth: usize,
+    /// Urls of the remote configuration files this configuration was resolved from.
+    pub remote_config_urls: Vec<Url>,
+    /// How the signatures of plugins should be verified.
+    pub plugin_signature_policy: PluginSignaturePolicy,
     pub config_map: ConfigMap,
 }
 
@@ -79,6 +91,9 @@ pub fn resolve_config_from_args<TEnvironment : Environment>(
         // Careful! Don't be fancy and ensure both of these are removed.
         let removed_includes = main_config_map.remove("includes").is_some(); // NEVER REMOVE THIS STATEMENT
         let removed_excludes = main_config_map.remove("excludes").is_some(); // NEVER REMOVE THIS STATEMENT
+        main_config_map.remove("incrementalCachePath"); // NEVER REMOVE THIS STATEMENT
+        main_config_map.remove("trustedPluginKeys"); // NEVER REMOVE THIS STATEMENT
+        main_config_map.remove("requireSignedProcessPlugins"); // NEVER REMOVE THIS STATEMENT
         let was_removed = removed_includes || removed_excludes;
         if was_removed && resolved_config_path.resolved_path.is_first_download {
             environment.log_error(&get_warn_includes_excludes_message());
@@ -89,8 +104,17 @@ pub fn resolve_config_from_args<TEnvironment : Environment>(
     let includes = take_array_from_config_map(&mut main_config_map, "includes")?;
     let excludes = take_array_from_config_map(&mut main_config_map, "excludes")?;
     let incremental = take_bool_from_config_map(&mut main_config_map, "incremental", false)?;
+    let incremental_cache_path = take_string_from_config_map(&mut main_config_map, "incrementalCachePath")?
+        .map(|path| resolved_config_path.base_path.join(path));
+    let use_gitignore = take_bool_from_config_map(&mut main_config_map, "useGitignore", false)?;
+    let interpreters = take_string_map_from_config_map(&mut main_config_map, "interpreters")?;
+    let max_host_format_depth = take_number_from_config_map(&mut main_config_map, "maxHostFormatDepth")?
+        .map(|depth| depth as usize)
+        .unwrap_or(DEFAULT_MAX_HOST_FORMAT_DEPTH);
+    let plugin_signature_policy = take_plugin_signature_policy(&mut main_config_map)?;
     main_config_map.remove("projectType"); // this was an old config property that's no longer used
     let extends = take_extends(&mut main_config_map)?;
+    let remote_config_urls = get_remote_url(&resolved_config_path.resolved_path).into_iter().collect();
     let mut resolved_config = ResolvedConfig {
         resolved_path: resolved_config_path.resolved_path,
         base_path: resolved_config_path.base_path,
@@ -99,6 +123,12 @@ pub fn resolve_config_from_args<TEnvironment : Environment>(
         excludes,
         plugins,
         incremental,
+        incremental_cache_path,
+        use_gitignore,
+        interpreters,
+        max_host_format_depth,
+        remote_config_urls,
+        plugin_signature_policy,
     };
 
     // resolve extends
@@ -125,6 +155,13 @@ fn resolve_extends<TEnvironment : Environment>(
     Ok(())
 }
 
+fn get_remote_url(resolved_path: &ResolvedPath) -> Option<Url> {
+    match &resolved_path.source {
+        PathSource::Remote(remote_source) => Some(remote_source.url.clone()),
+        PathSource::Local(_) => None,
+    }
+}
+
 fn handle_config_file<'a, TEnvironment : Environment>(
     resolved_path: &ResolvedPath,
     resolved_config: &mut ResolvedConfig,
@@ -137,6 +174,7 @@ fn handle_config_file<'a, TEnvironment : Environment>(
         Err(err) => return Err(err),
     };
     let extends = take_extends(&mut new_config_map)?;
+    resolved_config.remote_config_urls.extend(get_remote_url(resolved_path));
 
     // Discard any properties that shouldn't be inherited
     new_config_map.remove("projectType");
@@ -148,6 +186,10 @@ fn handle_config_file<'a, TEnvironment : Environment>(
     // control over what files get formatted.
     new_config_map.remove("includes"); // NEVER REMOVE THIS STATEMENT
     new_config_map.remove("excludes"); // NEVER REMOVE THIS STATEMENT
+    new_config_map.remove("incrementalCachePath"); // NEVER REMOVE THIS STATEMENT
+    // Only the main configuration file may decide which plugin publishers are trusted.
+    new_config_map.remove("trustedPluginKeys"); // NEVER REMOVE THIS STATEMENT
+    new_config_map.remove("requireSignedProcessPlugins"); // NEVER REMOVE THIS STATEMENT
     // Also remove any non-wasm plugins, but only for remote configurations.
     // The assumption here is that the user won't be malicious to themselves.
     let plugins = take_plugins_array_from_config_map(&mut new_config_map, &resolved_path.source.parent())?;
@@ -240,6 +282,20 @@ fn take_plugins_array_from_config_map(config_map: &mut ConfigMap, base_path: &Pa
     Ok(plugins)
 }
 
+fn take_plugin_signature_policy(config_map: &mut ConfigMap) -> Result<PluginSignaturePolicy, ErrBox> {
+    let trusted_public_keys = take_array_from_config_map(config_map, "trustedPluginKeys")?;
+    for public_key in trusted_public_keys.iter() {
+        if let Err(err) = verify_public_key_text(public_key) {
+            return err!("Error in 'trustedPluginKeys' property. {}", err.to_string());
+        }
+    }
+    let require_signed_process_plugins = take_bool_from_config_map(config_map, "requireSignedProcessPlugins", false)?;
+    Ok(PluginSignaturePolicy {
+        trusted_public_keys,
+        require_signed_process_plugins,
+    })
+}
+
 fn take_array_from_config_map(config_map: &mut ConfigMap, property_name: &str) -> Result<Vec<String>, ErrBox> {
     let mut result = Vec::new();
     if let Some(value) = config_map.remove(property_name) {
@@ -266,6 +322,42 @@ fn take_bool_from_config_map(config_map: &mut ConfigMap, property_name: &str, de
     Ok(result)
 }
 
+fn take_string_from_config_map(config_map: &mut ConfigMap, property_name: &str) -> Result<Option<String>, ErrBox> {
+    match config_map.remove(property_name) {
+        Some(ConfigMapValue::KeyValue(ConfigKeyValue::String(value))) => Ok(Some(value)),
+        Some(_) => err!("Expected string in '{}' property.", property_name),
+        None => Ok(None),
+    }
+}
+
+fn take_number_from_config_map(config_map: &mut ConfigMap, property_name: &str) -> Result<Option<u32>, ErrBox> {
+    match config_map.remove(property_name) {
+        Some(ConfigMapValue::KeyValue(ConfigKeyValue::Number(value))) if value >= 0 => Ok(Some(value as u32)),
+        Some(_) => err!("Expected non-negative number in '{}' property.", property_name),
+        None => Ok(None),
+    }
+}
+
+fn take_string_map_from_config_map(config_map: &mut ConfigMap, property_name: &str) -> Result<HashMap<String, String>, ErrBox> {
+    let mut result = HashMap::new();
+    if let Some(value) = config_map.remove(property_name) {
+        match value {
+            ConfigMapValue::HashMap(obj) => {
+                for (key, value) in obj {
+                    match value {
+                        ConfigKeyValue::String(value) => {
+                            result.insert(key, value);
+                        },
+                        _ => return err!("Expected string value for '{}' in '{}' property.", key, property_name),
+                    }
+                }
+            },
+            _ => return err!("Expected object in '{}' property.", property_name),
+        }
+    }
+    Ok(result)
+}
+
 fn filter_non_wasm_plugins(plugins: Vec<PluginSourceReference>, environment: &impl Environment) -> Vec<PluginSourceReference> {
     if plugins.iter().any(|plugin| !plugin.is_wasm_plugin()) {
         environment.log_error(&get_warn_non_wasm_plugins_message());
@@ -935,6 +1027,192 @@ mod tests {
         assert_eq!(result.incremental, false);
     }
 
+    #[test]
+    fn it_should_get_incremental_cache_path_relative_to_base_path() {
+        let environment = TestEnvironment::new();
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "incrementalCachePath": ".cache/dprint-incremental",
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+
+        let result = get_result("/test.json", &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(result.incremental_cache_path, Some(PathBuf::from("./.cache/dprint-incremental")));
+        assert_eq!(result.config_map.contains_key("incrementalCachePath"), false);
+    }
+
+    #[test]
+    fn it_should_ignore_incremental_cache_path_in_remote_config() {
+        let environment = TestEnvironment::new();
+        environment.add_remote_file("https://dprint.dev/test.json", r#"{
+            "incrementalCachePath": "/etc/file",
+            "plugins": ["https://plugins.dprint.dev/test-plugin.wasm"]
+        }"#.as_bytes());
+
+        let result = get_result("https://dprint.dev/test.json", &environment).unwrap();
+        assert_eq!(result.incremental_cache_path, None);
+        assert_eq!(result.config_map.contains_key("incrementalCachePath"), false);
+    }
+
+    #[test]
+    fn it_should_ignore_incremental_cache_path_in_extends() {
+        let environment = TestEnvironment::new();
+        environment.write_file(&PathBuf::from("/base.json"), r#"{
+            "incrementalCachePath": "/etc/file"
+        }"#).unwrap();
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "extends": "./base.json",
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+
+        let result = get_result("/test.json", &environment).unwrap();
+        assert_eq!(result.incremental_cache_path, None);
+        assert_eq!(result.config_map.contains_key("incrementalCachePath"), false);
+    }
+
+    #[test]
+    fn it_should_get_max_host_format_depth() {
+        let environment = TestEnvironment::new();
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+        let result = get_result("/test.json", &environment).unwrap();
+        assert_eq!(result.max_host_format_depth, DEFAULT_MAX_HOST_FORMAT_DEPTH);
+
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "maxHostFormatDepth": 3,
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+        let result = get_result("/test.json", &environment).unwrap();
+        assert_eq!(result.max_host_format_depth, 3);
+        assert_eq!(result.config_map.contains_key("maxHostFormatDepth"), false);
+
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "maxHostFormatDepth": -1,
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+        assert_eq!(
+            get_result("/test.json", &environment).err().unwrap().to_string(),
+            "Expected non-negative number in 'maxHostFormatDepth' property."
+        );
+    }
+
+    #[test]
+    fn it_should_get_plugin_signature_policy() {
+        let environment = TestEnvironment::new();
+        let public_key = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+        environment.write_file(&PathBuf::from("/test.json"), &format!(r#"{{
+            "trustedPluginKeys": ["{}"],
+            "requireSignedProcessPlugins": true,
+            "plugins": ["./testing/asdf.wasm"],
+        }}"#, public_key)).unwrap();
+
+        let result = get_result("/test.json", &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(result.plugin_signature_policy, PluginSignaturePolicy {
+            trusted_public_keys: vec![public_key.to_string()],
+            require_signed_process_plugins: true,
+        });
+        assert_eq!(result.config_map.contains_key("trustedPluginKeys"), false);
+        assert_eq!(result.config_map.contains_key("requireSignedProcessPlugins"), false);
+    }
+
+    #[test]
+    fn it_should_error_for_invalid_trusted_plugin_key() {
+        let environment = TestEnvironment::new();
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "trustedPluginKeys": ["abc"],
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+
+        assert_eq!(
+            get_result("/test.json", &environment).err().unwrap().to_string(),
+            "Error in 'trustedPluginKeys' property. The public key 'abc' was not a hex encoded ed25519 public key."
+        );
+    }
+
+    #[test]
+    fn it_should_ignore_plugin_signature_policy_in_remote_config() {
+        let environment = TestEnvironment::new();
+        environment.add_remote_file("https://dprint.dev/test.json", r#"{
+            "trustedPluginKeys": ["d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"],
+            "requireSignedProcessPlugins": true,
+            "plugins": ["https://plugins.dprint.dev/test-plugin.wasm"]
+        }"#.as_bytes());
+
+        let result = get_result("https://dprint.dev/test.json", &environment).unwrap();
+        assert_eq!(result.plugin_signature_policy, PluginSignaturePolicy::default());
+        assert_eq!(result.config_map.contains_key("trustedPluginKeys"), false);
+    }
+
+    #[test]
+    fn it_should_ignore_plugin_signature_policy_in_extends() {
+        let environment = TestEnvironment::new();
+        environment.write_file(&PathBuf::from("/base.json"), r#"{
+            "trustedPluginKeys": ["d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"],
+            "requireSignedProcessPlugins": true
+        }"#).unwrap();
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "extends": "./base.json",
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+
+        let result = get_result("/test.json", &environment).unwrap();
+        assert_eq!(result.plugin_signature_policy, PluginSignaturePolicy::default());
+        assert_eq!(result.config_map.contains_key("trustedPluginKeys"), false);
+        assert_eq!(result.config_map.contains_key("requireSignedProcessPlugins"), false);
+    }
+
+    #[test]
+    fn it_should_handle_use_gitignore_flag_when_not_specified() {
+        let environment = TestEnvironment::new();
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+
+        let result = get_result("/test.json", &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(result.use_gitignore, false);
+    }
+
+    #[test]
+    fn it_should_handle_use_gitignore_flag_when_true() {
+        let environment = TestEnvironment::new();
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "useGitignore": true,
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+
+        let result = get_result("/test.json", &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(result.use_gitignore, true);
+    }
+
+    #[test]
+    fn it_should_get_interpreters() {
+        let environment = TestEnvironment::new();
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "interpreters": { "node": "ts" },
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+
+        let result = get_result("/test.json", &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(result.interpreters, HashMap::from([("node".to_string(), "ts".to_string())]));
+        assert_eq!(result.config_map.contains_key("interpreters"), false);
+    }
+
+    #[test]
+    fn it_should_error_when_interpreters_has_non_string_value() {
+        let environment = TestEnvironment::new();
+        environment.write_file(&PathBuf::from("/test.json"), r#"{
+            "interpreters": { "node": true },
+            "plugins": ["./testing/asdf.wasm"],
+        }"#).unwrap();
+
+        assert_eq!(get_result("/test.json", &environment).err().unwrap().to_string(), "Expected string value for 'node' in 'interpreters' property.");
+    }
+
     #[test]
     fn it_should_ignore_non_wasm_plugins_in_remote_config() {
         let environment = TestEnvironment::new();
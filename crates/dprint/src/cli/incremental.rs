@@ -1,63 +1,102 @@
-use serde::{Serialize, Deserialize};
-use std::path::{Path, PathBuf};
-use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::collections::{HashMap, HashSet};
 use parking_lot::Mutex;
+use dprint_core::types::ErrBox;
 
-use crate::environment::Environment;
-use crate::utils::get_bytes_hash;
+use crate::environment::{Environment, FileMetadata};
+use crate::utils::{atomic_write_file_bytes, get_bytes_hash};
+
+/// Bytes at the start of the incremental file used to identify its format.
+const FILE_MAGIC: &[u8] = b"DPRINC";
+/// Bump this when changing the format.
+const FILE_FORMAT_VERSION: u8 = 4;
+const CLI_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
 struct IncrementalFileData {
-    plugins_hash: u64,
-    file_hashes: HashMap<PathBuf, u64>,
+    /// Version of the CLI that wrote the data.
+    cli_version: String,
+    /// The base directory the file paths are relative to.
+    base_dir_path: String,
+    /// Paths relative to the base directory (or absolute when outside it) with forward slashes.
+    files: HashMap<String, IncrementalFileEntry>,
 }
 
 impl IncrementalFileData {
-    pub fn new(plugins_hash: u64) -> IncrementalFileData {
+    pub fn new(base_dir_path: String) -> IncrementalFileData {
         IncrementalFileData {
-            plugins_hash,
-            file_hashes: HashMap::new(),
+            cli_version: CLI_VERSION.to_string(),
+            base_dir_path,
+            files: HashMap::new(),
         }
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+struct IncrementalFileEntry {
+    /// Hash of the plugin and plugin configuration that formatted the file.
+    plugin_hash: u64,
+    /// Hashes of the plugins the plugin used to format parts of the file (ex. embedded code).
+    host_format_plugin_hashes: Vec<u64>,
+    file_hash: u64,
+    /// The metadata of the file at the time it was hashed. This is `None` when
+    /// the file was written after formatting since the metadata is then unknown.
+    metadata: Option<FileMetadata>,
+    /// Milliseconds it took to format the file, which is used to format slow files first.
+    format_time: u64,
+}
+
 pub struct IncrementalFile<TEnvironment: Environment> {
     file_path: PathBuf,
     read_data: IncrementalFileData,
     write_data: Mutex<IncrementalFileData>,
     base_dir_path: PathBuf,
+    /// Hashes of all the plugins in the current run.
+    plugin_hashes: HashSet<u64>,
+    /// The time the run started in the same units as the file modified times.
+    start_time: u64,
     environment: TEnvironment,
 }
 
 impl<TEnvironment: Environment> IncrementalFile<TEnvironment> {
-    pub fn new(file_path: PathBuf, plugins_hash: u64, environment: TEnvironment, base_dir_path: PathBuf) -> Self {
-        let read_data = read_incremental(&file_path, &environment);
-        let read_data = if let Some(read_data) = read_data {
-            if read_data.plugins_hash == plugins_hash {
-                read_data
-            } else {
-                log_verbose!(environment, "Plugins changed. Creating new incremental file.");
-                IncrementalFileData::new(plugins_hash)
-            }
-        } else {
-            IncrementalFileData::new(plugins_hash)
-        };
+    pub fn new(file_path: PathBuf, environment: TEnvironment, base_dir_path: PathBuf, plugin_hashes: HashSet<u64>, start_time: u64) -> Self {
+        let base_dir_key = get_path_key(&base_dir_path);
+        let read_data = read_incremental(&file_path, &base_dir_key, &environment).unwrap_or_else(|| IncrementalFileData::new(base_dir_key.clone()));
         IncrementalFile {
             file_path,
             read_data,
-            write_data: Mutex::new(IncrementalFileData::new(plugins_hash)),
+            write_data: Mutex::new(IncrementalFileData::new(base_dir_key)),
             base_dir_path,
+            plugin_hashes,
+            start_time,
             environment,
         }
     }
 
-    pub fn is_file_same(&self, file_path: &Path, file_text: &str) -> bool {
-        let file_path = self.standardize_path(file_path);
-        if let Some(hash) = self.read_data.file_hashes.get(&file_path) {
-            if *hash == get_bytes_hash(file_text.as_bytes()) {
+    /// Gets if the file's size and modified time are the same as the last time it was
+    /// formatted by a plugin with the provided hash. This allows skipping reading the file.
+    pub fn is_file_metadata_same(&self, file_path: &Path, metadata: &FileMetadata, plugin_hash: u64) -> bool {
+        let file_path = self.get_file_path_key(file_path);
+        if let Some(entry) = self.read_data.files.get(&file_path) {
+            if self.is_plugin_same(entry, plugin_hash) && entry.metadata.as_ref() == Some(metadata) {
                 // the file is the same, so save it in the write data
-                self.add_to_write_data(file_path, file_text);
+                self.add_to_write_data(file_path, entry.clone());
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Gets if the file text is the same as the last time it was formatted by
+    /// a plugin with the provided hash.
+    pub fn is_file_same(&self, file_path: &Path, file_text: &str, plugin_hash: u64, metadata: Option<FileMetadata>) -> bool {
+        let file_path = self.get_file_path_key(file_path);
+        if let Some(entry) = self.read_data.files.get(&file_path) {
+            if self.is_plugin_same(entry, plugin_hash) && entry.file_hash == get_bytes_hash(file_text.as_bytes()) {
+                // the file is the same, so save it in the write data with the latest metadata
+                self.add_to_write_data(file_path, IncrementalFileEntry {
+                    metadata: self.get_trusted_metadata(metadata),
+                    ..entry.clone()
+                });
                 true
             } else {
                 false
@@ -67,14 +106,48 @@ impl<TEnvironment: Environment> IncrementalFile<TEnvironment> {
         }
     }
 
-    pub fn update_file(&self, file_path: &Path, file_text: &str) {
-        self.add_to_write_data(self.standardize_path(file_path), file_text)
+    /// Updates the file with its formatted text and the milliseconds it took to format. The metadata
+    /// should only be provided when the formatted text is the same as the text on the file system.
+    ///
+    /// The host format plugin hashes are the hashes of the plugins used to format parts of the file.
+    pub fn update_file(
+        &self,
+        file_path: &Path,
+        file_text: &str,
+        plugin_hash: u64,
+        host_format_plugin_hashes: Vec<u64>,
+        metadata: Option<FileMetadata>,
+        format_time: u64,
+    ) {
+        self.add_to_write_data(self.get_file_path_key(file_path), IncrementalFileEntry {
+            plugin_hash,
+            host_format_plugin_hashes,
+            file_hash: get_bytes_hash(file_text.as_bytes()),
+            metadata: self.get_trusted_metadata(metadata),
+            format_time,
+        })
+    }
+
+    /// Gets the milliseconds it took to format the file the last time it was formatted.
+    pub fn get_format_time(&self, file_path: &Path) -> Option<u64> {
+        self.read_data.files.get(&self.get_file_path_key(file_path)).map(|entry| entry.format_time)
+    }
+
+    fn get_trusted_metadata(&self, metadata: Option<FileMetadata>) -> Option<FileMetadata> {
+        // A file modified at or after the start of the run could be modified again with the same
+        // modified time (ex. with a coarse file system clock), so only store the metadata of files
+        // that were last modified before the run started.
+        metadata.filter(|metadata| metadata.modified_time < self.start_time)
+    }
+
+    fn is_plugin_same(&self, entry: &IncrementalFileEntry, plugin_hash: u64) -> bool {
+        // the plugins used to format parts of the file must also be unchanged
+        entry.plugin_hash == plugin_hash && entry.host_format_plugin_hashes.iter().all(|hash| self.plugin_hashes.contains(hash))
     }
 
-    fn add_to_write_data(&self, file_path: PathBuf, file_text: &str) {
-        let hash = get_bytes_hash(file_text.as_bytes());
+    fn add_to_write_data(&self, file_path: String, entry: IncrementalFileEntry) {
         let mut write_data = self.write_data.lock();
-        write_data.file_hashes.insert(file_path, hash);
+        write_data.files.insert(file_path, entry);
     }
 
     pub fn write(&self) {
@@ -82,19 +155,40 @@ impl<TEnvironment: Environment> IncrementalFile<TEnvironment> {
         write_incremental(&self.file_path, &write_data, &self.environment);
     }
 
-    fn standardize_path(&self, file_path: &Path) -> PathBuf {
-        // need to ensure the file is stored as an absolute path
-        if self.environment.is_absolute_path(file_path) {
+    fn get_file_path_key(&self, file_path: &Path) -> String {
+        let file_path = if self.environment.is_absolute_path(file_path) {
             file_path.to_owned()
         } else {
             self.base_dir_path.join(file_path)
+        };
+        // store paths relative to the base directory so the file may be used in other locations
+        match file_path.strip_prefix(&self.base_dir_path) {
+            Ok(relative_path) => get_path_key(relative_path),
+            Err(_) => get_path_key(&file_path),
         }
     }
 }
 
-fn read_incremental(file_path: &Path, environment: &impl Environment) -> Option<IncrementalFileData> {
-    let file_text = match environment.read_file(file_path) {
-        Ok(file_text) => file_text,
+/// Gets a string for the path that's the same on every operating system.
+fn get_path_key(path: &Path) -> String {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::RootDir => if parts.is_empty() { parts.push(String::new()) },
+            _ => parts.push(component.as_os_str().to_string_lossy().replace("\\", "/")),
+        }
+    }
+    if parts.len() == 1 && parts[0].is_empty() {
+        "/".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+fn read_incremental(file_path: &Path, base_dir_key: &str, environment: &impl Environment) -> Option<IncrementalFileData> {
+    let file_bytes = match environment.read_file_bytes(file_path) {
+        Ok(file_bytes) => file_bytes,
         Err(err) => {
             if environment.path_exists(file_path) {
                 environment.log_error(&format!("Error reading incremental file {}: {}", file_path.display(), err.to_string()));
@@ -103,27 +197,520 @@ fn read_incremental(file_path: &Path, environment: &impl Environment) -> Option<
         }
     };
 
-    match serde_json::from_str(&file_text) {
-        Ok(file_data) => Some(file_data),
+    if !is_current_format(&file_bytes) {
+        log_verbose!(environment, "Incremental file was in an old format. Creating new incremental file.");
+        return None;
+    }
+
+    let mut file_data = match deserialize_data(&file_bytes) {
+        Ok(file_data) => file_data,
         Err(err) => {
             environment.log_error(&format!("Error deserializing incremental file {}: {}", file_path.display(), err.to_string()));
-            None
+            return None;
         }
+    };
+
+    if file_data.cli_version != CLI_VERSION {
+        log_verbose!(environment, "Incremental file was created by dprint {}. Creating new incremental file.", file_data.cli_version);
+        return None;
     }
+
+    if file_data.base_dir_path != base_dir_key {
+        // The file was restored from another location (ex. a CI cache) so the file metadata can't
+        // be trusted. The file hashes will still be compared.
+        log_verbose!(environment, "Incremental file was created in {}. Ignoring file metadata.", file_data.base_dir_path);
+        for entry in file_data.files.values_mut() {
+            entry.metadata = None;
+        }
+    }
+
+    Some(file_data)
 }
 
 fn write_incremental(file_path: &Path, file_data: &IncrementalFileData, environment: &impl Environment) {
-    let json_text = match serde_json::to_string(&file_data) {
-        Ok(json_text) => json_text,
-        Err(err) => {
-            environment.log_error(&format!("Error serializing incremental file {}: {}", file_path.display(), err.to_string()));
-            return;
-        },
-    };
-    match environment.write_file(file_path, &json_text) {
-        Err(err) => {
-            environment.log_error(&format!("Error saving incremental file {}: {}", file_path.display(), err.to_string()));
-        },
-        _ => {}
+    // If two processes write at the same time then the last one to finish wins.
+    let file_bytes = serialize_data(file_data);
+    if let Err(err) = atomic_write_file_bytes(file_path, &file_bytes, environment) {
+        environment.log_error(&format!("Error saving incremental file {}: {}", file_path.display(), err.to_string()));
+    }
+}
+
+fn is_current_format(bytes: &[u8]) -> bool {
+    bytes.starts_with(FILE_MAGIC) && bytes.get(FILE_MAGIC.len()) == Some(&FILE_FORMAT_VERSION)
+}
+
+// The format is the magic bytes and version, the CLI version, and the base directory path
+// followed by a table of the plugin hashes, then the number of entries. The entries are sorted by path and each is:
+//
+// * Path - Varint length shared with the previous path, then the varint length
+//   and UTF-8 bytes of the remaining text
+// * Plugin - Varint index in the plugin hash table
+// * Host format plugins - Varint count then a varint index in the plugin hash table for each
+// * File hash - u64
+// * Format time - Varint
+// * Has metadata - u8
+// * Size - Varint (only when there's metadata)
+// * Modified time - u64 (only when there's metadata)
+//
+// Strings are a varint length then UTF-8 bytes and all fixed size numbers are little endian.
+
+fn serialize_data(data: &IncrementalFileData) -> Vec<u8> {
+    let mut plugin_hashes = Vec::new();
+    let mut plugin_hash_indexes = HashMap::new();
+    let mut entries = Vec::with_capacity(data.files.len());
+    let mut get_plugin_index = |plugin_hash: u64| {
+        *plugin_hash_indexes.entry(plugin_hash).or_insert_with(|| {
+            plugin_hashes.push(plugin_hash);
+            plugin_hashes.len() - 1
+        })
     };
+    for (file_path, entry) in data.files.iter() {
+        let plugin_index = get_plugin_index(entry.plugin_hash);
+        let host_format_plugin_indexes = entry.host_format_plugin_hashes.iter().map(|hash| get_plugin_index(*hash)).collect::<Vec<_>>();
+        entries.push((file_path, plugin_index, host_format_plugin_indexes, entry));
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut bytes = Vec::with_capacity(FILE_MAGIC.len() + 1 + plugin_hashes.len() * 8 + entries.len() * 32);
+    bytes.extend(FILE_MAGIC);
+    bytes.push(FILE_FORMAT_VERSION);
+    write_string(&mut bytes, &data.cli_version);
+    write_string(&mut bytes, &data.base_dir_path);
+    write_varint(&mut bytes, plugin_hashes.len() as u64);
+    for plugin_hash in plugin_hashes.iter() {
+        bytes.extend(&plugin_hash.to_le_bytes());
+    }
+
+    write_varint(&mut bytes, entries.len() as u64);
+    let mut previous_path: &[u8] = &[];
+    for (file_path, plugin_index, host_format_plugin_indexes, entry) in entries.iter() {
+        let file_path = file_path.as_bytes();
+        let shared_len = file_path.iter().zip(previous_path.iter()).take_while(|(a, b)| a == b).count();
+        write_varint(&mut bytes, shared_len as u64);
+        write_varint(&mut bytes, (file_path.len() - shared_len) as u64);
+        bytes.extend(&file_path[shared_len..]);
+        previous_path = file_path;
+
+        write_varint(&mut bytes, *plugin_index as u64);
+        write_varint(&mut bytes, host_format_plugin_indexes.len() as u64);
+        for index in host_format_plugin_indexes.iter() {
+            write_varint(&mut bytes, *index as u64);
+        }
+        bytes.extend(&entry.file_hash.to_le_bytes());
+        write_varint(&mut bytes, entry.format_time);
+        match &entry.metadata {
+            Some(metadata) => {
+                bytes.push(1);
+                write_varint(&mut bytes, metadata.size);
+                bytes.extend(&metadata.modified_time.to_le_bytes());
+            }
+            None => bytes.push(0),
+        }
+    }
+    bytes
+}
+
+fn deserialize_data(bytes: &[u8]) -> Result<IncrementalFileData, ErrBox> {
+    let mut reader = BytesReader::new(&bytes[FILE_MAGIC.len() + 1..]);
+    let cli_version = reader.read_string()?;
+    let base_dir_path = reader.read_string()?;
+    let plugin_hashes_len = reader.read_varint()?;
+    let mut plugin_hashes = Vec::new();
+    for _ in 0..plugin_hashes_len {
+        plugin_hashes.push(reader.read_u64()?);
+    }
+
+    let entries_len = reader.read_varint()?;
+    let mut files = HashMap::new();
+    let mut file_path = Vec::new();
+    for _ in 0..entries_len {
+        let shared_len = reader.read_varint()? as usize;
+        if shared_len > file_path.len() {
+            return err!("Invalid shared path length.");
+        }
+        file_path.truncate(shared_len);
+        let remaining_len = reader.read_varint()? as usize;
+        file_path.extend(reader.read_bytes(remaining_len)?);
+
+        let plugin_hash = read_plugin_hash(&mut reader, &plugin_hashes)?;
+        let host_format_plugins_len = reader.read_varint()?;
+        let mut host_format_plugin_hashes = Vec::new();
+        for _ in 0..host_format_plugins_len {
+            host_format_plugin_hashes.push(read_plugin_hash(&mut reader, &plugin_hashes)?);
+        }
+        let file_hash = reader.read_u64()?;
+        let format_time = reader.read_varint()?;
+        let metadata = match reader.read_u8()? {
+            0 => None,
+            1 => Some(FileMetadata {
+                size: reader.read_varint()?,
+                modified_time: reader.read_u64()?,
+            }),
+            value => return err!("Unexpected metadata flag: {}", value),
+        };
+        files.insert(String::from_utf8(file_path.clone())?, IncrementalFileEntry {
+            plugin_hash,
+            host_format_plugin_hashes,
+            file_hash,
+            metadata,
+            format_time,
+        });
+    }
+
+    if !reader.is_empty() {
+        return err!("Unexpected bytes at the end of the file.");
+    }
+
+    Ok(IncrementalFileData {
+        cli_version,
+        base_dir_path,
+        files,
+    })
+}
+
+fn read_plugin_hash(reader: &mut BytesReader, plugin_hashes: &[u64]) -> Result<u64, ErrBox> {
+    match plugin_hashes.get(reader.read_varint()? as usize) {
+        Some(plugin_hash) => Ok(*plugin_hash),
+        None => err!("Invalid plugin index."),
+    }
+}
+
+fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    write_varint(bytes, value.len() as u64);
+    bytes.extend(value.as_bytes());
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct BytesReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BytesReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        BytesReader { bytes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], ErrBox> {
+        if len > self.bytes.len() {
+            return err!("Unexpected end of file.");
+        }
+        let (result, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(result)
+    }
+
+    pub fn read_string(&mut self) -> Result<String, ErrBox> {
+        let len = self.read_varint()? as usize;
+        Ok(String::from_utf8(self.read_bytes(len)?.to_vec())?)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, ErrBox> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, ErrBox> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    pub fn read_varint(&mut self) -> Result<u64, ErrBox> {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift > 63 {
+                return err!("Invalid varint.");
+            }
+            result |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    use crate::environment::{Environment, FileMetadata, RealEnvironment, TestEnvironment};
+    use crate::utils::{get_bytes_hash, get_temp_file_path};
+    use super::*;
+
+    #[test]
+    fn it_should_serialize_and_deserialize() {
+        let data = create_data(3);
+        let bytes = serialize_data(&data);
+        assert_eq!(is_current_format(&bytes), true);
+        assert_eq!(deserialize_data(&bytes).unwrap().files, data.files);
+    }
+
+    #[test]
+    fn it_should_error_deserializing_truncated_data() {
+        let bytes = serialize_data(&create_data(2));
+        let err = deserialize_data(&bytes[..bytes.len() - 1]).err().unwrap();
+        assert_eq!(err.to_string(), "Unexpected end of file.");
+    }
+
+    #[test]
+    fn it_should_ignore_old_format() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/cache/incremental");
+        environment.write_file(&file_path, r#"{"pluginsHash":1,"fileHashes":{"/file.txt":2}}"#).unwrap();
+        let incremental_file = IncrementalFile::new(file_path, environment.clone(), PathBuf::from("/"), HashSet::new(), 100);
+        assert_eq!(incremental_file.read_data.files.len(), 0);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+    }
+
+    #[test]
+    fn it_should_log_error_for_corrupt_file() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/cache/incremental");
+        let mut bytes = serialize_data(&create_data(1));
+        bytes.push(5);
+        environment.write_file_bytes(&file_path, &bytes).unwrap();
+        IncrementalFile::new(file_path, environment.clone(), PathBuf::from("/"), HashSet::new(), 100);
+        assert_eq!(
+            environment.take_logged_errors(),
+            vec!["Error deserializing incremental file /cache/incremental: Unexpected bytes at the end of the file."],
+        );
+    }
+
+    #[test]
+    fn it_should_only_be_same_by_metadata_when_metadata_stored() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/cache/incremental");
+        let metadata = FileMetadata { size: 4, modified_time: 10 };
+        {
+            let incremental_file = IncrementalFile::new(file_path.clone(), environment.clone(), PathBuf::from("/"), HashSet::new(), 100);
+            incremental_file.update_file(Path::new("/a.txt"), "text", 1, Vec::new(), Some(metadata), 0);
+            incremental_file.update_file(Path::new("/b.txt"), "text", 1, Vec::new(), None, 0);
+            incremental_file.write();
+        }
+
+        let incremental_file = IncrementalFile::new(file_path, environment.clone(), PathBuf::from("/"), HashSet::new(), 100);
+        assert_eq!(incremental_file.is_file_metadata_same(Path::new("/a.txt"), &metadata, 1), true);
+        assert_eq!(incremental_file.is_file_metadata_same(Path::new("/a.txt"), &metadata, 2), false);
+        assert_eq!(incremental_file.is_file_metadata_same(Path::new("/a.txt"), &FileMetadata { size: 4, modified_time: 11 }, 1), false);
+        assert_eq!(incremental_file.is_file_metadata_same(Path::new("/b.txt"), &metadata, 1), false);
+        // falls back to the file hash
+        assert_eq!(incremental_file.is_file_same(Path::new("/b.txt"), "text", 1, Some(metadata)), true);
+        assert_eq!(incremental_file.write_data.lock().files.get("b.txt").unwrap().metadata, Some(metadata));
+    }
+
+    #[test]
+    fn it_should_not_be_same_when_host_format_plugin_changed() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/cache/incremental");
+        let metadata = FileMetadata { size: 4, modified_time: 10 };
+        {
+            let incremental_file = IncrementalFile::new(file_path.clone(), environment.clone(), PathBuf::from("/"), vec![1, 2].into_iter().collect(), 100);
+            incremental_file.update_file(Path::new("/a.txt"), "text", 1, vec![2], Some(metadata), 0);
+            incremental_file.write();
+        }
+
+        let incremental_file = IncrementalFile::new(file_path.clone(), environment.clone(), PathBuf::from("/"), vec![1, 2].into_iter().collect(), 100);
+        assert_eq!(incremental_file.is_file_metadata_same(Path::new("/a.txt"), &metadata, 1), true);
+        assert_eq!(incremental_file.is_file_same(Path::new("/a.txt"), "text", 1, Some(metadata)), true);
+
+        let incremental_file = IncrementalFile::new(file_path, environment.clone(), PathBuf::from("/"), vec![1, 3].into_iter().collect(), 100);
+        assert_eq!(incremental_file.is_file_metadata_same(Path::new("/a.txt"), &metadata, 1), false);
+        assert_eq!(incremental_file.is_file_same(Path::new("/a.txt"), "text", 1, Some(metadata)), false);
+    }
+
+    #[test]
+    fn it_should_not_store_metadata_modified_after_start() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/cache/incremental");
+        let metadata = FileMetadata { size: 4, modified_time: 10 };
+        {
+            let incremental_file = IncrementalFile::new(file_path.clone(), environment.clone(), PathBuf::from("/"), HashSet::new(), 10);
+            incremental_file.update_file(Path::new("/a.txt"), "text", 1, Vec::new(), Some(metadata), 0);
+            incremental_file.write();
+        }
+
+        let incremental_file = IncrementalFile::new(file_path, environment.clone(), PathBuf::from("/"), HashSet::new(), 20);
+        assert_eq!(incremental_file.is_file_metadata_same(Path::new("/a.txt"), &metadata, 1), false);
+        // falls back to the file hash and now stores the metadata
+        assert_eq!(incremental_file.is_file_same(Path::new("/a.txt"), "text", 1, Some(metadata)), true);
+        assert_eq!(incremental_file.write_data.lock().files.get("a.txt").unwrap().metadata, Some(metadata));
+    }
+
+    #[test]
+    fn it_should_store_paths_relative_to_base_dir() {
+        let environment = TestEnvironment::new();
+        let incremental_file = IncrementalFile::new(PathBuf::from("/cache/incremental"), environment.clone(), PathBuf::from("/project"), HashSet::new(), 100);
+        assert_eq!(incremental_file.get_file_path_key(Path::new("/project/src/file.ts")), "src/file.ts");
+        assert_eq!(incremental_file.get_file_path_key(Path::new("./src/file.ts")), "src/file.ts");
+        assert_eq!(incremental_file.get_file_path_key(Path::new("/other/file.ts")), "/other/file.ts");
+    }
+
+    #[test]
+    fn it_should_ignore_metadata_when_created_in_other_base_dir() {
+        let environment = TestEnvironment::new();
+        let metadata = FileMetadata { size: 4, modified_time: 10 };
+        {
+            let incremental_file = IncrementalFile::new(PathBuf::from("/cache/incremental"), environment.clone(), PathBuf::from("/project1"), HashSet::new(), 100);
+            incremental_file.update_file(Path::new("/project1/a.txt"), "text", 1, Vec::new(), Some(metadata), 0);
+            incremental_file.write();
+        }
+
+        let incremental_file = IncrementalFile::new(PathBuf::from("/cache/incremental"), environment.clone(), PathBuf::from("/project2"), HashSet::new(), 100);
+        assert_eq!(incremental_file.is_file_metadata_same(Path::new("/project2/a.txt"), &metadata, 1), false);
+        assert_eq!(incremental_file.is_file_same(Path::new("/project2/a.txt"), "text", 1, Some(metadata)), true);
+        assert_eq!(environment.path_exists(&get_temp_file_path(Path::new("/cache/incremental"))), false);
+    }
+
+    #[test]
+    fn it_should_get_previous_format_time() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/cache/incremental");
+        {
+            let incremental_file = IncrementalFile::new(file_path.clone(), environment.clone(), PathBuf::from("/"), HashSet::new(), 100);
+            incremental_file.update_file(Path::new("/a.txt"), "text", 1, Vec::new(), None, 2_500);
+            incremental_file.write();
+        }
+
+        let incremental_file = IncrementalFile::new(file_path, environment.clone(), PathBuf::from("/"), HashSet::new(), 100);
+        assert_eq!(incremental_file.get_format_time(Path::new("/a.txt")), Some(2_500));
+        assert_eq!(incremental_file.get_format_time(Path::new("/b.txt")), None);
+        // the format time is kept for files that haven't changed
+        assert_eq!(incremental_file.is_file_same(Path::new("/a.txt"), "text", 1, None), true);
+        assert_eq!(incremental_file.write_data.lock().files.get("a.txt").unwrap().format_time, 2_500);
+    }
+
+    #[test]
+    fn it_should_ignore_data_from_other_cli_version() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/cache/incremental");
+        let mut data = create_data(2);
+        data.cli_version = "0.0.1".to_string();
+        environment.write_file_bytes(&file_path, &serialize_data(&data)).unwrap();
+        let incremental_file = IncrementalFile::new(file_path, environment.clone(), PathBuf::from("/home/user/project"), HashSet::new(), 100);
+        assert_eq!(incremental_file.read_data.files.len(), 0);
+    }
+
+    // Run with `cargo test -p dprint bench_incremental -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_incremental_file_formats() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct JsonIncrementalFileData {
+            plugins_hash: u64,
+            file_hashes: HashMap<PathBuf, u64>,
+        }
+
+        let data = create_data(20_000);
+        let json_data = JsonIncrementalFileData {
+            plugins_hash: 1,
+            file_hashes: data.files.iter().map(|(path, entry)| (PathBuf::from(format!("/home/user/project/{}", path)), entry.file_hash)).collect(),
+        };
+
+        let start = Instant::now();
+        let json_text = serde_json::to_string(&json_data).unwrap();
+        let json_serialize_time = start.elapsed();
+        let start = Instant::now();
+        serde_json::from_str::<JsonIncrementalFileData>(&json_text).unwrap();
+        let json_deserialize_time = start.elapsed();
+
+        let start = Instant::now();
+        let bytes = serialize_data(&data);
+        let binary_serialize_time = start.elapsed();
+        let start = Instant::now();
+        deserialize_data(&bytes).unwrap();
+        let binary_deserialize_time = start.elapsed();
+
+        println!("20,000 entries:");
+        println!("  json:   {} bytes, serialize {:?}, deserialize {:?}", json_text.len(), json_serialize_time, json_deserialize_time);
+        println!("  binary: {} bytes, serialize {:?}, deserialize {:?}", bytes.len(), binary_serialize_time, binary_deserialize_time);
+    }
+
+    // Run with `cargo test -p dprint bench_incremental -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_incremental_file_checks() {
+        let environment = RealEnvironment::new(false, true, None).unwrap();
+        let dir_path = std::env::temp_dir().join(format!("dprint-incremental-bench-{}", std::process::id()));
+        let incremental_file_path = dir_path.join("incremental");
+        environment.mk_dir_all(&dir_path).unwrap();
+        let file_text = "const value = 5;\n".repeat(500);
+        let file_paths = (0..2_000).map(|i| dir_path.join(format!("file{}.ts", i))).collect::<Vec<_>>();
+        for file_path in file_paths.iter() {
+            environment.write_file(file_path, &file_text).unwrap();
+        }
+
+        // store the files as formatted in the incremental file
+        {
+            let start_time = environment.get_time_nanos();
+            let incremental_file = IncrementalFile::new(incremental_file_path.clone(), environment.clone(), dir_path.clone(), HashSet::new(), start_time);
+            for file_path in file_paths.iter() {
+                let metadata = environment.get_file_metadata(file_path).unwrap();
+                incremental_file.update_file(file_path, &file_text, 1, Vec::new(), Some(metadata), 0);
+            }
+            incremental_file.write();
+        }
+
+        let start_time = environment.get_time_nanos();
+        let incremental_file = IncrementalFile::new(incremental_file_path, environment.clone(), dir_path.clone(), HashSet::new(), start_time);
+        let start = Instant::now();
+        for file_path in file_paths.iter() {
+            let text = environment.read_file(file_path).unwrap();
+            assert!(incremental_file.is_file_same(file_path, &text, 1, None));
+        }
+        let read_time = start.elapsed();
+
+        let start = Instant::now();
+        for file_path in file_paths.iter() {
+            let metadata = environment.get_file_metadata(file_path).unwrap();
+            assert!(incremental_file.is_file_metadata_same(file_path, &metadata, 1));
+        }
+        let metadata_time = start.elapsed();
+
+        environment.remove_dir_all(&dir_path).unwrap();
+
+        println!("2,000 files of {} bytes:", file_text.len());
+        println!("  read and hash: {:?}", read_time);
+        println!("  metadata:      {:?}", metadata_time);
+    }
+
+    fn create_data(count: usize) -> IncrementalFileData {
+        let mut files = HashMap::new();
+        for i in 0..count {
+            files.insert(format!("src/some/directory/file{}.ts", i), IncrementalFileEntry {
+                plugin_hash: (i % 3) as u64,
+                host_format_plugin_hashes: if i % 5 == 0 { vec![3, 4] } else { Vec::new() },
+                file_hash: get_bytes_hash(i.to_string().as_bytes()),
+                metadata: if i % 2 == 0 {
+                    Some(FileMetadata {
+                        size: i as u64 * 100,
+                        modified_time: 1_600_000_000_000_000_000 + i as u64,
+                    })
+                } else {
+                    None
+                },
+                format_time: i as u64 % 50,
+            });
+        }
+        IncrementalFileData {
+            cli_version: CLI_VERSION.to_string(),
+            base_dir_path: "/home/user/project".to_string(),
+            files,
+        }
+    }
 }
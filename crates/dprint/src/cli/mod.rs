@@ -3,6 +3,8 @@ mod configuration;
 pub mod incremental;
 #[cfg(target_os = "windows")]
 mod install;
+mod plugins_bundle;
+mod plugins_commands;
 mod run_cli;
 mod stdin_reader;
 
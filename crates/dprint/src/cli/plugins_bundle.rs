@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use url::Url;
+
+use dprint_core::types::ErrBox;
+use dprint_cli_core::checksums::{get_sha256_checksum, verify_sha256_checksum};
+
+use crate::cache::{Cache, CreateCacheItemOptions};
+use crate::environment::Environment;
+use crate::plugins::{PluginCache, PluginSourceReference, LOCKFILE_NAME, get_referenced_plugin_files, get_signature_path_source, read_lockfile, verify_plugin_policy};
+use crate::utils::{PathSource, fetch_file_or_url_bytes, get_url_cache_key};
+use super::configuration::ResolvedConfig;
+
+/// File in the archive that describes its contents.
+const BUNDLE_FILE_NAME: &str = "bundle.json";
+/// Bump this when changing the format.
+const BUNDLE_VERSION: usize = 1;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PluginsBundle {
+    bundle_version: usize,
+    /// Urls of the plugins to install.
+    plugins: Vec<String>,
+    /// Urls of the remote configuration files to cache.
+    configs: Vec<String>,
+    /// Downloaded files by their url, including files referenced by
+    /// plugin files (ex. a process plugin's zip file).
+    files: Vec<PluginsBundleFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PluginsBundleFile {
+    url: String,
+    /// SHA-256 checksum of the file.
+    checksum: String,
+    /// Path of the file within the archive.
+    archive_path: String,
+}
+
+struct PluginsBundleBuilder {
+    bundle: PluginsBundle,
+    file_bytes: Vec<Vec<u8>>,
+}
+
+impl PluginsBundleBuilder {
+    fn new() -> Self {
+        PluginsBundleBuilder {
+            bundle: PluginsBundle {
+                bundle_version: BUNDLE_VERSION,
+                plugins: Vec::new(),
+                configs: Vec::new(),
+                files: Vec::new(),
+            },
+            file_bytes: Vec::new(),
+        }
+    }
+
+    fn add_file(&mut self, url: String, checksum: String, bytes: Vec<u8>) {
+        if self.bundle.files.iter().any(|file| file.url == url) {
+            return;
+        }
+
+        self.bundle.files.push(PluginsBundleFile {
+            url,
+            checksum,
+            archive_path: format!("files/{}", self.file_bytes.len()),
+        });
+        self.file_bytes.push(bytes);
+    }
+
+    fn build(self) -> Result<Vec<u8>, ErrBox> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        zip.start_file(BUNDLE_FILE_NAME, options)?;
+        zip.write_all(serde_json::to_string_pretty(&self.bundle)?.as_bytes())?;
+        for (file, bytes) in self.bundle.files.iter().zip(self.file_bytes.iter()) {
+            zip.start_file(&file.archive_path, options)?;
+            zip.write_all(bytes)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+/// Writes an archive with the remote plugins and remote configuration files to the
+/// provided path so they can be installed on a machine without internet access.
+///
+/// Local plugins are not included because they're already available without internet access.
+pub fn export_plugins_bundle<TEnvironment: Environment>(
+    archive_path: &Path,
+    plugin_references: &[PluginSourceReference],
+    remote_config_urls: &[Url],
+    cache: &Cache<TEnvironment>,
+    plugin_cache: &PluginCache<TEnvironment>,
+    environment: &TEnvironment,
+) -> Result<(), ErrBox> {
+    let mut builder = PluginsBundleBuilder::new();
+
+    for plugin_reference in plugin_references.iter().filter(|r| matches!(r.path_source, PathSource::Remote(_))) {
+        let url = plugin_reference.display();
+        // the cached checksum was verified against the lockfile when resolving
+        let cache_item = plugin_cache.get_plugin_cache_item(plugin_reference)?;
+        let checksum = match cache_item.checksum {
+            Some(checksum) => checksum,
+            None => return err!("Expected to have the plugin checksum stored in the cache."),
+        };
+        let file_bytes = fetch_file_with_checksum(&plugin_reference.path_source, &checksum, environment)?;
+
+        // include the signature so it can be verified again when importing
+        if cache_item.signed_by.is_some() {
+            let signature_path_source = get_signature_path_source(&plugin_reference.path_source)?;
+            let signature_bytes = fetch_file_or_url_bytes(&signature_path_source, environment)?;
+            builder.add_file(signature_path_source.display(), get_sha256_checksum(&signature_bytes), signature_bytes);
+        }
+
+        for referenced_file in get_referenced_plugin_files(&plugin_reference.path_source, &file_bytes)? {
+            let referenced_checksum = referenced_file.checksum.clone().unwrap();
+            let referenced_bytes = fetch_file_with_checksum(&referenced_file.path_source, &referenced_checksum, environment)?;
+            builder.add_file(referenced_file.display(), referenced_checksum, referenced_bytes);
+        }
+
+        builder.add_file(url.clone(), checksum, file_bytes);
+        builder.bundle.plugins.push(url);
+    }
+
+    for url in remote_config_urls {
+        let cache_item = match cache.get_cache_item(&get_url_cache_key(url)) {
+            Some(cache_item) => cache_item,
+            None => return err!("Expected to have the configuration file {} stored in the cache.", url),
+        };
+        let file_bytes = environment.read_file_bytes(&cache.resolve_cache_item_file_path(&cache_item))?;
+        builder.add_file(url.to_string(), get_sha256_checksum(&file_bytes), file_bytes);
+        builder.bundle.configs.push(url.to_string());
+    }
+
+    let plugin_count = builder.bundle.plugins.len();
+    let config_count = builder.bundle.configs.len();
+    environment.write_file_bytes(archive_path, &builder.build()?)?;
+    environment.log(&format!(
+        "Exported {} plugin(s) and {} configuration file(s) to {}",
+        plugin_count,
+        config_count,
+        archive_path.display(),
+    ));
+
+    Ok(())
+}
+
+/// Installs the plugins and remote configuration files of an archive created
+/// by `export_plugins_bundle` into the cache.
+///
+/// The remote configuration files are imported first so the configuration can be resolved
+/// without internet access. Then only the plugins used by the configuration are installed and
+/// their files must match the checksums in the configuration file or lockfile since the
+/// checksums stored in the archive only detect corruption. The plugin policy and signature
+/// policy are verified the same way as when resolving the plugins.
+pub fn import_plugins_bundle<TEnvironment: Environment>(
+    archive_path: &Path,
+    resolve_config: impl FnOnce() -> Result<ResolvedConfig, ErrBox>,
+    cache: &Cache<TEnvironment>,
+    plugin_cache: &PluginCache<TEnvironment>,
+    environment: &TEnvironment,
+) -> Result<(), ErrBox> {
+    let archive_bytes = environment.read_file_bytes(archive_path)?;
+    let mut zip = zip::ZipArchive::new(Cursor::new(&archive_bytes))?;
+    let bundle: PluginsBundle = match serde_json::from_slice(&read_zip_file(&mut zip, BUNDLE_FILE_NAME)?) {
+        Ok(bundle) => bundle,
+        Err(err) => return err!("Error deserializing {} in {}. {}", BUNDLE_FILE_NAME, archive_path.display(), err.to_string()),
+    };
+    if bundle.bundle_version != BUNDLE_VERSION {
+        return err!(
+            "Expected bundle version {}, but found {}. The archive may have been exported by a different version of the CLI.",
+            BUNDLE_VERSION,
+            bundle.bundle_version,
+        );
+    }
+
+    let mut files = HashMap::new();
+    for file in bundle.files.iter() {
+        let bytes = read_zip_file(&mut zip, &file.archive_path)?;
+        if let Err(err) = verify_sha256_checksum(&bytes, &file.checksum) {
+            return err!("Error importing {}. {}", file.url, err.to_string());
+        }
+        files.insert(file.url.clone(), bytes);
+    }
+
+    for url in bundle.configs.iter() {
+        let bytes = get_file(&files, url)?;
+        let cache_key = get_url_cache_key(&Url::parse(url)?);
+        cache.forget_item(&cache_key)?;
+        cache.create_cache_item(CreateCacheItemOptions {
+            key: cache_key,
+            extension: "tmp",
+            bytes: Some(bytes),
+            meta_data: None,
+        })?;
+    }
+
+    let config = resolve_config()?;
+    let lockfile_path = config.base_path.join(LOCKFILE_NAME);
+    let lockfile = read_lockfile(&lockfile_path, environment)?;
+    verify_plugin_policy(&config.plugins, environment)?;
+    plugin_cache.set_signature_policy(config.plugin_signature_policy.clone());
+
+    let mut plugin_count = 0;
+    for plugin_reference in config.plugins.iter().filter(|r| matches!(r.path_source, PathSource::Remote(_))) {
+        let url = plugin_reference.display();
+        if !bundle.plugins.contains(&url) {
+            continue;
+        }
+
+        let expected_checksum = match plugin_reference.checksum.clone().or_else(|| lockfile.as_ref().and_then(|l| l.get_entry(&url)).map(|e| e.checksum.clone())) {
+            Some(checksum) => checksum,
+            None => return err!(
+                "Error importing {}. The plugin must have a checksum in the configuration file or {} in order to verify it.",
+                url,
+                lockfile_path.display(),
+            ),
+        };
+        let bytes = get_file(&files, &url)?;
+        if let Err(err) = verify_sha256_checksum(bytes, &expected_checksum) {
+            return err!("Error importing {}. {}", url, err.to_string());
+        }
+
+        let plugin_reference = PluginSourceReference {
+            path_source: plugin_reference.path_source.clone(),
+            checksum: Some(expected_checksum),
+        };
+        plugin_cache.install_plugin(&plugin_reference, bytes, &|path_source| get_file(&files, &path_source.display()).map(|bytes| bytes.clone()))?;
+        plugin_count += 1;
+    }
+
+    environment.log(&format!(
+        "Imported {} plugin(s) and {} configuration file(s)",
+        plugin_count,
+        bundle.configs.len(),
+    ));
+
+    Ok(())
+}
+
+fn get_file<'a>(files: &'a HashMap<String, Vec<u8>>, url: &str) -> Result<&'a Vec<u8>, ErrBox> {
+    match files.get(url) {
+        Some(file) => Ok(file),
+        None => return err!("The archive did not contain {}.", url),
+    }
+}
+
+fn fetch_file_with_checksum(path_source: &PathSource, checksum: &str, environment: &impl Environment) -> Result<Vec<u8>, ErrBox> {
+    let bytes = fetch_file_or_url_bytes(path_source, environment)?;
+    if let Err(err) = verify_sha256_checksum(&bytes, checksum) {
+        return err!("Error exporting {}. {}", path_source.display(), err.to_string());
+    }
+    Ok(bytes)
+}
+
+fn read_zip_file(zip: &mut zip::ZipArchive<Cursor<&Vec<u8>>>, name: &str) -> Result<Vec<u8>, ErrBox> {
+    let mut file = match zip.by_name(name) {
+        Ok(file) => file,
+        Err(err) => return err!("Error reading {} from the archive. {}", name, err.to_string()),
+    };
+    let mut bytes = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
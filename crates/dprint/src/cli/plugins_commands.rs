@@ -0,0 +1,172 @@
+use std::cmp::Ordering;
+
+use dprint_core::types::ErrBox;
+use dprint_cli_core::logging::get_bytes_text;
+
+use crate::environment::Environment;
+use crate::plugins::{CachedPlugin, Plugin, PluginCache, read_info_file};
+use crate::utils::{PathSource, compare_versions, get_table_text};
+
+const SECONDS_IN_DAY: u64 = 60 * 60 * 24;
+
+/// Outputs the plugins stored in the cache.
+pub fn output_cached_plugins<TEnvironment: Environment>(
+    plugin_cache: &PluginCache<TEnvironment>,
+    environment: &TEnvironment,
+) -> Result<(), ErrBox> {
+    let cached_plugins = plugin_cache.get_cached_plugins()?;
+    if cached_plugins.is_empty() {
+        environment.log("No plugins are cached.");
+        return Ok(());
+    }
+
+    let now = environment.get_time_secs();
+    for (i, cached_plugin) in cached_plugins.iter().enumerate() {
+        if i > 0 {
+            environment.log("");
+        }
+
+        let kind_text = get_kind_text(&cached_plugin.path_source);
+        let size_text = match environment.get_file_metadata(&cached_plugin.file_path) {
+            Ok(metadata) => get_bytes_text(metadata.size as usize, metadata.size as usize),
+            Err(_) => String::from("unknown"),
+        };
+        let last_used_text = get_last_used_text(cached_plugin.last_used_time, now);
+        let source_text = cached_plugin.path_source.display();
+        let table_text = get_table_text(vec![
+            ("Kind:", kind_text),
+            ("Size:", size_text.as_str()),
+            ("Last used:", last_used_text.as_str()),
+            ("Source:", source_text.as_str()),
+        ]);
+        environment.log(&format!("{} {}", cached_plugin.info.name, cached_plugin.info.version));
+        environment.log(&table_text.render(
+            2, // indent
+            // don't render taking terminal width into account
+            // as the source may be a url and we want it to be clickable
+            None,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Outputs the configured plugins that have a newer version available.
+pub fn output_outdated_plugins(plugins: &[Box<dyn Plugin>], environment: &impl Environment) -> Result<(), ErrBox> {
+    let info_file = match read_info_file(environment) {
+        Ok(info_file) => info_file,
+        Err(err) => return err!("Error getting the latest plugin information. {}", err.to_string()),
+    };
+
+    let mut found_outdated = false;
+    for plugin in plugins {
+        let latest_plugin = match info_file.latest_plugins.iter().find(|p| p.name == plugin.name()) {
+            Some(latest_plugin) => latest_plugin,
+            None => continue, // not a plugin that's known about
+        };
+        if compare_versions(plugin.version(), &latest_plugin.version) != Ordering::Less {
+            continue;
+        }
+
+        found_outdated = true;
+        environment.log(&format!("{} {} -> {}", plugin.name(), plugin.version(), latest_plugin.version));
+        environment.log(&match &latest_plugin.checksum {
+            Some(checksum) => format!("  {}@{}", latest_plugin.url, checksum),
+            None => format!("  {}", latest_plugin.url),
+        });
+    }
+
+    if !found_outdated {
+        environment.log("All plugins are up to date.");
+    }
+
+    Ok(())
+}
+
+/// Removes every cached version of the plugin with the provided name.
+pub fn remove_cached_plugins<TEnvironment: Environment>(
+    plugin_name: &str,
+    plugin_cache: &PluginCache<TEnvironment>,
+    environment: &TEnvironment,
+) -> Result<(), ErrBox> {
+    let cached_plugins = plugin_cache.get_cached_plugins()?
+        .into_iter()
+        .filter(|cached_plugin| cached_plugin.info.name == plugin_name)
+        .collect::<Vec<_>>();
+    if cached_plugins.is_empty() {
+        return err!("Could not find a cached plugin named {}.", plugin_name);
+    }
+
+    forget_cached_plugins(&cached_plugins, plugin_cache, environment)
+}
+
+/// Removes the cached plugins that haven't been used in the provided number of days.
+pub fn prune_cached_plugins<TEnvironment: Environment>(
+    unused_days: u64,
+    plugin_cache: &PluginCache<TEnvironment>,
+    environment: &TEnvironment,
+) -> Result<(), ErrBox> {
+    let now = environment.get_time_secs();
+    let unused_secs = unused_days.saturating_mul(SECONDS_IN_DAY);
+    let cached_plugins = plugin_cache.get_cached_plugins()?
+        .into_iter()
+        .filter(|cached_plugin| now.saturating_sub(cached_plugin.last_used_time) >= unused_secs)
+        .collect::<Vec<_>>();
+    if cached_plugins.is_empty() {
+        environment.log(&format!("No plugins were unused for {} day(s).", unused_days));
+        return Ok(());
+    }
+
+    forget_cached_plugins(&cached_plugins, plugin_cache, environment)
+}
+
+fn forget_cached_plugins<TEnvironment: Environment>(
+    cached_plugins: &[CachedPlugin],
+    plugin_cache: &PluginCache<TEnvironment>,
+    environment: &TEnvironment,
+) -> Result<(), ErrBox> {
+    for cached_plugin in cached_plugins {
+        plugin_cache.forget_cached_plugin(cached_plugin)?;
+        environment.log(&format!(
+            "Removed {} {} ({})",
+            cached_plugin.info.name,
+            cached_plugin.info.version,
+            cached_plugin.path_source.display(),
+        ));
+    }
+    Ok(())
+}
+
+fn get_kind_text(path_source: &PathSource) -> &'static str {
+    if path_source.is_wasm_plugin() {
+        "wasm"
+    } else if path_source.is_process_plugin() {
+        "process"
+    } else if path_source.is_native_plugin() {
+        "native"
+    } else {
+        "unknown"
+    }
+}
+
+fn get_last_used_text(last_used_time: u64, now: u64) -> String {
+    match now.saturating_sub(last_used_time) / SECONDS_IN_DAY {
+        0 => String::from("today"),
+        1 => String::from("1 day ago"),
+        days => format!("{} days ago", days),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_should_get_last_used_text() {
+        assert_eq!(get_last_used_text(100, 100), "today");
+        assert_eq!(get_last_used_text(100, 100 + SECONDS_IN_DAY - 1), "today");
+        assert_eq!(get_last_used_text(100, 100 + SECONDS_IN_DAY), "1 day ago");
+        assert_eq!(get_last_used_text(100, 100 + SECONDS_IN_DAY * 5), "5 days ago");
+        assert_eq!(get_last_used_text(200, 100), "today");
+    }
+}
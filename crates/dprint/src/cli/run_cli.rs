@@ -1,5 +1,6 @@
 use crate::utils::to_absolute_glob;
 use std::borrow::Cow;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -8,17 +9,22 @@ use parking_lot::Mutex;
 use std::time::Instant;
 use crossterm::style::Stylize;
 use dprint_core::types::ErrBox;
+use dprint_core::plugins::{FormatDiagnostic, FormatDiagnosticRange, FormatDiagnosticSeverity, CancellationToken};
+use dprint_core::formatting::utils::string_utils::{format_diagnostic, get_line_number_of_pos, get_column_number_of_pos};
+use serde::Serialize;
 
 use crate::cache::{Cache, CreateCacheItemOptions};
-use crate::environment::Environment;
+use crate::environment::{Environment, FileMetadata};
 use crate::configuration::{self, get_global_config, get_plugin_config_map};
-use crate::plugins::{InitializedPlugin, InitializedPluginPool, Plugin, PluginResolver, PluginPools, do_batch_format,
-    output_plugin_config_diagnostics, TakePluginResult};
-use crate::utils::{get_table_text, get_difference, pretty_print_json_text, FileText, BOM_CHAR, ErrorCountLogger};
+use crate::plugins::{InitializedPlugin, InitializedPluginPool, Plugin, PluginCache, PluginResolver, PluginPools, do_batch_format, collect_host_format_plugin_hashes, FileEstimate,
+    output_plugin_config_diagnostics, verify_plugin_policy, TakePluginResult, LOCKFILE_NAME};
+use crate::utils::{get_table_text, get_difference_with_options, get_temp_file_path, pretty_print_json_text, DiffOptions, FileText, BOM_CHAR, ErrorCountLogger};
 
-use super::{CliArgs, SubCommand, EditorServiceSubCommand};
+use super::{CliArgs, SubCommand, EditorServiceSubCommand, Reporter};
 use super::configuration::{resolve_config_from_args, ResolvedConfig};
 use super::incremental::IncrementalFile;
+use super::plugins_bundle::{export_plugins_bundle, import_plugins_bundle};
+use super::plugins_commands::{output_cached_plugins, output_outdated_plugins, remove_cached_plugins, prune_cached_plugins};
 
 // TODO: probably a lot of these functions could be moved into new files
 
@@ -26,8 +32,10 @@ pub fn run_cli<TEnvironment: Environment>(
     args: CliArgs,
     environment: &TEnvironment,
     cache: &Cache<TEnvironment>,
+    plugin_cache: &PluginCache<TEnvironment>,
     plugin_resolver: &PluginResolver<TEnvironment>,
     plugin_pools: Arc<PluginPools<TEnvironment>>,
+    cancellation_token: &CancellationToken,
 ) -> Result<(), ErrBox> {
     // todo: reduce code duplication in this function
     match &args.sub_command {
@@ -40,8 +48,8 @@ pub fn run_cli<TEnvironment: Environment>(
         SubCommand::Version => output_version(environment),
         SubCommand::StdInFmt(cmd) => {
             let config = resolve_config_from_args(&args, cache, environment)?;
-            let plugins = resolve_plugins_and_err_if_empty(&config, environment, plugin_resolver)?;
-            plugin_pools.set_plugins(plugins);
+            let plugins = resolve_plugins_and_err_if_empty(&config, &args, environment, plugin_resolver)?;
+            plugin_pools.set_plugins(plugins, &config.interpreters, config.max_host_format_depth);
             // if the path is absolute, then apply exclusion rules
             if environment.is_absolute_path(&cmd.file_path) {
                 let file_paths = resolve_file_paths(&config, &args, environment)?;
@@ -57,48 +65,70 @@ pub fn run_cli<TEnvironment: Environment>(
                     Err(err) => return err!("Error canonicalizing file {}: {}", cmd.file_path.display(), err.to_string()),
                 }
             }
-            output_stdin_format(&cmd.file_path, &cmd.file_text, environment, plugin_pools)
+            output_stdin_format(&cmd.file_path, &cmd.file_text, environment, plugin_pools, cancellation_token)
         }
         SubCommand::OutputResolvedConfig => {
             let config = resolve_config_from_args(&args, cache, environment)?;
-            let plugins = resolve_plugins_and_err_if_empty(&config, environment, plugin_resolver)?;
+            let plugins = resolve_plugins_and_err_if_empty(&config, &args, environment, plugin_resolver)?;
             output_resolved_config(plugins, environment)
         }
         SubCommand::OutputFilePaths => {
             let config = resolve_config_from_args(&args, cache, environment)?;
-            let plugins = resolve_plugins_and_err_if_empty(&config, environment, plugin_resolver)?;
+            let plugins = resolve_plugins_and_err_if_empty(&config, &args, environment, plugin_resolver)?;
+            plugin_pools.set_plugins(plugins, &config.interpreters, config.max_host_format_depth);
             let file_paths = resolve_file_paths(&config, &args, environment)?;
-            let file_paths_by_plugin = get_file_paths_by_plugin(&plugins, file_paths);
+            let file_paths_by_plugin = get_file_paths_by_plugin(&plugin_pools, file_paths);
             output_file_paths(file_paths_by_plugin.values().flat_map(|x| x.iter()), environment);
             Ok(())
         }
         SubCommand::OutputFormatTimes => {
             let config = resolve_config_from_args(&args, cache, environment)?;
-            let plugins = resolve_plugins_and_err_if_empty(&config, environment, plugin_resolver)?;
-            let file_paths = resolve_file_paths(&config, &args, environment)?;
-            let file_paths_by_plugin = get_file_paths_by_plugin_and_err_if_empty(&plugins, file_paths)?;
-            plugin_pools.set_plugins(plugins);
-            output_format_times(file_paths_by_plugin, environment, plugin_pools)
+            let plugins = resolve_plugins_and_err_if_empty(&config, &args, environment, plugin_resolver)?;
+            plugin_pools.set_plugins(plugins, &config.interpreters, config.max_host_format_depth);
+            output_format_times(&config, &args, environment, plugin_pools, cancellation_token)
+        }
+        SubCommand::ConfigValidate => {
+            let config = resolve_config_from_args(&args, cache, environment)?;
+            let plugins = resolve_plugins_and_err_if_empty(&config, &args, environment, plugin_resolver)?;
+            validate_config(plugins, environment)
+        }
+        SubCommand::PluginsExport(archive_path) => {
+            let config = resolve_config_from_args(&args, cache, environment)?;
+            // resolve the plugins first to ensure they're cached and match the lockfile
+            resolve_plugins_and_err_if_empty(&config, &args, environment, plugin_resolver)?;
+            export_plugins_bundle(archive_path, &config.plugins, &config.remote_config_urls, cache, plugin_cache, environment)
+        }
+        SubCommand::PluginsImport(archive_path) => {
+            // the configuration is resolved after importing the remote configuration files
+            import_plugins_bundle(archive_path, || resolve_config_from_args(&args, cache, environment), cache, plugin_cache, environment)
+        }
+        SubCommand::PluginsList => output_cached_plugins(plugin_cache, environment),
+        SubCommand::PluginsOutdated => {
+            let config = resolve_config_from_args(&args, cache, environment)?;
+            let plugins = resolve_plugins_and_err_if_empty(&config, &args, environment, plugin_resolver)?;
+            output_outdated_plugins(&plugins, environment)
         }
+        SubCommand::PluginsUpdate => {
+            let config = resolve_config_from_args(&args, cache, environment)?;
+            update_plugins_lockfile(&config, &args, environment, plugin_resolver)
+        }
+        SubCommand::PluginsRemove(plugin_name) => remove_cached_plugins(plugin_name, plugin_cache, environment),
+        SubCommand::PluginsPrune(unused_days) => prune_cached_plugins(*unused_days, plugin_cache, environment),
         SubCommand::Check => {
             let config = resolve_config_from_args(&args, cache, environment)?;
-            let plugins = resolve_plugins_and_err_if_empty(&config, environment, plugin_resolver)?;
-            let file_paths = resolve_file_paths(&config, &args, environment)?;
-            let file_paths_by_plugin = get_file_paths_by_plugin_and_err_if_empty(&plugins, file_paths)?;
-            plugin_pools.set_plugins(plugins);
+            let plugins = resolve_plugins_and_err_if_empty(&config, &args, environment, plugin_resolver)?;
+            plugin_pools.set_plugins(plugins, &config.interpreters, config.max_host_format_depth);
 
             let incremental_file = get_incremental_file(&args, &config, &cache, &plugin_pools, &environment);
-            check_files(file_paths_by_plugin, environment, plugin_pools, incremental_file)
+            check_files(&config, &args, environment, plugin_pools, incremental_file, cancellation_token)
         }
         SubCommand::Fmt => {
             let config = resolve_config_from_args(&args, cache, environment)?;
-            let plugins = resolve_plugins_and_err_if_empty(&config, environment, plugin_resolver)?;
-            let file_paths = resolve_file_paths(&config, &args, environment)?;
-            let file_paths_by_plugin = get_file_paths_by_plugin_and_err_if_empty(&plugins, file_paths)?;
-            plugin_pools.set_plugins(plugins);
+            let plugins = resolve_plugins_and_err_if_empty(&config, &args, environment, plugin_resolver)?;
+            plugin_pools.set_plugins(plugins, &config.interpreters, config.max_host_format_depth);
 
             let incremental_file = get_incremental_file(&args, &config, &cache, &plugin_pools, &environment);
-            format_files(file_paths_by_plugin, environment, plugin_pools, incremental_file)
+            format_files(&config, &args, environment, plugin_pools, incremental_file, cancellation_token)
         }
         #[cfg(target_os = "windows")]
         SubCommand::Hidden(hidden_command) => {
@@ -110,42 +140,17 @@ pub fn run_cli<TEnvironment: Environment>(
     }
 }
 
-fn get_file_paths_by_plugin_and_err_if_empty(
-    plugins: &Vec<Box<dyn Plugin>>,
-    file_paths: Vec<PathBuf>
-) -> Result<HashMap<String, Vec<PathBuf>>, ErrBox> {
-    let file_paths_by_plugin = get_file_paths_by_plugin(plugins, file_paths);
-    if file_paths_by_plugin.is_empty() {
-        return err!("No files found to format with the specified plugins. You may want to try using `dprint output-file-paths` to see which files it's finding.");
-    }
-    Ok(file_paths_by_plugin)
-}
-
-fn get_file_paths_by_plugin(plugins: &Vec<Box<dyn Plugin>>, file_paths: Vec<PathBuf>) -> HashMap<String, Vec<PathBuf>> {
-    let mut plugin_by_file_extension: HashMap<&str, &str> = HashMap::new();
-    let mut plugin_by_file_name: HashMap<&str, &str> = HashMap::new();
-
-    for plugin in plugins.iter() {
-        for file_extension in plugin.file_extensions() {
-            plugin_by_file_extension.entry(file_extension).or_insert(plugin.name());
-        }
-        for file_name in plugin.file_names() {
-            plugin_by_file_name.entry(file_name).or_insert(plugin.name());
-        }
-    }
-
+fn get_file_paths_by_plugin<TEnvironment: Environment>(
+    plugin_pools: &PluginPools<TEnvironment>,
+    file_paths: Vec<PathBuf>,
+) -> HashMap<String, Vec<PathBuf>> {
     let mut file_paths_by_plugin: HashMap<String, Vec<PathBuf>> = HashMap::new();
 
     for file_path in file_paths.into_iter() {
-        let plugin = if let Some(plugin) = crate::utils::get_lowercase_file_name(&file_path).and_then(|k| plugin_by_file_name.get(k.as_str())) {
-            plugin
-        } else if let Some(plugin) = crate::utils::get_lowercase_file_extension(&file_path).and_then(|k| plugin_by_file_extension.get(k.as_str())) {
-            plugin
-        } else {
-            continue;
-        };
-        let file_paths = file_paths_by_plugin.entry(plugin.to_string()).or_insert(vec![]);
-        file_paths.push(file_path);
+        if let Some(plugin_name) = plugin_pools.get_plugin_name_from_file_name(&file_path) {
+            let file_paths = file_paths_by_plugin.entry(plugin_name).or_insert(vec![]);
+            file_paths.push(file_path);
+        }
     }
 
     file_paths_by_plugin
@@ -257,7 +262,7 @@ fn run_editor_service<TEnvironment: Environment>(
     plugin_pools: Arc<PluginPools<TEnvironment>>,
     editor_service_cmd: &EditorServiceSubCommand,
 ) -> Result<(), ErrBox> {
-    use dprint_core::plugins::process::{StdIoReaderWriter, StdIoMessenger, start_parent_process_checker_thread};
+    use dprint_core::plugins::process::{StdIoReaderWriter, StdIoMessenger, MultiplexedMessage, start_parent_process_checker_thread};
 
     // poll for the existence of the parent process and terminate this process when that process no longer exists
     let _handle = start_parent_process_checker_thread("editor-service".to_string(), editor_service_cmd.parent_pid);
@@ -266,7 +271,14 @@ fn run_editor_service<TEnvironment: Environment>(
     let stdout = environment.stdout();
     let reader_writer = StdIoReaderWriter::new(stdin, stdout);
     let mut messenger = StdIoMessenger::new(reader_writer);
-    let mut past_config: Option<ResolvedConfig> = None;
+    let mut context = EditorServiceContext {
+        args,
+        cache,
+        environment,
+        plugin_resolver,
+        plugin_pools: &plugin_pools,
+        past_config: None,
+    };
 
     loop {
         let message_kind = messenger.read_code()?;
@@ -276,19 +288,8 @@ fn run_editor_service<TEnvironment: Environment>(
             // check path
             1 => {
                 let file_path = messenger.read_single_part_path_buf_message()?;
-                let config = resolve_config_from_args(&args, cache, environment)?;
-                let file_paths = resolve_file_paths(&config, args, environment)?;
-
-                // canonicalize the file path, then check if it's in the list of file paths.
-                match environment.canonicalize(&file_path) {
-                    Ok(resolved_file_path) => {
-                        messenger.send_message(if file_paths.contains(&resolved_file_path) { 1 } else { 0 }, Vec::new())?;
-                    }
-                    Err(err) => {
-                        environment.log_error(&format!("Error canonicalizing file {}: {}", file_path.display(), err.to_string()));
-                        messenger.send_message(0, Vec::new())?; // don't format, something went wrong
-                    },
-                }
+                let can_format = can_format_file(args, cache, environment, &file_path)?;
+                messenger.send_message(if can_format { 1 } else { 0 }, Vec::new())?;
             },
             // format
             2 => {
@@ -296,25 +297,16 @@ fn run_editor_service<TEnvironment: Environment>(
                 let file_path = parts.take_path_buf()?;
                 let file_text = parts.take_string()?;
 
-                let result = format_text(args, cache, environment, plugin_resolver, &plugin_pools, &past_config, &file_path, &file_text);
-                match result {
-                    Ok((formatted_text, config)) => {
-                        if formatted_text == file_text {
-                            messenger.send_message(0, Vec::new())?; // no change
-                        } else {
-                            messenger.send_message(1, vec![ // change
-                                formatted_text.into()
-                            ])?;
-                        }
-
-                        past_config.replace(config);
-                    },
-                    Err(err) => {
-                        messenger.send_message(2, vec![ // error
-                            err.to_string().into()
-                        ])?;
-                    }
-                }
+                let result = format_text(&mut context, &file_path, &file_text, &CancellationToken::new());
+                let (response_code, text) = get_format_response(result, &file_text);
+                messenger.send_message(response_code, text.into_iter().map(|text| text.into()).collect())?;
+            },
+            // switch to the multiplexed mode
+            3 => {
+                messenger.read_zero_part_message()?;
+                messenger.send_message(0, Vec::new())?;
+                let (reader, writer) = messenger.into_inner();
+                return run_multiplexed(context, reader, writer);
             },
             _ => {
                 environment.log_error(&format!("Unknown message kind: {}", message_kind));
@@ -322,26 +314,173 @@ fn run_editor_service<TEnvironment: Environment>(
         }
     }
 
-    fn format_text<'a, TEnvironment: Environment>(
+    /// What's needed to respond to the requests of the editor service.
+    struct EditorServiceContext<'a, TEnvironment: Environment> {
+        args: &'a CliArgs,
+        cache: &'a Cache<TEnvironment>,
+        environment: &'a TEnvironment,
+        plugin_resolver: &'a PluginResolver<TEnvironment>,
+        plugin_pools: &'a Arc<PluginPools<TEnvironment>>,
+        /// The configuration the plugins were last set up with.
+        past_config: Option<ResolvedConfig>,
+    }
+
+    enum EditorServiceRequest {
+        Shutdown,
+        CheckPath { id: u32, file_path: PathBuf },
+        Format { id: u32, file_path: PathBuf, file_text: String, cancellation_token: CancellationToken },
+        Unknown { id: u32, message_kind: u32 },
+    }
+
+    fn run_multiplexed<TEnvironment: Environment>(
+        mut context: EditorServiceContext<'_, TEnvironment>,
+        mut reader: Box<dyn Read + Send>,
+        mut writer: Box<dyn Write + Send>,
+    ) -> Result<(), ErrBox> {
+        use dprint_core::plugins::process::{read_multiplexed_message, write_multiplexed_message};
+
+        // the format requests that haven't been responded to yet
+        let pending_formats: Arc<Mutex<HashMap<u32, (PathBuf, CancellationToken)>>> = Default::default();
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        // read on another thread so that requests can be cancelled while formatting
+        std::thread::spawn({
+            let pending_formats = pending_formats.clone();
+            move || loop {
+                let request = match read_multiplexed_message(&mut reader) {
+                    // cancel a format request, which has no response
+                    Ok(mut message) if message.code == 3 => {
+                        if let Ok(format_id) = message.parts.take_u32() {
+                            if let Some((_, cancellation_token)) = pending_formats.lock().get(&format_id) {
+                                cancellation_token.cancel();
+                            }
+                        }
+                        continue;
+                    },
+                    Ok(message) => parse_request(message, &pending_formats),
+                    Err(err) => Err(err),
+                };
+                let should_exit = matches!(request, Err(_) | Ok(EditorServiceRequest::Shutdown));
+                if sender.send(request).is_err() || should_exit {
+                    return;
+                }
+            }
+        });
+
+        loop {
+            let request = match receiver.recv() {
+                Ok(request) => request?,
+                Err(_) => return Ok(()),
+            };
+            match request {
+                EditorServiceRequest::Shutdown => return Ok(()),
+                EditorServiceRequest::CheckPath { id, file_path } => {
+                    let can_format = can_format_file(context.args, context.cache, context.environment, &file_path)?;
+                    write_multiplexed_message(&mut writer, id, if can_format { 1 } else { 0 }, Vec::new())?;
+                },
+                EditorServiceRequest::Format { id, file_path, file_text, cancellation_token } => {
+                    let result = if cancellation_token.is_cancelled() {
+                        None // stale, so don't bother formatting
+                    } else {
+                        Some(format_text(&mut context, &file_path, &file_text, &cancellation_token))
+                    };
+                    pending_formats.lock().remove(&id);
+                    match result {
+                        Some(result) if !cancellation_token.is_cancelled() => {
+                            let (response_code, text) = get_format_response(result, &file_text);
+                            write_multiplexed_message(&mut writer, id, response_code, text.into_iter().map(|text| text.into()).collect())?;
+                        },
+                        _ => write_multiplexed_message(&mut writer, id, 3, Vec::new())?, // cancelled
+                    }
+                },
+                EditorServiceRequest::Unknown { id, message_kind } => {
+                    let message = format!("Unknown message kind: {}", message_kind);
+                    context.environment.log_error(&message);
+                    write_multiplexed_message(&mut writer, id, 2, vec![message.into()])?;
+                },
+            }
+        }
+    }
+
+    fn parse_request(
+        mut message: MultiplexedMessage,
+        pending_formats: &Mutex<HashMap<u32, (PathBuf, CancellationToken)>>,
+    ) -> Result<EditorServiceRequest, ErrBox> {
+        Ok(match message.code {
+            0 => EditorServiceRequest::Shutdown,
+            1 => EditorServiceRequest::CheckPath {
+                id: message.id,
+                file_path: message.parts.take_path_buf()?,
+            },
+            2 => {
+                let file_path = message.parts.take_path_buf()?;
+                let file_text = message.parts.take_string()?;
+                let cancellation_token = CancellationToken::new();
+                let mut pending_formats = pending_formats.lock();
+                // a newer request for the same file makes the previous ones stale
+                for (pending_file_path, pending_token) in pending_formats.values() {
+                    if *pending_file_path == file_path {
+                        pending_token.cancel();
+                    }
+                }
+                pending_formats.insert(message.id, (file_path.clone(), cancellation_token.clone()));
+                EditorServiceRequest::Format { id: message.id, file_path, file_text, cancellation_token }
+            },
+            message_kind => EditorServiceRequest::Unknown { id: message.id, message_kind },
+        })
+    }
+
+    fn can_format_file<TEnvironment: Environment>(
         args: &CliArgs,
         cache: &Cache<TEnvironment>,
         environment: &TEnvironment,
-        plugin_resolver: &PluginResolver<TEnvironment>,
-        plugin_pools: &Arc<PluginPools<TEnvironment>>,
-        past_config: &Option<ResolvedConfig>,
         file_path: &Path,
-        file_text: &'a str,
-    ) -> Result<(Cow<'a, str>, ResolvedConfig), ErrBox> {
+    ) -> Result<bool, ErrBox> {
         let config = resolve_config_from_args(&args, cache, environment)?;
-        let has_config_changed = past_config.is_none() || *past_config.as_ref().unwrap() != config;
+        let file_paths = resolve_file_paths(&config, args, environment)?;
+
+        // canonicalize the file path, then check if it's in the list of file paths.
+        match environment.canonicalize(&file_path) {
+            Ok(resolved_file_path) => Ok(file_paths.contains(&resolved_file_path)),
+            Err(err) => {
+                environment.log_error(&format!("Error canonicalizing file {}: {}", file_path.display(), err.to_string()));
+                Ok(false) // don't format, something went wrong
+            },
+        }
+    }
+
+    /// Gets the response code and text for the result of formatting.
+    fn get_format_response(result: Result<Cow<str>, ErrBox>, file_text: &str) -> (u32, Option<String>) {
+        match result {
+            Ok(formatted_text) => {
+                if formatted_text == file_text {
+                    (0, None) // no change
+                } else {
+                    (1, Some(formatted_text.into_owned())) // change
+                }
+            },
+            Err(err) => (2, Some(err.to_string())), // error
+        }
+    }
+
+    fn format_text<'a, TEnvironment: Environment>(
+        context: &mut EditorServiceContext<'_, TEnvironment>,
+        file_path: &Path,
+        file_text: &'a str,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Cow<'a, str>, ErrBox> {
+        let (args, environment, plugin_pools) = (context.args, context.environment, context.plugin_pools);
+        let config = resolve_config_from_args(args, context.cache, environment)?;
+        let has_config_changed = context.past_config.is_none() || *context.past_config.as_ref().unwrap() != config;
         if has_config_changed {
             plugin_pools.drop_plugins(); // clear the existing plugins
-            let plugins = resolve_plugins(&config, environment, plugin_resolver)?;
-            plugin_pools.set_plugins(plugins);
+            let plugins = resolve_plugins(&config, args, environment, context.plugin_resolver)?;
+            plugin_pools.set_plugins(plugins, &config.interpreters, config.max_host_format_depth);
         }
 
-        let formatted_text = format_with_plugin_pools(&file_path, &file_text, environment, &plugin_pools)?;
-        Ok((formatted_text, config))
+        let formatted_text = format_with_plugin_pools(&file_path, &file_text, environment, &plugin_pools, cancellation_token)?;
+        context.past_config.replace(config);
+        Ok(formatted_text)
     }
 }
 
@@ -359,7 +498,7 @@ fn get_plugins_from_args<TEnvironment: Environment>(
     plugin_resolver: &PluginResolver<TEnvironment>,
 ) -> Result<Vec<Box<dyn Plugin>>, ErrBox> {
     match resolve_config_from_args(args, cache, environment) {
-        Ok(config) => resolve_plugins(&config, environment, plugin_resolver),
+        Ok(config) => resolve_plugins(&config, args, environment, plugin_resolver),
         Err(_) => Ok(Vec::new()), // ignore
     }
 }
@@ -389,6 +528,36 @@ fn output_resolved_config(
     Ok(())
 }
 
+/// Outputs the configuration diagnostics of all the plugins. Other commands only
+/// output the diagnostics of plugins that are used to format files.
+fn validate_config(
+    plugins: Vec<Box<dyn Plugin>>,
+    environment: &impl Environment,
+) -> Result<(), ErrBox> {
+    let error_logger = ErrorCountLogger::from_environment(environment);
+
+    for plugin in plugins {
+        // keep validating the other plugins when one can't be initialized
+        let initialized_plugin = match plugin.initialize() {
+            Ok(initialized_plugin) => initialized_plugin,
+            Err(err) => {
+                error_logger.log_error(&format!("Error initializing plugin {}. {}", plugin.name(), err.to_string()));
+                continue;
+            }
+        };
+        if let Err(err) = output_plugin_config_diagnostics(plugin.name(), &initialized_plugin, &error_logger) {
+            environment.log_error(&err.to_string());
+        }
+    }
+
+    let error_count = error_logger.get_error_count();
+    if error_count == 0 {
+        Ok(())
+    } else {
+        err!("Had {} configuration error(s).", error_count)
+    }
+}
+
 fn init_config_file(environment: &impl Environment, config_arg: &Option<String>) -> Result<(), ErrBox> {
     let config_file_path = get_config_path(config_arg)?;
     return if !environment.path_exists(&config_file_path) {
@@ -414,8 +583,9 @@ fn output_stdin_format<TEnvironment: Environment>(
     file_text: &str,
     environment: &TEnvironment,
     plugin_pools: Arc<PluginPools<TEnvironment>>,
+    cancellation_token: &CancellationToken,
 ) -> Result<(), ErrBox> {
-    let formatted_text = format_with_plugin_pools(file_name, file_text, environment, &plugin_pools)?;
+    let formatted_text = format_with_plugin_pools(file_name, file_text, environment, &plugin_pools, cancellation_token)?;
     environment.log_silent(&formatted_text);
     Ok(())
 }
@@ -425,15 +595,20 @@ fn format_with_plugin_pools<'a, TEnvironment: Environment>(
     file_text: &'a str,
     environment: &TEnvironment,
     plugin_pools: &Arc<PluginPools<TEnvironment>>,
+    cancellation_token: &CancellationToken,
 ) -> Result<Cow<'a, str>, ErrBox> {
-    if let Some(plugin_name) = plugin_pools.get_plugin_name_from_file_name(file_name) {
+    if let Some(plugin_name) = plugin_pools.get_plugin_name_from_file_text(file_name, file_text) {
         let plugin_pool = plugin_pools.get_pool(&plugin_name).unwrap();
         let error_logger = ErrorCountLogger::from_environment(environment);
         match plugin_pool.take_or_create_checking_config_diagnostics(&error_logger)? {
             TakePluginResult::Success(mut initialized_plugin) => {
-                let result = initialized_plugin.format_text(file_name, file_text, &HashMap::new());
+                let result = initialized_plugin.format_text(file_name, file_text, &HashMap::new(), cancellation_token);
                 plugin_pool.release(initialized_plugin);
-                Ok(Cow::Owned(result?)) // release plugin above, then propagate this error
+                let result = result?; // release plugin above, then propagate this error
+                for diagnostic in result.diagnostics.iter() {
+                    environment.log_error(&get_format_diagnostic_text(file_name, file_text, diagnostic));
+                }
+                Ok(Cow::Owned(result.text))
             }
             TakePluginResult::HadDiagnostics => {
                 err!("Had {} configuration errors.", error_logger.get_error_count())
@@ -445,36 +620,31 @@ fn format_with_plugin_pools<'a, TEnvironment: Environment>(
 }
 
 fn check_files<TEnvironment: Environment>(
-    file_paths_by_plugin: HashMap<String, Vec<PathBuf>>,
+    config: &ResolvedConfig,
+    args: &CliArgs,
     environment: &TEnvironment,
     plugin_pools: Arc<PluginPools<TEnvironment>>,
     incremental_file: Option<Arc<IncrementalFile<TEnvironment>>>,
+    cancellation_token: &CancellationToken,
 ) -> Result<(), ErrBox> {
     let not_formatted_files_count = Arc::new(AtomicUsize::new(0));
+    let mut diff_options = DiffOptions::default();
+    if let Some(diff_context_lines) = args.diff_context_lines {
+        diff_options.context_lines = diff_context_lines;
+    }
 
-    run_parallelized(file_paths_by_plugin, environment, plugin_pools, incremental_file, {
+    let error_logger = ErrorCountLogger::from_environment(environment);
+    run_parallelized(|on_file_path| discover_file_paths(config, args, environment, on_file_path), environment, &error_logger, plugin_pools, incremental_file, args.reporter, cancellation_token, {
         let not_formatted_files_count = not_formatted_files_count.clone();
         move |file_path, file_text, formatted_text, _, _, environment| {
             if formatted_text != file_text {
                 not_formatted_files_count.fetch_add(1, Ordering::SeqCst);
-                match get_difference(&file_text, &formatted_text) {
-                    Ok(difference_text) => {
-                        environment.log(&format!(
-                            "{} {}:\n{}\n--",
-                            "from".bold().red().to_string(),
-                            file_path.display(),
-                            difference_text,
-                        ));
-                    }
-                    Err(err) => {
-                        environment.log(&format!(
-                            "{} {}:\nError getting difference, but this file needs formatting.\n\nError message: {}\n--",
-                            "from".bold().red().to_string(),
-                            file_path.display(),
-                            err.to_string().red().to_string(),
-                        ));
-                    }
-                }
+                environment.log(&format!(
+                    "{} {}:\n{}\n--",
+                    "from".bold().red().to_string(),
+                    file_path.display(),
+                    get_difference_with_options(&file_text, &formatted_text, &diff_options),
+                ));
             }
             Ok(())
         }
@@ -490,17 +660,25 @@ fn check_files<TEnvironment: Environment>(
 }
 
 fn format_files<TEnvironment: Environment>(
-    file_paths_by_plugin: HashMap<String, Vec<PathBuf>>,
+    config: &ResolvedConfig,
+    args: &CliArgs,
     environment: &TEnvironment,
     plugin_pools: Arc<PluginPools<TEnvironment>>,
     incremental_file: Option<Arc<IncrementalFile<TEnvironment>>>,
+    cancellation_token: &CancellationToken,
 ) -> Result<(), ErrBox> {
     let formatted_files_count = Arc::new(AtomicUsize::new(0));
-    let files_count: usize = file_paths_by_plugin.values().map(|x| x.len()).sum();
+    let error_logger = ErrorCountLogger::from_environment(environment);
+    let file_writer = Arc::new(DeferredFileWriter::new(environment.clone()));
 
-    run_parallelized(file_paths_by_plugin, environment, plugin_pools, incremental_file.clone(), {
+    let files_count = run_parallelized(|on_file_path| {
+        let result = discover_file_paths(config, args, environment, on_file_path);
+        file_writer.complete_discovery(result.is_ok(), &error_logger);
+        result
+    }, environment, &error_logger, plugin_pools, incremental_file.clone(), args.reporter, cancellation_token, {
         let formatted_files_count = formatted_files_count.clone();
-        move |file_path, file_text, formatted_text, had_bom, _, environment| {
+        let file_writer = file_writer.clone();
+        move |file_path, file_text, formatted_text, had_bom, _, _| {
             if formatted_text != file_text {
                 let new_text = if had_bom {
                     // add back the BOM
@@ -510,7 +688,7 @@ fn format_files<TEnvironment: Environment>(
                 };
 
                 formatted_files_count.fetch_add(1, Ordering::SeqCst);
-                environment.write_file(&file_path, &new_text)?;
+                file_writer.write_file(&file_path, new_text)?;
             }
 
             Ok(())
@@ -530,89 +708,266 @@ fn format_files<TEnvironment: Environment>(
     Ok(())
 }
 
+/// Defers the files formatted while the file paths are still being discovered so
+/// that nothing is written when discovering them fails (ex. an error walking a directory).
+///
+/// The formatted text is written to temporary files beside the files in the meantime
+/// in order to not hold it in memory.
+struct DeferredFileWriter<TEnvironment: Environment> {
+    environment: TEnvironment,
+    state: Mutex<DeferredFileWriterState>,
+}
+
+enum DeferredFileWriterState {
+    /// The paths of the files that have been written to temporary files.
+    Discovering(Vec<PathBuf>),
+    Discovered,
+    DiscoveryFailed,
+}
+
+impl<TEnvironment: Environment> DeferredFileWriter<TEnvironment> {
+    fn new(environment: TEnvironment) -> Self {
+        DeferredFileWriter {
+            environment,
+            state: Mutex::new(DeferredFileWriterState::Discovering(Vec::new())),
+        }
+    }
+
+    /// Writes the file once the file paths have been discovered.
+    fn write_file(&self, file_path: &Path, file_text: String) -> Result<(), ErrBox> {
+        let is_discovering = match &*self.state.lock() {
+            DeferredFileWriterState::Discovering(_) => true,
+            DeferredFileWriterState::DiscoveryFailed => return Ok(()),
+            DeferredFileWriterState::Discovered => false,
+        };
+        if !is_discovering {
+            return self.environment.write_file(file_path, &file_text);
+        }
+
+        let temp_file_path = get_temp_file_path(file_path);
+        self.environment.write_file(&temp_file_path, &file_text)?;
+
+        // discovery may have completed while writing
+        let mut state = self.state.lock();
+        match &mut *state {
+            DeferredFileWriterState::Discovering(pending_file_paths) => {
+                pending_file_paths.push(file_path.to_path_buf());
+                Ok(())
+            }
+            DeferredFileWriterState::DiscoveryFailed => self.environment.remove_file(&temp_file_path),
+            DeferredFileWriterState::Discovered => self.move_temp_file(&temp_file_path, file_path),
+        }
+    }
+
+    /// Moves the pending files over the files when discovery succeeded or otherwise removes them.
+    fn complete_discovery(&self, succeeded: bool, error_logger: &ErrorCountLogger<TEnvironment>) {
+        let new_state = if succeeded { DeferredFileWriterState::Discovered } else { DeferredFileWriterState::DiscoveryFailed };
+        let pending_file_paths = match std::mem::replace(&mut *self.state.lock(), new_state) {
+            DeferredFileWriterState::Discovering(pending_file_paths) => pending_file_paths,
+            _ => Vec::new(),
+        };
+        for file_path in pending_file_paths {
+            let temp_file_path = get_temp_file_path(&file_path);
+            if !succeeded {
+                let _ = self.environment.remove_file(&temp_file_path);
+            } else if let Err(err) = self.move_temp_file(&temp_file_path, &file_path) {
+                error_logger.log_error(&format!("Error formatting {}. Message: {}", file_path.display(), err.to_string()));
+            }
+        }
+    }
+
+    fn move_temp_file(&self, temp_file_path: &Path, file_path: &Path) -> Result<(), ErrBox> {
+        let result = self.environment.rename(temp_file_path, file_path);
+        if result.is_err() {
+            let _ = self.environment.remove_file(temp_file_path);
+        }
+        result
+    }
+}
+
 fn output_format_times<TEnvironment: Environment>(
-    file_paths_by_plugin: HashMap<String, Vec<PathBuf>>,
+    config: &ResolvedConfig,
+    args: &CliArgs,
     environment: &TEnvironment,
     plugin_pools: Arc<PluginPools<TEnvironment>>,
+    cancellation_token: &CancellationToken,
 ) -> Result<(), ErrBox> {
-    let durations: Arc<Mutex<Vec<(PathBuf, u128)>>> = Arc::new(Mutex::new(Vec::new()));
-
-    run_parallelized(file_paths_by_plugin, environment, plugin_pools, None, {
+    // the file path, how long it took to format, and when it started formatting
+    let durations: Arc<Mutex<Vec<(PathBuf, u128, u128)>>> = Arc::new(Mutex::new(Vec::new()));
+    let start_instant = Instant::now();
+    let mut first_file_time = None;
+    let mut discovery_time = 0;
+
+    let files_count = run_parallelized(|on_file_path| {
+        let result = discover_file_paths(config, args, environment, &mut |file_path| {
+            first_file_time.get_or_insert_with(|| start_instant.elapsed().as_millis());
+            on_file_path(file_path);
+        });
+        discovery_time = start_instant.elapsed().as_millis();
+        result
+    }, environment, &ErrorCountLogger::from_environment(environment), plugin_pools, None, Reporter::Default, cancellation_token, {
         let durations = durations.clone();
-        move |file_path, _, _, _, start_instant, _| {
-            let duration = start_instant.elapsed().as_millis();
+        move |file_path, _, _, _, file_start_instant, _| {
+            let duration = file_start_instant.elapsed().as_millis();
+            let start_time = file_start_instant.duration_since(start_instant).as_millis();
             let mut durations = durations.lock();
-            durations.push((file_path.to_owned(), duration));
+            durations.push((file_path.to_owned(), duration, start_time));
             Ok(())
         }
     })?;
 
     let mut durations = durations.lock();
     durations.sort_by_key(|k| k.1);
-    for (file_path, duration) in durations.iter() {
+    for (file_path, duration, _) in durations.iter() {
         environment.log(&format!("{}ms - {}", duration, file_path.display()));
     }
 
+    // slow files should be started early so they don't hold up the end of the run
+    let slowest_file_start_time = durations.last().map(|(_, _, start_time)| *start_time).unwrap_or(0);
+    environment.log(&format!(
+        "Found {} file(s) in {}ms (first file found after {}ms). Finished in {}ms (slowest file started after {}ms).",
+        files_count,
+        discovery_time,
+        first_file_time.unwrap_or(0),
+        start_instant.elapsed().as_millis(),
+        slowest_file_start_time,
+    ));
+
     Ok(())
 }
 
-fn run_parallelized<F, TEnvironment: Environment>(
-    file_paths_by_plugin: HashMap<String, Vec<PathBuf>>,
+/// Formats the discovered file paths in parallel, returning the number of files found.
+fn run_parallelized<TDiscover, F, TEnvironment: Environment>(
+    discover_file_paths: TDiscover,
     environment: &TEnvironment,
+    error_logger: &ErrorCountLogger<TEnvironment>,
     plugin_pools: Arc<PluginPools<TEnvironment>>,
     incremental_file: Option<Arc<IncrementalFile<TEnvironment>>>,
+    reporter: Reporter,
+    cancellation_token: &CancellationToken,
     f: F,
-) -> Result<(), ErrBox> where F: Fn(&Path, &str, String, bool, Instant, &TEnvironment) -> Result<(), ErrBox> + Send + 'static + Clone {
-    let error_logger = ErrorCountLogger::from_environment(environment);
-
-    do_batch_format(environment, &error_logger, &plugin_pools, file_paths_by_plugin, {
-        let environment = environment.clone();
-        let incremental_file = incremental_file.clone();
-        let error_logger = error_logger.clone();
-        move |plugin_pool, file_path, plugin| {
-            let result = run_for_file_path(&environment, &incremental_file, plugin_pool, file_path, plugin, f.clone());
+) -> Result<usize, ErrBox>
+    where
+        TDiscover: FnOnce(&mut dyn FnMut(PathBuf)) -> Result<(), ErrBox>,
+        F: Fn(&Path, &str, String, bool, Instant, &TEnvironment) -> Result<(), ErrBox> + Send + 'static + Clone
+{
+    let get_file_estimate = |file_path: &Path| get_file_estimate(environment, &incremental_file, file_path);
+
+    let files_count = do_batch_format(environment, error_logger, &plugin_pools, discover_file_paths, &get_file_estimate, {
+        let context = FormatFileContext {
+            environment: environment.clone(),
+            error_logger: error_logger.clone(),
+            incremental_file: incremental_file.clone(),
+            reporter,
+            cancellation_token: cancellation_token.clone(),
+            f,
+        };
+        move |plugin_pool, file_path, file_metadata, plugin| {
+            // skip the remaining files once the run is cancelled
+            if context.cancellation_token.is_cancelled() {
+                return;
+            }
+            let result = run_for_file_path(&context, plugin_pool, file_path, file_metadata, plugin);
             if let Err(err) = result {
-                error_logger.log_error(&format!("Error formatting {}. Message: {}", file_path.display(), err.to_string()));
+                // the file was only partway formatted when the run was cancelled
+                if !context.cancellation_token.is_cancelled() {
+                    context.error_logger.log_error(&format!("Error formatting {}. Message: {}", file_path.display(), err.to_string()));
+                }
             }
         }
     })?;
 
+    if cancellation_token.is_cancelled() {
+        return err!("Cancelled formatting.");
+    }
+
+    if files_count == 0 {
+        return err!("No files found to format with the specified plugins. You may want to try using `dprint output-file-paths` to see which files it's finding.");
+    }
+
     let error_count = error_logger.get_error_count();
     return if error_count == 0 {
-        Ok(())
+        Ok(files_count)
     } else {
         err!("Had {0} error(s) formatting.", error_count)
     };
 
+    /// What's shared by the formatting of each file.
+    #[derive(Clone)]
+    struct FormatFileContext<TEnvironment: Environment, F> {
+        environment: TEnvironment,
+        error_logger: ErrorCountLogger<TEnvironment>,
+        incremental_file: Option<Arc<IncrementalFile<TEnvironment>>>,
+        reporter: Reporter,
+        cancellation_token: CancellationToken,
+        f: F,
+    }
+
     #[inline]
     fn run_for_file_path<F, TEnvironment: Environment>(
-        environment: &TEnvironment,
-        incremental_file: &Option<Arc<IncrementalFile<TEnvironment>>>,
+        context: &FormatFileContext<TEnvironment, F>,
         plugin_pool: &InitializedPluginPool<TEnvironment>,
         file_path: &Path,
+        file_metadata: Option<FileMetadata>,
         initialized_plugin: &mut Box<dyn InitializedPlugin>,
-        f: F
     ) -> Result<(), ErrBox> where F: Fn(&Path, &str, String, bool, Instant, &TEnvironment) -> Result<(), ErrBox> + Send + 'static + Clone {
+        let FormatFileContext { environment, error_logger, incremental_file, reporter, cancellation_token, f } = context;
+        // the metadata was retrieved on discovery before reading the file, so any changes made after are detected next time
+        if let (Some(incremental_file), Some(file_metadata)) = (incremental_file, &file_metadata) {
+            if incremental_file.is_file_metadata_same(file_path, file_metadata, plugin_pool.plugin_hash()) {
+                log_verbose!(environment, "No change: {}", file_path.display());
+                return Ok(());
+            }
+        }
+
         let file_text = FileText::new(environment.read_file(&file_path)?);
 
         if let Some(incremental_file) = incremental_file {
-            if incremental_file.is_file_same(file_path, file_text.as_str()) {
+            if incremental_file.is_file_same(file_path, file_text.as_str(), plugin_pool.plugin_hash(), file_metadata) {
                 log_verbose!(environment, "No change: {}", file_path.display());
                 return Ok(());
             }
         }
 
-        let (start_instant, formatted_text) = {
+        let (start_instant, format_text_result, host_format_plugin_hashes) = {
             let start_instant = Instant::now();
-            let format_text_result = plugin_pool.format_measuring_time(|| {
-                initialized_plugin.format_text(file_path, file_text.as_str(), &HashMap::new())
+            let (format_text_result, host_format_plugin_hashes) = collect_host_format_plugin_hashes(|| {
+                plugin_pool.format_measuring_time(|| {
+                    initialized_plugin.format_text(file_path, file_text.as_str(), &HashMap::new(), cancellation_token)
+                })
             });
             log_verbose!(environment, "Formatted file: {} in {}ms", file_path.display(), start_instant.elapsed().as_millis());
-            (start_instant, format_text_result?)
+            (start_instant, format_text_result?, host_format_plugin_hashes)
         };
+        let format_time = start_instant.elapsed().as_millis() as u64;
+        let formatted_text = format_text_result.text;
+
+        for diagnostic in format_text_result.diagnostics.iter() {
+            let is_error = diagnostic.severity == FormatDiagnosticSeverity::Error;
+            match reporter {
+                Reporter::Default => {
+                    let text = get_format_diagnostic_text(file_path, file_text.as_str(), diagnostic);
+                    if is_error {
+                        error_logger.log_error(&text);
+                    } else {
+                        environment.log_error(&text);
+                    }
+                }
+                Reporter::Json => {
+                    environment.log(&get_format_diagnostic_json(file_path, file_text.as_str(), diagnostic));
+                    if is_error {
+                        error_logger.count_error();
+                    }
+                }
+            }
+        }
 
+        // don't store files with diagnostics so they're reported again on the next run
+        let incremental_file = if format_text_result.diagnostics.is_empty() { incremental_file.as_ref() } else { None };
         if let Some(incremental_file) = incremental_file {
-            incremental_file.update_file(file_path, &formatted_text);
+            // the metadata will be different when the formatted text gets written to the file
+            let file_metadata = if formatted_text == file_text.as_str() { file_metadata } else { None };
+            incremental_file.update_file(file_path, &formatted_text, plugin_pool.plugin_hash(), host_format_plugin_hashes, file_metadata, format_time);
         }
 
         f(&file_path, file_text.as_str(), formatted_text, file_text.has_bom(), start_instant, &environment)?;
@@ -621,25 +976,121 @@ fn run_parallelized<F, TEnvironment: Environment>(
     }
 }
 
+/// Rough number of bytes formatted per millisecond, which is used to estimate
+/// how long a file will take to format when it hasn't been formatted before.
+const ESTIMATED_BYTES_PER_MILLISECOND: u64 = 1024;
+
+/// Gets the file's metadata along with the estimated milliseconds it will take to format
+/// based on how long it took the last time it was formatted or otherwise its size.
+fn get_file_estimate<TEnvironment: Environment>(
+    environment: &TEnvironment,
+    incremental_file: &Option<Arc<IncrementalFile<TEnvironment>>>,
+    file_path: &Path,
+) -> FileEstimate {
+    let file_metadata = match environment.get_file_metadata(file_path) {
+        Ok(file_metadata) => Some(file_metadata),
+        Err(err) => {
+            // the incremental file will fall back to comparing the file hash
+            log_verbose!(environment, "Could not get metadata for {}: {}", file_path.display(), err);
+            None
+        }
+    };
+    let format_time = incremental_file.as_ref().and_then(|incremental_file| incremental_file.get_format_time(file_path));
+    let estimated_time = match (format_time, &file_metadata) {
+        (Some(format_time), _) => format_time,
+        (None, Some(file_metadata)) => file_metadata.size / ESTIMATED_BYTES_PER_MILLISECOND,
+        (None, None) => 0,
+    };
+    FileEstimate { estimated_time, file_metadata }
+}
+
+fn get_format_diagnostic_text(file_path: &Path, file_text: &str, diagnostic: &FormatDiagnostic) -> String {
+    let range = get_valid_diagnostic_range(file_text, diagnostic).map(|range| (range.start, range.end));
+    format!(
+        "{} in {}. {}",
+        diagnostic.severity.as_display_str(),
+        file_path.display(),
+        format_diagnostic(range, &diagnostic.message, file_text),
+    )
+}
+
+/// A format diagnostic as it's output by the JSON reporter.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FormatDiagnosticJson<'a> {
+    file_path: String,
+    severity: FormatDiagnosticSeverity,
+    message: &'a str,
+    range: Option<FormatDiagnosticRange>,
+    /// The one-based line number of the start of the range.
+    line_number: Option<usize>,
+    /// The one-based column number of the start of the range.
+    column_number: Option<usize>,
+}
+
+fn get_format_diagnostic_json(file_path: &Path, file_text: &str, diagnostic: &FormatDiagnostic) -> String {
+    let range = get_valid_diagnostic_range(file_text, diagnostic);
+    serde_json::to_string(&FormatDiagnosticJson {
+        file_path: file_path.to_string_lossy().to_string(),
+        severity: diagnostic.severity,
+        message: &diagnostic.message,
+        range,
+        line_number: range.map(|range| get_line_number_of_pos(file_text, range.start)),
+        column_number: range.map(|range| get_column_number_of_pos(file_text, range.start)),
+    }).unwrap()
+}
+
+fn get_valid_diagnostic_range(file_text: &str, diagnostic: &FormatDiagnostic) -> Option<FormatDiagnosticRange> {
+    // plugins may provide an invalid range, so ignore it in that case rather than panicking
+    diagnostic.range.filter(|range| range.start <= range.end && file_text.get(range.start..range.end).is_some())
+}
+
 fn resolve_plugins_and_err_if_empty<TEnvironment: Environment>(
     config: &ResolvedConfig,
+    args: &CliArgs,
     environment: &TEnvironment,
     plugin_resolver: &PluginResolver<TEnvironment>,
 ) -> Result<Vec<Box<dyn Plugin>>, ErrBox> {
-    let plugins = resolve_plugins(config, environment, plugin_resolver)?;
+    let plugins = resolve_plugins(config, args, environment, plugin_resolver)?;
     if plugins.is_empty() {
         return err!("No formatting plugins found. Ensure at least one is specified in the 'plugins' array of the configuration file.");
     }
     Ok(plugins)
 }
 
+fn update_plugins_lockfile<TEnvironment: Environment>(
+    config: &ResolvedConfig,
+    args: &CliArgs,
+    environment: &TEnvironment,
+    plugin_resolver: &PluginResolver<TEnvironment>,
+) -> Result<(), ErrBox> {
+    if !args.plugins.is_empty() {
+        return err!("Cannot update {} with plugins provided via --plugins. Specify them in the configuration file instead.", LOCKFILE_NAME);
+    }
+    verify_plugin_policy(&config.plugins, environment)?;
+    plugin_resolver.set_signature_policy(config.plugin_signature_policy.clone());
+    let lockfile_path = config.base_path.join(LOCKFILE_NAME);
+    plugin_resolver.update_lockfile(config.plugins.clone(), &lockfile_path)?;
+    environment.log(&format!("Updated {}", lockfile_path.display()));
+    Ok(())
+}
+
 fn resolve_plugins<TEnvironment: Environment>(
     config: &ResolvedConfig,
+    args: &CliArgs,
     environment: &TEnvironment,
     plugin_resolver: &PluginResolver<TEnvironment>,
 ) -> Result<Vec<Box<dyn Plugin>>, ErrBox> {
     // resolve the plugins
-    let plugins = plugin_resolver.resolve_plugins(config.plugins.clone())?;
+    verify_plugin_policy(&config.plugins, environment)?;
+    plugin_resolver.set_signature_policy(config.plugin_signature_policy.clone());
+    let plugins = if args.plugins.is_empty() {
+        let lockfile_path = config.base_path.join(LOCKFILE_NAME);
+        plugin_resolver.resolve_plugins_with_lockfile(config.plugins.clone(), &lockfile_path, args.frozen)?
+    } else {
+        // plugins provided on the command line don't affect the lockfile
+        plugin_resolver.resolve_plugins(config.plugins.clone())?
+    };
     let mut config_map = config.config_map.clone();
 
     // resolve each plugin's configuration
@@ -666,13 +1117,28 @@ fn resolve_plugins<TEnvironment: Environment>(
 }
 
 fn resolve_file_paths(config: &ResolvedConfig, args: &CliArgs, environment: &impl Environment) -> Result<Vec<PathBuf>, ErrBox> {
+    let mut file_paths = Vec::new();
+    discover_file_paths(config, args, environment, &mut |file_path| file_paths.push(file_path))?;
+    Ok(file_paths)
+}
+
+/// Provides each resolved file path to `on_file_path` as it's found.
+fn discover_file_paths(
+    config: &ResolvedConfig,
+    args: &CliArgs,
+    environment: &impl Environment,
+    on_file_path: &mut dyn FnMut(PathBuf),
+) -> Result<(), ErrBox> {
     let cwd = environment.cwd()?;
     let mut file_patterns = get_file_patterns(config, args, &cwd.to_string_lossy());
     let absolute_paths = take_absolute_paths(&mut file_patterns, environment);
 
-    let mut file_paths = environment.glob(&config.base_path, &file_patterns)?;
-    file_paths.extend(absolute_paths);
-    return Ok(file_paths);
+    let use_gitignore = args.use_gitignore || config.use_gitignore;
+    environment.glob(&config.base_path, &file_patterns, use_gitignore, on_file_path)?;
+    for file_path in absolute_paths {
+        on_file_path(file_path);
+    }
+    return Ok(());
 
     fn get_file_patterns(config: &ResolvedConfig, args: &CliArgs, cwd: &str) -> Vec<String> {
         let mut file_patterns = Vec::new();
@@ -760,7 +1226,7 @@ fn get_incremental_file<TEnvironment: Environment>(
     environment: &TEnvironment,
 ) -> Option<Arc<IncrementalFile<TEnvironment>>> {
     if args.incremental || config.incremental {
-        // the incremental file is stored in the cache with a key based on the root directory
+        let start_time = environment.get_time_nanos();
         let base_path = match environment.canonicalize(&config.base_path) {
             Ok(base_path) => base_path,
             Err(err) => {
@@ -768,6 +1234,18 @@ fn get_incremental_file<TEnvironment: Environment>(
                 return None;
             }
         };
+
+        if let Some(file_path) = &config.incremental_cache_path {
+            if let Some(parent_dir_path) = file_path.parent() {
+                if let Err(err) = environment.mk_dir_all(parent_dir_path) {
+                    environment.log_error(&format!("Could not create directory for incremental file. {}", err));
+                    return None;
+                }
+            }
+            return Some(Arc::new(IncrementalFile::new(file_path.clone(), environment.clone(), base_path, plugin_pools.get_plugin_hashes(), start_time)));
+        }
+
+        // otherwise, the incremental file is stored in the cache with a key based on the root directory
         let key = format!("incremental_cache:{}", base_path.to_string_lossy());
         let cache_item = if let Some(cache_item) = cache.get_cache_item(&key) {
             cache_item
@@ -787,7 +1265,7 @@ fn get_incremental_file<TEnvironment: Environment>(
             }
         };
         let file_path = cache.resolve_cache_item_file_path(&cache_item);
-        Some(Arc::new(IncrementalFile::new(file_path, plugin_pools.get_plugins_hash(), environment.clone(), base_path)))
+        Some(Arc::new(IncrementalFile::new(file_path, environment.clone(), base_path, plugin_pools.get_plugin_hashes(), start_time)))
     } else {
         None
     }
@@ -798,18 +1276,20 @@ mod tests {
     use crossterm::style::Stylize;
     use pretty_assertions::assert_eq;
     use std::path::{Path, PathBuf};
+    use std::collections::HashSet;
     use std::sync::Arc;
     use std::io::{Read, Write};
 
     use crate::cache::Cache;
     use crate::environment::{Environment, TestEnvironment};
     use crate::configuration::*;
-    use crate::plugins::{PluginsDropper, PluginPools, CompilationResult, PluginResolver, PluginCache};
+    use crate::plugins::{PluginsDropper, PluginPools, CompilationResult, PluginResolver, PluginCache, read_lockfile, create_test_signature};
     use dprint_core::types::ErrBox;
-    use dprint_core::plugins::process::{StdIoReaderWriter, StdIoMessenger};
-    use crate::utils::get_difference;
+    use dprint_core::plugins::CancellationToken;
+    use dprint_core::plugins::process::{StdIoReaderWriter, StdIoMessenger, MultiplexedMessage, read_multiplexed_message, write_multiplexed_message};
+    use crate::utils::{get_difference, get_difference_with_options, get_temp_file_path, DiffOptions, ErrorCountLogger};
 
-    use super::run_cli;
+    use super::{run_cli, DeferredFileWriter};
     use super::super::{parse_args, TestStdInReader};
 
     fn run_test_cli(args: Vec<&str>, environment: &TestEnvironment) -> Result<(), ErrBox> {
@@ -820,19 +1300,30 @@ mod tests {
         args: Vec<&str>,
         environment: &TestEnvironment,
         stdin_reader: TestStdInReader, // todo: no clue why this can't be passed in by reference
+    ) -> Result<(), ErrBox> {
+        run_test_cli_with_stdin_and_cancellation_token(args, environment, stdin_reader, &CancellationToken::new())
+    }
+
+    fn run_test_cli_with_stdin_and_cancellation_token(
+        args: Vec<&str>,
+        environment: &TestEnvironment,
+        stdin_reader: TestStdInReader,
+        cancellation_token: &CancellationToken,
     ) -> Result<(), ErrBox> {
         let mut args: Vec<String> = args.into_iter().map(String::from).collect();
         args.insert(0, String::from(""));
-        environment.set_wasm_compile_result(COMPILATION_RESULT.clone());
+        if !environment.has_wasm_compile_result() {
+            environment.set_wasm_compile_result(COMPILATION_RESULT.clone());
+        }
         let cache = Arc::new(Cache::new(environment.clone()));
         let plugin_cache = Arc::new(PluginCache::new(environment.clone()));
         let plugin_pools = Arc::new(PluginPools::new(environment.clone()));
         let _plugins_dropper = PluginsDropper::new(plugin_pools.clone());
-        let plugin_resolver = PluginResolver::new(environment.clone(), plugin_cache, plugin_pools.clone());
+        let plugin_resolver = PluginResolver::new(environment.clone(), plugin_cache.clone(), plugin_pools.clone());
         let args = parse_args(args, &stdin_reader)?;
         environment.set_silent(args.is_silent_output());
         environment.set_verbose(args.verbose);
-        run_cli(args, environment, &cache, &plugin_resolver, plugin_pools)
+        run_cli(args, environment, &cache, &plugin_cache, &plugin_resolver, plugin_pools, cancellation_token)
     }
 
     #[test]
@@ -940,7 +1431,8 @@ mod tests {
         environment.write_file(&PathBuf::from("/file3.txt_ps"), "const t=4;").unwrap();
         run_test_cli(vec!["output-format-times", "**/*.*"], &environment).unwrap();
         let logged_messages = environment.take_logged_messages();
-        assert_eq!(logged_messages.len(), 3); // good enough
+        assert_eq!(logged_messages.len(), 4); // good enough
+        assert!(logged_messages[3].starts_with("Found 3 file(s) in "));
     }
 
     #[test]
@@ -968,6 +1460,24 @@ mod tests {
         assert_eq!(environment.read_file(&file_path2).unwrap(), "text2_formatted_process");
     }
 
+    #[test]
+    fn it_should_not_format_files_when_cancelled() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt");
+        environment.write_file(&file_path, "text").unwrap();
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        let error_message = run_test_cli_with_stdin_and_cancellation_token(
+            vec!["fmt", "/file.txt"],
+            &environment,
+            TestStdInReader::new(),
+            &cancellation_token,
+        ).err().unwrap();
+        assert_eq!(error_message.to_string(), "Cancelled formatting.");
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "text");
+    }
+
     #[test]
     fn it_should_format_plugin_explicitly_specified_files() {
         let environment = get_initialized_test_environment_with_remote_process_plugin().unwrap();
@@ -980,6 +1490,44 @@ mod tests {
         assert_eq!(environment.read_file(&file_path1).unwrap(), "text_formatted_process");
     }
 
+    #[test]
+    fn it_should_format_extensionless_files_using_plugin_interpreters() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        // this interpreter is mentioned in test-process-plugin's PluginInfo
+        let file_path1 = PathBuf::from("/bin/script");
+        let file_path2 = PathBuf::from("/bin/other");
+        environment.write_file(&file_path1, "#!/usr/bin/env dprint-test-process\ntext").unwrap();
+        environment.write_file(&file_path2, "#!/bin/sh\ntext").unwrap();
+        run_test_cli(vec!["fmt", "/bin/*"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path1).unwrap(), "#!/usr/bin/env dprint-test-process\ntext_formatted_process");
+        assert_eq!(environment.read_file(&file_path2).unwrap(), "#!/bin/sh\ntext");
+    }
+
+    #[test]
+    fn it_should_format_extensionless_files_using_configured_interpreters() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let plugin_file_checksum = get_process_plugin_checksum(&environment);
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "interpreters": {{ "node": "txt", "dprint-test-process": ".TXT" }},
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }}"#, plugin_file_checksum)).unwrap();
+        let file_path1 = PathBuf::from("/bin/script");
+        let file_path2 = PathBuf::from("/bin/other");
+        environment.write_file(&file_path1, "#!/usr/bin/env node\ntext").unwrap();
+        environment.write_file(&file_path2, "#!/usr/bin/env dprint-test-process\ntext").unwrap();
+        run_test_cli(vec!["fmt", "/bin/*"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_plural_formatted_text(2)]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path1).unwrap(), "#!/usr/bin/env node\ntext_formatted");
+        // the configuration takes precedence over the plugin
+        assert_eq!(environment.read_file(&file_path2).unwrap(), "#!/usr/bin/env dprint-test-process\ntext_formatted");
+    }
+
     #[test]
     fn it_should_format_files_with_local_plugin() {
         let environment = get_test_environment_with_local_wasm_plugin();
@@ -1019,17 +1567,99 @@ mod tests {
     }
 
     #[test]
-    fn it_should_handle_wasm_plugin_panicking() {
+    fn it_should_output_wasm_plugin_warning_diagnostics() {
         let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
-        environment.write_file(&PathBuf::from("/file1.txt"), "should_panic").unwrap();
-        environment.write_file(&PathBuf::from("/file2.txt"), "test").unwrap();
-        let error_message = run_test_cli(vec!["fmt", "**.txt"], &environment).err().unwrap();
-        assert_eq!(environment.take_logged_messages().len(), 0);
-        let logged_errors = environment.take_logged_errors();
-        assert_eq!(logged_errors.len(), 1);
-        assert_eq!(logged_errors[0].starts_with("Error formatting /file1.txt. Message: RuntimeError: unreachable"), true);
-        assert_eq!(error_message.to_string(), "Had 1 error(s) formatting.");
-        assert_eq!(environment.read_file(&PathBuf::from("/file2.txt")).unwrap(), "test_formatted");
+        let file_path = PathBuf::from("/file.txt");
+        environment.write_file(&file_path, "text diagnostic_warning").unwrap();
+        run_test_cli(vec!["fmt", "/file.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors(), vec![String::from(concat!(
+            "Warning in /file.txt. Line 1, column 6: Found a warning.\n",
+            "\n",
+            "  text diagnostic_warning\n",
+            "       ~~~~~~~~~~~~~~~~~~"
+        ))]);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "text diagnostic_warning_formatted");
+    }
+
+    #[test]
+    fn it_should_output_diagnostics_as_json_with_json_reporter() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/file.txt"), "text diagnostic_warning").unwrap();
+        environment.write_file(&PathBuf::from("/file.txt_ps"), "diagnostic_error").unwrap();
+        run_test_cli(vec!["fmt", "--reporter", "json", "/file.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![
+            String::from(r#"{"filePath":"/file.txt","severity":"warning","message":"Found a warning.","range":{"start":5,"end":23},"lineNumber":1,"columnNumber":6}"#),
+            get_singular_formatted_text(),
+        ]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+
+        let error_message = run_test_cli(vec!["check", "--reporter", "json", "/file.txt_ps"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Had 1 error(s) formatting.");
+        let logged_messages = environment.take_logged_messages();
+        assert_eq!(
+            logged_messages[0],
+            r#"{"filePath":"/file.txt_ps","severity":"error","message":"Found an error.","range":null,"lineNumber":null,"columnNumber":null}"#,
+        );
+        assert_eq!(environment.take_logged_errors().len(), 0);
+    }
+
+    #[test]
+    fn it_should_output_process_plugin_diagnostics_and_error_for_error_diagnostics() {
+        let environment = get_initialized_test_environment_with_remote_process_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt_ps");
+        environment.write_file(&file_path, "diagnostic_error").unwrap();
+        let error_message = run_test_cli(vec!["fmt", "/file.txt_ps"], &environment).err().unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(environment.take_logged_errors(), vec![String::from("Error in /file.txt_ps. Found an error.")]);
+        assert_eq!(error_message.to_string(), "Had 1 error(s) formatting.");
+        assert_eq!(environment.read_file(&file_path).unwrap(), "diagnostic_error_formatted_process");
+    }
+
+    #[test]
+    fn it_should_output_process_plugin_diagnostics_when_no_change() {
+        let environment = get_initialized_test_environment_with_remote_process_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt_ps");
+        environment.write_file(&file_path, "diagnostic_warning_formatted_process").unwrap();
+        run_test_cli(vec!["check", "/file.txt_ps"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(environment.take_logged_errors(), vec![String::from(concat!(
+            "Warning in /file.txt_ps. Line 1, column 1: Found a warning.\n",
+            "\n",
+            "  diagnostic_warning_formatted\n",
+            "  ~~~~~~~~~~~~~~~~~~"
+        ))]);
+    }
+
+    #[test]
+    fn it_should_output_diagnostics_again_when_incremental() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("./dprint.json"), r#"{
+            "incremental": true,
+            "includes": ["/*.txt"],
+            "plugins": ["https://plugins.dprint.dev/test-plugin.wasm"]
+        }"#).unwrap();
+        let file_path = PathBuf::from("/file.txt");
+        environment.write_file(&file_path, "diagnostic_warning_formatted").unwrap();
+        run_test_cli(vec!["fmt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_errors().len(), 1);
+        environment.clear_logs();
+        run_test_cli(vec!["fmt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_errors().len(), 1);
+    }
+
+    #[test]
+    fn it_should_handle_wasm_plugin_panicking() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/file1.txt"), "should_panic").unwrap();
+        environment.write_file(&PathBuf::from("/file2.txt"), "test").unwrap();
+        let error_message = run_test_cli(vec!["fmt", "**.txt"], &environment).err().unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        let logged_errors = environment.take_logged_errors();
+        assert_eq!(logged_errors.len(), 1);
+        assert_eq!(logged_errors[0].starts_with("Error formatting /file1.txt. Message: RuntimeError: unreachable"), true);
+        assert_eq!(error_message.to_string(), "Had 1 error(s) formatting.");
+        assert_eq!(environment.read_file(&PathBuf::from("/file2.txt")).unwrap(), "test_formatted");
     }
 
     #[test]
@@ -1054,6 +1684,53 @@ mod tests {
         assert_eq!(environment.read_file(&file_path).unwrap(), "format this text_formatted_process");
     }
 
+    #[test]
+    fn it_should_format_calling_process_plugin_with_wasm_plugin_by_language() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt");
+        environment.write_file(&file_path, "plugin-language: format this text").unwrap();
+        run_test_cli(vec!["fmt", "/file.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "format this text_formatted_process");
+    }
+
+    #[test]
+    fn it_should_format_calling_process_plugin_with_wasm_plugin_by_file_path_when_language_unknown() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt");
+        environment.write_file(&file_path, "plugin-unknown-language: format this text").unwrap();
+        run_test_cli(vec!["fmt", "/file.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "format this text_formatted_process");
+    }
+
+    #[test]
+    fn it_should_format_large_file_with_wasm_plugin() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt");
+        // much larger than the plugin's memory buffer
+        let file_text = "large json text\n".repeat(100_000);
+        environment.write_file(&file_path, &file_text).unwrap();
+        run_test_cli(vec!["fmt", "/file.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path).unwrap(), format!("{}_formatted", file_text));
+    }
+
+    #[test]
+    fn it_should_format_large_file_calling_process_plugin_with_wasm_plugin() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt");
+        let file_text = "large json text\n".repeat(100_000);
+        environment.write_file(&file_path, &format!("plugin: {}", file_text)).unwrap();
+        run_test_cli(vec!["fmt", "/file.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path).unwrap(), format!("{}_formatted_process", file_text));
+    }
+
     #[test]
     fn it_should_format_calling_process_plugin_with_wasm_plugin_using_additional_plugin_specified_config() {
         let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
@@ -1078,6 +1755,25 @@ mod tests {
         assert_eq!(environment.take_logged_errors(), vec![String::from("Error formatting /file.txt. Message: Did error.")]);
     }
 
+    #[test]
+    fn it_should_error_when_exceeding_max_host_format_depth() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "maxHostFormatDepth": 0,
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }}"#, get_process_plugin_checksum(&environment))).unwrap();
+        let file_path = PathBuf::from("/file.txt");
+        environment.write_file(&file_path, "plugin: format this text").unwrap();
+        let error_message = run_test_cli(vec!["fmt", "/file.txt"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Had 1 error(s) formatting.");
+        assert_eq!(environment.take_logged_errors(), vec![String::from(
+            "Error formatting /file.txt. Message: Exceeded the maximum host format depth of 0: test-plugin -> test-process-plugin"
+        )]);
+    }
+
     #[test]
     fn it_should_format_calling_other_plugin_with_process_plugin_and_no_plugin_exists() {
         let environment = get_initialized_test_environment_with_remote_process_plugin().unwrap();
@@ -1100,6 +1796,17 @@ mod tests {
         assert_eq!(environment.read_file(&file_path).unwrap(), "format this text_formatted");
     }
 
+    #[test]
+    fn it_should_format_calling_wasm_plugin_with_process_plugin_by_language() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt_ps");
+        environment.write_file(&file_path, "plugin-language: format this text").unwrap();
+        run_test_cli(vec!["fmt", "/file.txt_ps"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "format this text_formatted");
+    }
+
     #[test]
     fn it_should_format_calling_wasm_plugin_with_process_plugin_using_additional_plugin_specified_config() {
         let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
@@ -1114,6 +1821,56 @@ mod tests {
         assert_eq!(environment.read_file(&file_path2).unwrap(), "format this text_formatted");
     }
 
+    #[test]
+    fn it_should_format_calling_wasm_plugin_with_process_plugin_concurrently() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let file_paths = (0..10).map(|i| PathBuf::from(format!("/file{}.txt_ps", i))).collect::<Vec<_>>();
+        for (i, file_path) in file_paths.iter().enumerate() {
+            environment.write_file(file_path, &format!("plugin-concurrent: text{}", i)).unwrap();
+        }
+        run_test_cli(vec!["fmt", "*.txt_ps"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_plural_formatted_text(10)]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        let mut process_ids = HashSet::new();
+        for (i, file_path) in file_paths.iter().enumerate() {
+            // each request should get the response of its own host format request
+            let file_text = environment.read_file(file_path).unwrap();
+            let expected_start = format!("text{}_formatted_", i);
+            assert!(file_text.starts_with(&expected_start), "Unexpected text: {}", file_text);
+            process_ids.insert(file_text[expected_start.len()..].to_string());
+        }
+        // all the requests should have been handled by the same process
+        assert_eq!(process_ids.len(), 1);
+    }
+
+    #[test]
+    fn it_should_format_with_request_response_process_plugin() {
+        let environment = TestEnvironment::new();
+        setup_test_environment_with_remote_wasm_plugin(&environment);
+        setup_test_environment_with_remote_request_response_process_plugin(&environment);
+        let plugin_file_checksum = get_request_response_process_plugin_checksum(&environment);
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-process-request-response.exe-plugin@{}"
+            ]
+        }}"#, plugin_file_checksum)).unwrap();
+        run_test_cli(vec!["license"], &environment).unwrap(); // cause initialization
+        environment.clear_logs();
+        let file_path1 = PathBuf::from("/file1.txt_ps");
+        environment.write_file(&file_path1, "text").unwrap();
+        let file_path2 = PathBuf::from("/file2.txt_ps");
+        environment.write_file(&file_path2, "plugin: format this text").unwrap();
+        let file_path3 = PathBuf::from("/file3.txt");
+        environment.write_file(&file_path3, "plugin: format this text").unwrap();
+        run_test_cli(vec!["fmt", "/file*.*"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_plural_formatted_text(3)]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path1).unwrap(), "text_formatted_process");
+        assert_eq!(environment.read_file(&file_path2).unwrap(), "format this text_formatted");
+        assert_eq!(environment.read_file(&file_path3).unwrap(), "format this text_formatted_process");
+    }
+
     #[test]
     fn it_should_error_calling_wasm_plugin_with_process_plugin_and_wasm_plugin_errors() {
         let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
@@ -1170,6 +1927,69 @@ mod tests {
         assert_eq!(environment.take_logged_errors().len(), 0);
     }
 
+    #[test]
+    fn it_should_ignore_files_in_dprintignore() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/.dprintignore"), "generated/\n/ignored.txt").unwrap();
+        environment.write_file(&PathBuf::from("/sub/.dprintignore"), "*.txt\n!kept.txt").unwrap();
+        environment.write_file(&PathBuf::from("/generated/file.txt"), "").unwrap();
+        environment.write_file(&PathBuf::from("/ignored.txt"), "").unwrap();
+        environment.write_file(&PathBuf::from("/sub/other.txt"), "").unwrap();
+        environment.write_file(&PathBuf::from("/sub/kept.txt"), "").unwrap();
+        environment.write_file(&PathBuf::from("/file.txt"), "").unwrap();
+        run_test_cli(vec!["output-file-paths", "**/*.txt"], &environment).unwrap();
+        let mut logged_messages = environment.take_logged_messages();
+        logged_messages.sort();
+        assert_eq!(logged_messages, vec!["/file.txt", "/sub/kept.txt"]);
+    }
+
+    #[test]
+    fn it_should_not_use_gitignore_by_default() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/.gitignore"), "dist").unwrap();
+        environment.write_file(&PathBuf::from("/dist/file.txt"), "").unwrap();
+        environment.write_file(&PathBuf::from("/file.txt"), "").unwrap();
+        run_test_cli(vec!["output-file-paths", "**/*.txt"], &environment).unwrap();
+        let mut logged_messages = environment.take_logged_messages();
+        logged_messages.sort();
+        assert_eq!(logged_messages, vec!["/dist/file.txt", "/file.txt"]);
+    }
+
+    #[test]
+    fn it_should_use_gitignore_when_specified_on_cli() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/.gitignore"), "dist").unwrap();
+        environment.write_file(&PathBuf::from("/sub/.gitignore"), "*.gen.txt").unwrap();
+        environment.write_file(&PathBuf::from("/.git/info/exclude"), "local.txt").unwrap();
+        environment.write_file(&PathBuf::from("/dist/file.txt"), "").unwrap();
+        environment.write_file(&PathBuf::from("/sub/file.gen.txt"), "").unwrap();
+        environment.write_file(&PathBuf::from("/sub/file.txt"), "").unwrap();
+        environment.write_file(&PathBuf::from("/local.txt"), "").unwrap();
+        environment.write_file(&PathBuf::from("/file.gen.txt"), "").unwrap();
+        run_test_cli(vec!["output-file-paths", "--use-gitignore", "**/*.txt"], &environment).unwrap();
+        let mut logged_messages = environment.take_logged_messages();
+        logged_messages.sort();
+        assert_eq!(logged_messages, vec!["/file.gen.txt", "/sub/file.txt"]);
+    }
+
+    #[test]
+    fn it_should_use_gitignore_when_specified_via_config() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("./dprint.json"), r#"{
+            "useGitignore": true,
+            "includes": ["**/*.txt"],
+            "plugins": ["https://plugins.dprint.dev/test-plugin.wasm"]
+        }"#).unwrap();
+        environment.write_file(&PathBuf::from("/.gitignore"), "dist/").unwrap();
+        environment.write_file(&PathBuf::from("/dist/file.txt"), "text").unwrap();
+        environment.write_file(&PathBuf::from("/file.txt"), "text").unwrap();
+        run_test_cli(vec!["fmt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&PathBuf::from("/dist/file.txt")).unwrap(), "text");
+        assert_eq!(environment.read_file(&PathBuf::from("/file.txt")).unwrap(), "text_formatted");
+    }
+
     #[test]
     fn it_should_format_files_with_config() {
         let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
@@ -1297,6 +2117,438 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn it_should_only_output_config_diagnostics_of_used_plugins() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let plugin_file_checksum = get_process_plugin_checksum(&environment);
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "testProcessPlugin": {{ "non-existent": 25 }},
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }}"#, plugin_file_checksum)).unwrap();
+        environment.write_file(&PathBuf::from("/test.txt"), "test").unwrap();
+
+        run_test_cli(vec!["fmt", "**/*.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+
+        let error_message = run_test_cli(vec!["config", "validate"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Had 1 configuration error(s).");
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(environment.take_logged_errors(), vec![
+            "[test-process-plugin]: Unknown property in configuration: non-existent",
+            "[test-process-plugin]: Error initializing from configuration file. Had 1 diagnostic(s)."
+        ]);
+    }
+
+    #[test]
+    fn it_should_validate_config() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        run_test_cli(vec!["config", "validate"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+    }
+
+    #[test]
+    fn it_should_validate_config_of_other_plugins_when_one_fails_to_initialize() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "testProcessPlugin": {{ "non-existent": 25 }},
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }}"#, get_process_plugin_checksum(&environment))).unwrap();
+        create_lockfile(&environment);
+        let lockfile_path = PathBuf::from("./dprint.lock");
+        let lockfile_text = environment.read_file(&lockfile_path).unwrap();
+        environment.write_file(&lockfile_path, &lockfile_text.replace(&get_wasm_plugin_checksum(), "invalid")).unwrap();
+
+        let error_message = run_test_cli(vec!["config", "validate"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Had 2 configuration error(s).");
+        let checksum_message = format!("The checksum {} did not match the expected checksum of invalid.", get_wasm_plugin_checksum());
+        assert_eq!(environment.take_logged_errors(), vec![
+            format!("Error getting plugin from cache. Forgetting from cache and retrying. Message: {}", checksum_message),
+            format!("Error initializing plugin test-plugin. Error loading plugin https://plugins.dprint.dev/test-plugin.wasm: {}", checksum_message),
+            String::from("[test-process-plugin]: Unknown property in configuration: non-existent"),
+            String::from("[test-process-plugin]: Error initializing from configuration file. Had 1 diagnostic(s)."),
+        ]);
+    }
+
+    #[test]
+    fn it_should_only_load_plugins_used_to_format_files() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/test.txt"), "test").unwrap();
+        run_test_cli(vec!["fmt", "**/*.txt", "--verbose"], &environment).unwrap();
+        let logged_messages = environment.take_logged_messages();
+        assert!(logged_messages.iter().any(|message| message == "[VERBOSE]: Loading plugin test-plugin"));
+        assert!(!logged_messages.iter().any(|message| message == "[VERBOSE]: Loading plugin test-process-plugin"));
+        assert_eq!(environment.read_file(&PathBuf::from("/test.txt")).unwrap(), "test_formatted");
+    }
+
+    #[test]
+    fn it_should_not_create_lockfile_by_default() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/test.txt"), "test_formatted").unwrap();
+        run_test_cli(vec!["check", "/test.txt"], &environment).unwrap();
+        assert_eq!(read_lockfile(&PathBuf::from("./dprint.lock"), &environment).unwrap(), None);
+    }
+
+    #[test]
+    fn it_should_create_lockfile_on_plugins_update() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        run_test_cli(vec!["plugins", "update"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec!["Updated ./dprint.lock"]);
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/test-plugin.wasm"]);
+        let lockfile = read_lockfile(&PathBuf::from("./dprint.lock"), &environment).unwrap().unwrap();
+        assert_eq!(
+            lockfile.plugins.iter().map(|entry| (entry.url.as_str(), entry.info.name.as_str(), entry.info.config_key.as_str(), entry.checksum.clone())).collect::<Vec<_>>(),
+            vec![
+                ("https://plugins.dprint.dev/test-plugin.wasm", "test-plugin", "test-plugin", get_wasm_plugin_checksum()),
+                ("https://plugins.dprint.dev/test-process.exe-plugin", "test-process-plugin", "testProcessPlugin", get_process_plugin_checksum(&environment)),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_update_plugin_changed_upstream_on_plugins_update() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        create_lockfile(&environment);
+        let lockfile_path = PathBuf::from("./dprint.lock");
+        let lockfile_text = environment.read_file(&lockfile_path).unwrap();
+        environment.write_file(&lockfile_path, &lockfile_text.replace(&get_wasm_plugin_checksum(), "old-checksum")).unwrap();
+
+        run_test_cli(vec!["plugins", "update"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec!["Updated ./dprint.lock"]);
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/test-plugin.wasm"]);
+        let lockfile = read_lockfile(&lockfile_path, &environment).unwrap().unwrap();
+        assert_eq!(lockfile.plugins[0].checksum, get_wasm_plugin_checksum());
+    }
+
+    #[test]
+    fn it_should_error_frozen_when_no_lockfile() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        let error_message = run_test_cli(vec!["check", "--frozen"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Could not find a lockfile at ./dprint.lock. Run `dprint plugins update` to create it.");
+    }
+
+    #[test]
+    fn it_should_error_for_lockfile_of_different_version() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        let lockfile_path = PathBuf::from("./dprint.lock");
+        environment.write_file(&lockfile_path, &format!(r#"{{
+  "lockfileVersion": 1,
+  "plugins": [{{ "url": "https://plugins.dprint.dev/test-plugin.wasm", "name": "test-plugin", "version": "0.1.0", "configKey": "test-plugin", "checksum": "{}" }}]
+}}"#, get_wasm_plugin_checksum())).unwrap();
+        let expected_message = "The lockfile at ./dprint.lock is version 1, but this version of dprint expects version 2. Run `dprint plugins update` to update it.";
+
+        let error_message = run_test_cli(vec!["check"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), expected_message);
+        let error_message = run_test_cli(vec!["check", "--frozen"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), expected_message);
+
+        run_test_cli(vec!["plugins", "update"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec!["Updated ./dprint.lock"]);
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/test-plugin.wasm"]);
+        let lockfile = read_lockfile(&lockfile_path, &environment).unwrap().unwrap();
+        assert_eq!(lockfile.plugins[0].checksum, get_wasm_plugin_checksum());
+    }
+
+    #[test]
+    fn it_should_error_frozen_when_plugins_differ_from_lockfile() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        create_lockfile(&environment);
+        environment.write_file(&PathBuf::from("./dprint.json"), r#"{
+            "plugins": ["https://plugins.dprint.dev/test-plugin.wasm"]
+        }"#).unwrap();
+        environment.write_file(&PathBuf::from("/test.txt"), "test_formatted").unwrap();
+
+        let error_message = run_test_cli(vec!["check", "--frozen", "/test.txt"], &environment).err().unwrap();
+        assert_eq!(
+            error_message.to_string(),
+            "The plugin https://plugins.dprint.dev/test-process.exe-plugin in ./dprint.lock is no longer used. Run without --frozen to update it."
+        );
+
+        // should update the lockfile when not frozen
+        run_test_cli(vec!["check", "/test.txt"], &environment).unwrap();
+        let lockfile = read_lockfile(&PathBuf::from("./dprint.lock"), &environment).unwrap().unwrap();
+        assert_eq!(lockfile.plugins.len(), 1);
+        run_test_cli(vec!["check", "--frozen", "/test.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+    }
+
+    #[test]
+    fn it_should_error_when_plugin_does_not_match_lockfile_checksum() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        create_lockfile(&environment);
+        let lockfile_path = PathBuf::from("./dprint.lock");
+        let lockfile_text = environment.read_file(&lockfile_path).unwrap();
+        environment.write_file(&lockfile_path, &lockfile_text.replace(&get_wasm_plugin_checksum(), "invalid")).unwrap();
+        environment.write_file(&PathBuf::from("/test.txt"), "test").unwrap();
+
+        // the plugin is described by the lockfile, so it's only downloaded once a file needs it
+        let error_message = run_test_cli(vec!["fmt", "/test.txt"], &environment).err().unwrap();
+        let checksum_message = format!("The checksum {} did not match the expected checksum of invalid.", get_wasm_plugin_checksum());
+        assert_eq!(error_message.to_string(), "Had 1 error(s) formatting.");
+        assert_eq!(
+            environment.take_logged_errors(),
+            vec![
+                format!("Error getting plugin from cache. Forgetting from cache and retrying. Message: {}", checksum_message),
+                format!("Error loading plugin https://plugins.dprint.dev/test-plugin.wasm: {}", checksum_message),
+            ],
+        );
+    }
+
+    #[test]
+    fn it_should_only_download_plugins_in_lockfile_when_used_to_format_files() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        create_lockfile(&environment);
+        let manifest_path = environment.get_cache_dir().join("plugin-cache-manifest.json");
+        environment.remove_file(&manifest_path).unwrap();
+        environment.write_file(&PathBuf::from("/test.txt"), "test").unwrap();
+
+        run_test_cli(vec!["fmt", "/test.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/test-plugin.wasm"]);
+        assert_eq!(environment.read_file(&PathBuf::from("/test.txt")).unwrap(), "test_formatted");
+        let manifest_text = environment.read_file(&manifest_path).unwrap();
+        assert!(manifest_text.contains("remote:https://plugins.dprint.dev/test-plugin.wasm"));
+        assert!(!manifest_text.contains("remote:https://plugins.dprint.dev/test-process.exe-plugin"));
+    }
+
+    #[test]
+    fn it_should_export_and_import_plugins_bundle() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let plugin_file_checksum = get_process_plugin_checksum(&environment);
+        environment.add_remote_file("https://dprint.dev/test.json", r#"{
+            "test-plugin": {
+                "ending": "custom-formatted"
+            }
+        }"#.as_bytes());
+        let config_text = format!(r#"{{
+            "extends": "https://dprint.dev/test.json",
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }}"#, plugin_file_checksum);
+        environment.write_file(&PathBuf::from("./dprint.json"), &config_text).unwrap();
+        create_lockfile(&environment);
+        run_test_cli(vec!["plugins", "export", "/bundle.zip"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec!["Exported 2 plugin(s) and 1 configuration file(s) to /bundle.zip"]);
+
+        // import into an environment without internet access
+        let offline_environment = TestEnvironment::new();
+        offline_environment.write_file(&PathBuf::from("./dprint.json"), &config_text).unwrap();
+        offline_environment.write_file(&PathBuf::from("./dprint.lock"), &environment.read_file(&PathBuf::from("./dprint.lock")).unwrap()).unwrap();
+        offline_environment.write_file_bytes(&PathBuf::from("/bundle.zip"), &environment.read_file_bytes(&PathBuf::from("/bundle.zip")).unwrap()).unwrap();
+        run_test_cli(vec!["plugins", "import", "/bundle.zip"], &offline_environment).unwrap();
+        assert_eq!(offline_environment.take_logged_messages(), vec!["Imported 2 plugin(s) and 1 configuration file(s)"]);
+
+        offline_environment.write_file(&PathBuf::from("/file1.txt"), "text1").unwrap();
+        offline_environment.write_file(&PathBuf::from("/file2.txt_ps"), "text2").unwrap();
+        offline_environment.clear_logs();
+        run_test_cli(vec!["fmt", "--frozen", "*.*"], &offline_environment).unwrap();
+        assert_eq!(offline_environment.take_logged_messages(), vec![get_plural_formatted_text(2)]);
+        assert_eq!(offline_environment.take_logged_errors().len(), 0);
+        assert_eq!(offline_environment.read_file(&PathBuf::from("/file1.txt")).unwrap(), "text1_custom-formatted");
+        assert_eq!(offline_environment.read_file(&PathBuf::from("/file2.txt_ps")).unwrap(), "text2_formatted_process");
+    }
+
+    #[test]
+    fn it_should_error_importing_plugins_bundle_with_modified_file() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        create_lockfile(&environment);
+        run_test_cli(vec!["plugins", "export", "/bundle.zip"], &environment).unwrap();
+        environment.clear_logs();
+
+        // replace the plugin file in the archive
+        replace_plugins_bundle_plugin_file(&environment, |bundle_text| bundle_text);
+
+        let error_message = run_test_cli(vec!["plugins", "import", "/bundle.zip"], &environment).err().unwrap();
+        assert_eq!(
+            error_message.to_string(),
+            format!(
+                "Error importing https://plugins.dprint.dev/test-plugin.wasm. The checksum {} did not match the expected checksum of {}.",
+                dprint_cli_core::checksums::get_sha256_checksum(b"modified"),
+                get_wasm_plugin_checksum(),
+            )
+        );
+    }
+
+    #[test]
+    fn it_should_error_importing_plugins_bundle_with_file_not_matching_lockfile() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        create_lockfile(&environment);
+        run_test_cli(vec!["plugins", "export", "/bundle.zip"], &environment).unwrap();
+        environment.clear_logs();
+
+        // replace the plugin file and its checksum in the archive
+        let modified_checksum = dprint_cli_core::checksums::get_sha256_checksum(b"modified");
+        replace_plugins_bundle_plugin_file(&environment, |bundle_text| bundle_text.replace(&get_wasm_plugin_checksum(), &modified_checksum));
+
+        let error_message = run_test_cli(vec!["plugins", "import", "/bundle.zip"], &environment).err().unwrap();
+        assert_eq!(
+            error_message.to_string(),
+            format!(
+                "Error importing https://plugins.dprint.dev/test-plugin.wasm. The checksum {} did not match the expected checksum of {}.",
+                modified_checksum,
+                get_wasm_plugin_checksum(),
+            )
+        );
+    }
+
+    #[test]
+    fn it_should_error_importing_plugins_bundle_without_expected_checksum() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        run_test_cli(vec!["plugins", "export", "/bundle.zip"], &environment).unwrap();
+        environment.clear_logs();
+
+        let error_message = run_test_cli(vec!["plugins", "import", "/bundle.zip"], &environment).err().unwrap();
+        assert_eq!(
+            error_message.to_string(),
+            concat!(
+                "Error importing https://plugins.dprint.dev/test-plugin.wasm. ",
+                "The plugin must have a checksum in the configuration file or ./dprint.lock in order to verify it."
+            )
+        );
+    }
+
+    #[test]
+    fn it_should_error_importing_plugins_bundle_not_allowed_by_plugin_policy() {
+        let environment = get_initialized_test_environment_with_remote_process_plugin().unwrap();
+        run_test_cli(vec!["plugins", "export", "/bundle.zip"], &environment).unwrap();
+        environment.clear_logs();
+        environment.write_file(&environment.get_plugin_policy_file_path().unwrap(), r#"{ "denyProcessPlugins": true }"#).unwrap();
+
+        let error_message = run_test_cli(vec!["plugins", "import", "/bundle.zip"], &environment).err().unwrap();
+        assert_eq!(
+            error_message.to_string(),
+            concat!(
+                "The plugin https://plugins.dprint.dev/test-process.exe-plugin is not allowed by the plugin policy ",
+                "at /config/plugin-policy.json. Process plugins are denied."
+            )
+        );
+    }
+
+    fn replace_plugins_bundle_plugin_file(environment: &TestEnvironment, update_bundle_text: impl FnOnce(String) -> String) {
+        let archive_bytes = environment.read_file_bytes(&PathBuf::from("/bundle.zip")).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive_bytes)).unwrap();
+        let mut bundle_text = String::new();
+        archive.by_name("bundle.json").unwrap().read_to_string(&mut bundle_text).unwrap();
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("bundle.json", zip::write::FileOptions::default()).unwrap();
+        zip.write_all(update_bundle_text(bundle_text).as_bytes()).unwrap();
+        zip.start_file("files/0", zip::write::FileOptions::default()).unwrap();
+        zip.write_all(b"modified").unwrap();
+        environment.write_file_bytes(&PathBuf::from("/bundle.zip"), &zip.finish().unwrap().into_inner()).unwrap();
+    }
+
+    #[test]
+    fn it_should_list_cached_plugins() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        run_test_cli(vec!["plugins", "list"], &environment).unwrap();
+        let logged_messages = environment.take_logged_messages();
+        assert_eq!(logged_messages.len(), 2);
+        assert_eq!(logged_messages[0], "test-plugin 0.1.0");
+        let table_lines = logged_messages[1].lines().collect::<Vec<_>>();
+        assert_eq!(table_lines[0], "  Kind:      wasm");
+        assert!(table_lines[1].starts_with("  Size:      "));
+        assert!(table_lines[1].ends_with("KB") || table_lines[1].ends_with("MB"));
+        assert_eq!(table_lines[2], "  Last used: today");
+        assert_eq!(table_lines[3], "  Source:    https://plugins.dprint.dev/test-plugin.wasm");
+    }
+
+    #[test]
+    fn it_should_list_no_cached_plugins() {
+        let environment = TestEnvironment::new();
+        run_test_cli(vec!["plugins", "list"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec!["No plugins are cached."]);
+    }
+
+    #[test]
+    fn it_should_output_outdated_plugins() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        environment.add_remote_file(crate::plugins::REMOTE_INFO_URL, r#"{
+            "schemaVersion": 3,
+            "pluginSystemSchemaVersion": 3,
+            "latest": [{
+                "name": "test-plugin",
+                "version": "0.2.0",
+                "url": "https://plugins.dprint.dev/test-plugin-0.2.0.wasm",
+                "configKey": "test-plugin",
+                "fileExtensions": ["txt"],
+                "configExcludes": []
+            }, {
+                "name": "test-process-plugin",
+                "version": "0.1.0",
+                "url": "https://plugins.dprint.dev/test-process-0.1.0.exe-plugin",
+                "configKey": "testProcessPlugin",
+                "fileExtensions": ["txt_ps"],
+                "configExcludes": [],
+                "checksum": "checksum"
+            }]
+        }"#.as_bytes());
+        run_test_cli(vec!["plugins", "outdated"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![
+            "test-plugin 0.1.0 -> 0.2.0",
+            "  https://plugins.dprint.dev/test-plugin-0.2.0.wasm",
+        ]);
+    }
+
+    #[test]
+    fn it_should_output_when_no_plugins_outdated() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.add_remote_file(crate::plugins::REMOTE_INFO_URL, r#"{
+            "schemaVersion": 3,
+            "pluginSystemSchemaVersion": 3,
+            "latest": [{
+                "name": "test-plugin",
+                "version": "0.1.0",
+                "url": "https://plugins.dprint.dev/test-plugin-0.1.0.wasm",
+                "configKey": "test-plugin",
+                "fileExtensions": ["txt"],
+                "configExcludes": []
+            }]
+        }"#.as_bytes());
+        run_test_cli(vec!["plugins", "outdated"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec!["All plugins are up to date."]);
+    }
+
+    #[test]
+    fn it_should_remove_cached_plugin() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        let file_path = PathBuf::from("/cache/plugins/test-plugin/test-plugin-0.1.0.cached");
+        assert_eq!(environment.path_exists(&file_path), true);
+        run_test_cli(vec!["plugins", "remove", "test-plugin"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec!["Removed test-plugin 0.1.0 (https://plugins.dprint.dev/test-plugin.wasm)"]);
+        assert_eq!(environment.path_exists(&file_path), false);
+
+        let error_message = run_test_cli(vec!["plugins", "remove", "test-plugin"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Could not find a cached plugin named test-plugin.");
+    }
+
+    #[test]
+    fn it_should_prune_unused_cached_plugins() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        run_test_cli(vec!["plugins", "prune", "--unused-days", "1"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec!["No plugins were unused for 1 day(s)."]);
+
+        run_test_cli(vec!["plugins", "prune", "--unused-days", "0"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec!["Removed test-plugin 0.1.0 (https://plugins.dprint.dev/test-plugin.wasm)"]);
+        assert_eq!(environment.path_exists(&PathBuf::from("/cache/plugins/test-plugin/test-plugin-0.1.0.cached")), false);
+    }
+
+    #[test]
+    fn it_should_error_prune_invalid_unused_days() {
+        let environment = TestEnvironment::new();
+        let error_message = run_test_cli(vec!["plugins", "prune", "--unused-days", "a"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Expected --unused-days to be a number of days, but found: a");
+    }
+
     #[test]
     fn it_should_error_when_no_plugins_specified() {
         let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
@@ -1708,9 +2960,127 @@ mod tests {
 
         // change the cwd and ensure it's not formatted again
         environment.clear_logs();
-        environment.set_cwd("/test/other/");
-        run_test_cli(vec!["fmt", "--incremental", "--verbose"], &environment).unwrap();
-        assert_eq!(environment.take_logged_messages().iter().any(|msg| msg.contains("No change: /file1.txt")), true);
+        environment.set_cwd("/test/other/");
+        run_test_cli(vec!["fmt", "--incremental", "--verbose"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().iter().any(|msg| msg.contains("No change: /file1.txt")), true);
+    }
+
+    #[test]
+    fn it_should_format_incrementally_when_host_format_plugin_config_changes() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt");
+        environment.write_file(&file_path, "plugin: format this text").unwrap();
+        run_test_cli(vec!["fmt", "--incremental", "/file.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "format this text_formatted_process");
+
+        run_test_cli(vec!["fmt", "--incremental", "--verbose", "/file.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().iter().any(|msg| msg.contains("No change: /file.txt")), true);
+
+        // update the config of the plugin used to format the embedded text and ensure it's formatted
+        let plugin_file_checksum = get_process_plugin_checksum(&environment);
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "testProcessPlugin": {{
+                "ending": "custom_process"
+            }},
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }}"#, plugin_file_checksum)).unwrap();
+        run_test_cli(vec!["fmt", "--incremental", "--verbose", "/file.txt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().iter().any(|msg| msg.contains("No change: /file.txt")), false);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "format this text_formatted_process_formatted");
+        environment.clear_logs();
+    }
+
+    #[test]
+    fn it_should_not_format_incrementally_when_file_touched_without_changes() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("./dprint.json"), r#"{
+            "includes": ["**/*.txt"],
+            "plugins": ["https://plugins.dprint.dev/test-plugin.wasm"]
+        }"#).unwrap();
+        let file_path1 = PathBuf::from("/file1.txt");
+        environment.write_file(&file_path1, "text1").unwrap();
+
+        run_test_cli(vec!["fmt", "--incremental"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+
+        // the modified time changes, but the file hash stays the same
+        environment.write_file(&file_path1, "text1_formatted").unwrap();
+        run_test_cli(vec!["fmt", "--incremental", "--verbose"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().iter().any(|msg| msg.contains("No change: /file1.txt")), true);
+        run_test_cli(vec!["fmt", "--incremental", "--verbose"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages().iter().any(|msg| msg.contains("No change: /file1.txt")), true);
+
+        // same size, but different text
+        environment.write_file(&file_path1, "text1_formattex").unwrap();
+        run_test_cli(vec!["fmt", "--incremental"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.read_file(&file_path1).unwrap(), "text1_formattex_formatted");
+        environment.clear_logs();
+    }
+
+    #[test]
+    fn it_should_use_incremental_cache_path_from_other_directory() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.remove_file(&PathBuf::from("./dprint.json")).unwrap();
+        let config_text = r#"{
+            "incremental": true,
+            "incrementalCachePath": ".cache/incremental",
+            "includes": ["**/*.txt"],
+            "plugins": ["https://plugins.dprint.dev/test-plugin.wasm"]
+        }"#;
+        environment.write_file(&PathBuf::from("/project1/dprint.json"), config_text).unwrap();
+        environment.write_file(&PathBuf::from("/project1/file1.txt"), "text1").unwrap();
+        environment.set_cwd("/project1/");
+        run_test_cli(vec!["fmt"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+
+        // simulate restoring the incremental file on a different CI runner
+        environment.write_file(&PathBuf::from("/project2/dprint.json"), config_text).unwrap();
+        environment.write_file(&PathBuf::from("/project2/file1.txt"), "text1_formatted").unwrap();
+        let incremental_bytes = environment.read_file_bytes(&PathBuf::from("/project1/.cache/incremental")).unwrap();
+        environment.write_file_bytes(&PathBuf::from("/project2/.cache/incremental"), &incremental_bytes).unwrap();
+        environment.set_cwd("/project2/");
+        run_test_cli(vec!["fmt", "--verbose"], &environment).unwrap();
+        let logged_messages = environment.take_logged_messages();
+        assert_eq!(logged_messages.iter().any(|msg| msg.contains("Incremental file was created in /project1. Ignoring file metadata.")), true);
+        assert_eq!(logged_messages.iter().any(|msg| msg.contains("No change: /project2/file1.txt")), true);
+        environment.clear_logs();
+    }
+
+    #[test]
+    fn it_should_only_format_files_of_changed_plugins_incrementally() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        let plugin_file_checksum = get_process_plugin_checksum(&environment);
+        let file_path1 = PathBuf::from("/file1.txt");
+        let file_path2 = PathBuf::from("/file2.txt_ps");
+        environment.write_file(&file_path1, "text1").unwrap();
+        environment.write_file(&file_path2, "text2").unwrap();
+
+        run_test_cli(vec!["fmt", "--incremental", "*.*"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_plural_formatted_text(2)]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+
+        // update the process plugin's config and ensure only its file is formatted
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "testProcessPlugin": {{
+                "ending": "custom"
+            }},
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }}"#, plugin_file_checksum)).unwrap();
+        run_test_cli(vec!["fmt", "--incremental", "--verbose", "*.*"], &environment).unwrap();
+        let logged_messages = environment.take_logged_messages();
+        assert_eq!(logged_messages.iter().any(|msg| msg.contains("No change: /file1.txt")), true);
+        assert_eq!(logged_messages.iter().any(|msg| msg.contains("No change: /file2.txt_ps")), false);
+        assert_eq!(environment.read_file(&file_path1).unwrap(), "text1_formatted");
+        assert_eq!(environment.read_file(&file_path2).unwrap(), "text2_formatted_process_custom");
+        environment.clear_logs();
     }
 
     #[test]
@@ -1764,12 +3134,89 @@ mod tests {
             format!(
                 "{}\n{}\n--",
                 format!("{} /file.txt:", "from".bold().red().to_string()),
-                get_difference("const t=4;", "const t=4;_formatted").unwrap(),
+                get_difference("const t=4;", "const t=4;_formatted"),
+            ),
+        ]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+    }
+
+    #[test]
+    fn it_should_not_write_files_when_discovery_fails() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/file1.txt"), "text1").unwrap();
+        environment.write_file(&PathBuf::from("/file2.txt"), "text2").unwrap();
+        environment.set_glob_error("Error walking files: permission denied");
+        let error_message = run_test_cli(vec!["fmt", "*.txt"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Error walking files: permission denied");
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&PathBuf::from("/file1.txt")).unwrap(), "text1");
+        assert_eq!(environment.read_file(&PathBuf::from("/file2.txt")).unwrap(), "text2");
+        assert_eq!(environment.path_exists(&get_temp_file_path(&PathBuf::from("/file1.txt"))), false);
+        assert_eq!(environment.path_exists(&get_temp_file_path(&PathBuf::from("/file2.txt"))), false);
+    }
+
+    #[test]
+    fn it_should_write_deferred_files_to_temp_files_until_discovered() {
+        let environment = TestEnvironment::new();
+        let error_logger = ErrorCountLogger::from_environment(&environment);
+        let file_path = PathBuf::from("/file.txt");
+        let temp_file_path = get_temp_file_path(&file_path);
+        environment.write_file(&file_path, "text").unwrap();
+        let file_writer = DeferredFileWriter::new(environment.clone());
+
+        file_writer.write_file(&file_path, "formatted".to_string()).unwrap();
+        assert_eq!(environment.read_file(&file_path).unwrap(), "text");
+        assert_eq!(environment.read_file(&temp_file_path).unwrap(), "formatted");
+
+        file_writer.complete_discovery(true, &error_logger);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "formatted");
+        assert_eq!(environment.path_exists(&temp_file_path), false);
+
+        // files are written directly once discovered
+        file_writer.write_file(&file_path, "formatted_again".to_string()).unwrap();
+        assert_eq!(environment.read_file(&file_path).unwrap(), "formatted_again");
+        assert_eq!(environment.path_exists(&temp_file_path), false);
+    }
+
+    #[test]
+    fn it_should_remove_deferred_temp_files_when_discovery_fails() {
+        let environment = TestEnvironment::new();
+        let error_logger = ErrorCountLogger::from_environment(&environment);
+        let file_path = PathBuf::from("/file.txt");
+        environment.write_file(&file_path, "text").unwrap();
+        let file_writer = DeferredFileWriter::new(environment.clone());
+
+        file_writer.write_file(&file_path, "formatted".to_string()).unwrap();
+        file_writer.complete_discovery(false, &error_logger);
+        file_writer.write_file(&file_path, "formatted".to_string()).unwrap();
+        assert_eq!(environment.read_file(&file_path).unwrap(), "text");
+        assert_eq!(environment.path_exists(&get_temp_file_path(&file_path)), false);
+    }
+
+    #[test]
+    fn it_should_output_diff_context_lines_for_check() {
+        let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/file.txt"), "a\nb\nc\nd").unwrap();
+        let error_message = run_test_cli(vec!["check", "--diff-context-lines", "0", "/file.txt"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), get_singular_check_text());
+        assert_eq!(environment.take_logged_messages(), vec![
+            format!(
+                "{}\n{}\n--",
+                format!("{} /file.txt:", "from".bold().red().to_string()),
+                get_difference_with_options("a\nb\nc\nd", "a\nb\nc\nd_formatted", &DiffOptions { context_lines: 0, use_colors: true }),
             ),
         ]);
         assert_eq!(environment.take_logged_errors().len(), 0);
     }
 
+    #[test]
+    fn it_should_error_for_invalid_diff_context_lines() {
+        let environment = TestEnvironment::new();
+        let error_message = run_test_cli(vec!["check", "--diff-context-lines", "a"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Expected --diff-context-lines to be a number of lines, but found: a");
+    }
+
     #[test]
     fn it_should_output_when_files_need_formatting_for_check() {
         let environment = get_initialized_test_environment_with_remote_wasm_plugin().unwrap();
@@ -1784,12 +3231,12 @@ mod tests {
             format!(
                 "{}\n{}\n--",
                 format!("{} /file1.txt:", "from".bold().red().to_string()),
-                get_difference("const t=4;", "const t=4;_formatted").unwrap(),
+                get_difference("const t=4;", "const t=4;_formatted"),
             ),
             format!(
                 "{}\n{}\n--",
                 format!("{} /file2.txt:", "from".bold().red().to_string()),
-                get_difference("const t=5;", "const t=5;_formatted").unwrap(),
+                get_difference("const t=5;", "const t=5;_formatted"),
             ),
         ]);
         assert_eq!(environment.take_logged_errors().len(), 0);
@@ -1997,6 +3444,13 @@ SOFTWARE.
         pub fn exit(&mut self) {
             self.messenger.send_message(0, vec![]).unwrap();
         }
+
+        pub fn into_multiplexed(mut self) -> (Box<dyn Read + Send>, Box<dyn Write + Send>) {
+            self.messenger.send_message(3, vec![]).unwrap();
+            assert_eq!(self.messenger.read_code().unwrap(), 0);
+            self.messenger.read_zero_part_message().unwrap();
+            self.messenger.into_inner()
+        }
     }
 
     #[test]
@@ -2060,6 +3514,62 @@ SOFTWARE.
         result.join().unwrap();
     }
 
+    #[test]
+    fn it_should_cancel_format_requests_for_multiplexed_editor_service() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        environment.write_file(&PathBuf::from("./dprint.json"), r#"{
+            "includes": ["**/*.{txt,txt_ps}"],
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }"#).unwrap();
+        let txt_file_path = PathBuf::from("/file.txt");
+        environment.write_file(&txt_file_path, "").unwrap();
+        let ps_file_path = PathBuf::from("/file.txt_ps");
+        environment.write_file(&ps_file_path, "").unwrap();
+        let stdin = environment.stdin_writer();
+        let stdout = environment.stdout_reader();
+
+        let result = std::thread::spawn(move || {
+            let communicator = EditorServiceCommunicator::new(stdin, stdout);
+            let (mut reader, mut writer) = communicator.into_multiplexed();
+            let mut read_message = || -> MultiplexedMessage { read_multiplexed_message(&mut reader).unwrap() };
+
+            write_multiplexed_message(&mut writer, 1, 1, vec![txt_file_path.as_path().into()]).unwrap();
+            let message = read_message();
+            assert_eq!((message.id, message.code), (1, 1));
+
+            // a newer request for the same file cancels the process plugin's request
+            write_multiplexed_message(&mut writer, 2, 2, vec![ps_file_path.as_path().into(), "wait_cancellation".into()]).unwrap();
+            write_multiplexed_message(&mut writer, 3, 2, vec![ps_file_path.as_path().into(), "testing".into()]).unwrap();
+            let message = read_message();
+            assert_eq!((message.id, message.code), (2, 3));
+            let mut message = read_message();
+            assert_eq!((message.id, message.code), (3, 1));
+            assert_eq!(message.parts.take_string().unwrap(), "testing_formatted_process");
+
+            // the wasm plugin is interrupted while formatting with the host
+            write_multiplexed_message(&mut writer, 4, 2, vec![txt_file_path.as_path().into(), "plugin: wait_cancellation".into()]).unwrap();
+            write_multiplexed_message(&mut writer, 5, 3, vec![4.into()]).unwrap();
+            let message = read_message();
+            assert_eq!((message.id, message.code), (4, 3));
+
+            // and can still format afterwards
+            write_multiplexed_message(&mut writer, 6, 2, vec![txt_file_path.as_path().into(), "testing".into()]).unwrap();
+            let mut message = read_message();
+            assert_eq!((message.id, message.code), (6, 1));
+            assert_eq!(message.parts.take_string().unwrap(), "testing_formatted");
+
+            write_multiplexed_message(&mut writer, 7, 0, vec![]).unwrap();
+        });
+
+        let pid = std::process::id().to_string();
+        run_test_cli(vec!["editor-service", "--parent-pid", &pid], &environment).unwrap();
+
+        result.join().unwrap();
+    }
+
     #[test]
     fn it_should_format_for_stdin_fmt_with_file_name() {
         // it should not output anything when downloading plugins
@@ -2181,6 +3691,79 @@ SOFTWARE.
         assert_eq!(environment.take_logged_errors().len(), 0);
     }
 
+    #[test]
+    fn it_should_format_with_process_plugin_signed_by_trusted_public_key() {
+        let environment = get_initialized_test_environment_with_remote_process_plugin().unwrap();
+        let plugin_file_bytes = environment.download_file("https://plugins.dprint.dev/test-process.exe-plugin").unwrap();
+        let (public_key, signature) = create_test_signature(&[1; 32], &plugin_file_bytes);
+        environment.add_remote_file_bytes("https://plugins.dprint.dev/test-process.exe-plugin.sig", signature.into_bytes());
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "trustedPluginKeys": ["{}"],
+            "requireSignedProcessPlugins": true,
+            "plugins": [
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }}"#, public_key, get_process_plugin_checksum(&environment))).unwrap();
+        let file_path = PathBuf::from("/file.txt_ps");
+        environment.write_file(&file_path, "text").unwrap();
+        // the previously cached plugin wasn't verified, so this will verify it
+        run_test_cli(vec!["fmt", "/file.txt_ps"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "text_formatted_process");
+    }
+
+    #[test]
+    fn it_should_error_for_unsigned_process_plugin_when_signature_required() {
+        let environment = TestEnvironment::new();
+        setup_test_environment_with_remote_process_plugin(&environment);
+        let (public_key, _) = create_test_signature(&[1; 32], &[]);
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "trustedPluginKeys": ["{}"],
+            "requireSignedProcessPlugins": true,
+            "plugins": [
+                "https://plugins.dprint.dev/test-process.exe-plugin@{}"
+            ]
+        }}"#, public_key, get_process_plugin_checksum(&environment))).unwrap();
+        environment.write_file(&PathBuf::from("/test.txt_ps"), "").unwrap();
+        let error_message = run_test_cli(vec!["fmt", "*.*"], &environment).err().unwrap();
+
+        assert_eq!(
+            error_message.to_string(),
+            concat!(
+                "Error resolving plugin https://plugins.dprint.dev/test-process.exe-plugin: ",
+                "Process plugins must be signed by a trusted public key, but could not verify the signature of ",
+                "https://plugins.dprint.dev/test-process.exe-plugin. ",
+                "Could not find file at url https://plugins.dprint.dev/test-process.exe-plugin.sig",
+            )
+        );
+    }
+
+    #[test]
+    fn it_should_error_for_process_plugin_not_allowed_by_plugin_policy() {
+        let environment = get_initialized_test_environment_with_remote_process_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/config/plugin-policy.json"), r#"{
+            "allowedProcessPluginHosts": ["plugins.example.com"]
+        }"#).unwrap();
+        environment.write_file(&PathBuf::from("/test.txt_ps"), "").unwrap();
+        let error_message = run_test_cli(vec!["fmt", "*.*"], &environment).err().unwrap();
+
+        assert_eq!(
+            error_message.to_string(),
+            concat!(
+                "The plugin https://plugins.dprint.dev/test-process.exe-plugin is not allowed by the plugin policy at /config/plugin-policy.json. ",
+                "Process plugins must be from one of the allowed urls or hosts."
+            )
+        );
+
+        // should format when allowed
+        environment.write_file(&PathBuf::from("/config/plugin-policy.json"), r#"{
+            "allowedProcessPluginHosts": ["dprint.dev"]
+        }"#).unwrap();
+        environment.write_file(&PathBuf::from("/test.txt_ps"), "text").unwrap();
+        run_test_cli(vec!["fmt", "*.*"], &environment).unwrap();
+        assert_eq!(environment.read_file(&PathBuf::from("/test.txt_ps")).unwrap(), "text_formatted_process");
+    }
+
     #[test]
     fn it_should_error_if_process_plugin_has_no_checksum_in_config() {
         let environment = get_initialized_test_environment_with_remote_process_plugin().unwrap();
@@ -2277,7 +3860,7 @@ SOFTWARE.
     fn it_should_error_if_process_plugin_has_wrong_checksum_in_file_for_zip() {
         let environment = TestEnvironment::new();
         setup_test_environment_with_remote_process_plugin(&environment);
-        write_process_plugin_file(&environment, "asdf");
+        write_process_plugin_file(&environment, "test-process-plugin", "https://plugins.dprint.dev/test-process.exe-plugin", "asdf");
         environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
             "plugins": [
                 "https://plugins.dprint.dev/test-process.exe-plugin@{}"
@@ -2300,6 +3883,129 @@ SOFTWARE.
         )]);
     }
 
+    #[test]
+    fn it_should_format_with_native_plugin() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_native_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt_ns");
+        environment.write_file(&file_path, "text").unwrap();
+        run_test_cli(vec!["fmt", "/file.txt_ns"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "text_formatted_native");
+    }
+
+    #[test]
+    fn it_should_handle_native_plugin_erroring_and_panicking() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_native_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/file1.txt_ns"), "should_error").unwrap();
+        environment.write_file(&PathBuf::from("/file2.txt_ns"), "should_panic").unwrap();
+        let error_message = run_test_cli(vec!["fmt", "/file1.txt_ns"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Had 1 error(s) formatting.");
+        assert_eq!(environment.take_logged_errors(), vec![String::from("Error formatting /file1.txt_ns. Message: Did error.")]);
+        let error_message = run_test_cli(vec!["fmt", "/file2.txt_ns"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), "Had 1 error(s) formatting.");
+        assert_eq!(environment.take_logged_errors(), vec![String::from("Error formatting /file2.txt_ns. Message: The plugin panicked: Did panic.")]);
+    }
+
+    #[test]
+    fn it_should_format_calling_wasm_plugin_with_native_plugin() {
+        let environment = get_initialized_test_environment_with_remote_wasm_and_native_plugin().unwrap();
+        let file_path1 = PathBuf::from("/file1.txt_ns");
+        environment.write_file(&file_path1, "plugin-config: format this text").unwrap();
+        let file_path2 = PathBuf::from("/file2.txt_ns");
+        environment.write_file(&file_path2, "plugin: format this text").unwrap();
+        run_test_cli(vec!["fmt", "*.txt_ns"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_plural_formatted_text(2)]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path1).unwrap(), "format this text_custom_config");
+        assert_eq!(environment.read_file(&file_path2).unwrap(), "format this text_formatted");
+    }
+
+    #[test]
+    fn it_should_error_if_native_plugin_has_no_checksum_in_config() {
+        let environment = TestEnvironment::new();
+        setup_test_environment_with_remote_native_plugin(&environment);
+        environment.write_file(&PathBuf::from("./dprint.json"), r#"{
+            "plugins": [
+                "https://plugins.dprint.dev/test-native.native-plugin"
+            ]
+        }"#).unwrap();
+        environment.write_file(&PathBuf::from("/test.txt_ns"), "").unwrap();
+        let error_message = run_test_cli(vec!["fmt", "*.*"], &environment).err().unwrap();
+
+        assert_eq!(
+            error_message.to_string(),
+            concat!(
+                "The plugin 'https://plugins.dprint.dev/test-native.native-plugin' must have a checksum specified for security reasons ",
+                "since it is not a Wasm plugin. You may specify one by writing \"https://plugins.dprint.dev/test-native.native-plugin@checksum-goes-here\" ",
+                "when providing the url in the configuration file. Check the plugin's release notes for what ",
+                "the checksum is or calculate it yourself if you trust the source (it's SHA-256)."
+            )
+        );
+    }
+
+    #[test]
+    fn it_should_error_if_native_plugin_has_wrong_checksum_in_config() {
+        let environment = TestEnvironment::new();
+        setup_test_environment_with_remote_native_plugin(&environment);
+        let actual_plugin_file_checksum = get_native_plugin_checksum(&environment);
+        environment.write_file(&PathBuf::from("./dprint.json"), r#"{
+            "plugins": [
+                "https://plugins.dprint.dev/test-native.native-plugin@asdf"
+            ]
+        }"#).unwrap();
+        environment.write_file(&PathBuf::from("/test.txt_ns"), "").unwrap();
+        let error_message = run_test_cli(vec!["fmt", "*.*"], &environment).err().unwrap();
+
+        assert_eq!(
+            error_message.to_string(),
+            format!(
+                "Error resolving plugin https://plugins.dprint.dev/test-native.native-plugin: The checksum {} did not match the expected checksum of asdf.",
+                actual_plugin_file_checksum,
+            )
+        );
+        assert_eq!(environment.take_logged_errors(), vec![format!(
+            "Error getting plugin from cache. Forgetting from cache and retrying. Message: The checksum {} did not match the expected checksum of asdf.",
+            actual_plugin_file_checksum
+        )]);
+    }
+
+    #[test]
+    fn it_should_format_with_wasi_plugin() {
+        let environment = get_initialized_test_environment_with_remote_wasi_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt_wasi");
+        environment.write_file(&file_path, "text").unwrap();
+        run_test_cli(vec!["fmt", "/file.txt_wasi"], &environment).unwrap();
+        assert_eq!(environment.take_logged_messages(), vec![get_singular_formatted_text()]);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(environment.read_file(&file_path).unwrap(), "text_formatted_wasi");
+    }
+
+    #[test]
+    fn it_should_log_wasi_plugin_stdout_and_stderr() {
+        let environment = get_initialized_test_environment_with_remote_wasi_plugin().unwrap();
+        environment.write_file(&PathBuf::from("/file1.txt_wasi"), "stdout: Written to stdout.").unwrap();
+        environment.write_file(&PathBuf::from("/file2.txt_wasi"), "stderr: Written to stderr.").unwrap();
+        run_test_cli(vec!["fmt", "/file1.txt_wasi"], &environment).unwrap();
+        assert_eq!(environment.take_logged_errors(), vec!["Written to stdout."]);
+        run_test_cli(vec!["fmt", "/file2.txt_wasi"], &environment).unwrap();
+        assert_eq!(environment.take_logged_errors(), vec!["Written to stderr."]);
+        assert_eq!(environment.read_file(&PathBuf::from("/file2.txt_wasi")).unwrap(), "Written to stderr.");
+    }
+
+    #[test]
+    fn it_should_sandbox_wasi_plugin() {
+        let environment = get_initialized_test_environment_with_remote_wasi_plugin().unwrap();
+        let file_path = PathBuf::from("/file.txt_wasi");
+        environment.write_file(&file_path, "sandbox").unwrap();
+        run_test_cli(vec!["fmt", "/file.txt_wasi"], &environment).unwrap();
+        assert_eq!(environment.take_logged_errors().len(), 0);
+        assert_eq!(
+            environment.read_file(&file_path).unwrap(),
+            "preopens: none, env vars: 0, args: 0, random: ok, sockets: none"
+        );
+    }
+
     // todo: implement way of running these tests all on their own
 
     #[test]
@@ -2328,6 +4034,22 @@ SOFTWARE.
         }
     }
 
+    #[test]
+    fn it_should_check_many_files() {
+        // enough files to be given to the workers in several batches
+        let environment = get_initialized_test_environment_with_remote_wasm_and_process_plugin().unwrap();
+        for i in 0..300 {
+            environment.write_file(&PathBuf::from(format!("/file{}.txt", i)), &format!("text{}", i)).unwrap();
+            environment.write_file(&PathBuf::from(format!("/file{}.txt_ps", i)), &format!("text{}", i)).unwrap();
+        }
+        environment.write_file(&PathBuf::from("/formatted.txt"), "text_formatted").unwrap();
+
+        let error_message = run_test_cli(vec!["check", "/*.*"], &environment).err().unwrap();
+        assert_eq!(error_message.to_string(), get_plural_check_text(600));
+        assert_eq!(environment.take_logged_messages().len(), 600);
+        assert_eq!(environment.take_logged_errors().len(), 0);
+    }
+
     #[test]
     fn it_should_error_once_on_config_diagnostic_many_files() {
         // configuration diagnostic should only be shown by one thread
@@ -2395,6 +4117,8 @@ SUBCOMMANDS:
     output-file-paths         Prints the resolved file paths for the plugins based on the args and configuration.
     output-resolved-config    Prints the resolved configuration for the plugins based on the args and configuration.
     output-format-times       Prints the amount of time it takes to format each file. Use this for debugging.
+    config                    Commands for working with the configuration file.
+    plugins                   Commands for working with plugins.
     clear-cache               Deletes the plugin cache directory.
     license                   Outputs the software license.
 
@@ -2405,6 +4129,9 @@ OPTIONS:
                                      current or ancestor directory when not provided.
         --plugins <urls/files>...    List of urls or file paths of plugins to use. This overrides what is specified in
                                      the config file.
+        --frozen                     Errors if plugins would resolve differently than what is recorded in dprint.lock.
+        --cache-dir <path>           Directory to store cached plugins and remote configuration files in. Defaults to
+                                     the DPRINT_CACHE_DIR environment variable or the system cache directory.
         --verbose                    Prints additional diagnostic information.
     -v, --version                    Prints the version.
 
@@ -2441,6 +4168,31 @@ EXAMPLES:
         };
     }
 
+    // If this file doesn't exist, run `./build.sh` in /crates/test-wasi-plugin.
+    static WASI_PLUGIN_BYTES: &'static [u8] = include_bytes!("../../../test-wasi-plugin/target/wasm32-wasi/release/test_wasi_plugin.wasm");
+    lazy_static! {
+        static ref WASI_COMPILATION_RESULT: CompilationResult = {
+            crate::plugins::compile_wasm(WASI_PLUGIN_BYTES).unwrap()
+        };
+    }
+
+    fn get_initialized_test_environment_with_remote_wasi_plugin() -> Result<TestEnvironment, ErrBox> {
+        let environment = TestEnvironment::new();
+        environment.set_wasm_compile_result(WASI_COMPILATION_RESULT.clone());
+        environment.add_remote_file("https://plugins.dprint.dev/test-wasi-plugin.wasm", WASI_PLUGIN_BYTES);
+        environment.write_file(&PathBuf::from("./dprint.json"), r#"{
+            "plugins": ["https://plugins.dprint.dev/test-wasi-plugin.wasm"]
+        }"#).unwrap();
+        run_test_cli(vec!["license"], &environment).unwrap(); // cause initialization
+        environment.clear_logs();
+        Ok(environment)
+    }
+
+    fn create_lockfile(environment: &TestEnvironment) {
+        run_test_cli(vec!["plugins", "update"], environment).unwrap();
+        environment.clear_logs();
+    }
+
     fn get_initialized_test_environment_with_remote_wasm_and_process_plugin() -> Result<TestEnvironment, ErrBox> {
         let environment = TestEnvironment::new();
         setup_test_environment_with_remote_wasm_plugin(&environment);
@@ -2457,6 +4209,27 @@ EXAMPLES:
         Ok(environment)
     }
 
+    fn get_initialized_test_environment_with_remote_wasm_and_native_plugin() -> Result<TestEnvironment, ErrBox> {
+        let environment = TestEnvironment::new();
+        setup_test_environment_with_remote_wasm_plugin(&environment);
+        setup_test_environment_with_remote_native_plugin(&environment);
+        let plugin_file_checksum = get_native_plugin_checksum(&environment);
+        environment.write_file(&PathBuf::from("./dprint.json"), &format!(r#"{{
+            "plugins": [
+                "https://plugins.dprint.dev/test-plugin.wasm",
+                "https://plugins.dprint.dev/test-native.native-plugin@{}"
+            ]
+        }}"#, plugin_file_checksum)).unwrap();
+        run_test_cli(vec!["license"], &environment).unwrap(); // cause initialization
+        environment.clear_logs();
+        Ok(environment)
+    }
+
+    fn get_native_plugin_checksum(environment: &TestEnvironment) -> String {
+        let plugin_file_bytes = environment.download_file("https://plugins.dprint.dev/test-native.native-plugin").unwrap();
+        dprint_cli_core::checksums::get_sha256_checksum(&plugin_file_bytes)
+    }
+
     fn get_process_plugin_checksum(environment: &TestEnvironment) -> String {
         let plugin_file_bytes = environment.download_file("https://plugins.dprint.dev/test-process.exe-plugin").unwrap();
         dprint_cli_core::checksums::get_sha256_checksum(&plugin_file_bytes)
@@ -2518,41 +4291,110 @@ EXAMPLES:
     static PROCESS_PLUGIN_EXE_BYTES: &'static [u8] = include_bytes!("../../../../target/release/test-process-plugin");
 
     fn setup_test_environment_with_remote_process_plugin(environment: &TestEnvironment) {
+        setup_test_environment_with_remote_process_plugin_binary(
+            environment,
+            "test-process-plugin",
+            "https://plugins.dprint.dev/test-process.exe-plugin",
+            PROCESS_PLUGIN_EXE_BYTES,
+        );
+    }
+
+    // If this file doesn't exist, run `cargo build --release` for crates/test-process-plugin
+    #[cfg(target_os="windows")]
+    static PROCESS_PLUGIN_REQUEST_RESPONSE_EXE_BYTES: &'static [u8] = include_bytes!("../../../../target/release/test-process-plugin-request-response.exe");
+    #[cfg(not(target_os="windows"))]
+    static PROCESS_PLUGIN_REQUEST_RESPONSE_EXE_BYTES: &'static [u8] = include_bytes!("../../../../target/release/test-process-plugin-request-response");
+
+    fn setup_test_environment_with_remote_request_response_process_plugin(environment: &TestEnvironment) {
+        setup_test_environment_with_remote_process_plugin_binary(
+            environment,
+            "test-process-plugin-request-response",
+            "https://plugins.dprint.dev/test-process-request-response.exe-plugin",
+            PROCESS_PLUGIN_REQUEST_RESPONSE_EXE_BYTES,
+        );
+    }
+
+    /// Adds a process plugin file at the url that references a zip file containing the executable.
+    fn setup_test_environment_with_remote_process_plugin_binary(environment: &TestEnvironment, name: &str, plugin_url: &str, exe_bytes: &[u8]) {
         let buf: Vec<u8> = Vec::new();
         let w = std::io::Cursor::new(buf);
         let mut zip = zip::ZipWriter::new(w);
         let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
-        zip.start_file(if cfg!(target_os="windows") { "test-process-plugin.exe" } else { "test-process-plugin" }, options).unwrap();
-        zip.write(PROCESS_PLUGIN_EXE_BYTES).unwrap();
+        zip.start_file(if cfg!(target_os="windows") { format!("{}.exe", name) } else { name.to_string() }, options).unwrap();
+        zip.write(exe_bytes).unwrap();
         let result = zip.finish().unwrap().into_inner();
         let zip_file_checksum = dprint_cli_core::checksums::get_sha256_checksum(&result);
         environment.add_remote_file_bytes(
-            "https://github.com/dprint/test-process-plugin/releases/0.1.0/test-process-plugin.zip",
+            &format!("https://github.com/dprint/test-process-plugin/releases/0.1.0/{}.zip", name),
             result,
         );
-        write_process_plugin_file(environment, &zip_file_checksum);
+        write_process_plugin_file(environment, name, plugin_url, &zip_file_checksum);
+    }
+
+    fn get_request_response_process_plugin_checksum(environment: &TestEnvironment) -> String {
+        let plugin_file_bytes = environment.download_file("https://plugins.dprint.dev/test-process-request-response.exe-plugin").unwrap();
+        dprint_cli_core::checksums::get_sha256_checksum(&plugin_file_bytes)
     }
 
-    fn write_process_plugin_file(environment: &TestEnvironment, zip_checksum: &str) {
+    fn setup_test_environment_with_remote_native_plugin(environment: &TestEnvironment) {
+        // the library is loaded from the target directory in the tests (see get_test_safe_library_path),
+        // so the zip only needs to contain a file with the expected name
+        // If the library doesn't exist, run `cargo build --release` for crates/test-native-plugin
+        let buf: Vec<u8> = Vec::new();
+        let w = std::io::Cursor::new(buf);
+        let mut zip = zip::ZipWriter::new(w);
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let library_file_name = format!("{}test_native_plugin{}", std::env::consts::DLL_PREFIX, std::env::consts::DLL_SUFFIX);
+        zip.start_file(library_file_name, options).unwrap();
+        zip.write(b"test-native-plugin").unwrap();
+        let result = zip.finish().unwrap().into_inner();
+        let zip_file_checksum = dprint_cli_core::checksums::get_sha256_checksum(&result);
         environment.add_remote_file_bytes(
-            "https://plugins.dprint.dev/test-process.exe-plugin",
+            "https://github.com/dprint/test-native-plugin/releases/0.1.0/test-native-plugin.zip",
+            result,
+        );
+        environment.add_remote_file_bytes(
+            "https://plugins.dprint.dev/test-native.native-plugin",
             format!(r#"{{
     "schemaVersion": 1,
-    "name": "test-process-plugin",
+    "name": "test-native-plugin",
     "version": "0.1.0",
     "windows-x86_64": {{
-        "reference": "https://github.com/dprint/test-process-plugin/releases/0.1.0/test-process-plugin.zip",
+        "reference": "https://github.com/dprint/test-native-plugin/releases/0.1.0/test-native-plugin.zip",
         "checksum": "{0}"
     }},
     "linux-x86_64": {{
-        "reference": "https://github.com/dprint/test-process-plugin/releases/0.1.0/test-process-plugin.zip",
+        "reference": "https://github.com/dprint/test-native-plugin/releases/0.1.0/test-native-plugin.zip",
         "checksum": "{0}"
     }},
     "mac-x86_64": {{
-        "reference": "https://github.com/dprint/test-process-plugin/releases/0.1.0/test-process-plugin.zip",
+        "reference": "https://github.com/dprint/test-native-plugin/releases/0.1.0/test-native-plugin.zip",
         "checksum": "{0}"
     }}
-}}"#, zip_checksum).into_bytes()
+}}"#, zip_file_checksum).into_bytes()
+        );
+    }
+
+    fn write_process_plugin_file(environment: &TestEnvironment, name: &str, plugin_url: &str, zip_checksum: &str) {
+        environment.add_remote_file_bytes(
+            plugin_url,
+            format!(r#"{{
+    "schemaVersion": 1,
+    "name": "{0}",
+    "version": "0.1.0",
+    "windows-x86_64": {{
+        "reference": "https://github.com/dprint/test-process-plugin/releases/0.1.0/{0}.zip",
+        "checksum": "{1}"
+    }},
+    "linux-x86_64": {{
+        "reference": "https://github.com/dprint/test-process-plugin/releases/0.1.0/{0}.zip",
+        "checksum": "{1}"
+    }},
+    "mac-x86_64": {{
+        "reference": "https://github.com/dprint/test-process-plugin/releases/0.1.0/{0}.zip",
+        "checksum": "{1}"
+    }}
+}}"#, name, zip_checksum).into_bytes()
         );
     }
 }
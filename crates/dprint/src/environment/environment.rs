@@ -4,15 +4,59 @@ use dprint_core::types::ErrBox;
 
 use crate::plugins::CompilationResult;
 
+/// Information about a file that's used to tell if it changed without reading it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileMetadata {
+    pub size: u64,
+    /// Modified time in nanoseconds since the unix epoch.
+    pub modified_time: u64,
+}
+
+/// An exclusive lock on a file that's released when dropped.
+pub struct FileLock {
+    file: Option<std::fs::File>,
+}
+
+impl FileLock {
+    pub fn new(file: std::fs::File) -> FileLock {
+        FileLock { file: Some(file) }
+    }
+
+    /// Creates a lock that doesn't hold anything (ex. in a test environment).
+    #[cfg(test)]
+    pub fn new_noop() -> FileLock {
+        FileLock { file: None }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            let _ = fs2::FileExt::unlock(file);
+        }
+    }
+}
+
 pub trait Environment : Clone + std::marker::Send + std::marker::Sync + 'static {
     fn is_real(&self) -> bool;
     fn read_file(&self, file_path: &Path) -> Result<String, ErrBox>;
     fn read_file_bytes(&self, file_path: &Path) -> Result<Vec<u8>, ErrBox>;
+    /// Reads at most `max_len` bytes from the start of the file.
+    fn read_file_prefix(&self, file_path: &Path, max_len: usize) -> Result<Vec<u8>, ErrBox>;
     fn write_file(&self, file_path: &Path, file_text: &str) -> Result<(), ErrBox>;
     fn write_file_bytes(&self, file_path: &Path, bytes: &[u8]) -> Result<(), ErrBox>;
+    fn get_file_metadata(&self, file_path: &Path) -> Result<FileMetadata, ErrBox>;
     fn remove_file(&self, file_path: &Path) -> Result<(), ErrBox>;
+    /// Renames a file, replacing the destination if it exists.
+    fn rename(&self, from_path: &Path, to_path: &Path) -> Result<(), ErrBox>;
     fn remove_dir_all(&self, dir_path: &Path) -> Result<(), ErrBox>;
-    fn glob(&self, base: &Path, file_patterns: &Vec<String>) -> Result<Vec<PathBuf>, ErrBox>;
+    /// Acquires an exclusive lock on the provided file, waiting for other processes to
+    /// release it first. The file is created if it doesn't exist.
+    fn lock_file(&self, file_path: &Path) -> Result<FileLock, ErrBox>;
+    /// Provides each file path matching the provided patterns to `on_file_path` as
+    /// it's found. Paths ignored by `.dprintignore` files are always excluded and
+    /// paths ignored by git are excluded when `use_gitignore` is true.
+    fn glob(&self, base: &Path, file_patterns: &Vec<String>, use_gitignore: bool, on_file_path: &mut dyn FnMut(PathBuf)) -> Result<(), ErrBox>;
     fn path_exists(&self, file_path: &Path) -> bool;
     fn canonicalize(&self, path: &Path) -> Result<PathBuf, ErrBox>;
     fn is_absolute_path(&self, path: &Path) -> bool;
@@ -34,7 +78,11 @@ pub trait Environment : Clone + std::marker::Send + std::marker::Sync + 'static
     >(&self, message: &str, action: TCreate, total_size: usize) -> TResult;
     fn download_file(&self, url: &str) -> Result<Vec<u8>, ErrBox>;
     fn get_cache_dir(&self) -> PathBuf;
+    /// Gets the path of the organization's plugin policy file, which may not exist.
+    fn get_plugin_policy_file_path(&self) -> Option<PathBuf>;
     fn get_time_secs(&self) -> u64;
+    /// Gets the current time in the same units as a file's modified time.
+    fn get_time_nanos(&self) -> u64;
     fn get_selection(&self, prompt_message: &str, item_indent_width: u16, items: &Vec<String>) -> Result<usize, ErrBox>;
     fn get_multi_selection(&self, prompt_message: &str, item_indent_width: u16, items: &Vec<(bool, String)>) -> Result<Vec<usize>, ErrBox>;
     fn get_terminal_width(&self) -> u16;
@@ -2,22 +2,28 @@ use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 use std::io::ErrorKind;
 use std::fs;
+use ignore::{Match, WalkBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
 use dprint_core::types::ErrBox;
 use dprint_cli_core::{download_url};
 use dprint_cli_core::logging::{Logger, ProgressBars, log_action_with_progress, show_select, show_multi_select};
 
-use super::Environment;
+use super::{Environment, FileLock, FileMetadata};
 use crate::plugins::CompilationResult;
+use crate::utils::DPRINT_IGNORE_FILE_NAME;
 
 #[derive(Clone)]
 pub struct RealEnvironment {
     logger: Logger,
     progress_bars: Option<ProgressBars>,
     is_verbose: bool,
+    cache_dir: PathBuf,
 }
 
 impl RealEnvironment {
-    pub fn new(is_verbose: bool, is_silent: bool) -> Result<RealEnvironment, ErrBox> {
+    /// Creates the environment using the provided cache directory when specified,
+    /// then falling back to the `DPRINT_CACHE_DIR` environment variable.
+    pub fn new(is_verbose: bool, is_silent: bool, cache_dir: Option<PathBuf>) -> Result<RealEnvironment, ErrBox> {
         let logger = Logger::new("dprint", is_silent);
         let progress_bars = if is_silent {
             None
@@ -28,10 +34,11 @@ impl RealEnvironment {
             logger,
             progress_bars,
             is_verbose,
+            cache_dir: get_cache_dir(cache_dir)?,
         };
 
         // ensure the cache directory is created
-        if let Err(err) = environment.mk_dir_all(&get_cache_dir()?) {
+        if let Err(err) = environment.mk_dir_all(&environment.cache_dir) {
             return err!("Error creating cache directory: {:?}", err);
         }
 
@@ -56,6 +63,20 @@ impl Environment for RealEnvironment {
         }
     }
 
+    fn read_file_prefix(&self, file_path: &Path, max_len: usize) -> Result<Vec<u8>, ErrBox> {
+        use std::io::Read;
+        log_verbose!(self, "Reading file prefix: {}", file_path.display());
+        let result = fs::File::open(file_path).and_then(|file| {
+            let mut bytes = Vec::with_capacity(max_len);
+            file.take(max_len as u64).read_to_end(&mut bytes)?;
+            Ok(bytes)
+        });
+        match result {
+            Ok(bytes) => Ok(bytes),
+            Err(err) => err!("Error reading file {}: {}", file_path.display(), err.to_string()),
+        }
+    }
+
     fn write_file(&self, file_path: &Path, file_text: &str) -> Result<(), ErrBox> {
         self.write_file_bytes(file_path, file_text.as_bytes())
     }
@@ -68,6 +89,18 @@ impl Environment for RealEnvironment {
         }
     }
 
+    fn get_file_metadata(&self, file_path: &Path) -> Result<FileMetadata, ErrBox> {
+        let metadata = match fs::metadata(file_path) {
+            Ok(metadata) => metadata,
+            Err(err) => return err!("Error getting metadata for file {}: {}", file_path.display(), err.to_string()),
+        };
+        let modified_time = metadata.modified()?.duration_since(SystemTime::UNIX_EPOCH)?;
+        Ok(FileMetadata {
+            size: metadata.len(),
+            modified_time: modified_time.as_nanos() as u64,
+        })
+    }
+
     fn remove_file(&self, file_path: &Path) -> Result<(), ErrBox> {
         log_verbose!(self, "Deleting file: {}", file_path.display());
         match fs::remove_file(file_path) {
@@ -76,6 +109,14 @@ impl Environment for RealEnvironment {
         }
     }
 
+    fn rename(&self, from_path: &Path, to_path: &Path) -> Result<(), ErrBox> {
+        log_verbose!(self, "Renaming file: {} to {}", from_path.display(), to_path.display());
+        match fs::rename(from_path, to_path) {
+            Ok(_) => Ok(()),
+            Err(err) => err!("Error renaming file {} to {}: {}", from_path.display(), to_path.display(), err.to_string()),
+        }
+    }
+
     fn remove_dir_all(&self, dir_path: &Path) -> Result<(), ErrBox> {
         log_verbose!(self, "Deleting directory: {}", dir_path.display());
         match fs::remove_dir_all(dir_path) {
@@ -90,38 +131,67 @@ impl Environment for RealEnvironment {
         }
     }
 
+    fn lock_file(&self, file_path: &Path) -> Result<FileLock, ErrBox> {
+        log_verbose!(self, "Locking file: {}", file_path.display());
+        let file = match fs::OpenOptions::new().read(true).write(true).create(true).open(file_path) {
+            Ok(file) => file,
+            Err(err) => return err!("Error opening lock file {}: {}", file_path.display(), err.to_string()),
+        };
+        match fs2::FileExt::lock_exclusive(&file) {
+            Ok(_) => Ok(FileLock::new(file)),
+            Err(err) => err!("Error locking file {}: {}", file_path.display(), err.to_string()),
+        }
+    }
+
     fn download_file(&self, url: &str) -> Result<Vec<u8>, ErrBox> {
         log_verbose!(self, "Downloading url: {}", url);
 
         download_url(url, &self.progress_bars, |env_var_name| std::env::var(env_var_name).ok())
     }
 
-    fn glob(&self, base: &Path, file_patterns: &Vec<String>) -> Result<Vec<PathBuf>, ErrBox> {
+    fn glob(&self, base: &Path, file_patterns: &Vec<String>, use_gitignore: bool, on_file_path: &mut dyn FnMut(PathBuf)) -> Result<(), ErrBox> {
         let start_instant = std::time::Instant::now();
         log_verbose!(self, "Globbing: {:?}", file_patterns);
         let base = self.canonicalize(base)?;
-        let walker = globwalk::GlobWalkerBuilder::from_patterns(base, &file_patterns)
-            .case_insensitive(if cfg!(windows) { true } else { false })
-            .follow_links(false)
-            .file_type(globwalk::FileType::FILE)
-            .build();
-        let walker = match walker {
-            Ok(walker) => walker,
+        let overrides = match build_glob_overrides(&base, file_patterns) {
+            Ok(overrides) => overrides,
             Err(err) => return err!("Error parsing file patterns: {}", err),
         };
+        let walker = WalkBuilder::new(&base)
+            .standard_filters(false)
+            .follow_links(false)
+            .parents(true)
+            .require_git(false)
+            .git_ignore(use_gitignore)
+            .git_exclude(use_gitignore)
+            .add_custom_ignore_filename(DPRINT_IGNORE_FILE_NAME)
+            .filter_entry({
+                let overrides = overrides.clone();
+                // don't traverse the children of directories that are excluded
+                move |entry| {
+                    let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                    !is_dir || !matches!(overrides.matched(get_relative_path(&overrides, entry.path()), true), Match::Ignore(_))
+                }
+            })
+            .build();
 
-        let mut file_paths = Vec::new();
-        for result in walker.into_iter() {
+        let mut file_count = 0;
+        for result in walker {
             match result {
-                Ok(result) => file_paths.push(result.into_path()),
+                Ok(entry) => {
+                    let is_file = entry.file_type().map(|t| t.is_file()).unwrap_or(false);
+                    if is_file && matches!(overrides.matched(get_relative_path(&overrides, entry.path()), false), Match::Whitelist(_)) {
+                        file_count += 1;
+                        on_file_path(entry.into_path());
+                    }
+                }
                 Err(err) => return err!("Error walking files: {}", err),
             }
         }
 
-        log_verbose!(self, "File(s) matched: {:?}", file_paths);
-        log_verbose!(self, "Finished globbing in {}ms", start_instant.elapsed().as_millis());
+        log_verbose!(self, "Finished globbing {} file(s) in {}ms", file_count, start_instant.elapsed().as_millis());
 
-        Ok(file_paths)
+        Ok(())
     }
 
     fn path_exists(&self, file_path: &Path) -> bool {
@@ -173,14 +243,24 @@ impl Environment for RealEnvironment {
     }
 
     fn get_cache_dir(&self) -> PathBuf {
-        // this would have errored in the constructor so it's ok to unwrap here
-        get_cache_dir().unwrap()
+        self.cache_dir.clone()
+    }
+
+    fn get_plugin_policy_file_path(&self) -> Option<PathBuf> {
+        match std::env::var_os("DPRINT_PLUGIN_POLICY").filter(|value| !value.is_empty()) {
+            Some(file_path) => Some(PathBuf::from(file_path)),
+            None => dirs::config_dir().map(|dir| dir.join("dprint").join("plugin-policy.json")),
+        }
     }
 
     fn get_time_secs(&self) -> u64 {
         SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap().as_secs()
     }
 
+    fn get_time_nanos(&self) -> u64 {
+        SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap().as_nanos() as u64
+    }
+
     fn get_selection(&self, prompt_message: &str, item_indent_width: u16, items: &Vec<String>) -> Result<usize, ErrBox> {
         show_select(
             &self.logger,
@@ -265,7 +345,35 @@ impl Environment for RealEnvironment {
     }
 }
 
-fn get_cache_dir() -> Result<PathBuf, ErrBox> {
+fn build_glob_overrides(base: &Path, file_patterns: &Vec<String>) -> Result<Override, ignore::Error> {
+    let mut builder = OverrideBuilder::new(base);
+    builder.case_insensitive(cfg!(windows))?;
+    for file_pattern in file_patterns {
+        // a lone `*` should only match files in the base directory
+        if file_pattern == "*" {
+            builder.add("/*")?;
+        } else {
+            builder.add(file_pattern)?;
+        }
+    }
+    builder.build()
+}
+
+fn get_relative_path<'a>(overrides: &Override, path: &'a Path) -> &'a Path {
+    path.strip_prefix(overrides.path()).unwrap_or(path)
+}
+
+fn get_cache_dir(cache_dir: Option<PathBuf>) -> Result<PathBuf, ErrBox> {
+    let cache_dir = cache_dir.or_else(|| std::env::var_os("DPRINT_CACHE_DIR").filter(|value| !value.is_empty()).map(PathBuf::from));
+    if let Some(cache_dir) = cache_dir {
+        // resolve relative paths now so the cache directory doesn't change with the cwd
+        return Ok(if cache_dir.is_absolute() {
+            cache_dir
+        } else {
+            std::env::current_dir()?.join(cache_dir)
+        });
+    }
+
     match dirs::cache_dir() {
         Some(dir) => Ok(dir.join("dprint").join("cache")),
         None => err!("Expected to find cache directory")
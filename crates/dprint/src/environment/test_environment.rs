@@ -4,12 +4,15 @@ use std::sync::Arc;
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::io::{Read, Write, Error};
 use globset::{GlobSetBuilder, GlobSet, Glob};
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use parking_lot::Mutex;
 use path_clean::{PathClean};
 use dprint_core::types::ErrBox;
 
-use super::Environment;
+use super::{Environment, FileLock, FileMetadata};
 use crate::plugins::CompilationResult;
+use crate::utils::DPRINT_IGNORE_FILE_NAME;
 
 struct BufferData {
     data: Vec<u8>,
@@ -75,6 +78,9 @@ pub struct TestEnvironment {
     is_verbose: Arc<Mutex<bool>>,
     cwd: Arc<Mutex<String>>,
     files: Arc<Mutex<HashMap<PathBuf, Vec<u8>>>>,
+    /// Incremented on every write in order to give each write a new modified time.
+    write_count: Arc<Mutex<u64>>,
+    file_modified_times: Arc<Mutex<HashMap<PathBuf, u64>>>,
     logged_messages: Arc<Mutex<Vec<String>>>,
     logged_errors: Arc<Mutex<Vec<String>>>,
     remote_files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
@@ -83,6 +89,8 @@ pub struct TestEnvironment {
     multi_selection_result: Arc<Mutex<Vec<usize>>>,
     is_silent: Arc<Mutex<bool>>,
     wasm_compile_result: Arc<Mutex<Option<CompilationResult>>>,
+    /// Error returned by `glob` after providing the file paths, which simulates an error walking the files.
+    glob_error: Arc<Mutex<Option<String>>>,
     std_in: MockStdInOut,
     std_out: MockStdInOut,
     #[cfg(windows)]
@@ -95,6 +103,8 @@ impl TestEnvironment {
             is_verbose: Arc::new(Mutex::new(false)),
             cwd: Arc::new(Mutex::new(String::from("/"))),
             files: Arc::new(Mutex::new(HashMap::new())),
+            write_count: Arc::new(Mutex::new(0)),
+            file_modified_times: Arc::new(Mutex::new(HashMap::new())),
             logged_messages: Arc::new(Mutex::new(Vec::new())),
             logged_errors: Arc::new(Mutex::new(Vec::new())),
             remote_files: Arc::new(Mutex::new(HashMap::new())),
@@ -103,6 +113,7 @@ impl TestEnvironment {
             multi_selection_result: Arc::new(Mutex::new(Vec::new())),
             is_silent: Arc::new(Mutex::new(false)),
             wasm_compile_result: Arc::new(Mutex::new(None)),
+            glob_error: Arc::new(Mutex::new(None)),
             std_in: MockStdInOut::new(),
             std_out: MockStdInOut::new(),
             #[cfg(windows)]
@@ -162,11 +173,20 @@ impl TestEnvironment {
         *is_verbose = value;
     }
 
+    pub fn has_wasm_compile_result(&self) -> bool {
+        self.wasm_compile_result.lock().is_some()
+    }
+
     pub fn set_wasm_compile_result(&self, value: CompilationResult) {
         let mut wasm_compile_result = self.wasm_compile_result.lock();
         *wasm_compile_result = Some(value);
     }
 
+    pub fn set_glob_error(&self, message: &str) {
+        let mut glob_error = self.glob_error.lock();
+        *glob_error = Some(String::from(message));
+    }
+
     pub fn stdout_reader(&self) -> Box<dyn Read + Send> {
         Box::new(self.std_out.clone())
     }
@@ -212,22 +232,64 @@ impl Environment for TestEnvironment {
         }
     }
 
+    fn read_file_prefix(&self, file_path: &Path, max_len: usize) -> Result<Vec<u8>, ErrBox> {
+        let mut bytes = self.read_file_bytes(file_path)?;
+        bytes.truncate(max_len);
+        Ok(bytes)
+    }
+
     fn write_file(&self, file_path: &Path, file_text: &str) -> Result<(), ErrBox> {
         self.write_file_bytes(file_path, file_text.as_bytes())
     }
 
     fn write_file_bytes(&self, file_path: &Path, bytes: &[u8]) -> Result<(), ErrBox> {
         let mut files = self.files.lock();
-        files.insert(file_path.to_path_buf().clean(), Vec::from(bytes));
+        let file_path = file_path.to_path_buf().clean();
+        let modified_time = {
+            let mut write_count = self.write_count.lock();
+            *write_count += 1;
+            *write_count
+        };
+        self.file_modified_times.lock().insert(file_path.clone(), modified_time);
+        files.insert(file_path, Vec::from(bytes));
         Ok(())
     }
 
+    fn get_file_metadata(&self, file_path: &Path) -> Result<FileMetadata, ErrBox> {
+        let files = self.files.lock();
+        let file_path = file_path.to_path_buf().clean();
+        match files.get(&file_path) {
+            Some(bytes) => Ok(FileMetadata {
+                size: bytes.len() as u64,
+                modified_time: self.file_modified_times.lock().get(&file_path).copied().unwrap_or(0),
+            }),
+            None => err!("Could not find file at path {}", file_path.display()),
+        }
+    }
+
     fn remove_file(&self, file_path: &Path) -> Result<(), ErrBox> {
         let mut files = self.files.lock();
         files.remove(&file_path.to_path_buf().clean());
         Ok(())
     }
 
+    fn rename(&self, from_path: &Path, to_path: &Path) -> Result<(), ErrBox> {
+        let mut files = self.files.lock();
+        let mut file_modified_times = self.file_modified_times.lock();
+        let from_path = from_path.to_path_buf().clean();
+        let to_path = to_path.to_path_buf().clean();
+        match files.remove(&from_path) {
+            Some(bytes) => {
+                files.insert(to_path.clone(), bytes);
+                if let Some(modified_time) = file_modified_times.remove(&from_path) {
+                    file_modified_times.insert(to_path, modified_time);
+                }
+                Ok(())
+            }
+            None => err!("Could not find file at path {}", from_path.display()),
+        }
+    }
+
     fn remove_dir_all(&self, dir_path: &Path) -> Result<(), ErrBox> {
         {
             let mut deleted_directories = self.deleted_directories.lock();
@@ -247,6 +309,10 @@ impl Environment for TestEnvironment {
         Ok(())
     }
 
+    fn lock_file(&self, _: &Path) -> Result<FileLock, ErrBox> {
+        Ok(FileLock::new_noop())
+    }
+
     fn download_file(&self, url: &str) -> Result<Vec<u8>, ErrBox> {
         let remote_files = self.remote_files.lock();
         match remote_files.get(&String::from(url)) {
@@ -255,34 +321,45 @@ impl Environment for TestEnvironment {
         }
     }
 
-    fn glob(&self, _: &Path, file_patterns: &Vec<String>) -> Result<Vec<PathBuf>, ErrBox> {
+    fn glob(&self, _: &Path, file_patterns: &Vec<String>, use_gitignore: bool, on_file_path: &mut dyn FnMut(PathBuf)) -> Result<(), ErrBox> {
         // todo: would be nice to test the base parameter here somehow...
         let mut file_paths = Vec::new();
         let includes_set = file_patterns_to_glob_set(file_patterns.iter().filter(|p| !p.starts_with("!")).map(|p| p.to_owned()))?;
         let excludes_set = file_patterns_to_glob_set(file_patterns.iter().filter(|p| p.starts_with("!")).map(|p| String::from(&p[1..])))?;
-        let files = self.files.lock();
-
-        for key in files.keys() {
-            let mut has_exclude = false;
-            if excludes_set.is_match(key.file_name().unwrap()) {
-                has_exclude = true;
-            } else {
-                for ancestor in key.ancestors() {
-                    if excludes_set.is_match(ancestor) {
-                        has_exclude = true;
-                        break;
+        {
+            let files = self.files.lock();
+            let ignore_matchers = get_ignore_matchers(&files, use_gitignore)?;
+
+            for key in files.keys() {
+                let mut has_exclude = false;
+                if excludes_set.is_match(key.file_name().unwrap()) {
+                    has_exclude = true;
+                } else {
+                    for ancestor in key.ancestors() {
+                        if excludes_set.is_match(ancestor) {
+                            has_exclude = true;
+                            break;
+                        }
                     }
                 }
-            }
 
-            if !has_exclude {
-                if includes_set.is_match(key) || includes_set.is_match(key.file_name().unwrap()) {
-                    file_paths.push(key.clone());
+                if !has_exclude && !is_ignored(&ignore_matchers, key) {
+                    if includes_set.is_match(key) || includes_set.is_match(key.file_name().unwrap()) {
+                        file_paths.push(key.clone());
+                    }
                 }
             }
         }
 
-        Ok(file_paths)
+        // provide the file paths after releasing the lock as the files may be read while globbing
+        for file_path in file_paths {
+            on_file_path(file_path);
+        }
+
+        match self.glob_error.lock().clone() {
+            Some(message) => err!("{}", message),
+            None => Ok(()),
+        }
     }
 
     fn path_exists(&self, file_path: &Path) -> bool {
@@ -335,10 +412,19 @@ impl Environment for TestEnvironment {
         PathBuf::from("/cache")
     }
 
+    fn get_plugin_policy_file_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from("/config/plugin-policy.json"))
+    }
+
     fn get_time_secs(&self) -> u64 {
         123456
     }
 
+    fn get_time_nanos(&self) -> u64 {
+        // file modified times are the write count
+        *self.write_count.lock()
+    }
+
     fn get_terminal_width(&self) -> u16 {
         60
     }
@@ -391,6 +477,46 @@ impl Environment for TestEnvironment {
     }
 }
 
+fn get_ignore_matchers(files: &HashMap<PathBuf, Vec<u8>>, use_gitignore: bool) -> Result<Vec<Gitignore>, ErrBox> {
+    let mut matchers = Vec::new();
+    for (file_path, bytes) in files.iter() {
+        let file_name = file_path.file_name().and_then(|name| name.to_str());
+        let root_dir = if file_name == Some(DPRINT_IGNORE_FILE_NAME) {
+            file_path.parent()
+        } else if !use_gitignore {
+            None
+        } else if file_name == Some(".gitignore") {
+            file_path.parent()
+        } else if file_path.ends_with(".git/info/exclude") {
+            file_path.ancestors().nth(3)
+        } else {
+            None
+        };
+
+        if let Some(root_dir) = root_dir {
+            let mut builder = GitignoreBuilder::new(root_dir);
+            for line in String::from_utf8(bytes.clone())?.lines() {
+                builder.add_line(None, line)?;
+            }
+            matchers.push(builder.build()?);
+        }
+    }
+    // ignore files in descendant directories take precedence
+    matchers.sort_by_key(|matcher| std::cmp::Reverse(matcher.path().components().count()));
+    Ok(matchers)
+}
+
+fn is_ignored(matchers: &[Gitignore], file_path: &Path) -> bool {
+    for matcher in matchers.iter().filter(|matcher| file_path.starts_with(matcher.path())) {
+        match matcher.matched_path_or_any_parents(file_path, false) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {},
+        }
+    }
+    false
+}
+
 fn file_patterns_to_glob_set(file_patterns: impl Iterator<Item = String>) -> Result<GlobSet, ErrBox> {
     let mut builder = GlobSetBuilder::new();
     for file_pattern in file_patterns {
@@ -7,6 +7,7 @@ extern crate lazy_static;
 #[macro_use]
 mod environment;
 
+use dprint_core::plugins::CancellationToken;
 use dprint_core::types::ErrBox;
 use std::sync::Arc;
 use environment::RealEnvironment;
@@ -32,12 +33,32 @@ fn main() -> Result<(), ErrBox> {
 fn run() -> Result<(), ErrBox> {
     let stdin_reader = cli::RealStdInReader::new();
     let args = cli::parse_args(wild::args().collect(), &stdin_reader)?;
-    let environment = RealEnvironment::new(args.verbose, args.is_silent_output())?;
+    let environment = RealEnvironment::new(args.verbose, args.is_silent_output(), args.cache_dir.clone())?;
     let cache = Arc::new(cache::Cache::new(environment.clone()));
     let plugin_cache = Arc::new(plugins::PluginCache::new(environment.clone()));
     let plugin_pools = Arc::new(plugins::PluginPools::new(environment.clone()));
     let _plugins_dropper = plugins::PluginsDropper::new(plugin_pools.clone());
-    let plugin_resolver = plugins::PluginResolver::new(environment.clone(), plugin_cache, plugin_pools.clone());
+    let plugin_resolver = plugins::PluginResolver::new(environment.clone(), plugin_cache.clone(), plugin_pools.clone());
+    let cancellation_token = CancellationToken::new();
+    if args.is_cancellable() {
+        cancel_on_interrupt(&cancellation_token)?;
+    }
+
+    cli::run_cli(args, &environment, &cache, &plugin_cache, &plugin_resolver, plugin_pools.clone(), &cancellation_token)
+}
 
-    cli::run_cli(args, &environment, &cache, &plugin_resolver, plugin_pools.clone())
+/// Cancels the in-flight format requests the first time the process is interrupted
+/// or terminated, then exits right away the next time.
+fn cancel_on_interrupt(cancellation_token: &CancellationToken) -> Result<(), ErrBox> {
+    let cancellation_token = cancellation_token.clone();
+    let result = ctrlc::set_handler(move || {
+        if cancellation_token.is_cancelled() {
+            std::process::exit(130);
+        }
+        cancellation_token.cancel();
+    });
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) => err!("Error setting the interrupt handler. {}", err.to_string()),
+    }
 }
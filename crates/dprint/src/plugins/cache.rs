@@ -1,24 +1,50 @@
 use std::path::PathBuf;
 use parking_lot::RwLock;
+use url::Url;
 
 use dprint_core::plugins::PluginInfo;
 use dprint_core::types::ErrBox;
-use dprint_cli_core::checksums::verify_sha256_checksum;
+use dprint_cli_core::checksums::{get_sha256_checksum, verify_sha256_checksum};
 
 use crate::environment::Environment;
-use crate::plugins::{PluginSourceReference};
-use crate::utils::{PathSource, get_bytes_hash};
+use crate::plugins::{PluginSourceReference, PluginSignaturePolicy, get_signature_path_source};
+use crate::utils::{PathSource, fetch_file_or_url_bytes, get_bytes_hash, compare_versions};
 use super::implementations::{setup_plugin, cleanup_plugin, get_file_path_from_plugin_info};
-use super::{PluginCacheManifest, PluginCacheManifestItem, read_manifest, write_manifest};
+use super::{PluginCacheManifest, PluginCacheManifestItem, read_manifest};
 
+#[derive(Clone)]
 pub struct PluginCacheItem {
     pub file_path: PathBuf,
     pub info: PluginInfo,
+    /// Hash of the downloaded or local plugin file. This may not exist for
+    /// remote plugins that were cached by older versions of the CLI.
+    pub file_hash: Option<u64>,
+    /// SHA-256 checksum of the plugin file. This may not exist for remote
+    /// plugins that were cached by older versions of the CLI.
+    pub checksum: Option<String>,
+    /// Public key of the trusted publisher that signed the plugin file.
+    pub signed_by: Option<String>,
+}
+
+/// Only update a plugin's last used time when it's older than this
+/// in order to avoid writing the manifest every time it's used.
+const LAST_USED_TIME_UPDATE_INTERVAL_SECS: u64 = 60 * 60;
+
+/// A plugin stored in the cache.
+#[derive(Clone)]
+pub struct CachedPlugin {
+    cache_key: String,
+    pub path_source: PathSource,
+    pub info: PluginInfo,
+    pub file_path: PathBuf,
+    /// Last used time in *seconds* since epoch.
+    pub last_used_time: u64,
 }
 
 pub struct PluginCache<TEnvironment : Environment> {
     environment: TEnvironment,
     manifest: RwLock<PluginCacheManifest>,
+    signature_policy: RwLock<PluginSignaturePolicy>,
 }
 
 impl<TEnvironment> PluginCache<TEnvironment> where TEnvironment : Environment {
@@ -27,17 +53,56 @@ impl<TEnvironment> PluginCache<TEnvironment> where TEnvironment : Environment {
         PluginCache {
             environment,
             manifest,
+            signature_policy: RwLock::new(Default::default()),
         }
     }
 
+    /// Sets how the signatures of plugins are verified before they're set up.
+    pub fn set_signature_policy(&self, signature_policy: PluginSignaturePolicy) {
+        *self.signature_policy.write() = signature_policy;
+    }
+
     pub fn forget(&self, source_reference: &PluginSourceReference) -> Result<(), ErrBox> {
         let cache_key = self.get_cache_key(&source_reference.path_source)?;
-        let mut manifest = self.manifest.write();
-        let cache_item = manifest.remove_item(&cache_key);
-        write_manifest(&manifest, &self.environment)?;
+        self.forget_cache_key(&cache_key, &source_reference.path_source)
+    }
+
+    /// Removes a plugin provided by `get_cached_plugins` from the cache. This works
+    /// even when the local file of a local plugin no longer exists.
+    pub fn forget_cached_plugin(&self, cached_plugin: &CachedPlugin) -> Result<(), ErrBox> {
+        self.forget_cache_key(&cached_plugin.cache_key, &cached_plugin.path_source)
+    }
+
+    /// Gets the plugins stored in the cache sorted by name then version.
+    pub fn get_cached_plugins(&self) -> Result<Vec<CachedPlugin>, ErrBox> {
+        let manifest = self.manifest.read();
+        let mut cached_plugins = Vec::new();
+        for (cache_key, cache_item) in manifest.items() {
+            let path_source = match get_path_source_from_cache_key(cache_key) {
+                Some(path_source) => path_source,
+                None => continue,
+            };
+            cached_plugins.push(CachedPlugin {
+                cache_key: cache_key.clone(),
+                file_path: get_file_path_from_plugin_info(&path_source, &cache_item.info, &self.environment)?,
+                path_source,
+                info: cache_item.info.clone(),
+                last_used_time: cache_item.last_used_time.unwrap_or(cache_item.created_time),
+            });
+        }
+        cached_plugins.sort_by(|a, b| {
+            a.info.name.cmp(&b.info.name)
+                .then_with(|| compare_versions(&a.info.version, &b.info.version))
+                .then_with(|| a.cache_key.cmp(&b.cache_key))
+        });
+        Ok(cached_plugins)
+    }
+
+    fn forget_cache_key(&self, cache_key: &str, path_source: &PathSource) -> Result<(), ErrBox> {
+        let cache_item = self.update_manifest(|manifest| manifest.remove_item(cache_key))?;
 
         if let Some(cache_item) = cache_item {
-            match cleanup_plugin(&source_reference.path_source, &cache_item.info, &self.environment) {
+            match cleanup_plugin(path_source, &cache_item.info, &self.environment) {
                 Err(err) => self.environment.log_error(&format!("Error forgetting plugin: {}", err.to_string())),
                 _ => {},
             }
@@ -65,6 +130,16 @@ impl<TEnvironment> PluginCache<TEnvironment> where TEnvironment : Environment {
         }
     }
 
+    /// Gets the plugin from the cache without downloading or setting it up. This is `None` when
+    /// the plugin isn't cached or when it would need to be downloaded and set up again.
+    pub fn get_cached_plugin_cache_item(&self, source_reference: &PluginSourceReference) -> Result<Option<PluginCacheItem>, ErrBox> {
+        let cache_key = self.get_cache_key(&source_reference.path_source)?;
+        match &source_reference.path_source {
+            PathSource::Remote(_) => self.get_valid_cache_item(&cache_key, source_reference, false, &download_url),
+            PathSource::Local(_) => self.get_valid_cache_item(&cache_key, source_reference, true, &get_file_bytes),
+        }
+    }
+
     fn get_plugin(
         &self,
         source_reference: PluginSourceReference,
@@ -72,67 +147,196 @@ impl<TEnvironment> PluginCache<TEnvironment> where TEnvironment : Environment {
         read_bytes: impl Fn(PathSource, TEnvironment) -> Result<Vec<u8>, ErrBox>,
     ) -> Result<PluginCacheItem, ErrBox> {
         let cache_key = self.get_cache_key(&source_reference.path_source)?;
-        let cache_item = self.manifest.read().get_item(&cache_key).map(|x| x.to_owned()); // drop lock
-        if let Some(cache_item) = cache_item {
-            let file_path = get_file_path_from_plugin_info(
-                &source_reference.path_source,
-                &cache_item.info,
-                &self.environment,
-            )?;
-
-            if check_file_hash {
-                let file_bytes = read_bytes(source_reference.path_source.clone(), self.environment.clone())?;
-                let file_hash = get_bytes_hash(&file_bytes);
-                let cache_file_hash = match &cache_item.file_hash {
-                    Some(file_hash) => *file_hash,
-                    None => return err!("Expected to have the plugin file hash stored in the cache."),
-                };
-
-                if file_hash == cache_file_hash {
-                    return Ok(PluginCacheItem {
-                        file_path,
-                        info: cache_item.info,
-                    });
-                } else {
-                    self.forget(&source_reference)?;
-                }
-            } else {
-                return Ok(PluginCacheItem {
-                    file_path,
-                    info: cache_item.info,
-                });
-            }
+        if let Some(cache_item) = self.get_valid_cache_item(&cache_key, &source_reference, check_file_hash, &read_bytes)? {
+            return Ok(cache_item);
+        }
+
+        if self.manifest.read().get_item(&cache_key).is_some() {
+            // the cached plugin is from an older version of the CLI, its bytes differ from
+            // what's expected, or it wasn't signed by a now required trusted public key,
+            // so download and verify it again
+            self.forget(&source_reference)?;
         }
 
         // get bytes
         let file_bytes = read_bytes(source_reference.path_source.clone(), self.environment.clone())?;
+        self.setup_and_cache_plugin(cache_key, &source_reference, &file_bytes, &|path_source| fetch_file_or_url_bytes(path_source, &self.environment))
+    }
+
+    fn get_valid_cache_item(
+        &self,
+        cache_key: &str,
+        source_reference: &PluginSourceReference,
+        check_file_hash: bool,
+        read_bytes: &impl Fn(PathSource, TEnvironment) -> Result<Vec<u8>, ErrBox>,
+    ) -> Result<Option<PluginCacheItem>, ErrBox> {
+        let cache_item = match self.manifest.read().get_item(cache_key).map(|x| x.to_owned()) { // drop lock
+            Some(cache_item) => cache_item,
+            None => return Ok(None),
+        };
+        if !self.has_required_signature(&source_reference.path_source, &cache_item) {
+            return Ok(None);
+        }
+
+        let (file_hash, checksum) = if check_file_hash {
+            let file_bytes = read_bytes(source_reference.path_source.clone(), self.environment.clone())?;
+            let file_hash = get_bytes_hash(&file_bytes);
+            match &cache_item.file_hash {
+                Some(cache_file_hash) => if *cache_file_hash != file_hash {
+                    return Ok(None);
+                },
+                None => return err!("Expected to have the plugin file hash stored in the cache."),
+            }
+            let checksum = match &cache_item.checksum {
+                Some(checksum) => checksum.clone(),
+                None => {
+                    // cached by an older version of the CLI, so store the checksum of the file
+                    let checksum = get_sha256_checksum(&file_bytes);
+                    let mut cache_item = cache_item.clone();
+                    cache_item.checksum = Some(checksum.clone());
+                    self.update_manifest(|manifest| manifest.add_item(cache_key.to_string(), cache_item))?;
+                    checksum
+                }
+            };
+            (Some(file_hash), Some(checksum))
+        } else if is_expected_checksum(source_reference, &cache_item) {
+            (cache_item.file_hash, cache_item.checksum.clone())
+        } else {
+            return Ok(None);
+        };
+
+        let file_path = get_file_path_from_plugin_info(
+            &source_reference.path_source,
+            &cache_item.info,
+            &self.environment,
+        )?;
+        self.update_last_used_time(cache_key, &cache_item);
+        Ok(Some(PluginCacheItem {
+            file_path,
+            info: cache_item.info,
+            file_hash,
+            checksum,
+            signed_by: cache_item.signed_by,
+        }))
+    }
+
+    /// Sets up the plugin from the provided file bytes instead of downloading it, getting
+    /// any files referenced by the plugin file (ex. a process plugin's zip file) with `fetch_bytes`.
+    pub fn install_plugin(
+        &self,
+        source_reference: &PluginSourceReference,
+        file_bytes: &[u8],
+        fetch_bytes: &dyn Fn(&PathSource) -> Result<Vec<u8>, ErrBox>,
+    ) -> Result<PluginCacheItem, ErrBox> {
+        let cache_key = self.get_cache_key(&source_reference.path_source)?;
+        if self.manifest.read().get_item(&cache_key).is_some() {
+            self.forget(source_reference)?;
+        }
+        self.setup_and_cache_plugin(cache_key, source_reference, file_bytes, fetch_bytes)
+    }
 
+    fn setup_and_cache_plugin(
+        &self,
+        cache_key: String,
+        source_reference: &PluginSourceReference,
+        file_bytes: &[u8],
+        fetch_bytes: &dyn Fn(&PathSource) -> Result<Vec<u8>, ErrBox>,
+    ) -> Result<PluginCacheItem, ErrBox> {
         // check checksum only if provided (not required for Wasm plugins)
         if let Some(checksum) = &source_reference.checksum {
             verify_sha256_checksum(&file_bytes, checksum)?;
         }
 
-        let setup_result = setup_plugin(&source_reference.path_source, &file_bytes, &self.environment)?;
+        let signed_by = self.verify_signature(&source_reference.path_source, &file_bytes, fetch_bytes)?;
+        let setup_result = setup_plugin(&source_reference.path_source, &file_bytes, fetch_bytes, &self.environment)?;
+        let file_hash = get_bytes_hash(&file_bytes);
+        let checksum = get_sha256_checksum(&file_bytes);
         let cache_item = PluginCacheManifestItem {
             info: setup_result.plugin_info.clone(),
-            file_hash: if check_file_hash {
-                Some(get_bytes_hash(&file_bytes))
-            } else {
-                None
-            },
+            file_hash: Some(file_hash),
+            checksum: Some(checksum.clone()),
+            signed_by: signed_by.clone(),
             created_time: self.environment.get_time_secs(),
+            last_used_time: None,
         };
 
-        let mut manifest = self.manifest.write();
-        manifest.add_item(cache_key, cache_item);
-        write_manifest(&manifest, &self.environment)?;
+        self.update_manifest(|manifest| manifest.add_item(cache_key, cache_item))?;
 
         Ok(PluginCacheItem {
             file_path: setup_result.file_path,
             info: setup_result.plugin_info,
+            file_hash: Some(file_hash),
+            checksum: Some(checksum),
+            signed_by,
         })
     }
 
+    /// Verifies the plugin's detached signature before it's set up, returning
+    /// the trusted public key that signed it.
+    fn verify_signature(
+        &self,
+        path_source: &PathSource,
+        file_bytes: &[u8],
+        fetch_bytes: &dyn Fn(&PathSource) -> Result<Vec<u8>, ErrBox>,
+    ) -> Result<Option<String>, ErrBox> {
+        let signature_policy = self.signature_policy.read();
+        if !signature_policy.should_verify(path_source) {
+            return Ok(None);
+        }
+
+        let is_required = signature_policy.requires_signature(path_source);
+        let signature_path_source = get_signature_path_source(path_source)?;
+        let signature_result = fetch_bytes(&signature_path_source)
+            .and_then(|bytes| Ok(String::from_utf8(bytes)?))
+            .and_then(|signature_text| signature_policy.verify(file_bytes, &signature_text));
+        match signature_result {
+            Ok(public_key) => Ok(Some(public_key)),
+            Err(err) => {
+                if is_required {
+                    err!(
+                        "Process plugins must be signed by a trusted public key, but could not verify the signature of {}. {}",
+                        path_source.display(),
+                        err.to_string(),
+                    )
+                } else {
+                    log_verbose!(self.environment, "Using {} as an unsigned plugin. {}", path_source.display(), err.to_string());
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    fn has_required_signature(&self, path_source: &PathSource, cache_item: &PluginCacheManifestItem) -> bool {
+        let signature_policy = self.signature_policy.read();
+        !signature_policy.requires_signature(path_source)
+            || cache_item.signed_by.as_ref().map(|public_key| signature_policy.is_trusted_public_key(public_key)).unwrap_or(false)
+    }
+
+    /// Updates the plugin's last used time. This is best effort since it's only used for
+    /// cleaning up the cache and shouldn't prevent using the plugin (ex. read-only cache directory).
+    fn update_last_used_time(&self, cache_key: &str, cache_item: &PluginCacheManifestItem) {
+        let now = self.environment.get_time_secs();
+        let last_used_time = cache_item.last_used_time.unwrap_or(cache_item.created_time);
+        if now.saturating_sub(last_used_time) < LAST_USED_TIME_UPDATE_INTERVAL_SECS {
+            return;
+        }
+
+        let mut cache_item = cache_item.clone();
+        cache_item.last_used_time = Some(now);
+        if let Err(err) = self.update_manifest(|manifest| manifest.add_item(cache_key.to_string(), cache_item)) {
+            log_verbose!(self.environment, "Error updating the last used time of {}: {}", cache_key, err.to_string());
+        }
+    }
+
+    /// Applies the change to the manifest on the file system, then
+    /// stores the result that may include changes from other processes.
+    fn update_manifest<TResult>(&self, action: impl FnOnce(&mut PluginCacheManifest) -> TResult) -> Result<TResult, ErrBox> {
+        let mut manifest = self.manifest.write();
+        let (updated_manifest, result) = super::update_manifest(&self.environment, |manifest| Ok(action(manifest)))?;
+        *manifest = updated_manifest;
+        Ok(result)
+    }
+
     fn get_cache_key(&self, path_source: &PathSource) -> Result<String, ErrBox> {
         Ok(match path_source {
             PathSource::Remote(remote_source) => format!("remote:{}", remote_source.url.as_str()),
@@ -144,6 +348,25 @@ impl<TEnvironment> PluginCache<TEnvironment> where TEnvironment : Environment {
     }
 }
 
+fn get_path_source_from_cache_key(cache_key: &str) -> Option<PathSource> {
+    if let Some(url) = cache_key.strip_prefix("remote:") {
+        Url::parse(url).ok().map(PathSource::new_remote)
+    } else if let Some(file_path) = cache_key.strip_prefix("local:") {
+        Some(PathSource::new_local(PathBuf::from(file_path)))
+    } else {
+        None
+    }
+}
+
+fn is_expected_checksum(source_reference: &PluginSourceReference, cache_item: &PluginCacheManifestItem) -> bool {
+    match (&source_reference.checksum, &cache_item.checksum) {
+        (Some(expected_checksum), Some(checksum)) => expected_checksum == checksum,
+        // the checksum of plugins cached by older versions of the CLI is unknown
+        (Some(_), None) => false,
+        (None, _) => true,
+    }
+}
+
 fn download_url<TEnvironment: Environment>(path_source: PathSource, environment: TEnvironment) -> Result<Vec<u8>, ErrBox> {
     environment.download_file(path_source.unwrap_remote().url.as_str())
 }
@@ -158,7 +381,7 @@ mod test {
     use pretty_assertions::assert_eq;
     use dprint_core::plugins::PluginInfo;
     use crate::environment::TestEnvironment;
-    use crate::plugins::{CompilationResult, PluginSourceReference};
+    use crate::plugins::{CompilationResult, PluginSourceReference, create_test_signature};
     use dprint_core::types::ErrBox;
     use super::*;
 
@@ -183,7 +406,7 @@ mod test {
         // should have saved the manifest
         assert_eq!(
             environment.read_file(&environment.get_cache_dir().join("plugin-cache-manifest.json")).unwrap(),
-            r#"{"schemaVersion":3,"plugins":{"remote:https://plugins.dprint.dev/test.wasm":{"createdTime":123456,"info":{"name":"test-plugin","version":"0.1.0","configKey":"test-plugin","fileExtensions":["txt","dat"],"fileNames":[],"helpUrl":"test-url","configSchemaUrl":"schema-url"}}}}"#,
+            r#"{"schemaVersion":5,"plugins":{"remote:https://plugins.dprint.dev/test.wasm":{"createdTime":123456,"fileHash":10632242795325663332,"checksum":"e3b98a4da31a127d4bde6e43033f66ba274cab0eb7eb1c70ec41402bf6273dd8","info":{"name":"test-plugin","version":"0.1.0","configKey":"test-plugin","fileExtensions":["txt","dat"],"fileNames":[],"interpreters":[],"languages":[],"helpUrl":"test-url","configSchemaUrl":"schema-url"}}}}"#,
         );
 
         // should forget it afterwards
@@ -193,12 +416,114 @@ mod test {
         // should have saved the manifest
         assert_eq!(
             environment.read_file(&environment.get_cache_dir().join("plugin-cache-manifest.json")).unwrap(),
-            r#"{"schemaVersion":3,"plugins":{}}"#,
+            r#"{"schemaVersion":5,"plugins":{}}"#,
         );
 
         Ok(())
     }
 
+    #[test]
+    fn it_should_download_remote_file_again_when_checksum_differs() -> Result<(), ErrBox> {
+        let environment = TestEnvironment::new();
+        environment.add_remote_file("https://plugins.dprint.dev/test.wasm", "t".as_bytes());
+        environment.set_wasm_compile_result(create_compilation_result("t".as_bytes()));
+
+        let plugin_cache = PluginCache::new(environment.clone());
+        let plugin_source = PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.wasm");
+        let cache_item = plugin_cache.get_plugin_cache_item(&plugin_source)?;
+        assert_eq!(cache_item.checksum, Some(get_sha256_checksum("t".as_bytes())));
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/test.wasm"]);
+
+        // should use the cached plugin when the checksum matches
+        let plugin_source = PluginSourceReference {
+            path_source: plugin_source.path_source.clone(),
+            checksum: Some(get_sha256_checksum("t".as_bytes())),
+        };
+        plugin_cache.get_plugin_cache_item(&plugin_source)?;
+        assert_eq!(environment.take_logged_errors().len(), 0);
+
+        // should download it again when the checksum doesn't match
+        environment.add_remote_file("https://plugins.dprint.dev/test.wasm", "u".as_bytes());
+        let plugin_source = PluginSourceReference {
+            path_source: plugin_source.path_source.clone(),
+            checksum: Some(get_sha256_checksum("u".as_bytes())),
+        };
+        let cache_item = plugin_cache.get_plugin_cache_item(&plugin_source)?;
+        assert_eq!(cache_item.checksum, Some(get_sha256_checksum("u".as_bytes())));
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/test.wasm"]);
+
+        // should error when the downloaded file doesn't match the checksum
+        let plugin_source = PluginSourceReference {
+            path_source: plugin_source.path_source.clone(),
+            checksum: Some(get_sha256_checksum("t".as_bytes())),
+        };
+        let err = plugin_cache.get_plugin_cache_item(&plugin_source).err().unwrap();
+        assert_eq!(err.to_string(), format!(
+            "The checksum {} did not match the expected checksum of {}.",
+            get_sha256_checksum("u".as_bytes()),
+            get_sha256_checksum("t".as_bytes()),
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_should_verify_signature_with_trusted_public_keys() -> Result<(), ErrBox> {
+        let environment = TestEnvironment::new();
+        let (public_key, signature) = create_test_signature(&[1; 32], "t".as_bytes());
+        environment.add_remote_file("https://plugins.dprint.dev/test.wasm", "t".as_bytes());
+        environment.add_remote_file_bytes("https://plugins.dprint.dev/test.wasm.sig", signature.into_bytes());
+        environment.add_remote_file("https://plugins.dprint.dev/other.wasm", "t".as_bytes());
+        environment.set_wasm_compile_result(create_compilation_result("t".as_bytes()));
+
+        let plugin_cache = PluginCache::new(environment.clone());
+        plugin_cache.set_signature_policy(PluginSignaturePolicy {
+            trusted_public_keys: vec![public_key.clone()],
+            require_signed_process_plugins: true,
+        });
+        let cache_item = plugin_cache.get_plugin_cache_item(&PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.wasm"))?;
+        assert_eq!(cache_item.signed_by, Some(public_key));
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/test.wasm"]);
+
+        // unsigned wasm plugins are still allowed
+        let cache_item = plugin_cache.get_plugin_cache_item(&PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/other.wasm"))?;
+        assert_eq!(cache_item.signed_by, None);
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/other.wasm"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_should_error_for_unsigned_process_plugin_when_signature_required() {
+        let environment = TestEnvironment::new();
+        let (public_key, signature) = create_test_signature(&[1; 32], "other".as_bytes());
+        environment.add_remote_file("https://plugins.dprint.dev/test.exe-plugin", "t".as_bytes());
+        environment.add_remote_file_bytes("https://plugins.dprint.dev/test.exe-plugin.sig", signature.into_bytes());
+        environment.add_remote_file("https://plugins.dprint.dev/unsigned.exe-plugin", "t".as_bytes());
+
+        let plugin_cache = PluginCache::new(environment.clone());
+        plugin_cache.set_signature_policy(PluginSignaturePolicy {
+            trusted_public_keys: vec![public_key],
+            require_signed_process_plugins: true,
+        });
+        let err = plugin_cache.get_plugin_cache_item(&PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.exe-plugin")).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            concat!(
+                "Process plugins must be signed by a trusted public key, but could not verify the signature of ",
+                "https://plugins.dprint.dev/test.exe-plugin. The signature was not created by any of the trusted public keys.",
+            ),
+        );
+        let err = plugin_cache.get_plugin_cache_item(&PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/unsigned.exe-plugin")).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            concat!(
+                "Process plugins must be signed by a trusted public key, but could not verify the signature of ",
+                "https://plugins.dprint.dev/unsigned.exe-plugin. Could not find file at url https://plugins.dprint.dev/unsigned.exe-plugin.sig",
+            ),
+        );
+    }
+
     #[test]
     fn it_should_cache_local_file() -> Result<(), ErrBox> {
         let environment = TestEnvironment::new();
@@ -224,9 +549,9 @@ mod test {
         assert_eq!(
             environment.read_file(&environment.get_cache_dir().join("plugin-cache-manifest.json")).unwrap(),
             concat!(
-                r#"{"schemaVersion":3,"plugins":{"local:/test.wasm":{"createdTime":123456,"fileHash":10632242795325663332,"info":{"#,
+                r#"{"schemaVersion":5,"plugins":{"local:/test.wasm":{"createdTime":123456,"fileHash":10632242795325663332,"checksum":"e3b98a4da31a127d4bde6e43033f66ba274cab0eb7eb1c70ec41402bf6273dd8","info":{"#,
                 r#""name":"test-plugin","version":"0.1.0","configKey":"test-plugin","#,
-                r#""fileExtensions":["txt","dat"],"fileNames":[],"helpUrl":"test-url","configSchemaUrl":"schema-url"}}}}"#,
+                r#""fileExtensions":["txt","dat"],"fileNames":[],"interpreters":[],"languages":[],"helpUrl":"test-url","configSchemaUrl":"schema-url"}}}}"#,
             )
         );
 
@@ -243,9 +568,9 @@ mod test {
         assert_eq!(
             environment.read_file(&environment.get_cache_dir().join("plugin-cache-manifest.json")).unwrap(),
             concat!(
-                r#"{"schemaVersion":3,"plugins":{"local:/test.wasm":{"createdTime":123456,"fileHash":6989588595861227504,"info":{"#,
+                r#"{"schemaVersion":5,"plugins":{"local:/test.wasm":{"createdTime":123456,"fileHash":6989588595861227504,"checksum":"0bfe935e70c321c7ca3afc75ce0d0ca2f98b5422e008bb31c00c6d7f1f1c0ad6","info":{"#,
                 r#""name":"test-plugin","version":"0.1.0","configKey":"test-plugin","#,
-                r#""fileExtensions":["txt","dat"],"fileNames":[],"helpUrl":"test-url","configSchemaUrl":"schema-url"}}}}"#,
+                r#""fileExtensions":["txt","dat"],"fileNames":[],"interpreters":[],"languages":[],"helpUrl":"test-url","configSchemaUrl":"schema-url"}}}}"#,
             )
         );
 
@@ -258,8 +583,95 @@ mod test {
         // should have saved the manifest
         assert_eq!(
             environment.read_file(&environment.get_cache_dir().join("plugin-cache-manifest.json")).unwrap(),
-            r#"{"schemaVersion":3,"plugins":{}}"#,
+            r#"{"schemaVersion":5,"plugins":{}}"#,
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_should_get_and_forget_cached_plugins() -> Result<(), ErrBox> {
+        let environment = TestEnvironment::new();
+        environment.add_remote_file("https://plugins.dprint.dev/test.wasm", "t".as_bytes());
+        environment.set_wasm_compile_result(create_compilation_result("t".as_bytes()));
+
+        let plugin_cache = PluginCache::new(environment.clone());
+        let plugin_source = PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.wasm");
+        plugin_cache.get_plugin_cache_item(&plugin_source)?;
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/test.wasm"]);
+
+        let cached_plugins = plugin_cache.get_cached_plugins()?;
+        assert_eq!(cached_plugins.len(), 1);
+        assert_eq!(cached_plugins[0].path_source, plugin_source.path_source);
+        assert_eq!(cached_plugins[0].info, get_test_plugin_info());
+        assert_eq!(cached_plugins[0].file_path, PathBuf::from("/cache").join("plugins").join("test-plugin").join("test-plugin-0.1.0.cached"));
+        assert_eq!(cached_plugins[0].last_used_time, 123456);
+
+        plugin_cache.forget_cached_plugin(&cached_plugins[0])?;
+        assert_eq!(plugin_cache.get_cached_plugins()?.len(), 0);
+        assert_eq!(environment.path_exists(&cached_plugins[0].file_path), false);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_should_update_last_used_time_when_old() -> Result<(), ErrBox> {
+        let environment = TestEnvironment::new();
+        let manifest_file_path = environment.get_cache_dir().join("plugin-cache-manifest.json");
+        environment.write_file(
+            &manifest_file_path,
+            concat!(
+                r#"{"schemaVersion":5,"plugins":{"remote:https://plugins.dprint.dev/test.wasm":{"createdTime":100,"checksum":"e3b98a4da31a127d4bde6e43033f66ba274cab0eb7eb1c70ec41402bf6273dd8","info":{"#,
+                r#""name":"test-plugin","version":"0.1.0","configKey":"test-plugin","#,
+                r#""fileExtensions":["txt","dat"],"fileNames":[],"interpreters":[],"languages":[],"helpUrl":"test-url","configSchemaUrl":"schema-url"}}}}"#,
+            ),
+        ).unwrap();
+
+        let plugin_cache = PluginCache::new(environment.clone());
+        let plugin_source = PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.wasm");
+        plugin_cache.get_plugin_cache_item(&plugin_source)?;
+
+        assert_eq!(
+            environment.read_file(&manifest_file_path).unwrap(),
+            concat!(
+                r#"{"schemaVersion":5,"plugins":{"remote:https://plugins.dprint.dev/test.wasm":{"createdTime":100,"lastUsedTime":123456,"checksum":"e3b98a4da31a127d4bde6e43033f66ba274cab0eb7eb1c70ec41402bf6273dd8","info":{"#,
+                r#""name":"test-plugin","version":"0.1.0","configKey":"test-plugin","#,
+                r#""fileExtensions":["txt","dat"],"fileNames":[],"interpreters":[],"languages":[],"helpUrl":"test-url","configSchemaUrl":"schema-url"}}}}"#,
+            ),
         );
+        assert_eq!(plugin_cache.get_cached_plugins()?[0].last_used_time, 123456);
+        // should not have downloaded or compiled the plugin
+        assert_eq!(environment.take_logged_errors().len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_should_keep_plugins_cached_by_other_processes() -> Result<(), ErrBox> {
+        let environment = TestEnvironment::new();
+        environment.add_remote_file("https://plugins.dprint.dev/test.wasm", "t".as_bytes());
+        environment.set_wasm_compile_result(create_compilation_result("t".as_bytes()));
+        let plugin_cache = PluginCache::new(environment.clone());
+
+        // another process caches a plugin after this one read the manifest
+        environment.write_file(
+            &environment.get_cache_dir().join("plugin-cache-manifest.json"),
+            concat!(
+                r#"{"schemaVersion":5,"plugins":{"remote:https://plugins.dprint.dev/other.wasm":{"createdTime":100,"info":{"#,
+                r#""name":"other-plugin","version":"0.2.0","configKey":"other-plugin","#,
+                r#""fileExtensions":["other"],"fileNames":[],"interpreters":[],"languages":[],"helpUrl":"test-url","configSchemaUrl":"schema-url"}}}}"#,
+            ),
+        ).unwrap();
+
+        let plugin_source = PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.wasm");
+        plugin_cache.get_plugin_cache_item(&plugin_source)?;
+        assert_eq!(environment.take_logged_errors(), vec!["Compiling https://plugins.dprint.dev/test.wasm"]);
+
+        let cached_plugin_names = plugin_cache.get_cached_plugins()?.into_iter().map(|p| p.info.name).collect::<Vec<_>>();
+        assert_eq!(cached_plugin_names, vec!["other-plugin", "test-plugin"]);
+        let manifest_text = environment.read_file(&environment.get_cache_dir().join("plugin-cache-manifest.json")).unwrap();
+        assert!(manifest_text.contains("remote:https://plugins.dprint.dev/other.wasm"));
+        assert!(manifest_text.contains("remote:https://plugins.dprint.dev/test.wasm"));
 
         Ok(())
     }
@@ -278,6 +690,8 @@ mod test {
             config_key: String::from("test-plugin"),
             file_extensions: vec![String::from("txt"), String::from("dat")],
             file_names: vec![],
+            interpreters: vec![],
+            languages: vec![],
             help_url: String::from("test-url"),
             config_schema_url: String::from("schema-url"),
         }
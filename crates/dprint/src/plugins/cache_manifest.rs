@@ -6,8 +6,11 @@ use dprint_core::plugins::PluginInfo;
 use dprint_core::types::ErrBox;
 
 use crate::environment::Environment;
+use crate::utils::atomic_write_file;
 
-const PLUGIN_SCHEMA_VERSION: usize = 3;
+/// Incremented when the cached plugin information changes (ex. new `PluginInfo` properties or
+/// Wasm modules compiled differently) so that plugins cached by older versions are set up again.
+const PLUGIN_SCHEMA_VERSION: usize = 5;
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -35,6 +38,10 @@ impl PluginCacheManifest {
     pub fn remove_item(&mut self, key: &str) -> Option<PluginCacheManifestItem> {
         self.plugins.remove(key)
     }
+
+    pub fn items(&self) -> impl Iterator<Item = (&String, &PluginCacheManifestItem)> {
+        self.plugins.iter()
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -42,13 +49,30 @@ impl PluginCacheManifest {
 pub struct PluginCacheManifestItem {
     /// Created time in *seconds* since epoch.
     pub created_time: u64,
+    /// Last used time in *seconds* since epoch. This is only updated
+    /// periodically and may not exist for plugins cached by older versions
+    /// of the CLI, in which case the created time should be used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_time: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file_hash: Option<u64>,
+    /// SHA-256 checksum of the downloaded or local plugin file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// Public key of the trusted publisher that signed the plugin file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed_by: Option<String>,
     pub info: PluginInfo,
 }
 
 pub fn read_manifest(environment: &impl Environment) -> PluginCacheManifest {
-    return match try_deserialize(environment) {
+    let file_path = get_manifest_file_path(environment);
+    let text = match environment.read_file(&file_path) {
+        Ok(text) => text,
+        Err(_) => return PluginCacheManifest::new(),
+    };
+
+    match serde_json::from_str::<PluginCacheManifest>(&text) {
         Ok(manifest) => {
             if manifest.schema_version != PLUGIN_SCHEMA_VERSION {
                 let _ = environment.remove_dir_all(&environment.get_cache_dir().join("plugins"));
@@ -57,27 +81,53 @@ pub fn read_manifest(environment: &impl Environment) -> PluginCacheManifest {
                 manifest
             }
         },
-        Err(_) => {
-            let _ = environment.remove_dir_all(&environment.get_cache_dir());
-            PluginCacheManifest::new()
+        Err(err) => {
+            // keep what's still readable instead of deleting the cache
+            environment.log_error(&format!("Recovering plugin cache manifest. Message: {}", err));
+            recover_manifest(&text)
         }
+    }
+}
+
+/// Gets the items that can still be deserialized from a manifest that failed to deserialize.
+fn recover_manifest(text: &str) -> PluginCacheManifest {
+    let mut manifest = PluginCacheManifest::new();
+    let value = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(value) => value,
+        Err(_) => return manifest,
     };
+    if value.get("schemaVersion").and_then(|value| value.as_u64()) != Some(PLUGIN_SCHEMA_VERSION as u64) {
+        return manifest;
+    }
 
-    fn try_deserialize(environment: &impl Environment) -> Result<PluginCacheManifest, ErrBox> {
-        let file_path = get_manifest_file_path(environment);
-        return match environment.read_file(&file_path) {
-            Ok(text) => {
-                Ok(serde_json::from_str::<PluginCacheManifest>(&text)?)
-            },
-            Err(_) => Ok(PluginCacheManifest::new()),
-        };
+    if let Some(serde_json::Value::Object(plugins)) = value.get("plugins") {
+        for (key, value) in plugins {
+            if let Ok(item) = serde_json::from_value::<PluginCacheManifestItem>(value.clone()) {
+                manifest.add_item(key.clone(), item);
+            }
+        }
     }
+
+    manifest
 }
 
 pub fn write_manifest(manifest: &PluginCacheManifest, environment: &impl Environment) -> Result<(), ErrBox> {
     let file_path = get_manifest_file_path(environment);
     let serialized_manifest = serde_json::to_string(&manifest)?;
-    environment.write_file(&file_path, &serialized_manifest)
+    atomic_write_file(&file_path, &serialized_manifest, environment)
+}
+
+/// Re-reads the manifest while holding a lock on it, applies the change, then writes it
+/// back so that changes made by other dprint processes since it was last read aren't lost.
+pub fn update_manifest<TEnvironment: Environment, TResult>(
+    environment: &TEnvironment,
+    action: impl FnOnce(&mut PluginCacheManifest) -> Result<TResult, ErrBox>,
+) -> Result<(PluginCacheManifest, TResult), ErrBox> {
+    let _file_lock = environment.lock_file(&get_manifest_lock_file_path(environment))?;
+    let mut manifest = read_manifest(environment);
+    let result = action(&mut manifest)?;
+    write_manifest(&manifest, environment)?;
+    Ok((manifest, result))
 }
 
 fn get_manifest_file_path(environment: &impl Environment) -> PathBuf {
@@ -85,6 +135,11 @@ fn get_manifest_file_path(environment: &impl Environment) -> PathBuf {
     cache_dir.join("plugin-cache-manifest.json")
 }
 
+fn get_manifest_lock_file_path(environment: &impl Environment) -> PathBuf {
+    let cache_dir = environment.get_cache_dir();
+    cache_dir.join("plugin-cache-manifest.json.lock")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -96,7 +151,7 @@ mod test {
         environment.write_file(
             &environment.get_cache_dir().join("plugin-cache-manifest.json"),
             r#"{
-    "schemaVersion": 3,
+    "schemaVersion": 5,
     "plugins": {
         "a": {
             "createdTime": 123,
@@ -111,7 +166,9 @@ mod test {
         },
         "c": {
             "createdTime": 456,
+            "lastUsedTime": 789,
             "fileHash": 10,
+            "checksum": "abc",
             "info": {
                 "name": "dprint-plugin-json",
                 "version": "0.2.0",
@@ -141,39 +198,54 @@ mod test {
         let mut expected_manifest = PluginCacheManifest::new();
         expected_manifest.add_item(String::from("a"), PluginCacheManifestItem {
             created_time: 123,
+            last_used_time: None,
             file_hash: None,
+            checksum: None,
+            signed_by: None,
             info: PluginInfo {
                 name: "dprint-plugin-typescript".to_string(),
                 version: "0.1.0".to_string(),
                 config_key: "typescript".to_string(),
                 file_extensions: vec![".ts".to_string()],
                 file_names: vec![],
+                interpreters: vec![],
+                languages: vec![],
                 help_url: "help url".to_string(),
                 config_schema_url: "schema url".to_string()
             }
         });
         expected_manifest.add_item(String::from("c"), PluginCacheManifestItem {
             created_time: 456,
+            last_used_time: Some(789),
             file_hash: Some(10),
+            checksum: Some(String::from("abc")),
+            signed_by: None,
             info: PluginInfo {
                 name: "dprint-plugin-json".to_string(),
                 version: "0.2.0".to_string(),
                 config_key: "json".to_string(),
                 file_extensions: vec![".json".to_string()],
                 file_names: vec![],
+                interpreters: vec![],
+                languages: vec![],
                 help_url: "help url 2".to_string(),
                 config_schema_url: "schema url 2".to_string()
             }
         });
         expected_manifest.add_item(String::from("cargo"), PluginCacheManifestItem {
             created_time: 210530,
+            last_used_time: None,
             file_hash: Some(1226),
+            checksum: None,
+            signed_by: None,
             info: PluginInfo {
                 name: "dprint-plugin-cargo".to_string(),
                 version: "0.2.1".to_string(),
                 config_key: "cargo".to_string(),
                 file_extensions: vec![],
                 file_names: vec!["Cargo.toml".to_string()],
+                interpreters: vec![],
+                languages: vec![],
                 help_url: "cargo help url".to_string(),
                 config_schema_url: "cargo schema url".to_string()
             }
@@ -218,6 +290,101 @@ mod test {
         ).unwrap();
 
         assert_eq!(read_manifest(&environment), PluginCacheManifest::new());
+        assert_eq!(environment.take_logged_errors(), vec![
+            String::from("Recovering plugin cache manifest. Message: key must be a string at line 1 column 23")
+        ]);
+        // should not have deleted the cache
+        assert_eq!(environment.is_dir_deleted(&environment.get_cache_dir()), false);
+    }
+
+    #[test]
+    fn it_should_recover_readable_items_of_manifest() {
+        let environment = TestEnvironment::new();
+        environment.write_file(
+            &environment.get_cache_dir().join("plugin-cache-manifest.json"),
+            r#"{
+    "schemaVersion": 5,
+    "plugins": {
+        "a": {
+            "createdTime": 123,
+            "info": {
+                "name": "dprint-plugin-typescript",
+                "version": "0.1.0",
+                "configKey": "typescript",
+                "fileExtensions": [".ts"],
+                "helpUrl": "help url",
+                "configSchemaUrl": "schema url"
+            }
+        },
+        "b": {
+            "createdTime": "invalid"
+        }
+    }
+}"#
+        ).unwrap();
+
+        let mut expected_manifest = PluginCacheManifest::new();
+        expected_manifest.add_item(String::from("a"), PluginCacheManifestItem {
+            created_time: 123,
+            last_used_time: None,
+            file_hash: None,
+            checksum: None,
+            signed_by: None,
+            info: PluginInfo {
+                name: "dprint-plugin-typescript".to_string(),
+                version: "0.1.0".to_string(),
+                config_key: "typescript".to_string(),
+                file_extensions: vec![".ts".to_string()],
+                file_names: vec![],
+                interpreters: vec![],
+                languages: vec![],
+                help_url: "help url".to_string(),
+                config_schema_url: "schema url".to_string()
+            }
+        });
+
+        assert_eq!(read_manifest(&environment), expected_manifest);
+        assert_eq!(environment.take_logged_errors().len(), 1);
+        assert_eq!(environment.is_dir_deleted(&environment.get_cache_dir()), false);
+    }
+
+    #[test]
+    fn it_should_update_manifest_with_changes_from_file_system() {
+        let environment = TestEnvironment::new();
+        let mut manifest = PluginCacheManifest::new();
+        manifest.add_item(String::from("a"), PluginCacheManifestItem {
+            created_time: 123,
+            last_used_time: None,
+            file_hash: None,
+            checksum: None,
+            signed_by: None,
+            info: PluginInfo {
+                name: "dprint-plugin-typescript".to_string(),
+                version: "0.1.0".to_string(),
+                config_key: "typescript".to_string(),
+                file_extensions: vec![".ts".to_string()],
+                file_names: vec![],
+                interpreters: vec![],
+                languages: vec![],
+                help_url: "help url".to_string(),
+                config_schema_url: "schema url".to_string()
+            }
+        });
+        // ex. written by another process
+        write_manifest(&manifest, &environment).unwrap();
+
+        let (updated_manifest, removed_item) = update_manifest(&environment, |manifest| Ok(manifest.remove_item("a"))).unwrap();
+        assert_eq!(removed_item.is_some(), true);
+        assert_eq!(updated_manifest, PluginCacheManifest::new());
+        assert_eq!(read_manifest(&environment), PluginCacheManifest::new());
+    }
+
+    #[test]
+    fn it_should_not_write_manifest_when_update_errors() {
+        let environment = TestEnvironment::new();
+        let result = update_manifest(&environment, |_| -> Result<(), ErrBox> { err!("Error.") });
+        assert_eq!(result.err().unwrap().to_string(), "Error.");
+        assert_eq!(environment.path_exists(&environment.get_cache_dir().join("plugin-cache-manifest.json")), false);
     }
 
     #[test]
@@ -234,26 +401,36 @@ mod test {
         let mut manifest = PluginCacheManifest::new();
         manifest.add_item(String::from("a"), PluginCacheManifestItem {
             created_time: 456,
+            last_used_time: None,
             file_hash: Some(256),
+            checksum: None,
+            signed_by: None,
             info: PluginInfo {
                 name: "dprint-plugin-typescript".to_string(),
                 version: "0.1.0".to_string(),
                 config_key: "typescript".to_string(),
                 file_extensions: vec![".ts".to_string()],
                 file_names: vec![],
+                interpreters: vec![],
+                languages: vec![],
                 help_url: "help url".to_string(),
                 config_schema_url: "schema url".to_string()
             }
         });
         manifest.add_item(String::from("b"), PluginCacheManifestItem {
             created_time: 456,
+            last_used_time: Some(789),
             file_hash: None,
+            checksum: None,
+            signed_by: None,
             info: PluginInfo {
                 name: "dprint-plugin-json".to_string(),
                 version: "0.2.0".to_string(),
                 config_key: "json".to_string(),
                 file_extensions: vec![".json".to_string()],
                 file_names: vec!["file.test".to_string()],
+                interpreters: vec!["node".to_string()],
+                languages: vec![],
                 help_url: "help url 2".to_string(),
                 config_schema_url: "schema url 2".to_string()
             }
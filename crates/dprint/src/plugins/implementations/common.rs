@@ -1,24 +1,123 @@
 use dprint_core::configuration::ConfigKeyMap;
-use std::path::Path;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use dprint_core::plugins::{HostInfo, PluginCapabilities, PluginHandshake, CancellationToken};
 use dprint_core::types::ErrBox;
 
 use crate::plugins::pool::PluginPools;
-use crate::environment::Environment;
+use crate::environment::{Environment, FileLock};
+use crate::utils::compare_versions;
 
+const CLI_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+thread_local! {
+    /// Names of the plugins in the chain of host format requests currently being handled on this
+    /// thread, from the outermost plugin to the innermost. Every protocol calls back into the host
+    /// on the thread that made the format request, so nested requests end up on the same thread.
+    static HOST_FORMAT_CHAIN: RefCell<Vec<String>> = RefCell::new(Vec::new());
+}
+
+/// Gets the information provided to plugins in the handshake.
+pub fn get_host_info() -> HostInfo {
+    HostInfo {
+        cli_version: CLI_VERSION.to_string(),
+        capabilities: PluginCapabilities::SUPPORTED,
+    }
+}
+
+/// Gets the information provided to process plugins in the handshake.
+///
+/// Process plugins may additionally negotiate the multiplexed protocol mode and cancellation.
+pub fn get_process_host_info() -> HostInfo {
+    HostInfo {
+        capabilities: PluginCapabilities {
+            multiplexed: true,
+            cancellation: true,
+            ..PluginCapabilities::SUPPORTED
+        },
+        ..get_host_info()
+    }
+}
+
+/// Gets the information provided to Wasm plugins in the handshake.
+///
+/// Wasm plugins may additionally send text to the host directly from their memory.
+pub fn get_wasm_host_info() -> HostInfo {
+    HostInfo {
+        capabilities: PluginCapabilities {
+            direct_transfer: true,
+            ..PluginCapabilities::SUPPORTED
+        },
+        ..get_host_info()
+    }
+}
+
+/// Ensures the plugin can be used with this version of the CLI.
+pub fn verify_plugin_handshake(handshake: &PluginHandshake) -> Result<(), ErrBox> {
+    verify_minimum_cli_version(&handshake.minimum_cli_version, CLI_VERSION)
+}
+
+fn verify_minimum_cli_version(minimum_cli_version: &Option<String>, cli_version: &str) -> Result<(), ErrBox> {
+    if let Some(minimum_cli_version) = minimum_cli_version {
+        if compare_versions(cli_version, minimum_cli_version) == Ordering::Less {
+            return err!(
+                "The plugin requires dprint {} or greater, but the current version is {}. Please upgrade dprint.",
+                minimum_cli_version,
+                cli_version,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Locks the directory a plugin is extracted to so that multiple processes
+/// don't set up or remove the same plugin version at the same time.
+pub fn lock_plugin_dir(plugin_dir_path: &Path, environment: &impl Environment) -> Result<FileLock, ErrBox> {
+    let mut lock_file_path = PathBuf::from(plugin_dir_path);
+    lock_file_path.set_extension("lock");
+    if let Some(parent_dir_path) = lock_file_path.parent() {
+        environment.mk_dir_all(parent_dir_path)?;
+    }
+    environment.lock_file(&lock_file_path)
+}
+
+/// Formats text for a plugin using another plugin. The plugin is picked using the language
+/// id or media type of the text when a plugin declares it and otherwise the file path.
 pub fn format_with_plugin_pool<TEnvironment: Environment>(
     parent_plugin_name: &str,
     file_path: &Path,
+    language: Option<&str>,
     file_text: &str,
     override_config: &ConfigKeyMap,
+    cancellation_token: &CancellationToken,
     pools: &PluginPools<TEnvironment>,
 ) -> Result<Option<String>, ErrBox> {
-    if let Some(sub_plugin_name) = pools.get_plugin_name_from_file_name(file_path) {
+    let language_plugin = language.and_then(|language| pools.get_plugin_from_language(language));
+    let (sub_plugin_name, file_path) = match language_plugin {
+        Some(language_plugin) => {
+            // give the plugin a file path it formats instead of the path of the file the text is embedded in
+            let file_path = match language_plugin.file_name {
+                Some(file_name) => Cow::Owned(file_path.with_file_name(file_name)),
+                None => Cow::Borrowed(file_path),
+            };
+            (Some(language_plugin.plugin_name), file_path)
+        },
+        None => (pools.get_plugin_name_from_file_text(file_path, file_text), Cow::Borrowed(file_path)),
+    };
+    // the file needs to be formatted again when the plugin used for the text changes
+    pools.record_host_format_plugin(sub_plugin_name.as_deref());
+    if let Some(sub_plugin_name) = sub_plugin_name {
+        let _chain_guard = HostFormatChainGuard::enter(parent_plugin_name, &sub_plugin_name, pools.max_host_format_depth())?;
         let initialized_plugin = pools.take_instance_for_plugin(&parent_plugin_name, &sub_plugin_name);
         match initialized_plugin {
             Ok(mut initialized_plugin) => {
-                let format_result = initialized_plugin.format_text(&file_path, &file_text, &override_config);
+                let format_result = initialized_plugin.format_text(&file_path, &file_text, &override_config, cancellation_token);
                 pools.release_instance_for_plugin(&parent_plugin_name, &sub_plugin_name, initialized_plugin);
-                let formatted_text = format_result?; // do this after releasing
+                // diagnostics are not surfaced for host formatting because their
+                // ranges are relative to the embedded text rather than the file
+                let formatted_text = format_result?.text; // do this after releasing
                 Ok(if formatted_text == file_text {
                     None // no change
                 } else {
@@ -31,3 +130,108 @@ pub fn format_with_plugin_pool<TEnvironment: Environment>(
         Ok(None) // no plugin, no change
     }
 }
+
+/// Adds the plugins of a host format request to the thread's chain, removing them when dropped.
+struct HostFormatChainGuard {
+    pushed_count: usize,
+}
+
+impl HostFormatChainGuard {
+    fn enter(parent_plugin_name: &str, sub_plugin_name: &str, max_depth: usize) -> Result<HostFormatChainGuard, ErrBox> {
+        HOST_FORMAT_CHAIN.with(|chain| {
+            let mut chain = chain.borrow_mut();
+            let mut pushed_count = 1;
+            if chain.is_empty() {
+                chain.push(parent_plugin_name.to_string());
+                pushed_count += 1;
+            }
+            chain.push(sub_plugin_name.to_string());
+
+            // the outermost plugin isn't a host format request
+            if chain.len() - 1 > max_depth {
+                let err = get_max_depth_error(&chain, max_depth);
+                let new_len = chain.len() - pushed_count;
+                chain.truncate(new_len);
+                return Err(err);
+            }
+
+            Ok(HostFormatChainGuard { pushed_count })
+        })
+    }
+}
+
+impl Drop for HostFormatChainGuard {
+    fn drop(&mut self) {
+        HOST_FORMAT_CHAIN.with(|chain| {
+            let mut chain = chain.borrow_mut();
+            let new_len = chain.len().saturating_sub(self.pushed_count);
+            chain.truncate(new_len);
+        });
+    }
+}
+
+fn get_max_depth_error(chain: &[String], max_depth: usize) -> ErrBox {
+    let chain_text = chain.join(" -> ");
+    let has_cycle = chain.iter().enumerate().any(|(i, name)| chain[..i].contains(name));
+    if has_cycle {
+        err_obj!(
+            "Plugins formatting each other's embedded code exceeded the maximum host format depth of {} and may be in a cycle: {}",
+            max_depth,
+            chain_text,
+        )
+    } else {
+        err_obj!("Exceeded the maximum host format depth of {}: {}", max_depth, chain_text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_verify_minimum_cli_version() {
+        assert_eq!(verify_minimum_cli_version(&None, "0.15.0").is_ok(), true);
+        assert_eq!(verify_minimum_cli_version(&Some("0.15.0".to_string()), "0.15.0").is_ok(), true);
+        assert_eq!(verify_minimum_cli_version(&Some("0.14.2".to_string()), "0.15.0").is_ok(), true);
+        assert_eq!(
+            verify_minimum_cli_version(&Some("0.16.0".to_string()), "0.15.0").err().unwrap().to_string(),
+            "The plugin requires dprint 0.16.0 or greater, but the current version is 0.15.0. Please upgrade dprint."
+        );
+    }
+
+    #[test]
+    fn it_should_track_host_format_chain() {
+        {
+            let _guard1 = HostFormatChainGuard::enter("markdown", "typescript", 2).unwrap();
+            assert_eq!(get_chain(), vec!["markdown", "typescript"]);
+            {
+                let _guard2 = HostFormatChainGuard::enter("typescript", "json", 2).unwrap();
+                assert_eq!(get_chain(), vec!["markdown", "typescript", "json"]);
+                assert_eq!(
+                    HostFormatChainGuard::enter("json", "toml", 2).err().unwrap().to_string(),
+                    "Exceeded the maximum host format depth of 2: markdown -> typescript -> json -> toml"
+                );
+                assert_eq!(get_chain(), vec!["markdown", "typescript", "json"]);
+            }
+            assert_eq!(get_chain(), vec!["markdown", "typescript"]);
+        }
+        assert_eq!(get_chain().len(), 0);
+    }
+
+    #[test]
+    fn it_should_describe_host_format_cycle() {
+        let _guard1 = HostFormatChainGuard::enter("markdown", "typescript", 2).unwrap();
+        let _guard2 = HostFormatChainGuard::enter("typescript", "markdown", 2).unwrap();
+        assert_eq!(
+            HostFormatChainGuard::enter("markdown", "typescript", 2).err().unwrap().to_string(),
+            concat!(
+                "Plugins formatting each other's embedded code exceeded the maximum host format depth of 2 and may be in a cycle: ",
+                "markdown -> typescript -> markdown -> typescript"
+            )
+        );
+    }
+
+    fn get_chain() -> Vec<String> {
+        HOST_FORMAT_CHAIN.with(|chain| chain.borrow().clone())
+    }
+}
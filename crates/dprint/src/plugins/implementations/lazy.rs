@@ -0,0 +1,189 @@
+use std::sync::Arc;
+use parking_lot::Mutex;
+
+use dprint_core::configuration::{GlobalConfiguration, ConfigKeyMap};
+use dprint_core::plugins::PluginInfo;
+use dprint_core::types::ErrBox;
+
+use crate::environment::Environment;
+use crate::plugins::{Plugin, InitializedPlugin, PluginSourceReference, PluginCache, PluginCacheItem, PluginPools};
+use crate::utils::get_bytes_hash;
+use super::{get_plugin_cache_item, load_plugin};
+
+/// A plugin that's described by its cached or locked information and only downloaded,
+/// set up, and loaded (ex. compiled or opened) the first time an instance is initialized.
+///
+/// This way plugins that don't have any files to format in a run cost nothing.
+pub struct LazyPlugin<TEnvironment: Environment> {
+    plugin_pools: Arc<PluginPools<TEnvironment>>,
+    plugin_cache: Arc<PluginCache<TEnvironment>>,
+    environment: TEnvironment,
+    plugin_reference: PluginSourceReference,
+    info: PluginInfo,
+    file_hash: Option<u64>,
+    /// This is `None` when the plugin is described by the lockfile and not yet cached.
+    cache_item: Option<PluginCacheItem>,
+    config: Option<(ConfigKeyMap, GlobalConfiguration)>,
+    loaded_plugin: Mutex<Option<Arc<dyn Plugin>>>,
+}
+
+impl<TEnvironment: Environment> LazyPlugin<TEnvironment> {
+    pub fn new(
+        plugin_pools: Arc<PluginPools<TEnvironment>>,
+        plugin_cache: Arc<PluginCache<TEnvironment>>,
+        environment: TEnvironment,
+        plugin_reference: PluginSourceReference,
+        cache_item: PluginCacheItem,
+    ) -> Self {
+        let file_hash = get_file_hash(&cache_item.checksum, cache_item.file_hash);
+        LazyPlugin {
+            plugin_pools,
+            plugin_cache,
+            environment,
+            plugin_reference,
+            info: cache_item.info.clone(),
+            file_hash,
+            cache_item: Some(cache_item),
+            config: None,
+            loaded_plugin: Mutex::new(None),
+        }
+    }
+
+    /// Creates a plugin from the information recorded in the lockfile. The
+    /// plugin is downloaded and set up once it's initialized.
+    pub fn new_locked(
+        plugin_pools: Arc<PluginPools<TEnvironment>>,
+        plugin_cache: Arc<PluginCache<TEnvironment>>,
+        environment: TEnvironment,
+        plugin_reference: PluginSourceReference,
+        locked_info: PluginInfo,
+    ) -> Self {
+        let file_hash = get_file_hash(&plugin_reference.checksum, None);
+        LazyPlugin {
+            plugin_pools,
+            plugin_cache,
+            environment,
+            plugin_reference,
+            info: locked_info,
+            file_hash,
+            cache_item: None,
+            config: None,
+            loaded_plugin: Mutex::new(None),
+        }
+    }
+
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn get_cache_item(&self) -> Result<PluginCacheItem, ErrBox> {
+        if let Some(cache_item) = &self.cache_item {
+            return Ok(cache_item.clone());
+        }
+
+        let cache_item = get_plugin_cache_item(&self.plugin_cache, &self.environment, &self.plugin_reference)?;
+        if cache_item.info.name != self.info.name || cache_item.info.version != self.info.version || cache_item.info.config_key != self.info.config_key {
+            return err!(
+                "Expected the plugin to be {} {} as recorded in the lockfile, but it was {} {}.",
+                self.info.name,
+                self.info.version,
+                cache_item.info.name,
+                cache_item.info.version,
+            );
+        }
+        Ok(cache_item)
+    }
+
+    fn get_loaded_plugin(&self) -> Result<Arc<dyn Plugin>, ErrBox> {
+        // hold the lock while loading so that the plugin is only loaded once
+        let mut loaded_plugin = self.loaded_plugin.lock();
+        if let Some(plugin) = loaded_plugin.as_ref() {
+            return Ok(plugin.clone());
+        }
+
+        log_verbose!(self.environment, "Loading plugin {}", self.name());
+        let mut plugin = match self.get_cache_item().and_then(|cache_item| load_plugin(
+            self.plugin_pools.clone(),
+            &self.plugin_cache,
+            self.environment.clone(),
+            &self.plugin_reference,
+            cache_item,
+        )) {
+            Ok(plugin) => plugin,
+            Err(err) => {
+                if let Err(inner_err) = self.plugin_cache.forget(&self.plugin_reference) {
+                    return err!("Error loading plugin {} and forgetting from cache: {}\n{}", self.plugin_reference.display(), err, inner_err);
+                }
+                return err!("Error loading plugin {}: {}", self.plugin_reference.display(), err);
+            }
+        };
+        let (plugin_config, global_config) = self.get_config().clone();
+        plugin.set_config(plugin_config, global_config);
+
+        let plugin: Arc<dyn Plugin> = Arc::from(plugin);
+        loaded_plugin.replace(plugin.clone());
+        Ok(plugin)
+    }
+}
+
+/// Prefers the checksum since it's known for plugins that aren't cached
+/// yet, which keeps the plugin's hash the same once it's cached.
+fn get_file_hash(checksum: &Option<String>, file_hash: Option<u64>) -> Option<u64> {
+    checksum.as_ref().map(|checksum| get_bytes_hash(checksum.as_bytes())).or(file_hash)
+}
+
+impl<TEnvironment: Environment> Plugin for LazyPlugin<TEnvironment> {
+    fn name(&self) -> &str {
+        &self.info().name
+    }
+
+    fn version(&self) -> &str {
+        &self.info().version
+    }
+
+    fn config_key(&self) -> &str {
+        &self.info().config_key
+    }
+
+    fn file_extensions(&self) -> &Vec<String> {
+        &self.info().file_extensions
+    }
+
+    fn file_names(&self) -> &Vec<String> {
+        &self.info().file_names
+    }
+
+    fn interpreters(&self) -> &Vec<String> {
+        &self.info().interpreters
+    }
+
+    fn languages(&self) -> &Vec<String> {
+        &self.info().languages
+    }
+
+    fn file_hash(&self) -> Option<u64> {
+        self.file_hash
+    }
+
+    fn help_url(&self) -> &str {
+        &self.info().help_url
+    }
+
+    fn config_schema_url(&self) -> &str {
+        &self.info().config_schema_url
+    }
+
+    fn set_config(&mut self, plugin_config: ConfigKeyMap, global_config: GlobalConfiguration) {
+        // a plugin that was already loaded keeps its configuration, so drop it to use the new one
+        self.loaded_plugin.lock().take();
+        self.config = Some((plugin_config, global_config));
+    }
+
+    fn get_config(&self) -> &(ConfigKeyMap, GlobalConfiguration) {
+        self.config.as_ref().expect("Call set_config first.")
+    }
+
+    fn initialize(&self) -> Result<Box<dyn InitializedPlugin>, ErrBox> {
+        self.get_loaded_plugin()?.initialize()
+    }
+}
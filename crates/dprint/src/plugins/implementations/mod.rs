@@ -1,4 +1,6 @@
 mod common;
+mod lazy;
+mod native;
 mod process;
 mod public;
 mod wasm;
@@ -0,0 +1,279 @@
+use std::ffi::c_void;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use dprint_core::configuration::{ConfigurationDiagnostic, GlobalConfiguration, ConfigKeyMap};
+use dprint_core::plugins::{PluginInfo, FormatTextResult, HostInfo, PluginHandshake};
+use dprint_core::plugins::native::{export_names, NATIVE_PLUGIN_ABI_VERSION, NativeSlice, NativeBuffer, NativeHost, NativeResult, NativeFormatResult};
+use dprint_core::types::ErrBox;
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type CreateFn = unsafe extern "C" fn() -> *mut c_void;
+type DestroyFn = unsafe extern "C" fn(instance: *mut c_void);
+type FreeBufferFn = unsafe extern "C" fn(buffer: NativeBuffer);
+type InputFn = unsafe extern "C" fn(instance: *mut c_void, input: NativeSlice, output: *mut NativeBuffer) -> u32;
+type OutputFn = unsafe extern "C" fn(instance: *mut c_void, output: *mut NativeBuffer) -> u32;
+type FormatTextFn = unsafe extern "C" fn(
+    instance: *mut c_void,
+    file_path: NativeSlice,
+    file_text: NativeSlice,
+    override_config: NativeSlice,
+    host: *const NativeHost,
+    output: *mut NativeBuffer,
+    diagnostics_output: *mut NativeBuffer,
+) -> u32;
+
+/// A loaded native plugin dynamic library.
+pub struct NativePluginLibrary {
+    create: CreateFn,
+    destroy: DestroyFn,
+    free_buffer: FreeBufferFn,
+    handshake: InputFn,
+    get_plugin_info: OutputFn,
+    get_license_text: OutputFn,
+    set_global_config: InputFn,
+    set_plugin_config: InputFn,
+    get_resolved_config: OutputFn,
+    get_config_diagnostics: OutputFn,
+    format_text: FormatTextFn,
+    _library: libloading::Library,
+}
+
+impl NativePluginLibrary {
+    pub fn load(file_path: &Path) -> Result<Arc<NativePluginLibrary>, ErrBox> {
+        // loading a library runs its initialization code, which is why native plugins require a checksum
+        let library = match unsafe { libloading::Library::new(file_path) } {
+            Ok(library) => library,
+            Err(err) => return err!("Error loading native plugin at {}: {}", file_path.display(), err.to_string()),
+        };
+
+        unsafe {
+            let abi_version = get_symbol::<AbiVersionFn>(&library, export_names::ABI_VERSION)?();
+            if abi_version != NATIVE_PLUGIN_ABI_VERSION {
+                return err!(
+                    "The native plugin ABI version {} is not supported (expected {}). This may indicate you need to upgrade your CLI version or plugin.",
+                    abi_version,
+                    NATIVE_PLUGIN_ABI_VERSION,
+                );
+            }
+
+            Ok(Arc::new(NativePluginLibrary {
+                create: get_symbol(&library, export_names::CREATE)?,
+                destroy: get_symbol(&library, export_names::DESTROY)?,
+                free_buffer: get_symbol(&library, export_names::FREE_BUFFER)?,
+                handshake: get_symbol(&library, export_names::HANDSHAKE)?,
+                get_plugin_info: get_symbol(&library, export_names::GET_PLUGIN_INFO)?,
+                get_license_text: get_symbol(&library, export_names::GET_LICENSE_TEXT)?,
+                set_global_config: get_symbol(&library, export_names::SET_GLOBAL_CONFIG)?,
+                set_plugin_config: get_symbol(&library, export_names::SET_PLUGIN_CONFIG)?,
+                get_resolved_config: get_symbol(&library, export_names::GET_RESOLVED_CONFIG)?,
+                get_config_diagnostics: get_symbol(&library, export_names::GET_CONFIG_DIAGNOSTICS)?,
+                format_text: get_symbol(&library, export_names::FORMAT_TEXT)?,
+                _library: library,
+            }))
+        }
+    }
+}
+
+unsafe fn get_symbol<T: Copy>(library: &libloading::Library, name: &[u8]) -> Result<T, ErrBox> {
+    match library.get::<T>(name) {
+        Ok(symbol) => Ok(*symbol),
+        Err(err) => err!("Error getting {} from native plugin: {}", String::from_utf8_lossy(name), err.to_string()),
+    }
+}
+
+/// An instance of a plugin created by a native plugin library.
+pub struct NativePluginInstance {
+    library: Arc<NativePluginLibrary>,
+    instance: *mut c_void,
+}
+
+// Instances are only used by one thread at a time. The plugin is required to
+// not share state between instances that isn't thread safe.
+unsafe impl Send for NativePluginInstance {}
+
+impl Drop for NativePluginInstance {
+    fn drop(&mut self) {
+        unsafe { (self.library.destroy)(self.instance) };
+    }
+}
+
+impl NativePluginInstance {
+    pub fn new(library: Arc<NativePluginLibrary>) -> Result<Self, ErrBox> {
+        let instance = unsafe { (library.create)() };
+        if instance.is_null() {
+            return err!("The native plugin failed to create an instance.");
+        }
+        Ok(NativePluginInstance { library, instance })
+    }
+
+    /// Creates an instance and initializes it with the provided configuration.
+    pub fn new_with_config(library: Arc<NativePluginLibrary>, host_info: &HostInfo, config: &(ConfigKeyMap, GlobalConfiguration)) -> Result<(Self, PluginHandshake), ErrBox> {
+        let instance = NativePluginInstance::new(library)?;
+        let handshake = instance.handshake(host_info)?;
+        instance.set_global_config(&config.1)?;
+        instance.set_plugin_config(&config.0)?;
+        Ok((instance, handshake))
+    }
+
+    pub fn handshake(&self, host_info: &HostInfo) -> Result<PluginHandshake, ErrBox> {
+        let bytes = self.call_with_input(self.library.handshake, &serde_json::to_vec(host_info)?)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub fn get_plugin_info(&self) -> Result<PluginInfo, ErrBox> {
+        let bytes = self.call(self.library.get_plugin_info)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub fn get_license_text(&self) -> Result<String, ErrBox> {
+        Ok(String::from_utf8(self.call(self.library.get_license_text)?)?)
+    }
+
+    pub fn set_global_config(&self, global_config: &GlobalConfiguration) -> Result<(), ErrBox> {
+        self.call_with_input(self.library.set_global_config, &serde_json::to_vec(global_config)?)?;
+        Ok(())
+    }
+
+    pub fn set_plugin_config(&self, plugin_config: &ConfigKeyMap) -> Result<(), ErrBox> {
+        self.call_with_input(self.library.set_plugin_config, &serde_json::to_vec(plugin_config)?)?;
+        Ok(())
+    }
+
+    pub fn get_resolved_config(&self) -> Result<String, ErrBox> {
+        Ok(String::from_utf8(self.call(self.library.get_resolved_config)?)?)
+    }
+
+    pub fn get_config_diagnostics(&self) -> Result<Vec<ConfigurationDiagnostic>, ErrBox> {
+        let bytes = self.call(self.library.get_config_diagnostics)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub fn format_text(
+        &mut self,
+        file_path: &Path,
+        file_text: &str,
+        override_config: &ConfigKeyMap,
+        format_with_host: &dyn Fn(PathBuf, Option<String>, String, ConfigKeyMap) -> Result<Option<String>, ErrBox>,
+    ) -> Result<FormatTextResult, ErrBox> {
+        let file_path_text = file_path.to_string_lossy();
+        let override_config = if override_config.is_empty() {
+            Vec::new()
+        } else {
+            serde_json::to_vec(override_config)?
+        };
+        let mut host_context = HostFormatContext { format_with_host };
+        let host = NativeHost {
+            context: &mut host_context as *mut HostFormatContext as *mut c_void,
+            format: host_format,
+            free_buffer: host_free_buffer,
+            format_with_language: host_format_with_language,
+        };
+        let mut output = NativeBuffer::empty();
+        let mut diagnostics_output = NativeBuffer::empty();
+        let result = unsafe {
+            (self.library.format_text)(
+                self.instance,
+                NativeSlice::new(file_path_text.as_bytes()),
+                NativeSlice::new(file_text.as_bytes()),
+                NativeSlice::new(&override_config),
+                &host,
+                &mut output,
+                &mut diagnostics_output,
+            )
+        };
+        let output = self.take_buffer(output);
+        let diagnostics = self.take_buffer(diagnostics_output);
+
+        let text = match result.into() {
+            NativeFormatResult::NoChange => file_text.to_string(),
+            NativeFormatResult::Change => String::from_utf8(output)?,
+            NativeFormatResult::Error => return err!("{}", String::from_utf8_lossy(&output)),
+        };
+        Ok(FormatTextResult {
+            text,
+            diagnostics: if diagnostics.is_empty() {
+                Vec::new()
+            } else {
+                serde_json::from_slice(&diagnostics)?
+            },
+        })
+    }
+
+    fn call(&self, func: OutputFn) -> Result<Vec<u8>, ErrBox> {
+        let mut output = NativeBuffer::empty();
+        let result = unsafe { func(self.instance, &mut output) };
+        self.get_call_result(result, output)
+    }
+
+    fn call_with_input(&self, func: InputFn, input: &[u8]) -> Result<Vec<u8>, ErrBox> {
+        let mut output = NativeBuffer::empty();
+        let result = unsafe { func(self.instance, NativeSlice::new(input), &mut output) };
+        self.get_call_result(result, output)
+    }
+
+    fn get_call_result(&self, result: u32, output: NativeBuffer) -> Result<Vec<u8>, ErrBox> {
+        let bytes = self.take_buffer(output);
+        match result.into() {
+            NativeResult::Success => Ok(bytes),
+            NativeResult::Error => err!("{}", String::from_utf8_lossy(&bytes)),
+        }
+    }
+
+    /// Copies a buffer allocated by the plugin, then gives it back to the plugin to be freed.
+    fn take_buffer(&self, buffer: NativeBuffer) -> Vec<u8> {
+        let bytes = unsafe { buffer.as_bytes() }.to_vec();
+        unsafe { (self.library.free_buffer)(buffer) };
+        bytes
+    }
+}
+
+struct HostFormatContext<'a> {
+    format_with_host: &'a dyn Fn(PathBuf, Option<String>, String, ConfigKeyMap) -> Result<Option<String>, ErrBox>,
+}
+
+unsafe extern "C" fn host_format(
+    context: *mut c_void,
+    file_path: NativeSlice,
+    file_text: NativeSlice,
+    override_config: NativeSlice,
+    output: *mut NativeBuffer,
+) -> u32 {
+    host_format_with_language(context, file_path, NativeSlice::new(&[]), file_text, override_config, output)
+}
+
+unsafe extern "C" fn host_format_with_language(
+    context: *mut c_void,
+    file_path: NativeSlice,
+    language: NativeSlice,
+    file_text: NativeSlice,
+    override_config: NativeSlice,
+    output: *mut NativeBuffer,
+) -> u32 {
+    let context = &*(context as *const HostFormatContext);
+    let result = catch_unwind(AssertUnwindSafe(|| -> Result<Option<String>, ErrBox> {
+        let file_path = PathBuf::from(String::from_utf8(file_path.as_bytes().to_vec())?);
+        let language = String::from_utf8(language.as_bytes().to_vec())?;
+        let language = if language.is_empty() { None } else { Some(language) };
+        let file_text = String::from_utf8(file_text.as_bytes().to_vec())?;
+        let override_config = override_config.as_bytes();
+        let override_config = if override_config.is_empty() {
+            ConfigKeyMap::new()
+        } else {
+            serde_json::from_slice(override_config)?
+        };
+        (context.format_with_host)(file_path, language, file_text, override_config)
+    }));
+    let (result, bytes) = match result {
+        Ok(Ok(None)) => (NativeFormatResult::NoChange, Vec::new()),
+        Ok(Ok(Some(formatted_text))) => (NativeFormatResult::Change, formatted_text.into_bytes()),
+        Ok(Err(err)) => (NativeFormatResult::Error, err.to_string().into_bytes()),
+        Err(_) => (NativeFormatResult::Error, b"Panicked while formatting with the host.".to_vec()),
+    };
+    output.write(NativeBuffer::from_vec(bytes));
+    result as u32
+}
+
+unsafe extern "C" fn host_free_buffer(buffer: NativeBuffer) {
+    drop(buffer.into_vec());
+}
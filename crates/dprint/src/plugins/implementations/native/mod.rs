@@ -0,0 +1,7 @@
+mod library;
+mod plugin;
+mod setup_native_plugin;
+
+use library::*;
+pub use plugin::*;
+pub use setup_native_plugin::*;
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use dprint_core::configuration::{ConfigurationDiagnostic, GlobalConfiguration, ConfigKeyMap};
+use dprint_core::plugins::{PluginInfo, FormatTextResult, CancellationToken};
+use dprint_core::types::ErrBox;
+
+use crate::environment::Environment;
+use crate::plugins::{Plugin, InitializedPlugin, PluginPools};
+
+use super::{NativePluginLibrary, NativePluginInstance};
+use super::super::{format_with_plugin_pool, get_host_info, verify_plugin_handshake};
+
+pub struct NativePlugin<TEnvironment: Environment> {
+    library: Arc<NativePluginLibrary>,
+    plugin_info: PluginInfo,
+    file_hash: Option<u64>,
+    config: Option<(ConfigKeyMap, GlobalConfiguration)>,
+    plugin_pools: Arc<PluginPools<TEnvironment>>,
+}
+
+impl<TEnvironment: Environment> NativePlugin<TEnvironment> {
+    pub fn new(
+        library_file_path: &Path,
+        plugin_info: PluginInfo,
+        file_hash: Option<u64>,
+        plugin_pools: Arc<PluginPools<TEnvironment>>,
+    ) -> Result<Self, ErrBox> {
+        let library = NativePluginLibrary::load(library_file_path)?;
+        Ok(NativePlugin {
+            library,
+            plugin_info,
+            file_hash,
+            config: None,
+            plugin_pools,
+        })
+    }
+}
+
+impl<TEnvironment: Environment> Plugin for NativePlugin<TEnvironment> {
+    fn name(&self) -> &str {
+        &self.plugin_info.name
+    }
+
+    fn version(&self) -> &str {
+        &self.plugin_info.version
+    }
+
+    fn config_key(&self) -> &str {
+        &self.plugin_info.config_key
+    }
+
+    fn file_extensions(&self) -> &Vec<String> {
+        &self.plugin_info.file_extensions
+    }
+
+    fn file_names(&self) -> &Vec<String> {
+        &self.plugin_info.file_names
+    }
+
+    fn interpreters(&self) -> &Vec<String> {
+        &self.plugin_info.interpreters
+    }
+
+    fn languages(&self) -> &Vec<String> {
+        &self.plugin_info.languages
+    }
+
+    fn file_hash(&self) -> Option<u64> {
+        self.file_hash
+    }
+
+    fn help_url(&self) -> &str {
+        &self.plugin_info.help_url
+    }
+
+    fn config_schema_url(&self) -> &str {
+        &self.plugin_info.config_schema_url
+    }
+
+    fn set_config(&mut self, plugin_config: ConfigKeyMap, global_config: GlobalConfiguration) {
+        self.config = Some((plugin_config, global_config));
+    }
+
+    fn get_config(&self) -> &(ConfigKeyMap, GlobalConfiguration) {
+        self.config.as_ref().expect("Call set_config first.")
+    }
+
+    fn initialize(&self) -> Result<Box<dyn InitializedPlugin>, ErrBox> {
+        let config = self.config.as_ref().expect("Call set_config first.");
+        let (instance, handshake) = NativePluginInstance::new_with_config(self.library.clone(), &get_host_info(), config)?;
+        verify_plugin_handshake(&handshake)?;
+
+        Ok(Box::new(InitializedNativePlugin {
+            name: self.name().to_string(),
+            instance,
+            plugin_pools: self.plugin_pools.clone(),
+        }))
+    }
+}
+
+pub struct InitializedNativePlugin<TEnvironment: Environment> {
+    name: String,
+    instance: NativePluginInstance,
+    plugin_pools: Arc<PluginPools<TEnvironment>>,
+}
+
+impl<TEnvironment: Environment> InitializedPlugin for InitializedNativePlugin<TEnvironment> {
+    fn get_license_text(&self) -> Result<String, ErrBox> {
+        self.instance.get_license_text()
+    }
+
+    fn get_resolved_config(&self) -> Result<String, ErrBox> {
+        self.instance.get_resolved_config()
+    }
+
+    fn get_config_diagnostics(&self) -> Result<Vec<ConfigurationDiagnostic>, ErrBox> {
+        self.instance.get_config_diagnostics()
+    }
+
+    fn format_text(&mut self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap, cancellation_token: &CancellationToken) -> Result<FormatTextResult, ErrBox> {
+        // native plugins run in process, so they can only be cancelled before starting
+        if cancellation_token.is_cancelled() {
+            return err!("The format request was cancelled.");
+        }
+
+        let name = &self.name;
+        let plugin_pools = &self.plugin_pools;
+        self.instance.format_text(file_path, file_text, override_config, &|file_path: PathBuf, language: Option<String>, file_text: String, override_config: ConfigKeyMap| {
+            format_with_plugin_pool(name, &file_path, language.as_deref(), &file_text, &override_config, cancellation_token, plugin_pools)
+        })
+    }
+}
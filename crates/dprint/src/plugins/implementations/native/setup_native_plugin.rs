@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+use dprint_core::plugins::PluginInfo;
+use dprint_core::types::ErrBox;
+
+use crate::environment::Environment;
+use crate::utils::{PathSource, extract_zip};
+
+use super::super::{SetupPluginResult, get_host_info, lock_plugin_dir, verify_plugin_handshake};
+use super::super::process::get_plugin_zip_bytes;
+use super::{NativePluginLibrary, NativePluginInstance};
+
+pub fn get_file_path_from_plugin_info(plugin_info: &PluginInfo, environment: &impl Environment) -> PathBuf {
+    let dir_path = get_plugin_dir_path(&plugin_info.name, &plugin_info.version, environment);
+    get_plugin_library_file_path(&dir_path, &plugin_info.name)
+}
+
+fn get_plugin_dir_path(name: &str, version: &str, environment: &impl Environment) -> PathBuf {
+    let cache_dir_path = environment.get_cache_dir();
+    cache_dir_path.join("plugins").join(&name).join(&version)
+}
+
+/// Gets the file name cargo gives a cdylib for the crate (ex. libdprint_plugin_example.so)
+fn get_plugin_library_file_path(dir_path: &Path, plugin_name: &str) -> PathBuf {
+    dir_path.join(get_library_file_name(plugin_name))
+}
+
+fn get_library_file_name(plugin_name: &str) -> String {
+    format!("{}{}{}", std::env::consts::DLL_PREFIX, plugin_name.replace('-', "_"), std::env::consts::DLL_SUFFIX)
+}
+
+/// Use this to get a library file path that also works in the tests.
+pub fn get_test_safe_library_path(library_file_path: PathBuf, environment: &impl Environment) -> PathBuf {
+    if environment.is_real() {
+        library_file_path
+    } else {
+        // the test environment's file system is in memory, so load the built test plugin
+        PathBuf::from("../../target/release").join(get_library_file_name("test-native-plugin"))
+    }
+}
+
+/// Takes a url or file path and extracts the plugin to a cache folder.
+/// Returns the library file path once complete
+pub fn setup_native_plugin(
+    url_or_file_path: &PathSource,
+    plugin_file_bytes: &[u8],
+    fetch_bytes: &dyn Fn(&PathSource) -> Result<Vec<u8>, ErrBox>,
+    environment: &impl Environment,
+) -> Result<SetupPluginResult, ErrBox> {
+    let plugin_zip_bytes = get_plugin_zip_bytes(url_or_file_path, plugin_file_bytes, fetch_bytes)?;
+    let plugin_cache_dir_path = get_plugin_dir_path(&plugin_zip_bytes.name, &plugin_zip_bytes.version, environment);
+    let _dir_lock = lock_plugin_dir(&plugin_cache_dir_path, environment)?;
+
+    let result = setup_inner(&plugin_cache_dir_path, plugin_zip_bytes.name, &plugin_zip_bytes.zip_bytes, environment);
+
+    return match result {
+        Ok(result) => Ok(result),
+        Err(err) => {
+            // failed, so delete the dir if it exists
+            let _ignore = environment.remove_dir_all(&plugin_cache_dir_path);
+            Err(err)
+        }
+    };
+
+    fn setup_inner<TEnvironment: Environment>(
+        plugin_cache_dir_path: &Path,
+        plugin_name: String,
+        zip_bytes: &[u8],
+        environment: &TEnvironment,
+    ) -> Result<SetupPluginResult, ErrBox> {
+        if environment.path_exists(plugin_cache_dir_path) {
+            environment.remove_dir_all(plugin_cache_dir_path)?;
+        }
+
+        extract_zip(&format!("Extracting zip for {}", plugin_name), &zip_bytes, &plugin_cache_dir_path, environment)?;
+
+        let plugin_library_file_path = get_plugin_library_file_path(plugin_cache_dir_path, &plugin_name);
+        if !environment.path_exists(&plugin_library_file_path) {
+            return err!("Plugin zip file did not contain required library at: {}", plugin_library_file_path.display());
+        }
+
+        let library_path = get_test_safe_library_path(plugin_library_file_path.clone(), environment);
+        let library = NativePluginLibrary::load(&library_path)?;
+        let instance = NativePluginInstance::new(library)?;
+        verify_plugin_handshake(&instance.handshake(&get_host_info())?)?;
+        let plugin_info = instance.get_plugin_info()?;
+
+        Ok(SetupPluginResult {
+            plugin_info,
+            file_path: plugin_library_file_path,
+        })
+    }
+}
+
+pub fn cleanup_native_plugin(plugin_info: &PluginInfo, environment: &impl Environment) -> Result<(), ErrBox> {
+    let plugin_cache_dir_path = get_plugin_dir_path(&plugin_info.name, &plugin_info.version, environment);
+    let _dir_lock = lock_plugin_dir(&plugin_cache_dir_path, environment)?;
+    environment.remove_dir_all(&plugin_cache_dir_path)?;
+    Ok(())
+}
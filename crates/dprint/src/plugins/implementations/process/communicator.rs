@@ -1,9 +1,21 @@
 use std::cell::RefCell;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use dprint_core::configuration::{ConfigurationDiagnostic, GlobalConfiguration, ConfigKeyMap};
-use dprint_core::plugins::process::ProcessPluginCommunicator;
+use dprint_core::plugins::{FormatTextResult, CancellationToken};
+use dprint_core::plugins::process::{ProcessPluginCommunicator, MultiplexedProcessPluginCommunicator};
 use dprint_core::types::ErrBox;
 use crate::environment::Environment;
+use super::super::{get_process_host_info, verify_plugin_handshake};
+
+/// A multiplexed communicator shared by all the initialized instances of a process plugin.
+pub type SharedMultiplexedCommunicator = Arc<Mutex<Option<Arc<MultiplexedProcessPluginCommunicator>>>>;
+
+enum Communicator {
+    Single(ProcessPluginCommunicator),
+    /// The process may handle many requests at once, so one process is shared between the instances.
+    Multiplexed(Arc<MultiplexedProcessPluginCommunicator>),
+}
 
 /// A communicator that can recreate the process if it's unresponsive
 /// and initializes the plugin with the configuration on each startup.
@@ -12,7 +24,8 @@ pub struct InitializedProcessPluginCommunicator<TEnvironment: Environment> {
     plugin_name: String,
     executable_file_path: PathBuf,
     config: (ConfigKeyMap, GlobalConfiguration),
-    communicator: RefCell<ProcessPluginCommunicator>,
+    shared_communicator: SharedMultiplexedCommunicator,
+    communicator: RefCell<Communicator>,
 }
 
 impl<TEnvironment: Environment> InitializedProcessPluginCommunicator<TEnvironment> {
@@ -21,13 +34,27 @@ impl<TEnvironment: Environment> InitializedProcessPluginCommunicator<TEnvironmen
         plugin_name: String,
         executable_file_path: PathBuf,
         config: (ConfigKeyMap, GlobalConfiguration),
+        shared_communicator: SharedMultiplexedCommunicator,
     ) -> Result<Self, ErrBox> {
-        let communicator = create_new_communicator(environment.clone(), plugin_name.clone(), &executable_file_path, &config)?;
+        let communicator = {
+            let mut shared_communicator = shared_communicator.lock().unwrap();
+            match shared_communicator.as_ref() {
+                Some(communicator) if !communicator.has_exited() => Communicator::Multiplexed(communicator.clone()),
+                _ => {
+                    let communicator = create_new_communicator(environment.clone(), plugin_name.clone(), &executable_file_path, &config)?;
+                    if let Communicator::Multiplexed(communicator) = &communicator {
+                        *shared_communicator = Some(communicator.clone());
+                    }
+                    communicator
+                }
+            }
+        };
         let initialized_communicator = InitializedProcessPluginCommunicator {
             environment,
             plugin_name,
             executable_file_path,
             config,
+            shared_communicator,
             communicator: RefCell::new(communicator),
         };
 
@@ -35,19 +62,31 @@ impl<TEnvironment: Environment> InitializedProcessPluginCommunicator<TEnvironmen
     }
 
     pub fn get_license_text(&self) -> Result<String, ErrBox> {
-        self.communicator.borrow_mut().get_license_text()
+        match &mut *self.communicator.borrow_mut() {
+            Communicator::Single(communicator) => communicator.get_license_text(),
+            Communicator::Multiplexed(communicator) => communicator.get_license_text(),
+        }
     }
 
     pub fn get_resolved_config(&self) -> Result<String, ErrBox> {
-        self.communicator.borrow_mut().get_resolved_config()
+        match &mut *self.communicator.borrow_mut() {
+            Communicator::Single(communicator) => communicator.get_resolved_config(),
+            Communicator::Multiplexed(communicator) => communicator.get_resolved_config(),
+        }
     }
 
     pub fn get_config_diagnostics(&self) -> Result<Vec<ConfigurationDiagnostic>, ErrBox> {
-        self.communicator.borrow_mut().get_config_diagnostics()
+        match &mut *self.communicator.borrow_mut() {
+            Communicator::Single(communicator) => communicator.get_config_diagnostics(),
+            Communicator::Multiplexed(communicator) => communicator.get_config_diagnostics(),
+        }
     }
 
     pub fn recreate_process_if_dead(&self) -> Result<bool, ErrBox> {
-        let is_process_alive = { self.communicator.borrow_mut().is_process_alive() };
+        let is_process_alive = match &mut *self.communicator.borrow_mut() {
+            Communicator::Single(communicator) => communicator.is_process_alive(),
+            Communicator::Multiplexed(communicator) => communicator.is_process_alive(),
+        };
         if is_process_alive {
             Ok(false)
         } else {
@@ -57,19 +96,50 @@ impl<TEnvironment: Environment> InitializedProcessPluginCommunicator<TEnvironmen
     }
 
     pub fn force_recreate_process(&self) -> Result<(), ErrBox> {
+        let mut communicator = self.communicator.borrow_mut();
+        let mut shared_communicator = self.shared_communicator.lock().unwrap();
+
+        // another instance may have already recreated the shared process
+        if let (Communicator::Multiplexed(current), Some(shared)) = (&*communicator, shared_communicator.as_ref()) {
+            if !Arc::ptr_eq(current, shared) && !shared.has_exited() {
+                *communicator = Communicator::Multiplexed(shared.clone());
+                return Ok(());
+            }
+        }
+
         let new_communicator = create_new_communicator(
             self.environment.clone(),
             self.plugin_name.clone(),
             &self.executable_file_path,
             &self.config
         )?;
-        let mut communicator = self.communicator.borrow_mut();
+        if let Communicator::Multiplexed(new_communicator) = &new_communicator {
+            *shared_communicator = Some(new_communicator.clone());
+        }
         *communicator = new_communicator;
         Ok(())
     }
 
-    pub fn format_text(&self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap, format_with_host: impl Fn(PathBuf, String, ConfigKeyMap) -> Result<Option<String>, ErrBox>) -> Result<String, ErrBox> {
-        self.communicator.borrow_mut().format_text(file_path, file_text, override_config, format_with_host)
+    pub fn format_text(
+        &self,
+        file_path: &Path,
+        file_text: &str,
+        override_config: &ConfigKeyMap,
+        cancellation_token: &CancellationToken,
+        format_with_host: impl Fn(PathBuf, Option<String>, String, ConfigKeyMap) -> Result<Option<String>, ErrBox>,
+    ) -> Result<FormatTextResult, ErrBox> {
+        let communicator = match &mut *self.communicator.borrow_mut() {
+            Communicator::Single(communicator) => {
+                // requests can't be cancelled once sent in the request/response mode
+                if cancellation_token.is_cancelled() {
+                    return err!("The format request was cancelled.");
+                }
+                return communicator.format_text(file_path, file_text, override_config, format_with_host);
+            },
+            Communicator::Multiplexed(communicator) => communicator.clone(),
+        };
+        // don't hold the borrow while formatting so the shared process may be swapped on failure
+        communicator.format_text(file_path, file_text, override_config, cancellation_token, format_with_host)
     }
 }
 
@@ -78,12 +148,20 @@ fn create_new_communicator<TEnvironment: Environment>(
     plugin_name: String,
     executable_file_path: &Path,
     config: &(ConfigKeyMap, GlobalConfiguration)
-) -> Result<ProcessPluginCommunicator, ErrBox> {
+) -> Result<Communicator, ErrBox> {
     // ensure it's initialized each time
     let mut communicator = ProcessPluginCommunicator::new(executable_file_path, move |error_message| {
         environment.log_error_with_context(&error_message, &plugin_name);
     })?;
-    communicator.set_global_config(&config.1)?;
-    communicator.set_plugin_config(&config.0)?;
-    Ok(communicator)
+    verify_plugin_handshake(&communicator.handshake(&get_process_host_info())?)?;
+    if communicator.is_multiplexed() {
+        let communicator = communicator.into_multiplexed();
+        communicator.set_global_config(&config.1)?;
+        communicator.set_plugin_config(&config.0)?;
+        Ok(Communicator::Multiplexed(Arc::new(communicator)))
+    } else {
+        communicator.set_global_config(&config.1)?;
+        communicator.set_plugin_config(&config.0)?;
+        Ok(Communicator::Single(communicator))
+    }
 }
@@ -1,16 +1,16 @@
 use std::sync::Arc;
 use std::path::{Path, PathBuf};
 use dprint_core::configuration::{ConfigurationDiagnostic, GlobalConfiguration, ConfigKeyMap};
-use dprint_core::plugins::PluginInfo;
+use dprint_core::plugins::{PluginInfo, FormatTextResult, CancellationToken};
 use dprint_core::types::ErrBox;
 
 use crate::environment::Environment;
 use crate::plugins::{Plugin, InitializedPlugin, PluginPools};
 
-use super::InitializedProcessPluginCommunicator;
+use super::{InitializedProcessPluginCommunicator, SharedMultiplexedCommunicator};
 use super::super::format_with_plugin_pool;
 
-static PLUGIN_FILE_INITIALIZE: std::sync::Once = std::sync::Once::new();
+static INITIALIZED_PLUGIN_FILES: parking_lot::Mutex<Vec<PathBuf>> = parking_lot::const_mutex(Vec::new());
 
 /// Use this to get an executable file name that also works in the tests.
 pub fn get_test_safe_executable_path(executable_file_path: PathBuf, environment: &impl Environment) -> PathBuf {
@@ -18,18 +18,21 @@ pub fn get_test_safe_executable_path(executable_file_path: PathBuf, environment:
         executable_file_path
     } else {
         // do this so that we can launch the process in the tests
+        let file_name = executable_file_path.file_name().expect("Expected the executable to have a file name.");
         if cfg!(target_os="windows") {
             let tmp_dir = PathBuf::from("temp");
-            let temp_process_plugin_file = tmp_dir.join(if cfg!(target_os="windows") { "temp-plugin.exe" } else { "temp-plugin" });
-            PLUGIN_FILE_INITIALIZE.call_once(|| {
+            let temp_process_plugin_file = tmp_dir.join(file_name);
+            let mut initialized_files = INITIALIZED_PLUGIN_FILES.lock();
+            if !initialized_files.contains(&temp_process_plugin_file) {
+                initialized_files.push(temp_process_plugin_file.clone());
                 let _ = std::fs::create_dir(&tmp_dir);
+                // ignore errors if path already exists
                 let _ = std::fs::write(&temp_process_plugin_file, environment.read_file_bytes(&executable_file_path).unwrap());
-            });
-            // ignore errors if path already exists
+            }
             temp_process_plugin_file
         } else {
             // couldn't figure out how to do chmod +x on a file in rust
-            PathBuf::from("../../target/release/test-process-plugin")
+            PathBuf::from("../../target/release").join(file_name)
         }
     }
 }
@@ -38,8 +41,10 @@ pub struct ProcessPlugin<TEnvironment: Environment> {
     environment: TEnvironment,
     executable_file_path: PathBuf,
     plugin_info: PluginInfo,
+    file_hash: Option<u64>,
     config: Option<(ConfigKeyMap, GlobalConfiguration)>,
     plugin_pools: Arc<PluginPools<TEnvironment>>,
+    shared_communicator: SharedMultiplexedCommunicator,
 }
 
 impl<TEnvironment: Environment> ProcessPlugin<TEnvironment> {
@@ -47,14 +52,17 @@ impl<TEnvironment: Environment> ProcessPlugin<TEnvironment> {
         environment: TEnvironment,
         executable_file_path: PathBuf,
         plugin_info: PluginInfo,
+        file_hash: Option<u64>,
         plugin_pools: Arc<PluginPools<TEnvironment>>
     ) -> Self {
         ProcessPlugin {
             environment,
             executable_file_path,
             plugin_info,
+            file_hash,
             config: None,
-            plugin_pools
+            plugin_pools,
+            shared_communicator: Default::default(),
         }
     }
 }
@@ -80,6 +88,18 @@ impl<TEnvironment: Environment> Plugin for ProcessPlugin<TEnvironment> {
         &self.plugin_info.file_names
     }
 
+    fn interpreters(&self) -> &Vec<String> {
+        &self.plugin_info.interpreters
+    }
+
+    fn languages(&self) -> &Vec<String> {
+        &self.plugin_info.languages
+    }
+
+    fn file_hash(&self) -> Option<u64> {
+        self.file_hash
+    }
+
     fn help_url(&self) -> &str {
         &self.plugin_info.help_url
     }
@@ -90,6 +110,8 @@ impl<TEnvironment: Environment> Plugin for ProcessPlugin<TEnvironment> {
 
     fn set_config(&mut self, plugin_config: ConfigKeyMap, global_config: GlobalConfiguration) {
         self.config = Some((plugin_config, global_config));
+        // the shared process was initialized with the previous config
+        self.shared_communicator = Default::default();
     }
 
     fn get_config(&self) -> &(ConfigKeyMap, GlobalConfiguration) {
@@ -103,6 +125,7 @@ impl<TEnvironment: Environment> Plugin for ProcessPlugin<TEnvironment> {
             self.plugin_info.name.clone(),
             self.executable_file_path.clone(),
             config.clone(),
+            self.shared_communicator.clone(),
         )?;
         let process_plugin = InitializedProcessPlugin::new(
             self.name().to_string(),
@@ -139,9 +162,9 @@ impl<TEnvironment: Environment> InitializedProcessPlugin<TEnvironment> {
         Ok(initialized_plugin)
     }
 
-    fn inner_format_text(&self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap) -> Result<String, ErrBox> {
-        self.communicator.format_text(file_path, file_text, override_config, |file_path, file_text, override_config| {
-            format_with_plugin_pool(&self.name, &file_path, &file_text, &override_config, &self.plugin_pools)
+    fn inner_format_text(&self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap, cancellation_token: &CancellationToken) -> Result<FormatTextResult, ErrBox> {
+        self.communicator.format_text(file_path, file_text, override_config, cancellation_token, |file_path, language, file_text, override_config| {
+            format_with_plugin_pool(&self.name, &file_path, language.as_deref(), &file_text, &override_config, cancellation_token, &self.plugin_pools)
         })
     }
 }
@@ -159,11 +182,12 @@ impl<TEnvironment: Environment> InitializedPlugin for InitializedProcessPlugin<T
         self.communicator.get_config_diagnostics()
     }
 
-    fn format_text(&mut self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap) -> Result<String, ErrBox> {
-        let result = self.inner_format_text(file_path, file_text, override_config);
+    fn format_text(&mut self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap, cancellation_token: &CancellationToken) -> Result<FormatTextResult, ErrBox> {
+        let result = self.inner_format_text(file_path, file_text, override_config, cancellation_token);
 
         match result {
             Ok(result) => Ok(result),
+            Err(original_err) if cancellation_token.is_cancelled() => Err(original_err),
             Err(original_err) => {
                 // todo: tests for this somehow
                 let process_recreated = match self.communicator.recreate_process_if_dead() {
@@ -179,7 +203,7 @@ impl<TEnvironment: Environment> InitializedPlugin for InitializedProcessPlugin<T
 
                 if process_recreated {
                     // attempt formatting again
-                    self.inner_format_text(file_path, file_text, override_config)
+                    self.inner_format_text(file_path, file_text, override_config, cancellation_token)
                 } else {
                     return Err(original_err);
                 }
@@ -7,9 +7,10 @@ use dprint_core::types::ErrBox;
 use dprint_cli_core::checksums::verify_sha256_checksum;
 
 use crate::environment::Environment;
-use crate::utils::{PathSource, fetch_file_or_url_bytes, resolve_url_or_file_path_to_path_source, extract_zip};
+use crate::plugins::PluginSourceReference;
+use crate::utils::{PathSource, resolve_url_or_file_path_to_path_source, extract_zip};
 
-use super::super::SetupPluginResult;
+use super::super::{SetupPluginResult, get_host_info, lock_plugin_dir, verify_plugin_handshake};
 
 pub fn get_file_path_from_plugin_info(plugin_info: &PluginInfo, environment: &impl Environment) -> PathBuf {
     let dir_path = get_plugin_dir_path(&plugin_info.name, &plugin_info.version, environment);
@@ -31,9 +32,15 @@ fn get_plugin_executable_file_path(dir_path: &Path, plugin_name: &str) -> PathBu
 
 /// Takes a url or file path and extracts the plugin to a cache folder.
 /// Returns the executable file path once complete
-pub fn setup_process_plugin(url_or_file_path: &PathSource, plugin_file_bytes: &[u8], environment: &impl Environment) -> Result<SetupPluginResult, ErrBox> {
-    let plugin_zip_bytes = get_plugin_zip_bytes(url_or_file_path, plugin_file_bytes, environment)?;
+pub fn setup_process_plugin(
+    url_or_file_path: &PathSource,
+    plugin_file_bytes: &[u8],
+    fetch_bytes: &dyn Fn(&PathSource) -> Result<Vec<u8>, ErrBox>,
+    environment: &impl Environment,
+) -> Result<SetupPluginResult, ErrBox> {
+    let plugin_zip_bytes = get_plugin_zip_bytes(url_or_file_path, plugin_file_bytes, fetch_bytes)?;
     let plugin_cache_dir_path = get_plugin_dir_path(&plugin_zip_bytes.name, &plugin_zip_bytes.version, environment);
+    let _dir_lock = lock_plugin_dir(&plugin_cache_dir_path, environment)?;
 
     let result = setup_inner(&plugin_cache_dir_path, plugin_zip_bytes.name, &plugin_zip_bytes.zip_bytes, environment);
 
@@ -73,6 +80,7 @@ pub fn setup_process_plugin(url_or_file_path: &PathSource, plugin_file_bytes: &[
                 }
             }
         )?;
+        verify_plugin_handshake(&communicator.handshake(&get_host_info())?)?;
         let plugin_info = communicator.get_plugin_info()?;
 
         Ok(SetupPluginResult {
@@ -84,6 +92,7 @@ pub fn setup_process_plugin(url_or_file_path: &PathSource, plugin_file_bytes: &[
 
 pub fn cleanup_process_plugin(plugin_info: &PluginInfo, environment: &impl Environment) -> Result<(), ErrBox> {
     let plugin_cache_dir_path = get_plugin_dir_path(&plugin_info.name, &plugin_info.version, environment);
+    let _dir_lock = lock_plugin_dir(&plugin_cache_dir_path, environment)?;
     environment.remove_dir_all(&plugin_cache_dir_path)?;
     Ok(())
 }
@@ -109,17 +118,24 @@ struct ProcessPluginPath {
     checksum: String,
 }
 
-struct ProcessPluginZipBytes {
-    name: String,
-    version: String,
-    zip_bytes: Vec<u8>,
+pub struct ProcessPluginZipBytes {
+    pub name: String,
+    pub version: String,
+    pub zip_bytes: Vec<u8>,
 }
 
-fn get_plugin_zip_bytes<TEnvironment: Environment>(url_or_file_path: &PathSource, plugin_file_bytes: &[u8], environment: &TEnvironment) -> Result<ProcessPluginZipBytes, ErrBox> {
+/// Gets the zip bytes for the current operating system from a plugin file, verifying the zip's checksum.
+///
+/// Native plugin files use the same format as process plugin files.
+pub fn get_plugin_zip_bytes(
+    url_or_file_path: &PathSource,
+    plugin_file_bytes: &[u8],
+    fetch_bytes: &dyn Fn(&PathSource) -> Result<Vec<u8>, ErrBox>,
+) -> Result<ProcessPluginZipBytes, ErrBox> {
     let plugin_file = deserialize_file(&plugin_file_bytes)?;
     let plugin_path = get_os_path(&plugin_file)?;
     let plugin_zip_path = resolve_url_or_file_path_to_path_source(&plugin_path.reference, &url_or_file_path.parent())?;
-    let plugin_zip_bytes = fetch_file_or_url_bytes(&plugin_zip_path, environment)?;
+    let plugin_zip_bytes = fetch_bytes(&plugin_zip_path)?;
     verify_sha256_checksum(&plugin_zip_bytes, &plugin_path.checksum)?;
 
     Ok(ProcessPluginZipBytes {
@@ -129,6 +145,19 @@ fn get_plugin_zip_bytes<TEnvironment: Environment>(url_or_file_path: &PathSource
     })
 }
 
+/// Gets the zip files and their checksums for every operating system the plugin file supports.
+pub fn get_plugin_zip_references(url_or_file_path: &PathSource, plugin_file_bytes: &[u8]) -> Result<Vec<PluginSourceReference>, ErrBox> {
+    let plugin_file = deserialize_file(&plugin_file_bytes)?;
+    let mut references = Vec::new();
+    for plugin_path in vec![&plugin_file.linux, &plugin_file.mac, &plugin_file.windows].into_iter().flatten() {
+        references.push(PluginSourceReference {
+            path_source: resolve_url_or_file_path_to_path_source(&plugin_path.reference, &url_or_file_path.parent())?,
+            checksum: Some(plugin_path.checksum.clone()),
+        });
+    }
+    Ok(references)
+}
+
 fn deserialize_file(bytes: &[u8]) -> Result<ProcessPluginFile, ErrBox> {
     // todo: don't use serde because this should fail with a nice error message if the schema version is not equal
     let plugin_file: ProcessPluginFile = match serde_json::from_slice(&bytes) {
@@ -5,8 +5,10 @@ use dprint_core::plugins::PluginInfo;
 use dprint_core::types::ErrBox;
 
 use crate::environment::Environment;
-use crate::plugins::{Plugin, PluginSourceReference, PluginCache, PluginPools};
+use crate::plugins::{Plugin, PluginSourceReference, PluginCache, PluginCacheItem, PluginPools};
 use crate::utils::PathSource;
+use super::lazy::LazyPlugin;
+use super::native::{self};
 use super::process::{self};
 use super::wasm::{self};
 
@@ -15,15 +17,32 @@ pub struct SetupPluginResult {
     pub plugin_info: PluginInfo,
 }
 
+/// Sets up the plugin from its file bytes, getting any files referenced by
+/// the plugin file (ex. a process plugin's zip file) with `fetch_bytes`.
 pub fn setup_plugin<TEnvironment: Environment>(
     url_or_file_path: &PathSource,
     file_bytes: &[u8],
+    fetch_bytes: &dyn Fn(&PathSource) -> Result<Vec<u8>, ErrBox>,
     environment: &TEnvironment
 ) -> Result<SetupPluginResult, ErrBox> {
     if url_or_file_path.is_wasm_plugin() {
         wasm::setup_wasm_plugin(url_or_file_path, file_bytes, environment)
     } else if url_or_file_path.is_process_plugin() {
-        process::setup_process_plugin(url_or_file_path, file_bytes, environment)
+        process::setup_process_plugin(url_or_file_path, file_bytes, fetch_bytes, environment)
+    } else if url_or_file_path.is_native_plugin() {
+        native::setup_native_plugin(url_or_file_path, file_bytes, fetch_bytes, environment)
+    } else {
+        return err!("Could not resolve plugin type from url or file path: {}", url_or_file_path.display());
+    }
+}
+
+/// Gets the files referenced by the plugin file that are necessary to set it up.
+pub fn get_referenced_plugin_files(url_or_file_path: &PathSource, file_bytes: &[u8]) -> Result<Vec<PluginSourceReference>, ErrBox> {
+    if url_or_file_path.is_wasm_plugin() {
+        Ok(Vec::new())
+    } else if url_or_file_path.is_process_plugin() || url_or_file_path.is_native_plugin() {
+        // native plugin files use the same format as process plugin files
+        process::get_plugin_zip_references(url_or_file_path, file_bytes)
     } else {
         return err!("Could not resolve plugin type from url or file path: {}", url_or_file_path.display());
     }
@@ -38,6 +57,8 @@ pub fn get_file_path_from_plugin_info<TEnvironment: Environment>(
         Ok(wasm::get_file_path_from_plugin_info(plugin_info, environment))
     } else if url_or_file_path.is_process_plugin() {
         Ok(process::get_file_path_from_plugin_info(plugin_info, environment))
+    } else if url_or_file_path.is_native_plugin() {
+        Ok(native::get_file_path_from_plugin_info(plugin_info, environment))
     } else {
         return err!("Could not resolve plugin type from url or file path: {}", url_or_file_path.display());
     }
@@ -53,19 +74,50 @@ pub fn cleanup_plugin<TEnvironment: Environment>(
         wasm::cleanup_wasm_plugin(plugin_info, environment)
     } else if url_or_file_path.is_process_plugin() {
         process::cleanup_process_plugin(plugin_info, environment)
+    } else if url_or_file_path.is_native_plugin() {
+        native::cleanup_native_plugin(plugin_info, environment)
     } else {
         return err!("Could not resolve plugin type from url or file path: {}", url_or_file_path.display());
     }
 }
 
+/// Creates a plugin that is only downloaded, set up, and loaded once it's initialized.
+///
+/// The plugin's information is taken from the cache or the provided information
+/// recorded in the lockfile. Only when neither has it is the plugin downloaded and
+/// set up right away in order to get its information.
 pub fn create_plugin<TEnvironment : Environment>(
     plugin_pools: Arc<PluginPools<TEnvironment>>,
-    plugin_cache: &PluginCache<TEnvironment>,
+    plugin_cache: Arc<PluginCache<TEnvironment>>,
     environment: TEnvironment,
     plugin_reference: &PluginSourceReference,
+    locked_info: Option<PluginInfo>,
 ) -> Result<Box<dyn Plugin>, ErrBox> {
-    let cache_item = plugin_cache.get_plugin_cache_item(plugin_reference);
-    let cache_item = match cache_item {
+    let cache_item = match plugin_cache.get_cached_plugin_cache_item(plugin_reference) {
+        Ok(cache_item) => cache_item,
+        Err(err) => {
+            log_verbose!(environment, "Error getting plugin {} from cache. {}", plugin_reference.display(), err.to_string());
+            None
+        }
+    };
+
+    Ok(Box::new(match (cache_item, locked_info) {
+        (Some(cache_item), _) => LazyPlugin::new(plugin_pools, plugin_cache, environment, plugin_reference.clone(), cache_item),
+        (None, Some(locked_info)) => LazyPlugin::new_locked(plugin_pools, plugin_cache, environment, plugin_reference.clone(), locked_info),
+        (None, None) => {
+            let cache_item = get_plugin_cache_item(&plugin_cache, &environment, plugin_reference)?;
+            LazyPlugin::new(plugin_pools, plugin_cache, environment, plugin_reference.clone(), cache_item)
+        }
+    }))
+}
+
+/// Gets the plugin from the cache, downloading and setting it up when necessary.
+pub fn get_plugin_cache_item<TEnvironment : Environment>(
+    plugin_cache: &PluginCache<TEnvironment>,
+    environment: &TEnvironment,
+    plugin_reference: &PluginSourceReference,
+) -> Result<PluginCacheItem, ErrBox> {
+    match plugin_cache.get_plugin_cache_item(plugin_reference) {
         Ok(cache_item) => Ok(cache_item),
         Err(err) => {
             environment.log_error(&format!(
@@ -77,8 +129,17 @@ pub fn create_plugin<TEnvironment : Environment>(
             plugin_cache.forget(plugin_reference)?;
             plugin_cache.get_plugin_cache_item(plugin_reference)
         }
-    }?;
+    }
+}
 
+/// Loads the plugin from its cached file.
+pub fn load_plugin<TEnvironment : Environment>(
+    plugin_pools: Arc<PluginPools<TEnvironment>>,
+    plugin_cache: &PluginCache<TEnvironment>,
+    environment: TEnvironment,
+    plugin_reference: &PluginSourceReference,
+    cache_item: PluginCacheItem,
+) -> Result<Box<dyn Plugin>, ErrBox> {
     if plugin_reference.is_wasm_plugin() {
         let file_bytes = match environment.read_file_bytes(&cache_item.file_path) {
             Ok(file_bytes) => file_bytes,
@@ -95,7 +156,7 @@ pub fn create_plugin<TEnvironment : Environment>(
             }
         };
 
-        Ok(Box::new(wasm::WasmPlugin::new(file_bytes, cache_item.info, plugin_pools)?))
+        Ok(Box::new(wasm::WasmPlugin::new(environment.clone(), file_bytes, cache_item.info, cache_item.file_hash, plugin_pools)?))
     } else if plugin_reference.is_process_plugin() {
         let cache_item = if !environment.path_exists(&cache_item.file_path) {
             environment.log_error(&format!(
@@ -111,7 +172,23 @@ pub fn create_plugin<TEnvironment : Environment>(
         };
 
         let executable_path = super::process::get_test_safe_executable_path(cache_item.file_path, &environment);
-        Ok(Box::new(process::ProcessPlugin::new(environment.clone(), executable_path, cache_item.info, plugin_pools)))
+        Ok(Box::new(process::ProcessPlugin::new(environment.clone(), executable_path, cache_item.info, cache_item.file_hash, plugin_pools)))
+    } else if plugin_reference.is_native_plugin() {
+        let cache_item = if !environment.path_exists(&cache_item.file_path) {
+            environment.log_error(&format!(
+                "Could not find native plugin at {}. Forgetting from cache and retrying.",
+                cache_item.file_path.display()
+            ));
+
+            // forget and try again
+            plugin_cache.forget(plugin_reference)?;
+            plugin_cache.get_plugin_cache_item(plugin_reference)?
+        } else {
+            cache_item
+        };
+
+        let library_path = native::get_test_safe_library_path(cache_item.file_path, &environment);
+        Ok(Box::new(native::NativePlugin::new(&library_path, cache_item.info, cache_item.file_hash, plugin_pools)?))
     } else {
         return err!("Could not resolve plugin type from url or file path: {}", plugin_reference.display());
     }
@@ -1,12 +1,16 @@
+use std::sync::Arc;
 use dprint_core::types::ErrBox;
-use wasmer::{Store, Module};
+use wasmer::{CompilerConfig, Cranelift, Module, Store, Universal};
 
 use crate::plugins::CompilationResult;
-use super::{InitializedWasmPlugin, create_identity_import_object};
+use super::{InitializedWasmPlugin, InterruptMiddleware, create_identity_import_object, add_wasi_imports};
 
 /// Compiles a Wasm module.
 pub fn compile(wasm_bytes: &[u8]) -> Result<CompilationResult, ErrBox> {
-    let store = Store::default();
+    // the middleware allows interrupting a plugin when its format request is cancelled
+    let mut compiler_config = Cranelift::default();
+    compiler_config.push_middleware(Arc::new(InterruptMiddleware::default()));
+    let store = Store::new(&Universal::new(compiler_config).engine());
     let module = Module::new(&store, wasm_bytes)?;
     let bytes = match module.serialize() {
         Ok(bytes) => Ok(bytes),
@@ -15,8 +19,14 @@ pub fn compile(wasm_bytes: &[u8]) -> Result<CompilationResult, ErrBox> {
 
     // load the plugin and get the info
     let plugin = InitializedWasmPlugin::new(
-        module,
-        Box::new(move || create_identity_import_object(&store)), // we're not formatting anything so this is ok
+        module.clone(),
+        Box::new(move || {
+            // we're not formatting anything so this is ok
+            let mut import_object = create_identity_import_object(&store);
+            add_wasi_imports(&mut import_object, &store, &module, Arc::new(|_| {}));
+            import_object
+        }),
+        Default::default(),
     )?;
     let plugin_info = plugin.get_plugin_info()?;
 
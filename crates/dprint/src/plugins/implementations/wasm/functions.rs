@@ -3,6 +3,8 @@ use wasmer::{Instance, WasmPtr, Array, Memory, NativeFunc, RuntimeError, WasmTyp
 use dprint_core::types::{ErrBox, Error};
 use dprint_core::plugins::wasm::PLUGIN_SYSTEM_SCHEMA_VERSION;
 
+use super::InterruptHandle;
+
 pub enum FormatResult {
     NoChange = 0,
     Change = 1,
@@ -58,6 +60,20 @@ impl WasmFunctions {
         wasm_runtime_error_to_err_box(set_plugin_config_func.call())
     }
 
+    /// Gets if the plugin exports the handshake, which plugins built
+    /// before the handshake existed don't.
+    #[inline]
+    pub fn has_handshake(&self) -> bool {
+        self.instance.exports.get_function("handshake").is_ok()
+    }
+
+    /// Gets the length of the plugin handshake JSON.
+    #[inline]
+    pub fn handshake(&self) -> Result<usize, ErrBox> {
+        let handshake_func = self.get_export::<(), u32>("handshake")?;
+        wasm_runtime_error_to_err_box(handshake_func.call()).map(|value| value as usize)
+    }
+
     #[inline]
     pub fn get_plugin_info(&self) -> Result<usize, ErrBox> {
         let get_plugin_info_func = self.get_export::<(), u32>("get_plugin_info")?;
@@ -106,6 +122,15 @@ impl WasmFunctions {
         wasm_runtime_error_to_err_box(get_formatted_text_func.call()).map(|value| value as usize)
     }
 
+    /// Gets the length of the diagnostics JSON of the last format or
+    /// `None` when there are no diagnostics.
+    #[inline]
+    pub fn get_format_diagnostics(&self) -> Result<Option<usize>, ErrBox> {
+        let get_format_diagnostics_func = self.get_export::<(), u32>("get_format_diagnostics")?;
+        wasm_runtime_error_to_err_box(get_format_diagnostics_func.call())
+            .map(|value| if value == 0 { None } else { Some(value as usize) })
+    }
+
     #[inline]
     pub fn get_error_text(&self) -> Result<usize, ErrBox> {
         let get_error_text_func = self.get_export::<(), u32>("get_error_text")?;
@@ -117,12 +142,32 @@ impl WasmFunctions {
         &self.memory
     }
 
+    /// Gets the handle for interrupting the instance from another thread.
+    pub fn get_interrupt_handle(&self) -> Option<InterruptHandle> {
+        InterruptHandle::from_instance(&self.instance)
+    }
+
     #[inline]
     pub fn clear_shared_bytes(&self, capacity: usize) -> Result<(), ErrBox> {
         let clear_shared_bytes_func = self.get_export::<u32, ()>("clear_shared_bytes")?;
         wasm_runtime_error_to_err_box(clear_shared_bytes_func.call(capacity as u32))
     }
 
+    /// Allocates the shared bytes in the plugin with the provided length and
+    /// returns a pointer to them so they may be written to directly.
+    #[inline]
+    pub fn allocate_shared_bytes(&self, length: usize) -> Result<WasmPtr<u8, Array>, ErrBox> {
+        let allocate_shared_bytes_func = self.get_export::<u32, WasmPtr<u8, Array>>("allocate_shared_bytes")?;
+        wasm_runtime_error_to_err_box(allocate_shared_bytes_func.call(length as u32))
+    }
+
+    /// Gets a pointer to the plugin's shared bytes so they may be read from directly.
+    #[inline]
+    pub fn get_shared_bytes_ptr(&self) -> Result<WasmPtr<u8, Array>, ErrBox> {
+        let get_shared_bytes_ptr_func = self.get_export::<(), WasmPtr<u8, Array>>("get_shared_bytes_ptr")?;
+        wasm_runtime_error_to_err_box(get_shared_bytes_ptr_func.call())
+    }
+
     #[inline]
     pub fn get_wasm_memory_buffer_size(&self) -> Result<usize, ErrBox> {
         let get_wasm_memory_buffer_size_func = self.get_export::<(), u32>("get_wasm_memory_buffer_size")?;
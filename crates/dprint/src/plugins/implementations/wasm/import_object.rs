@@ -2,7 +2,8 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use std::collections::HashMap;
 use dprint_core::configuration::ConfigKeyMap;
-use wasmer::{Function, Store, LazyInit, Instance, Memory, WasmerEnv, HostEnvInitError};
+use dprint_core::plugins::CancellationToken;
+use wasmer::{Function, Store, LazyInit, Instance, Memory, WasmerEnv, HostEnvInitError, RuntimeError};
 use parking_lot::Mutex;
 
 use crate::plugins::pool::PluginPools;
@@ -34,9 +35,13 @@ pub fn create_identity_import_object(store: &Store) -> wasmer::ImportObject {
     }
 }
 
+/// The cancellation token of the instance's current format request.
+pub type SharedCancellationToken = Arc<Mutex<CancellationToken>>;
+
 pub struct ImportObjectEnvironmentCellItems {
     override_config: Option<ConfigKeyMap>,
     file_path: Option<PathBuf>,
+    language: Option<String>,
     shared_bytes: Vec<u8>,
     formatted_text_store: String,
     error_text_store: String,
@@ -47,26 +52,29 @@ pub struct ImportObjectEnvironment<TEnvironment: Environment> {
     parent_plugin_name: String,
     memory: LazyInit<Memory>,
     pools: Arc<PluginPools<TEnvironment>>,
+    cancellation_token: SharedCancellationToken,
     cell: Arc<Mutex<ImportObjectEnvironmentCellItems>>,
 }
 
 impl<TEnvironment: Environment> WasmerEnv for ImportObjectEnvironment<TEnvironment> {
     fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
-        let memory = instance.exports.get_memory("memory").unwrap();
+        let memory = instance.exports.get_memory("memory")?;
         self.memory.initialize(memory.clone());
         Ok(())
     }
 }
 
 impl<TEnvironment: Environment> ImportObjectEnvironment<TEnvironment> {
-    pub fn new(plugin_name: &str, pools: Arc<PluginPools<TEnvironment>>) -> Self {
+    pub fn new(plugin_name: &str, pools: Arc<PluginPools<TEnvironment>>, cancellation_token: SharedCancellationToken) -> Self {
         ImportObjectEnvironment {
             parent_plugin_name: plugin_name.to_string(),
             pools,
+            cancellation_token,
             memory: LazyInit::new(),
             cell: Arc::new(Mutex::new(ImportObjectEnvironmentCellItems {
                 override_config: None,
                 file_path: None,
+                language: None,
                 shared_bytes: Vec::new(),
                 formatted_text_store: String::new(),
                 error_text_store: String::new(),
@@ -126,22 +134,42 @@ pub fn create_pools_import_object<TEnvironment: Environment>(
         |env: &ImportObjectEnvironment<TEnvironment>| {
             let mut cell = env.cell.lock();
             let bytes = std::mem::replace(&mut cell.shared_bytes, Vec::new());
-            let file_path_str = String::from_utf8(bytes).unwrap();
-            cell.file_path.replace(PathBuf::from(file_path_str));
+            let (file_path, language) = parse_file_path_and_language(String::from_utf8(bytes).unwrap());
+            cell.file_path.replace(file_path);
+            cell.language = language;
         }
     };
     let host_format = {
-        |env: &ImportObjectEnvironment<TEnvironment>| {
-            let (override_config, file_path, file_text) = {
+        |env: &ImportObjectEnvironment<TEnvironment>| -> Result<u32, RuntimeError> {
+            // trap in order to interrupt the instance when the request was cancelled
+            let cancellation_token = env.cancellation_token.lock().clone();
+            if cancellation_token.is_cancelled() {
+                return Err(RuntimeError::new("The format request was cancelled."));
+            }
+
+            let (override_config, file_path, language, file_text) = {
                 let mut cell = env.cell.lock();
                 let override_config = cell.override_config.take().unwrap_or(HashMap::new());
                 let file_path = cell.file_path.take().expect("Expected to have file path.");
+                let language = cell.language.take();
                 let bytes = std::mem::replace(&mut cell.shared_bytes, Vec::new());
                 let file_text = String::from_utf8(bytes).unwrap();
-                (override_config, file_path, file_text)
+                (override_config, file_path, language, file_text)
             };
 
-            match format_with_plugin_pool(&env.parent_plugin_name, &file_path, &file_text, &override_config, &env.pools) {
+            let result = format_with_plugin_pool(
+                &env.parent_plugin_name,
+                &file_path,
+                language.as_deref(),
+                &file_text,
+                &override_config,
+                &cancellation_token,
+                &env.pools,
+            );
+            if cancellation_token.is_cancelled() {
+                return Err(RuntimeError::new("The format request was cancelled."));
+            }
+            Ok(match result {
                 Ok(Some(formatted_text)) => {
                     let mut cell = env.cell.lock();
                     cell.formatted_text_store = formatted_text;
@@ -155,7 +183,7 @@ pub fn create_pools_import_object<TEnvironment: Environment>(
                     cell.error_text_store = err.to_string();
                     2 // error
                 }
-            }
+            })
         }
     };
     let host_get_formatted_text = {
@@ -191,3 +219,65 @@ pub fn create_pools_import_object<TEnvironment: Environment>(
         }
     }
 }
+
+/// Plugins that negotiated the `hostFormatLanguage` capability send the file path as
+/// `<language>\0<file path>`. File paths can't contain a nul character, so text without
+/// one is only a file path.
+fn parse_file_path_and_language(text: String) -> (PathBuf, Option<String>) {
+    match text.find('\0') {
+        Some(index) => {
+            let language = &text[..index];
+            let file_path = PathBuf::from(&text[index + 1..]);
+            (file_path, if language.is_empty() { None } else { Some(language.to_string()) })
+        }
+        None => (PathBuf::from(text), None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasmer::Module;
+
+    use crate::environment::TestEnvironment;
+    use super::*;
+
+    #[test]
+    fn it_should_parse_file_path_without_language() {
+        assert_eq!(parse_file_path_and_language("/dir/file.ts".to_string()), (PathBuf::from("/dir/file.ts"), None));
+    }
+
+    #[test]
+    fn it_should_parse_file_path_with_language() {
+        assert_eq!(
+            parse_file_path_and_language("typescript\0/dir/file.md".to_string()),
+            (PathBuf::from("/dir/file.md"), Some("typescript".to_string()))
+        );
+        assert_eq!(parse_file_path_and_language("\0/dir/file.md".to_string()), (PathBuf::from("/dir/file.md"), None));
+    }
+
+    // plugins built with older versions of dprint-core only import these functions
+    const SCHEMA_3_PLUGIN_WAT: &'static str = r#"(module
+        (import "dprint" "host_clear_bytes" (func (param i32)))
+        (import "dprint" "host_read_buffer" (func (param i32 i32)))
+        (import "dprint" "host_write_buffer" (func (param i32 i32 i32)))
+        (import "dprint" "host_take_override_config" (func))
+        (import "dprint" "host_take_file_path" (func))
+        (import "dprint" "host_format" (func (result i32)))
+        (import "dprint" "host_get_formatted_text" (func (result i32)))
+        (import "dprint" "host_get_error_text" (func (result i32)))
+        (memory (export "memory") 1)
+    )"#;
+
+    #[test]
+    fn it_should_instantiate_plugin_without_newer_imports() {
+        let store = Store::default();
+        let module = Module::new(&store, SCHEMA_3_PLUGIN_WAT).unwrap();
+
+        Instance::new(&module, &create_identity_import_object(&store)).unwrap();
+
+        let environment = TestEnvironment::new();
+        let pools = Arc::new(PluginPools::new(environment));
+        let import_obj_env = ImportObjectEnvironment::new("test-plugin", pools, SharedCancellationToken::default());
+        Instance::new(&module, &create_pools_import_object(&store, &import_obj_env)).unwrap();
+    }
+}
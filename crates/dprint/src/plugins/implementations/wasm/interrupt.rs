@@ -0,0 +1,99 @@
+use std::sync::Mutex;
+use loupe::MemoryUsage;
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{ExportIndex, FunctionMiddleware, Global, GlobalInit, GlobalType, Instance, LocalFunctionIndex, MiddlewareError,
+    MiddlewareReaderState, ModuleMiddleware, Mutability, Type, Value};
+use wasmer_types::GlobalIndex;
+use wasmer_vm::ModuleInfo;
+
+/// Name of the exported global that's set to interrupt the instance.
+const INTERRUPTED_GLOBAL_NAME: &str = "dprint_interrupted";
+
+/// Middleware that makes an instance trap at the start of the next function call
+/// or loop iteration once it's interrupted. This allows the host to stop a plugin
+/// that's busy formatting without the plugin's cooperation.
+///
+/// A new middleware should be used for each compiled module.
+#[derive(Debug, Default, MemoryUsage)]
+pub struct InterruptMiddleware {
+    #[loupe(skip)]
+    global_index: Mutex<Option<GlobalIndex>>,
+}
+
+impl ModuleMiddleware for InterruptMiddleware {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionInterruptMiddleware {
+            global_index: self.global_index.lock().unwrap().expect("The module info should be transformed first."),
+            is_start: true,
+        })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_index = self.global_index.lock().unwrap();
+        if global_index.is_some() {
+            panic!("An interrupt middleware can only be used with one module.");
+        }
+
+        let index = module_info.globals.push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info.global_initializers.push(GlobalInit::I32Const(0));
+        module_info.exports.insert(INTERRUPTED_GLOBAL_NAME.to_string(), ExportIndex::Global(index));
+        *global_index = Some(index);
+    }
+}
+
+#[derive(Debug)]
+struct FunctionInterruptMiddleware {
+    global_index: GlobalIndex,
+    is_start: bool,
+}
+
+impl FunctionInterruptMiddleware {
+    fn push_check<'a>(&self, state: &mut MiddlewareReaderState<'a>) {
+        state.extend(&[
+            Operator::GlobalGet { global_index: self.global_index.as_u32() },
+            Operator::If { ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType) },
+            Operator::Unreachable,
+            Operator::End,
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionInterruptMiddleware {
+    fn feed<'a>(&mut self, operator: Operator<'a>, state: &mut MiddlewareReaderState<'a>) -> Result<(), MiddlewareError> {
+        // check at the start of each function and loop iteration since all long running code goes through them
+        if self.is_start {
+            self.is_start = false;
+            self.push_check(state);
+        }
+        let is_loop = matches!(operator, Operator::Loop { .. });
+        state.push_operator(operator);
+        if is_loop {
+            self.push_check(state);
+        }
+        Ok(())
+    }
+}
+
+/// Interrupts an instance compiled with the `InterruptMiddleware` from any thread.
+#[derive(Clone)]
+pub struct InterruptHandle {
+    global: Global,
+}
+
+impl InterruptHandle {
+    /// Gets the handle or `None` when the module wasn't compiled with the middleware.
+    pub fn from_instance(instance: &Instance) -> Option<InterruptHandle> {
+        instance.exports.get_global(INTERRUPTED_GLOBAL_NAME).ok().map(|global| InterruptHandle { global: global.clone() })
+    }
+
+    /// Makes the instance trap the next time it calls a function or loops.
+    pub fn interrupt(&self) {
+        // the global is created as mutable by the middleware, so this won't fail
+        let _ = self.global.set(Value::I32(1));
+    }
+
+    /// Allows the instance to run again after being interrupted.
+    pub fn reset(&self) {
+        let _ = self.global.set(Value::I32(0));
+    }
+}
@@ -3,11 +3,19 @@ use wasmer::{Instance, Store, Module, ImportObject};
 
 /// Loads a compiled wasm module from the specified bytes.
 pub fn load_instance(module: &Module, import_object: &ImportObject) -> Result<Instance, ErrBox> {
-    let instance = Instance::new(module, import_object);
-    match instance {
-        Ok(instance) => Ok(instance),
-        Err(err) => err!("Error instantiating module: {}", err),
+    let instance = match Instance::new(module, import_object) {
+        Ok(instance) => instance,
+        Err(err) => return err!("Error instantiating module: {}", err),
+    };
+
+    // WASI reactor modules need to be initialized before any of their other exports are called
+    if let Ok(initialize) = instance.exports.get_function("_initialize") {
+        if let Err(err) = initialize.call(&[]) {
+            return err!("Error initializing module: {}", err);
+        }
     }
+
+    Ok(instance)
 }
 
 pub fn create_module(compiled_module_bytes: &[u8]) -> Result<Module, ErrBox> {
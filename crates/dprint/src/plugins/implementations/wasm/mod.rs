@@ -1,13 +1,17 @@
 mod compile;
 mod functions;
 mod import_object;
+mod interrupt;
 mod load_instance;
 mod plugin;
 mod setup_wasm_plugin;
+mod wasi;
 
 pub use compile::*;
 use functions::*;
 pub use import_object::*;
+use interrupt::*;
 use load_instance::*;
 pub use plugin::*;
 pub use setup_wasm_plugin::*;
+use wasi::*;
@@ -3,26 +3,39 @@ use std::path::Path;
 use std::sync::Arc;
 
 use dprint_core::configuration::{ConfigurationDiagnostic, GlobalConfiguration, ConfigKeyMap};
-use dprint_core::plugins::{PluginInfo};
+use dprint_core::plugins::{PluginInfo, FormatTextResult, FormatDiagnostic, PluginCapabilities, PluginHandshake, CancellationToken};
 use dprint_core::types::ErrBox;
+use wasmer::{Array, WasmPtr};
 
 use crate::environment::Environment;
 use crate::plugins::{Plugin, InitializedPlugin, PluginPools};
-use super::{WasmFunctions, FormatResult, load_instance, create_module, create_pools_import_object, ImportObjectEnvironment};
+use super::super::{get_wasm_host_info, verify_plugin_handshake};
+use super::{WasmFunctions, FormatResult, load_instance, create_module, create_pools_import_object, ImportObjectEnvironment, SharedCancellationToken,
+    WasiOutputHandler, add_wasi_imports};
 
 pub struct WasmPlugin<TEnvironment: Environment> {
+    environment: TEnvironment,
     module: wasmer::Module,
     plugin_info: PluginInfo,
+    file_hash: Option<u64>,
     config: Option<(ConfigKeyMap, GlobalConfiguration)>,
     plugin_pools: Arc<PluginPools<TEnvironment>>,
 }
 
 impl<TEnvironment: Environment> WasmPlugin<TEnvironment> {
-    pub fn new(compiled_wasm_bytes: Vec<u8>, plugin_info: PluginInfo, plugin_pools: Arc<PluginPools<TEnvironment>>) -> Result<Self, ErrBox> {
+    pub fn new(
+        environment: TEnvironment,
+        compiled_wasm_bytes: Vec<u8>,
+        plugin_info: PluginInfo,
+        file_hash: Option<u64>,
+        plugin_pools: Arc<PluginPools<TEnvironment>>,
+    ) -> Result<Self, ErrBox> {
         let module = create_module(&compiled_wasm_bytes)?;
         Ok(WasmPlugin {
+            environment,
             module,
             plugin_info,
+            file_hash,
             config: None,
             plugin_pools,
         })
@@ -50,6 +63,18 @@ impl<TEnvironment: Environment> Plugin for WasmPlugin<TEnvironment> {
         &self.plugin_info.file_names
     }
 
+    fn interpreters(&self) -> &Vec<String> {
+        &self.plugin_info.interpreters
+    }
+
+    fn languages(&self) -> &Vec<String> {
+        &self.plugin_info.languages
+    }
+
+    fn file_hash(&self) -> Option<u64> {
+        self.file_hash
+    }
+
     fn help_url(&self) -> &str {
         &self.plugin_info.help_url
     }
@@ -68,14 +93,24 @@ impl<TEnvironment: Environment> Plugin for WasmPlugin<TEnvironment> {
 
     fn initialize(&self) -> Result<Box<dyn InitializedPlugin>, ErrBox> {
         let store = wasmer::Store::default();
+        let cancellation_token = SharedCancellationToken::default();
         let mut wasm_plugin = InitializedWasmPlugin::new(self.module.clone(), Box::new({
             let name = self.name().to_string();
+            let module = self.module.clone();
             let plugin_pools = self.plugin_pools.clone();
+            let cancellation_token = cancellation_token.clone();
+            let wasi_output_handler: WasiOutputHandler = Arc::new({
+                let environment = self.environment.clone();
+                let name = name.clone();
+                move |text| environment.log_error_with_context(text, &name)
+            });
             move || {
-                let import_obj_env = ImportObjectEnvironment::new(&name, plugin_pools.clone());
-                create_pools_import_object(&store, &import_obj_env)
+                let import_obj_env = ImportObjectEnvironment::new(&name, plugin_pools.clone(), cancellation_token.clone());
+                let mut import_object = create_pools_import_object(&store, &import_obj_env);
+                add_wasi_imports(&mut import_object, &store, &module, wasi_output_handler.clone());
+                import_object
             }
-        }))?;
+        }), cancellation_token)?;
         let (plugin_config, global_config) = self.config.as_ref().expect("Call set_config first.");
 
         wasm_plugin.set_global_config(&global_config)?;
@@ -88,6 +123,8 @@ impl<TEnvironment: Environment> Plugin for WasmPlugin<TEnvironment> {
 pub struct InitializedWasmPlugin {
     wasm_functions: WasmFunctions,
     buffer_size: usize,
+    capabilities: PluginCapabilities,
+    cancellation_token: SharedCancellationToken,
 
     // below is for recreating an instance after panic
     module: wasmer::Module,
@@ -97,14 +134,20 @@ pub struct InitializedWasmPlugin {
 }
 
 impl InitializedWasmPlugin {
-    pub fn new(module: wasmer::Module, create_import_object: Box<dyn Fn() -> wasmer::ImportObject + Send>) -> Result<Self, ErrBox> {
+    pub fn new(
+        module: wasmer::Module,
+        create_import_object: Box<dyn Fn() -> wasmer::ImportObject + Send>,
+        cancellation_token: SharedCancellationToken,
+    ) -> Result<Self, ErrBox> {
         let instance = load_instance(&module, &create_import_object())?;
         let wasm_functions = WasmFunctions::new(instance)?;
         let buffer_size = wasm_functions.get_wasm_memory_buffer_size()?;
 
-        Ok(InitializedWasmPlugin {
+        let mut plugin = InitializedWasmPlugin {
             wasm_functions,
             buffer_size,
+            capabilities: PluginCapabilities::NONE,
+            cancellation_token,
             module,
             create_import_object,
             global_config: GlobalConfiguration {
@@ -114,7 +157,26 @@ impl InitializedWasmPlugin {
                 new_line_kind: None,
             },
             plugin_config: HashMap::new(),
-        })
+        };
+        plugin.handshake()?;
+
+        Ok(plugin)
+    }
+
+    fn handshake(&mut self) -> Result<(), ErrBox> {
+        self.capabilities = PluginCapabilities::NONE;
+        // plugins built before the handshake existed have nothing to receive the host info
+        let handshake = if self.wasm_functions.has_handshake() {
+            // the handshake is always sent in chunks because the capabilities aren't known yet
+            self.send_string(&serde_json::to_string(&get_wasm_host_info())?);
+            let len = self.wasm_functions.handshake()?;
+            serde_json::from_str::<PluginHandshake>(&self.receive_string(len)?)?
+        } else {
+            PluginHandshake::default()
+        };
+        verify_plugin_handshake(&handshake)?;
+        self.capabilities = handshake.capabilities;
+        Ok(())
     }
 
     pub fn set_global_config(&mut self, global_config: &GlobalConfiguration) -> Result<(), ErrBox> {
@@ -144,6 +206,13 @@ impl InitializedWasmPlugin {
     // a major problem where the CLI is out of sync with the plugin.
 
     fn send_string(&self, text: &str) {
+        if self.capabilities.direct_transfer {
+            let text_bytes = text.as_bytes();
+            let shared_bytes_pointer = self.wasm_functions.allocate_shared_bytes(text_bytes.len()).unwrap();
+            self.write_bytes_to_memory(shared_bytes_pointer, text_bytes);
+            return;
+        }
+
         let mut index = 0;
         let len = text.len();
         let text_bytes = text.as_bytes();
@@ -157,9 +226,13 @@ impl InitializedWasmPlugin {
     }
 
     fn write_bytes_to_memory_buffer(&self, bytes: &[u8]) {
-        let length = bytes.len();
         let wasm_buffer_pointer = self.wasm_functions.get_wasm_memory_buffer_ptr().unwrap();
-        let memory_writer = wasm_buffer_pointer
+        self.write_bytes_to_memory(wasm_buffer_pointer, bytes);
+    }
+
+    fn write_bytes_to_memory(&self, pointer: WasmPtr<u8, Array>, bytes: &[u8]) {
+        let length = bytes.len();
+        let memory_writer = pointer
             .deref(self.wasm_functions.get_memory(), 0, length as u32)
             .unwrap();
         for i in 0..length {
@@ -168,8 +241,14 @@ impl InitializedWasmPlugin {
     }
 
     fn receive_string(&self, len: usize) -> Result<String, ErrBox> {
-        let mut index = 0;
         let mut bytes: Vec<u8> = vec![0; len];
+        if self.capabilities.direct_transfer {
+            let shared_bytes_pointer = self.wasm_functions.get_shared_bytes_ptr()?;
+            self.read_bytes_from_memory(shared_bytes_pointer, &mut bytes);
+            return Ok(String::from_utf8(bytes)?);
+        }
+
+        let mut index = 0;
         while index < len {
             let read_count = std::cmp::min(len - index, self.buffer_size);
             self.wasm_functions.set_buffer_with_shared_bytes(index, read_count).unwrap();
@@ -180,9 +259,13 @@ impl InitializedWasmPlugin {
     }
 
     fn read_bytes_from_memory_buffer(&self, bytes: &mut [u8]) {
-        let length = bytes.len();
         let wasm_buffer_pointer = self.wasm_functions.get_wasm_memory_buffer_ptr().unwrap();
-        let memory_reader = wasm_buffer_pointer
+        self.read_bytes_from_memory(wasm_buffer_pointer, bytes);
+    }
+
+    fn read_bytes_from_memory(&self, pointer: WasmPtr<u8, Array>, bytes: &mut [u8]) {
+        let length = bytes.len();
+        let memory_reader = pointer
             .deref(self.wasm_functions.get_memory(), 0, length as u32)
             .unwrap();
         for i in 0..length {
@@ -207,32 +290,15 @@ impl InitializedWasmPlugin {
 
         self.wasm_functions = wasm_functions;
         self.buffer_size = buffer_size;
+        self.handshake()?;
 
         self.set_global_config(&self.global_config.clone())?;
         self.set_plugin_config(&self.plugin_config.clone())?;
 
         Ok(())
     }
-}
-
-impl InitializedPlugin for InitializedWasmPlugin {
-    fn get_license_text(&self) -> Result<String, ErrBox> {
-        let len = self.wasm_functions.get_license_text()?;
-        self.receive_string(len)
-    }
-
-    fn get_resolved_config(&self) -> Result<String, ErrBox> {
-        let len = self.wasm_functions.get_resolved_config()?;
-        self.receive_string(len)
-    }
 
-    fn get_config_diagnostics(&self) -> Result<Vec<ConfigurationDiagnostic>, ErrBox> {
-        let len = self.wasm_functions.get_config_diagnostics()?;
-        let json_text = self.receive_string(len)?;
-        Ok(serde_json::from_str(&json_text)?)
-    }
-
-    fn format_text(&mut self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap) -> Result<String, ErrBox> {
+    fn format_text_with_instance(&mut self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap) -> Result<FormatTextResult, ErrBox> {
         // send override config if necessary
         if !override_config.is_empty() {
             self.send_string(&serde_json::to_string(override_config)?);
@@ -261,8 +327,8 @@ impl InitializedPlugin for InitializedWasmPlugin {
         };
 
         // handle the response
-        match response_code {
-            FormatResult::NoChange => Ok(String::from(file_text)),
+        let text = match response_code {
+            FormatResult::NoChange => String::from(file_text),
             FormatResult::Change => {
                 let len = match self.wasm_functions.get_formatted_text() {
                     Ok(len) => len,
@@ -272,7 +338,7 @@ impl InitializedPlugin for InitializedWasmPlugin {
                     },
                 };
                 match self.receive_string(len) {
-                    Ok(text) => Ok(text),
+                    Ok(text) => text,
                     Err(err) => {
                         self.reinitialize_due_to_panic(&err);
                         return Err(err);
@@ -287,14 +353,162 @@ impl InitializedPlugin for InitializedWasmPlugin {
                         return Err(err);
                     },
                 };
-                match self.receive_string(len) {
+                return match self.receive_string(len) {
                     Ok(text) => err!("{}", text),
                     Err(err) => {
                         self.reinitialize_due_to_panic(&err);
-                        return Err(err);
+                        Err(err)
                     },
-                }
+                };
             }
+        };
+
+        // get the diagnostics
+        let diagnostics_result = if self.capabilities.diagnostics {
+            self.wasm_functions.get_format_diagnostics()
+        } else {
+            Ok(None)
+        };
+        let diagnostics = match diagnostics_result {
+            Ok(Some(len)) => {
+                let json_text = match self.receive_string(len) {
+                    Ok(json_text) => json_text,
+                    Err(err) => {
+                        self.reinitialize_due_to_panic(&err);
+                        return Err(err);
+                    },
+                };
+                serde_json::from_str::<Vec<FormatDiagnostic>>(&json_text)?
+            },
+            Ok(None) => Vec::new(),
+            Err(err) => {
+                self.reinitialize_due_to_panic(&err);
+                return Err(err);
+            },
+        };
+
+        Ok(FormatTextResult {
+            text,
+            diagnostics,
+        })
+    }
+}
+
+impl InitializedPlugin for InitializedWasmPlugin {
+    fn get_license_text(&self) -> Result<String, ErrBox> {
+        let len = self.wasm_functions.get_license_text()?;
+        self.receive_string(len)
+    }
+
+    fn get_resolved_config(&self) -> Result<String, ErrBox> {
+        let len = self.wasm_functions.get_resolved_config()?;
+        self.receive_string(len)
+    }
+
+    fn get_config_diagnostics(&self) -> Result<Vec<ConfigurationDiagnostic>, ErrBox> {
+        let len = self.wasm_functions.get_config_diagnostics()?;
+        let json_text = self.receive_string(len)?;
+        Ok(serde_json::from_str(&json_text)?)
+    }
+
+    fn format_text(&mut self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap, cancellation_token: &CancellationToken) -> Result<FormatTextResult, ErrBox> {
+        if cancellation_token.is_cancelled() {
+            return err!("The format request was cancelled.");
+        }
+        // the instance's imports trap when this is cancelled
+        *self.cancellation_token.lock() = cancellation_token.clone();
+        // and the instance itself traps at its next function call or loop iteration
+        let _interrupt_guard = self.wasm_functions.get_interrupt_handle().map(|interrupt_handle| {
+            // a previous request may have been cancelled after it finished
+            interrupt_handle.reset();
+            cancellation_token.on_cancelled(move || interrupt_handle.interrupt())
+        });
+
+        match self.format_text_with_instance(file_path, file_text, override_config) {
+            // the instance is reinitialized after trapping, so only the error needs to change
+            Err(_) if cancellation_token.is_cancelled() => err!("The format request was cancelled."),
+            result => result,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::time::Instant;
+    use dprint_core::plugins::{CancellationToken, PluginCapabilities};
+
+    use crate::plugins::InitializedPlugin;
+    use super::super::{compile, create_module, create_identity_import_object, SharedCancellationToken};
+    use super::InitializedWasmPlugin;
+
+    // If this file doesn't exist, run `./build.sh` in /crates/test-plugin.
+    static WASM_PLUGIN_BYTES: &'static [u8] = include_bytes!("../../../../../test-plugin/target/wasm32-unknown-unknown/release/test_plugin.wasm");
+
+    // schema version 3 plugins built before the handshake existed, which trap if
+    // the host sends them anything since they have no handshake to receive it
+    const PLUGIN_WITHOUT_HANDSHAKE_WAT: &'static str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "get_plugin_schema_version") (result i32) i32.const 3)
+        (func (export "get_wasm_memory_buffer_size") (result i32) i32.const 1024)
+        (func (export "clear_shared_bytes") (param i32) unreachable)
+    )"#;
+
+    #[test]
+    fn it_should_initialize_plugin_without_handshake() {
+        let store = wasmer::Store::default();
+        let module = wasmer::Module::new(&store, PLUGIN_WITHOUT_HANDSHAKE_WAT).unwrap();
+        let plugin = InitializedWasmPlugin::new(
+            module,
+            Box::new(move || create_identity_import_object(&store)),
+            SharedCancellationToken::default(),
+        ).unwrap();
+
+        assert_eq!(plugin.capabilities, PluginCapabilities::NONE);
+    }
+
+    // Run with `cargo test -p dprint bench_wasm_text_transfer -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_wasm_text_transfer() {
+        for (file_size, iterations) in [(1_000, 10_000), (2_000_000, 20)].iter() {
+            let direct_transfer_time = time_format(true, *file_size, *iterations);
+            let chunked_time = time_format(false, *file_size, *iterations);
+            println!("{} bytes ({} iterations):", file_size, iterations);
+            println!("  direct transfer: {:?} per format", direct_transfer_time);
+            println!("  chunked:         {:?} per format", chunked_time);
+        }
+    }
+
+    fn time_format(direct_transfer: bool, file_size: usize, iterations: u32) -> std::time::Duration {
+        let mut plugin = create_plugin();
+        // the plugin reads and writes its shared bytes the same way either way,
+        // so only the host needs to change how it transfers the text
+        plugin.capabilities.direct_transfer = direct_transfer;
+        let file_path = PathBuf::from("file.txt");
+        let file_text = "a".repeat(file_size);
+        let override_config = HashMap::new();
+        let cancellation_token = CancellationToken::new();
+        let start = Instant::now();
+        for _ in 0..iterations {
+            let result = plugin.format_text(&file_path, &file_text, &override_config, &cancellation_token).unwrap();
+            assert_eq!(result.text.len(), file_size + "_formatted".len());
+        }
+        start.elapsed() / iterations
+    }
+
+    fn create_plugin() -> InitializedWasmPlugin {
+        let compilation_result = compile(WASM_PLUGIN_BYTES).unwrap();
+        let module = create_module(&compilation_result.bytes).unwrap();
+        let store = wasmer::Store::default();
+        let mut plugin = InitializedWasmPlugin::new(
+            module,
+            Box::new(move || create_identity_import_object(&store)),
+            SharedCancellationToken::default(),
+        ).unwrap();
+        plugin.set_global_config(&plugin.global_config.clone()).unwrap();
+        plugin.set_plugin_config(&HashMap::new()).unwrap();
+        plugin
+    }
+}
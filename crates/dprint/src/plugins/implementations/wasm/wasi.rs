@@ -0,0 +1,220 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use wasmer::{Exports, ExternType, Function, FunctionType, HostEnvInitError, ImportObject, Instance, LazyInit, Memory, Module, RuntimeError, Store, Type, Value, WasmerEnv};
+use parking_lot::Mutex;
+
+const WASI_MODULE_NAME: &str = "wasi_snapshot_preview1";
+
+// https://github.com/WebAssembly/WASI/blob/main/phases/snapshot/docs.md#errno
+const ERRNO_SUCCESS: u32 = 0;
+const ERRNO_BADF: u32 = 8;
+const ERRNO_FAULT: u32 = 21;
+const ERRNO_INVAL: u32 = 28;
+const ERRNO_NOSYS: u32 = 52;
+const ERRNO_SPIPE: u32 = 70;
+
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const RIGHTS_FD_READ: u64 = 1 << 1;
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+
+/// Receives each line a WASI plugin writes to stdout or stderr.
+pub type WasiOutputHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+#[derive(Clone)]
+struct WasiEnvironment {
+    memory: LazyInit<Memory>,
+    output_handler: WasiOutputHandler,
+    /// Output that hasn't ended with a newline yet for stdout and stderr.
+    pending_output: Arc<Mutex<[Vec<u8>; 2]>>,
+}
+
+impl WasmerEnv for WasiEnvironment {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        let memory = instance.exports.get_memory("memory")?;
+        self.memory.initialize(memory.clone());
+        Ok(())
+    }
+}
+
+impl WasiEnvironment {
+    fn read_bytes(&self, pointer: u32, length: u32) -> Option<Vec<u8>> {
+        let view = self.memory.get_ref()?.view::<u8>();
+        let start = pointer as usize;
+        let end = start.checked_add(length as usize)?;
+        if end > view.len() {
+            return None;
+        }
+        Some(view[start..end].iter().map(|cell| cell.get()).collect())
+    }
+
+    fn write_bytes(&self, pointer: u32, bytes: &[u8]) -> u32 {
+        let view = match self.memory.get_ref() {
+            Some(memory) => memory.view::<u8>(),
+            None => return ERRNO_FAULT,
+        };
+        let start = pointer as usize;
+        let end = start + bytes.len();
+        if end > view.len() {
+            return ERRNO_FAULT;
+        }
+        for (cell, byte) in view[start..end].iter().zip(bytes) {
+            cell.set(*byte);
+        }
+        ERRNO_SUCCESS
+    }
+
+    fn write_u32(&self, pointer: u32, value: u32) -> u32 {
+        self.write_bytes(pointer, &value.to_le_bytes())
+    }
+
+    fn write_output(&self, fd: u32, bytes: &[u8]) {
+        let mut pending_output = self.pending_output.lock();
+        let pending = &mut pending_output[fd as usize - 1];
+        pending.extend(bytes);
+        // only provide complete lines so output from concurrent instances doesn't interleave mid-line
+        while let Some(index) = pending.iter().position(|b| *b == b'\n') {
+            let line = pending.drain(..=index).collect::<Vec<_>>();
+            let text = String::from_utf8_lossy(&line);
+            (self.output_handler)(text.trim_end_matches(|c| c == '\n' || c == '\r'));
+        }
+    }
+}
+
+/// Adds a sandboxed WASI environment to the import object when the module imports WASI functions.
+///
+/// The plugin has no access to the file system, network, environment variables, or arguments.
+/// Stdin is always empty and lines written to stdout and stderr are provided to the output handler.
+/// WASI functions that aren't supported return `ENOSYS`.
+pub fn add_wasi_imports(import_object: &mut ImportObject, store: &Store, module: &Module, output_handler: WasiOutputHandler) {
+    let wasi_imports = module
+        .imports()
+        .filter(|import| import.module() == WASI_MODULE_NAME)
+        .filter_map(|import| match import.ty() {
+            ExternType::Function(function_type) => Some((import.name().to_string(), function_type.clone())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if wasi_imports.is_empty() {
+        return;
+    }
+
+    let env = WasiEnvironment {
+        memory: LazyInit::new(),
+        output_handler,
+        pending_output: Arc::new(Mutex::new([Vec::new(), Vec::new()])),
+    };
+    let mut exports = Exports::new();
+    for (name, function_type) in wasi_imports {
+        let function = create_wasi_function(store, &env, &name).unwrap_or_else(|| create_unsupported_function(store, &function_type));
+        exports.insert(name, function);
+    }
+    import_object.register(WASI_MODULE_NAME, exports);
+}
+
+fn create_wasi_function(store: &Store, env: &WasiEnvironment, name: &str) -> Option<Function> {
+    let env = env.clone();
+    Some(match name {
+        "fd_write" => Function::new_native_with_env(store, env, |env: &WasiEnvironment, fd: u32, iovs: u32, iovs_len: u32, nwritten: u32| -> u32 {
+            if fd != 1 && fd != 2 {
+                return ERRNO_BADF;
+            }
+            let mut bytes = Vec::new();
+            for i in 0..iovs_len {
+                let iovec = match i.checked_mul(8).and_then(|offset| iovs.checked_add(offset)).and_then(|pointer| env.read_bytes(pointer, 8)) {
+                    Some(iovec) => iovec,
+                    None => return ERRNO_FAULT,
+                };
+                let buf = u32::from_le_bytes([iovec[0], iovec[1], iovec[2], iovec[3]]);
+                let buf_len = u32::from_le_bytes([iovec[4], iovec[5], iovec[6], iovec[7]]);
+                match env.read_bytes(buf, buf_len) {
+                    Some(buf_bytes) => bytes.extend(buf_bytes),
+                    None => return ERRNO_FAULT,
+                }
+            }
+            env.write_output(fd, &bytes);
+            env.write_u32(nwritten, bytes.len() as u32)
+        }),
+        "fd_read" => Function::new_native_with_env(store, env, |env: &WasiEnvironment, fd: u32, _iovs: u32, _iovs_len: u32, nread: u32| -> u32 {
+            if fd != 0 {
+                return ERRNO_BADF;
+            }
+            env.write_u32(nread, 0) // stdin is always empty
+        }),
+        "fd_close" => Function::new_native_with_env(store, env, |_: &WasiEnvironment, fd: u32| -> u32 {
+            if fd <= 2 { ERRNO_SUCCESS } else { ERRNO_BADF }
+        }),
+        "fd_seek" => Function::new_native_with_env(store, env, |_: &WasiEnvironment, fd: u32, _offset: i64, _whence: u32, _new_offset: u32| -> u32 {
+            if fd <= 2 { ERRNO_SPIPE } else { ERRNO_BADF }
+        }),
+        "fd_fdstat_get" => Function::new_native_with_env(store, env, |env: &WasiEnvironment, fd: u32, stat: u32| -> u32 {
+            let rights = match fd {
+                0 => RIGHTS_FD_READ,
+                1 | 2 => RIGHTS_FD_WRITE,
+                _ => return ERRNO_BADF,
+            };
+            let mut bytes = [0; 24];
+            bytes[0] = FILETYPE_CHARACTER_DEVICE;
+            bytes[8..16].copy_from_slice(&rights.to_le_bytes());
+            env.write_bytes(stat, &bytes)
+        }),
+        // there are no preopened directories, which is what prevents access to the file system
+        "fd_prestat_get" => Function::new_native_with_env(store, env, |_: &WasiEnvironment, _fd: u32, _prestat: u32| -> u32 { ERRNO_BADF }),
+        "fd_prestat_dir_name" => Function::new_native_with_env(store, env, |_: &WasiEnvironment, _fd: u32, _path: u32, _path_len: u32| -> u32 { ERRNO_BADF }),
+        "environ_sizes_get" | "args_sizes_get" => Function::new_native_with_env(store, env, |env: &WasiEnvironment, count: u32, buf_size: u32| -> u32 {
+            match env.write_u32(count, 0) {
+                ERRNO_SUCCESS => env.write_u32(buf_size, 0),
+                errno => errno,
+            }
+        }),
+        "environ_get" | "args_get" => Function::new_native_with_env(store, env, |_: &WasiEnvironment, _values: u32, _buf: u32| -> u32 { ERRNO_SUCCESS }),
+        "clock_res_get" => Function::new_native_with_env(store, env, |env: &WasiEnvironment, clock_id: u32, resolution: u32| -> u32 {
+            if clock_id > 3 {
+                return ERRNO_INVAL;
+            }
+            env.write_bytes(resolution, &1_000u64.to_le_bytes())
+        }),
+        "clock_time_get" => Function::new_native_with_env(store, env, |env: &WasiEnvironment, clock_id: u32, _precision: u64, time: u32| -> u32 {
+            if clock_id > 3 {
+                return ERRNO_INVAL;
+            }
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+            env.write_bytes(time, &nanos.to_le_bytes())
+        }),
+        "random_get" => Function::new_native_with_env(store, env, |env: &WasiEnvironment, buf: u32, buf_len: u32| -> u32 {
+            env.write_bytes(buf, &get_random_bytes(buf_len as usize))
+        }),
+        "sched_yield" => Function::new_native_with_env(store, env, |_: &WasiEnvironment| -> u32 { ERRNO_SUCCESS }),
+        "proc_exit" => Function::new_native_with_env(store, env, |_: &WasiEnvironment, code: u32| -> Result<(), RuntimeError> {
+            Err(RuntimeError::new(format!("The plugin exited with code {}.", code)))
+        }),
+        _ => return None,
+    })
+}
+
+fn create_unsupported_function(store: &Store, function_type: &FunctionType) -> Function {
+    let result_types = function_type.results().to_vec();
+    Function::new(store, function_type, move |_| {
+        Ok(result_types.iter().map(|result_type| match result_type {
+            Type::I32 => Value::I32(ERRNO_NOSYS as i32),
+            Type::I64 => Value::I64(0),
+            Type::F32 => Value::F32(0.0),
+            Type::F64 => Value::F64(0.0),
+            _ => Value::I32(0),
+        }).collect())
+    })
+}
+
+fn get_random_bytes(length: usize) -> Vec<u8> {
+    // RandomState is seeded randomly by the standard library, which is enough for what plugins use this for (ex. hash map seeds)
+    let mut bytes = Vec::with_capacity(length);
+    let mut counter = 0u64;
+    while bytes.len() < length {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(counter);
+        bytes.extend(hasher.finish().to_le_bytes().iter().take(length - bytes.len()));
+        counter += 1;
+    }
+    bytes
+}
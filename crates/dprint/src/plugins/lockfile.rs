@@ -0,0 +1,177 @@
+use serde::{Serialize, Deserialize};
+use std::path::Path;
+
+use dprint_core::plugins::PluginInfo;
+use dprint_core::types::ErrBox;
+
+use crate::environment::Environment;
+
+pub const LOCKFILE_NAME: &str = "dprint.lock";
+const LOCKFILE_VERSION: usize = 2;
+
+/// Records the exact plugins that were resolved for a configuration so
+/// that every run formats with identical plugin bytes.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginsLockfile {
+    lockfile_version: usize,
+    pub plugins: Vec<PluginsLockfileEntry>,
+}
+
+impl PluginsLockfile {
+    pub fn new(plugins: Vec<PluginsLockfileEntry>) -> PluginsLockfile {
+        PluginsLockfile {
+            lockfile_version: LOCKFILE_VERSION,
+            plugins,
+        }
+    }
+
+    pub fn get_entry(&self, url: &str) -> Option<&PluginsLockfileEntry> {
+        self.plugins.iter().find(|entry| entry.url == url)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginsLockfileEntry {
+    pub url: String,
+    /// SHA-256 checksum of the downloaded plugin file.
+    pub checksum: String,
+    /// Information about the plugin, which allows only downloading and setting
+    /// up the plugin once there's a file for it to format.
+    #[serde(flatten)]
+    pub info: PluginInfo,
+}
+
+/// Reads the lockfile, returning `None` when it doesn't exist.
+///
+/// The version is checked before the rest of the file is deserialized so that a lockfile
+/// created by a different version of dprint errors with how to update it.
+pub fn read_lockfile(file_path: &Path, environment: &impl Environment) -> Result<Option<PluginsLockfile>, ErrBox> {
+    if !environment.path_exists(file_path) {
+        return Ok(None);
+    }
+
+    let text = environment.read_file(file_path)?;
+    let value = match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(value) => value,
+        Err(err) => return err!("Error deserializing {}. {}", file_path.display(), err.to_string()),
+    };
+
+    let lockfile_version = value.get("lockfileVersion").and_then(|version| version.as_u64());
+    if lockfile_version != Some(LOCKFILE_VERSION as u64) {
+        return err!(
+            "The lockfile at {} is version {}, but this version of dprint expects version {}. Run `dprint plugins update` to update it.",
+            file_path.display(),
+            lockfile_version.map(|version| version.to_string()).unwrap_or_else(|| String::from("unknown")),
+            LOCKFILE_VERSION,
+        );
+    }
+
+    match serde_json::from_value::<PluginsLockfile>(value) {
+        Ok(lockfile) => Ok(Some(lockfile)),
+        Err(err) => err!("Error deserializing {}. {}", file_path.display(), err.to_string()),
+    }
+}
+
+pub fn write_lockfile(file_path: &Path, lockfile: &PluginsLockfile, environment: &impl Environment) -> Result<(), ErrBox> {
+    let mut text = serde_json::to_string_pretty(lockfile)?;
+    text.push('\n');
+    environment.write_file(file_path, &text)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use pretty_assertions::assert_eq;
+    use super::*;
+    use crate::environment::TestEnvironment;
+
+    #[test]
+    fn it_should_write_and_read_lockfile() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/dprint.lock");
+        let lockfile = PluginsLockfile::new(vec![PluginsLockfileEntry {
+            url: String::from("https://plugins.dprint.dev/test.wasm"),
+            checksum: String::from("checksum"),
+            info: PluginInfo {
+                name: String::from("test-plugin"),
+                version: String::from("0.1.0"),
+                config_key: String::from("test"),
+                file_extensions: vec![String::from("txt")],
+                file_names: vec![],
+                interpreters: vec![],
+                languages: vec![],
+                help_url: String::from("https://dprint.dev/plugins/test"),
+                config_schema_url: String::from(""),
+            },
+        }]);
+        write_lockfile(&file_path, &lockfile, &environment).unwrap();
+
+        assert_eq!(
+            environment.read_file(&file_path).unwrap(),
+            r#"{
+  "lockfileVersion": 2,
+  "plugins": [
+    {
+      "url": "https://plugins.dprint.dev/test.wasm",
+      "checksum": "checksum",
+      "name": "test-plugin",
+      "version": "0.1.0",
+      "configKey": "test",
+      "fileExtensions": [
+        "txt"
+      ],
+      "fileNames": [],
+      "interpreters": [],
+      "languages": [],
+      "helpUrl": "https://dprint.dev/plugins/test",
+      "configSchemaUrl": ""
+    }
+  ]
+}
+"#
+        );
+        assert_eq!(read_lockfile(&file_path, &environment).unwrap(), Some(lockfile));
+    }
+
+    #[test]
+    fn it_should_not_read_lockfile_that_does_not_exist() {
+        let environment = TestEnvironment::new();
+        assert_eq!(read_lockfile(&PathBuf::from("/dprint.lock"), &environment).unwrap(), None);
+    }
+
+    #[test]
+    fn it_should_error_for_lockfile_of_different_version() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/dprint.lock");
+        // version 1 entries don't have the plugin information required by the current version
+        environment.write_file(&file_path, r#"{
+  "lockfileVersion": 1,
+  "plugins": [{ "url": "https://plugins.dprint.dev/test.wasm", "name": "test-plugin", "version": "0.1.0", "configKey": "test", "checksum": "checksum" }]
+}"#).unwrap();
+        assert_eq!(
+            read_lockfile(&file_path, &environment).err().unwrap().to_string(),
+            "The lockfile at /dprint.lock is version 1, but this version of dprint expects version 2. Run `dprint plugins update` to update it.",
+        );
+    }
+
+    #[test]
+    fn it_should_error_for_lockfile_without_version() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/dprint.lock");
+        environment.write_file(&file_path, r#"{ "plugins": [] }"#).unwrap();
+        assert_eq!(
+            read_lockfile(&file_path, &environment).err().unwrap().to_string(),
+            "The lockfile at /dprint.lock is version unknown, but this version of dprint expects version 2. Run `dprint plugins update` to update it.",
+        );
+    }
+
+    #[test]
+    fn it_should_error_for_invalid_lockfile() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/dprint.lock");
+        environment.write_file(&file_path, "{").unwrap();
+        assert_eq!(read_lockfile(&file_path, &environment).err().unwrap().to_string().starts_with("Error deserializing /dprint.lock."), true);
+    }
+}
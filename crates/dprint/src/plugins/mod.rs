@@ -3,7 +3,10 @@ mod implementations;
 mod plugin;
 mod cache;
 mod cache_manifest;
+mod lockfile;
+mod policy;
 mod resolver;
+mod signatures;
 mod pool;
 mod repo;
 mod types;
@@ -13,10 +16,13 @@ pub use helpers::*;
 pub use plugin::*;
 pub use cache::*;
 use cache_manifest::*;
+pub use lockfile::*;
+pub use policy::*;
 pub use resolver::*;
+pub use signatures::*;
 pub use pool::*;
 pub use repo::*;
 pub use types::*;
 pub use worker::*;
 
-pub use implementations::compile_wasm;
\ No newline at end of file
+pub use implementations::{compile_wasm, get_referenced_plugin_files};
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::path::Path;
 
 use dprint_core::configuration::{ConfigurationDiagnostic, GlobalConfiguration, ConfigKeyMap, ConfigKeyValue};
+use dprint_core::plugins::{FormatTextResult, CancellationToken};
 use dprint_core::types::ErrBox;
 
 pub trait Plugin : std::marker::Send + std::marker::Sync {
@@ -14,6 +15,13 @@ pub trait Plugin : std::marker::Send + std::marker::Sync {
     fn file_extensions(&self) -> &Vec<String>;
     /// Gets the exact file names.
     fn file_names(&self) -> &Vec<String>;
+    /// Gets the interpreter names found in the shebangs of extension-less files.
+    fn interpreters(&self) -> &Vec<String>;
+    /// Gets the language ids and media types of embedded code formatted with the host.
+    fn languages(&self) -> &Vec<String>;
+    /// Gets a hash of the plugin's file when known. This changes when a plugin
+    /// is rebuilt even if its version stays the same.
+    fn file_hash(&self) -> Option<u64>;
     /// Gets the help url.
     fn help_url(&self) -> &str;
     /// Gets the configuration schema url.
@@ -34,6 +42,9 @@ pub trait Plugin : std::marker::Send + std::marker::Sync {
         // list everything in here that would affect formatting
         hash_str.push_str(&self.name());
         hash_str.push_str(&self.version());
+        if let Some(file_hash) = self.file_hash() {
+            hash_str.push_str(&file_hash.to_string());
+        }
 
         // serialize the config keys in order to prevent the hash from changing
         let sorted_config: std::collections::BTreeMap::<&String, &ConfigKeyValue> = config.0.iter().collect();
@@ -53,7 +64,9 @@ pub trait InitializedPlugin : std::marker::Send {
     /// Gets the configuration diagnostics.
     fn get_config_diagnostics(&self) -> Result<Vec<ConfigurationDiagnostic>, ErrBox>;
     /// Formats the text in memory based on the file path and file text.
-    fn format_text(&mut self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap) -> Result<String, ErrBox>;
+    ///
+    /// The plugin should stop formatting and return an error when the cancellation token is cancelled.
+    fn format_text(&mut self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap, cancellation_token: &CancellationToken) -> Result<FormatTextResult, ErrBox>;
 }
 
 #[cfg(test)]
@@ -62,6 +75,8 @@ pub struct TestPlugin {
     config_key: String,
     file_extensions: Vec<String>,
     file_names: Vec<String>,
+    interpreters: Vec<String>,
+    languages: Vec<String>,
     initialized_test_plugin: Option<InitializedTestPlugin>,
     config: (ConfigKeyMap, GlobalConfiguration),
 }
@@ -74,6 +89,8 @@ impl TestPlugin {
             config_key: String::from(config_key),
             file_extensions: file_extensions.into_iter().map(String::from).collect(),
             file_names: file_names.into_iter().map(String::from).collect(),
+            interpreters: Vec::new(),
+            languages: Vec::new(),
             initialized_test_plugin: Some(InitializedTestPlugin::new()),
             config: (std::collections::HashMap::new(), GlobalConfiguration {
                 line_width: None,
@@ -83,6 +100,11 @@ impl TestPlugin {
             })
         }
     }
+
+    pub fn with_languages(mut self, languages: Vec<&'static str>) -> TestPlugin {
+        self.languages = languages.into_iter().map(String::from).collect();
+        self
+    }
 }
 
 #[cfg(test)]
@@ -94,6 +116,9 @@ impl Plugin for TestPlugin {
     fn config_key(&self) -> &str { &self.config_key }
     fn file_extensions(&self) -> &Vec<String> { &self.file_extensions }
     fn file_names(&self) -> &Vec<String> { &self.file_names }
+    fn interpreters(&self) -> &Vec<String> { &self.interpreters }
+    fn languages(&self) -> &Vec<String> { &self.languages }
+    fn file_hash(&self) -> Option<u64> { None }
     fn set_config(&mut self, _: ConfigKeyMap, _: GlobalConfiguration) {}
     fn get_config(&self) -> &(ConfigKeyMap, GlobalConfiguration) {
         &self.config
@@ -120,7 +145,10 @@ impl InitializedPlugin for InitializedTestPlugin {
     fn get_license_text(&self) -> Result<String, ErrBox> { Ok(String::from("License Text")) }
     fn get_resolved_config(&self) -> Result<String, ErrBox> { Ok(String::from("{}")) }
     fn get_config_diagnostics(&self) -> Result<Vec<ConfigurationDiagnostic>, ErrBox> { Ok(vec![]) }
-    fn format_text(&mut self, _: &Path, text: &str, _: &ConfigKeyMap) -> Result<String, ErrBox> {
-        Ok(format!("{}_formatted", text))
+    fn format_text(&mut self, _: &Path, text: &str, _: &ConfigKeyMap, _: &CancellationToken) -> Result<FormatTextResult, ErrBox> {
+        Ok(FormatTextResult {
+            text: format!("{}_formatted", text),
+            diagnostics: Vec::new(),
+        })
     }
 }
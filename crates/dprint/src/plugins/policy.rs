@@ -0,0 +1,179 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use dprint_core::types::ErrBox;
+
+use crate::environment::Environment;
+use crate::plugins::PluginSourceReference;
+use crate::utils::PathSource;
+
+/// Organization wide policy of which process plugins may be used. Wasm plugins
+/// are sandboxed, so they're always allowed.
+#[derive(Clone, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PluginPolicy {
+    /// Don't allow any process plugins.
+    #[serde(default)]
+    pub deny_process_plugins: bool,
+    /// Urls of the process plugins that are allowed.
+    #[serde(default)]
+    pub allowed_process_plugin_urls: Vec<String>,
+    /// Hosts (and their subdomains) that process plugins are allowed to be downloaded from.
+    #[serde(default)]
+    pub allowed_process_plugin_hosts: Vec<String>,
+}
+
+impl PluginPolicy {
+    fn verify_process_plugin(&self, path_source: &PathSource) -> Result<(), &'static str> {
+        if self.deny_process_plugins {
+            return Err("Process plugins are denied.");
+        }
+        if self.allowed_process_plugin_urls.is_empty() && self.allowed_process_plugin_hosts.is_empty() {
+            return Ok(());
+        }
+
+        match path_source {
+            PathSource::Remote(remote_source) => {
+                let url = remote_source.url.as_str();
+                let host = remote_source.url.host_str().unwrap_or("");
+                if self.allowed_process_plugin_urls.iter().any(|allowed_url| allowed_url == url)
+                    || self.allowed_process_plugin_hosts.iter().any(|allowed_host| is_host_match(host, allowed_host))
+                {
+                    Ok(())
+                } else {
+                    Err("Process plugins must be from one of the allowed urls or hosts.")
+                }
+            }
+            PathSource::Local(_) => Err("Local process plugins are not allowed when the policy specifies allowed urls or hosts."),
+        }
+    }
+}
+
+fn is_host_match(host: &str, allowed_host: &str) -> bool {
+    let host = host.to_lowercase();
+    let allowed_host = allowed_host.to_lowercase();
+    host == allowed_host || host.ends_with(&format!(".{}", allowed_host))
+}
+
+/// Errors when any of the plugins are not allowed by the plugin policy file, if it exists.
+pub fn verify_plugin_policy(plugin_references: &[PluginSourceReference], environment: &impl Environment) -> Result<(), ErrBox> {
+    let file_path = match environment.get_plugin_policy_file_path() {
+        Some(file_path) => file_path,
+        None => return Ok(()),
+    };
+    let policy = match read_plugin_policy(&file_path, environment)? {
+        Some(policy) => policy,
+        None => return Ok(()),
+    };
+
+    for plugin_reference in plugin_references.iter().filter(|r| !r.is_wasm_plugin()) {
+        if let Err(reason) = policy.verify_process_plugin(&plugin_reference.path_source) {
+            return err!(
+                "The plugin {} is not allowed by the plugin policy at {}. {}",
+                plugin_reference.display(),
+                file_path.display(),
+                reason,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn read_plugin_policy(file_path: &Path, environment: &impl Environment) -> Result<Option<PluginPolicy>, ErrBox> {
+    if !environment.path_exists(file_path) {
+        return Ok(None);
+    }
+
+    let text = environment.read_file(file_path)?;
+    match serde_json::from_str::<PluginPolicy>(&text) {
+        Ok(policy) => Ok(Some(policy)),
+        Err(err) => err!("Error deserializing plugin policy at {}. {}", file_path.display(), err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use pretty_assertions::assert_eq;
+    use super::*;
+    use crate::environment::TestEnvironment;
+
+    #[test]
+    fn it_should_allow_any_plugin_when_no_policy() {
+        let environment = TestEnvironment::new();
+        let plugin_references = vec![PluginSourceReference::new_remote_from_str("https://example.com/test.exe-plugin")];
+        assert_eq!(verify_plugin_policy(&plugin_references, &environment).is_ok(), true);
+    }
+
+    #[test]
+    fn it_should_deny_process_plugins() {
+        let environment = TestEnvironment::new();
+        write_policy(&environment, r#"{ "denyProcessPlugins": true }"#);
+        let plugin_references = vec![
+            PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.wasm"),
+            PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.exe-plugin"),
+        ];
+        assert_eq!(
+            verify_plugin_policy(&plugin_references, &environment).err().unwrap().to_string(),
+            "The plugin https://plugins.dprint.dev/test.exe-plugin is not allowed by the plugin policy at /config/plugin-policy.json. Process plugins are denied."
+        );
+
+        // wasm plugins are still allowed
+        assert_eq!(verify_plugin_policy(&plugin_references[0..1], &environment).is_ok(), true);
+    }
+
+    #[test]
+    fn it_should_allow_process_plugins_with_allowed_urls_or_hosts() {
+        let environment = TestEnvironment::new();
+        write_policy(&environment, r#"{
+            "allowedProcessPluginUrls": ["https://example.com/test.exe-plugin"],
+            "allowedProcessPluginHosts": ["plugins.dprint.dev", "corp.example"]
+        }"#);
+        let plugin_references = vec![
+            PluginSourceReference::new_remote_from_str("https://example.com/test.exe-plugin"),
+            PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.exe-plugin"),
+            PluginSourceReference::new_remote_from_str("https://plugins.CORP.example/test.exe-plugin"),
+        ];
+        assert_eq!(verify_plugin_policy(&plugin_references, &environment).is_ok(), true);
+
+        let plugin_references = vec![PluginSourceReference::new_remote_from_str("https://example.com/other.exe-plugin")];
+        assert_eq!(
+            verify_plugin_policy(&plugin_references, &environment).err().unwrap().to_string(),
+            concat!(
+                "The plugin https://example.com/other.exe-plugin is not allowed by the plugin policy at /config/plugin-policy.json. ",
+                "Process plugins must be from one of the allowed urls or hosts."
+            )
+        );
+
+        let plugin_references = vec![PluginSourceReference::new_remote_from_str("https://notplugins.dprint.dev/test.exe-plugin")];
+        assert_eq!(verify_plugin_policy(&plugin_references, &environment).is_err(), true);
+    }
+
+    #[test]
+    fn it_should_not_allow_local_process_plugins_when_allowlist_specified() {
+        let environment = TestEnvironment::new();
+        write_policy(&environment, r#"{ "allowedProcessPluginHosts": ["plugins.dprint.dev"] }"#);
+        let plugin_references = vec![PluginSourceReference::new_local(PathBuf::from("/test.exe-plugin"))];
+        assert_eq!(
+            verify_plugin_policy(&plugin_references, &environment).err().unwrap().to_string(),
+            concat!(
+                "The plugin /test.exe-plugin is not allowed by the plugin policy at /config/plugin-policy.json. ",
+                "Local process plugins are not allowed when the policy specifies allowed urls or hosts."
+            )
+        );
+    }
+
+    #[test]
+    fn it_should_error_for_unknown_policy_property() {
+        let environment = TestEnvironment::new();
+        write_policy(&environment, r#"{ "denyProcessPlugin": true }"#);
+        let plugin_references = vec![PluginSourceReference::new_remote_from_str("https://plugins.dprint.dev/test.exe-plugin")];
+        let err_text = verify_plugin_policy(&plugin_references, &environment).err().unwrap().to_string();
+        assert_eq!(err_text.starts_with("Error deserializing plugin policy at /config/plugin-policy.json. unknown field `denyProcessPlugin`"), true);
+    }
+
+    fn write_policy(environment: &TestEnvironment, text: &str) {
+        environment.write_file(&environment.get_plugin_policy_file_path().unwrap(), text).unwrap();
+    }
+}
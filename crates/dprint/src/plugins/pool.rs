@@ -1,16 +1,42 @@
 use std::sync::Arc;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use parking_lot::{Mutex, RwLock};
 
+use dprint_core::configuration::{ConfigurationDiagnostic, ConfigKeyMap};
+use dprint_core::plugins::{FormatTextResult, CancellationToken};
 use dprint_core::types::ErrBox;
 
 use crate::environment::Environment;
-use crate::utils::{get_lowercase_file_extension, get_lowercase_file_name};
-use crate::utils::ErrorCountLogger;
+use crate::utils::{get_lowercase_file_extension, get_lowercase_file_name, get_shebang_interpreter, strip_interpreter_version, DEFAULT_INTERPRETER_EXTENSIONS};
+use crate::utils::{get_bytes_hash, ErrorCountLogger};
 use super::{Plugin, InitializedPlugin, output_plugin_config_diagnostics};
 
+/// The default number of nested host format requests allowed, which prevents plugins that
+/// format each other's embedded code from recursing forever.
+pub const DEFAULT_MAX_HOST_FORMAT_DEPTH: usize = 10;
+
+/// The number of bytes read from the start of a file without an extension to find its shebang.
+const SHEBANG_MAX_LEN: usize = 256;
+
+thread_local! {
+    /// Hashes of the plugins that formatted embedded text for the file being formatted on this thread
+    /// or `None` when they're not being collected. Every protocol calls back into the host on the
+    /// thread that made the format request, so nested requests are collected as well.
+    static HOST_FORMAT_PLUGIN_HASHES: RefCell<Option<Vec<u64>>> = RefCell::new(None);
+}
+
+/// Runs the action, collecting the hashes of the plugins used to format embedded text on this thread.
+pub fn collect_host_format_plugin_hashes<TResult>(action: impl FnOnce() -> TResult) -> (TResult, Vec<u64>) {
+    HOST_FORMAT_PLUGIN_HASHES.with(|hashes| hashes.replace(Some(Vec::new())));
+    let result = action();
+    let hashes = HOST_FORMAT_PLUGIN_HASHES.with(|hashes| hashes.replace(None)).unwrap_or_default();
+    (result, hashes)
+}
+
 /// This is necessary because of a circular reference where
 /// PluginPools hold plugins and the plugins hold a PluginPools.
 pub struct PluginsDropper<TEnvironment: Environment> {
@@ -32,6 +58,38 @@ impl<TEnvironment: Environment> PluginsDropper<TEnvironment> {
 struct PluginNameResolutionMaps {
     extension_to_plugin_name_map: HashMap<String, String>,
     file_name_to_plugin_name_map: HashMap<String, String>,
+    interpreter_to_plugin_name_map: HashMap<String, String>,
+    /// Language ids and media types of embedded code mapped to the plugin that formats it.
+    language_to_plugin_map: HashMap<String, LanguagePlugin>,
+    /// Interpreters mapped to a file extension in the configuration file.
+    interpreter_to_extension_map: HashMap<String, String>,
+}
+
+impl PluginNameResolutionMaps {
+    fn get_plugin_name_from_interpreter(&self, interpreter: &str) -> Option<&String> {
+        let stripped_interpreter = strip_interpreter_version(interpreter);
+        let interpreters = [interpreter, stripped_interpreter];
+
+        // the configuration takes precedence, then the plugins, then the defaults
+        if let Some(extension) = interpreters.iter().find_map(|i| self.interpreter_to_extension_map.get(*i)) {
+            return self.extension_to_plugin_name_map.get(extension);
+        }
+        if let Some(plugin_name) = interpreters.iter().find_map(|i| self.interpreter_to_plugin_name_map.get(*i)) {
+            return Some(plugin_name);
+        }
+        interpreters.iter()
+            .find_map(|i| DEFAULT_INTERPRETER_EXTENSIONS.iter().find(|(default_interpreter, _)| default_interpreter == i))
+            .and_then(|(_, extension)| self.extension_to_plugin_name_map.get(*extension))
+    }
+}
+
+/// The plugin that formats embedded code of a language.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LanguagePlugin {
+    pub plugin_name: String,
+    /// A file name the plugin formats (ex. `file.ts`), which is used to build the
+    /// file path the plugin is given since a language isn't a file path.
+    pub file_name: Option<String>,
 }
 
 pub struct PluginPools<TEnvironment : Environment> {
@@ -41,6 +99,7 @@ pub struct PluginPools<TEnvironment : Environment> {
     /// Plugins may format using other plugins. If so, they should have a locally
     /// owned plugin instance that will be created on demand.
     plugins_for_plugins: Mutex<HashMap<String, HashMap<String, Vec<Box<dyn InitializedPlugin>>>>>,
+    max_host_format_depth: AtomicUsize,
 }
 
 impl<TEnvironment : Environment> PluginPools<TEnvironment> {
@@ -51,8 +110,12 @@ impl<TEnvironment : Environment> PluginPools<TEnvironment> {
             plugin_name_maps: RwLock::new(PluginNameResolutionMaps {
                 extension_to_plugin_name_map: HashMap::new(),
                 file_name_to_plugin_name_map: HashMap::new(),
+                interpreter_to_plugin_name_map: HashMap::new(),
+                language_to_plugin_map: HashMap::new(),
+                interpreter_to_extension_map: HashMap::new(),
             }),
             plugins_for_plugins: Mutex::new(HashMap::new()),
+            max_host_format_depth: AtomicUsize::new(DEFAULT_MAX_HOST_FORMAT_DEPTH),
         }
     }
 
@@ -70,13 +133,21 @@ impl<TEnvironment : Environment> PluginPools<TEnvironment> {
         }
     }
 
-    pub fn set_plugins(&self, plugins: Vec<Box<dyn Plugin>>) {
+    /// Sets the plugins to use. The interpreter extensions map interpreter names
+    /// found in shebangs to file extensions and take precedence over the plugins.
+    pub fn set_plugins(&self, plugins: Vec<Box<dyn Plugin>>, interpreter_extensions: &HashMap<String, String>, max_host_format_depth: usize) {
+        self.max_host_format_depth.store(max_host_format_depth, Ordering::SeqCst);
         let mut pools = self.pools.lock();
         let mut plugin_name_maps = self.plugin_name_maps.write();
+        plugin_name_maps.interpreter_to_extension_map = interpreter_extensions.iter()
+            .map(|(interpreter, extension)| (interpreter.to_lowercase(), extension.trim_start_matches('.').to_lowercase()))
+            .collect();
         for plugin in plugins {
             let plugin_name = String::from(plugin.name());
             let plugin_extensions = plugin.file_extensions().clone();
             let plugin_file_names = plugin.file_names().clone();
+            let plugin_interpreters = plugin.interpreters().clone();
+            let plugin_languages = plugin.languages().clone();
             pools.insert(plugin_name.clone(), Arc::new(InitializedPluginPool::new(plugin, self.environment.clone())));
             for extension in plugin_extensions.iter() {
                 // first added plugin takes precedence
@@ -86,7 +157,59 @@ impl<TEnvironment : Environment> PluginPools<TEnvironment> {
                 // first added plugin takes precedence
                 plugin_name_maps.file_name_to_plugin_name_map.entry(file_name.to_owned()).or_insert(plugin_name.clone());
             }
+            for interpreter in plugin_interpreters.iter() {
+                // first added plugin takes precedence
+                plugin_name_maps.interpreter_to_plugin_name_map.entry(interpreter.to_lowercase()).or_insert(plugin_name.clone());
+            }
+            for language in plugin_languages.iter() {
+                let language = normalize_language(language);
+                let file_name = get_language_file_name(&language, &plugin_extensions, &plugin_file_names);
+                // first added plugin takes precedence
+                plugin_name_maps.language_to_plugin_map.entry(language).or_insert(LanguagePlugin {
+                    plugin_name: plugin_name.clone(),
+                    file_name,
+                });
+            }
+        }
+    }
+
+    /// Gets the hashes of the current plugins along with the hash of all of them together.
+    pub fn get_plugin_hashes(&self) -> HashSet<u64> {
+        let pools = self.pools.lock();
+        let mut plugin_hashes = pools.values().map(|pool| pool.plugin_hash()).collect::<HashSet<_>>();
+        plugin_hashes.insert(get_all_plugins_hash(&pools));
+        plugin_hashes
+    }
+
+    /// Records the plugin used to format embedded text of the file being formatted on this thread.
+    ///
+    /// When no plugin was found, the hash of all the plugins is recorded instead so the file
+    /// is formatted again once the plugins change (ex. a plugin for the text was added).
+    pub fn record_host_format_plugin(&self, plugin_name: Option<&str>) {
+        let is_collecting = HOST_FORMAT_PLUGIN_HASHES.with(|hashes| hashes.borrow().is_some());
+        if !is_collecting {
+            return;
         }
+
+        let plugin_hash = {
+            let pools = self.pools.lock();
+            match plugin_name.and_then(|plugin_name| pools.get(plugin_name)) {
+                Some(pool) => pool.plugin_hash(),
+                None => get_all_plugins_hash(&pools),
+            }
+        };
+        HOST_FORMAT_PLUGIN_HASHES.with(|hashes| {
+            if let Some(hashes) = hashes.borrow_mut().as_mut() {
+                if !hashes.contains(&plugin_hash) {
+                    hashes.push(plugin_hash);
+                }
+            }
+        });
+    }
+
+    /// Gets the number of nested host format requests allowed.
+    pub fn max_host_format_depth(&self) -> usize {
+        self.max_host_format_depth.load(Ordering::SeqCst)
     }
 
     pub fn get_pool(&self, plugin_name: &str) -> Option<Arc<InitializedPluginPool<TEnvironment>>> {
@@ -140,16 +263,47 @@ impl<TEnvironment : Environment> PluginPools<TEnvironment> {
         with_plugins(&mut plugins)
     }
 
-    pub fn get_plugin_name_from_file_name(&self, file_name: &Path) -> Option<String> {
+    /// Gets the name of the plugin that should format the file. For files without
+    /// an extension, this falls back to reading the interpreter from the file's shebang.
+    pub fn get_plugin_name_from_file_name(&self, file_path: &Path) -> Option<String> {
+        self.get_plugin_name(file_path, || {
+            let file_bytes = self.environment.read_file_prefix(file_path, SHEBANG_MAX_LEN).ok()?;
+            let first_line = file_bytes.split(|b| *b == b'\n').next().unwrap_or(&[]);
+            Some(String::from_utf8_lossy(first_line).into_owned())
+        })
+    }
+
+    /// Gets the name of the plugin that should format the file using the provided
+    /// file text instead of reading the file for files without an extension.
+    pub fn get_plugin_name_from_file_text(&self, file_path: &Path, file_text: &str) -> Option<String> {
+        self.get_plugin_name(file_path, || file_text.lines().next().map(|line| line.to_string()))
+    }
+
+    /// Gets the plugin that formats embedded code of the provided language
+    /// id (ex. `typescript`) or media type (ex. `text/css; charset=utf-8`).
+    pub fn get_plugin_from_language(&self, language: &str) -> Option<LanguagePlugin> {
         let plugin_name_maps = self.plugin_name_maps.read();
-        get_lowercase_file_name(file_name)
-            .map(|file_name| plugin_name_maps.file_name_to_plugin_name_map.get(&file_name))
-            .flatten()
-            .or_else(|| get_lowercase_file_extension(file_name)
-                .map(|ext| plugin_name_maps.extension_to_plugin_name_map.get(&ext))
-                .flatten()
-            )
-            .map(|name| name.to_owned())
+        plugin_name_maps.language_to_plugin_map.get(&normalize_language(language)).map(|plugin| plugin.clone())
+    }
+
+    fn get_plugin_name(&self, file_path: &Path, get_first_line: impl FnOnce() -> Option<String>) -> Option<String> {
+        {
+            let plugin_name_maps = self.plugin_name_maps.read();
+            let plugin_name = get_lowercase_file_name(file_path)
+                .map(|file_name| plugin_name_maps.file_name_to_plugin_name_map.get(&file_name))
+                .flatten();
+            if let Some(plugin_name) = plugin_name {
+                return Some(plugin_name.to_owned());
+            }
+            if let Some(ext) = get_lowercase_file_extension(file_path) {
+                return plugin_name_maps.extension_to_plugin_name_map.get(&ext).map(|name| name.to_owned());
+            }
+        }
+
+        // read the first line outside the lock since it might do IO
+        let interpreter = get_shebang_interpreter(&get_first_line()?)?;
+        let plugin_name_maps = self.plugin_name_maps.read();
+        plugin_name_maps.get_plugin_name_from_interpreter(&interpreter).map(|name| name.to_owned())
     }
 
     pub fn release(&self, parent_plugin_name: &str) {
@@ -162,19 +316,6 @@ impl<TEnvironment : Environment> PluginPools<TEnvironment> {
             }
         }
     }
-
-    /// Gets a hash to be used for the "incremental" feature to tell if any plugins have changed.
-    pub fn get_plugins_hash(&self) -> u64 {
-        use std::num::Wrapping;
-        // yeah, I know adding hashes isn't right, but the chance of this not working
-        // in order to tell when a plugin has changed is super low.
-        let pools = self.pools.lock();
-        let mut hash_sum = Wrapping(0);
-        for (_, pool) in pools.iter() {
-            hash_sum += Wrapping(pool.plugin.get_hash());
-        }
-        hash_sum.0
-    }
 }
 
 pub struct PoolTimeSnapshot {
@@ -198,9 +339,15 @@ pub struct InitializedPluginPool<TEnvironment : Environment> {
     environment: TEnvironment,
     name: String,
     plugin: Box<dyn Plugin>,
+    plugin_hash: u64,
     items: Mutex<Vec<Box<dyn InitializedPlugin>>>, // todo: RwLock
     time_stats: RwLock<PluginTimeStats>,
     checked_diagnostics: Mutex<Option<bool>>,
+    /// The number of instances that currently exist, whether in the pool or taken from it.
+    instance_count: Arc<AtomicUsize>,
+    /// The last decision on creating another instance, which is stored
+    /// in order to only report when it changes.
+    last_new_instance_decision: Mutex<Option<bool>>,
 }
 
 impl<TEnvironment : Environment> InitializedPluginPool<TEnvironment> {
@@ -208,6 +355,7 @@ impl<TEnvironment : Environment> InitializedPluginPool<TEnvironment> {
         InitializedPluginPool {
             environment,
             name: plugin.name().to_string(),
+            plugin_hash: plugin.get_hash(),
             plugin: plugin,
             items: Mutex::new(Vec::new()),
             time_stats: RwLock::new(PluginTimeStats {
@@ -218,6 +366,8 @@ impl<TEnvironment : Environment> InitializedPluginPool<TEnvironment> {
                 format_count: 1,
             }),
             checked_diagnostics: Mutex::new(None),
+            instance_count: Arc::new(AtomicUsize::new(0)),
+            last_new_instance_decision: Mutex::new(None),
         }
     }
 
@@ -225,6 +375,11 @@ impl<TEnvironment : Environment> InitializedPluginPool<TEnvironment> {
         self.name.as_str()
     }
 
+    /// Gets a hash to be used for the "incremental" feature to tell if the plugin has changed.
+    pub fn plugin_hash(&self) -> u64 {
+        self.plugin_hash
+    }
+
     pub fn drop_plugins(&self) {
         let mut items = self.items.lock();
         items.clear();
@@ -236,6 +391,9 @@ impl<TEnvironment : Environment> InitializedPluginPool<TEnvironment> {
     ) -> Result<TakePluginResult, ErrBox> {
         if let Some(plugin) = self.take_if_available() {
             Ok(TakePluginResult::Success(plugin))
+        } else if *self.checked_diagnostics.lock() == Some(false) {
+            // don't bother creating an instance when more work arrives for a plugin that had diagnostics
+            Ok(TakePluginResult::HadDiagnostics)
         } else {
             let instance = self.create_instance()?;
 
@@ -285,14 +443,55 @@ impl<TEnvironment : Environment> InitializedPluginPool<TEnvironment> {
         }
     }
 
+    /// Gets if it's worth creating another instance to format the provided number of files.
+    ///
+    /// Another instance is only worth it when the time spent formatting the files would
+    /// exceed the time it takes to start up an instance. Otherwise, it's faster overall to
+    /// wait for an existing instance.
+    pub fn is_new_instance_worthwhile(&self, work_items_len: usize) -> bool {
+        let instance_count = self.instance_count.load(Ordering::SeqCst);
+        if instance_count == 0 {
+            return true;
+        }
+
+        let time_snapshot = self.get_time_snapshot();
+        let work_time = work_items_len as u64 * time_snapshot.average_format_time;
+        let is_worthwhile = work_time > time_snapshot.startup_time;
+
+        let mut last_new_instance_decision = self.last_new_instance_decision.lock();
+        if *last_new_instance_decision != Some(is_worthwhile) {
+            *last_new_instance_decision = Some(is_worthwhile);
+            // growing is reported once the instance is created since the caller may not create one
+            if !is_worthwhile {
+                log_verbose!(
+                    self.environment,
+                    "Limiting {} to {} instance(s) (startup: {}ms, average format: {}ms)",
+                    self.name,
+                    instance_count,
+                    time_snapshot.startup_time,
+                    time_snapshot.average_format_time,
+                );
+            }
+        }
+
+        is_worthwhile
+    }
+
     fn create_instance(&self) -> Result<Box<dyn InitializedPlugin>, ErrBox> {
         let start_instant = Instant::now();
         log_verbose!(self.environment, "Creating instance of {}", self.plugin.name());
         let plugin = self.plugin.initialize()?;
+        let instance_count = self.instance_count.fetch_add(1, Ordering::SeqCst) + 1;
         let startup_duration = start_instant.elapsed().as_millis() as u64;
         log_verbose!(self.environment, "Created instance of {} in {}ms", self.plugin.name(), startup_duration);
+        if instance_count > 1 {
+            log_verbose!(self.environment, "Grew {} to {} instance(s)", self.name, instance_count);
+        }
         self.time_stats.write().startup_time = startup_duration; // store the latest duration
-        Ok(plugin)
+        Ok(Box::new(PoolInstance {
+            inner: plugin,
+            instance_count: self.instance_count.clone(),
+        }))
     }
 
     pub fn format_measuring_time<TResult>(&self, mut action: impl FnMut() -> TResult) -> TResult {
@@ -305,3 +504,163 @@ impl<TEnvironment : Environment> InitializedPluginPool<TEnvironment> {
         result
     }
 }
+
+/// An instance created by a pool, which removes itself from the pool's
+/// instance count once it's dropped (ex. the pool was cleared or the
+/// instance was discarded after its configuration had diagnostics).
+struct PoolInstance {
+    inner: Box<dyn InitializedPlugin>,
+    instance_count: Arc<AtomicUsize>,
+}
+
+impl Drop for PoolInstance {
+    fn drop(&mut self) {
+        self.instance_count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl InitializedPlugin for PoolInstance {
+    fn get_license_text(&self) -> Result<String, ErrBox> {
+        self.inner.get_license_text()
+    }
+
+    fn get_resolved_config(&self) -> Result<String, ErrBox> {
+        self.inner.get_resolved_config()
+    }
+
+    fn get_config_diagnostics(&self) -> Result<Vec<ConfigurationDiagnostic>, ErrBox> {
+        self.inner.get_config_diagnostics()
+    }
+
+    fn format_text(&mut self, file_path: &Path, file_text: &str, override_config: &ConfigKeyMap, cancellation_token: &CancellationToken) -> Result<FormatTextResult, ErrBox> {
+        self.inner.format_text(file_path, file_text, override_config, cancellation_token)
+    }
+}
+
+fn get_all_plugins_hash<TEnvironment: Environment>(pools: &HashMap<String, Arc<InitializedPluginPool<TEnvironment>>>) -> u64 {
+    let mut plugin_hashes = pools.values().map(|pool| pool.plugin_hash()).collect::<Vec<_>>();
+    plugin_hashes.sort();
+    let bytes = plugin_hashes.iter().flat_map(|plugin_hash| plugin_hash.to_le_bytes().to_vec()).collect::<Vec<_>>();
+    get_bytes_hash(&bytes)
+}
+
+/// Lowercases the language and removes any media type parameters.
+fn normalize_language(language: &str) -> String {
+    language.split(';').next().unwrap_or("").trim().to_lowercase()
+}
+
+/// Gets a file name for formatting code of the language, preferring an extension
+/// with the same name as the language (ex. `tsx`) over the plugin's first one.
+fn get_language_file_name(language: &str, extensions: &[String], file_names: &[String]) -> Option<String> {
+    let extension = extensions.iter().find(|extension| extension.to_lowercase() == language).or_else(|| extensions.first());
+    match extension {
+        Some(extension) => Some(format!("file.{}", extension)),
+        None => file_names.first().map(|file_name| file_name.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::environment::TestEnvironment;
+    use crate::plugins::TestPlugin;
+
+    #[test]
+    fn it_should_only_create_another_instance_when_worthwhile() {
+        let environment = TestEnvironment::new();
+        let plugin = TestPlugin::new("test-plugin", "test", vec!["txt"], vec![]);
+        let pool = InitializedPluginPool::new(Box::new(plugin), environment);
+
+        // the first instance is always worth creating
+        assert_eq!(pool.is_new_instance_worthwhile(1), true);
+        let instance = pool.create_instance().unwrap();
+        pool.release(instance);
+
+        {
+            let mut time_stats = pool.time_stats.write();
+            time_stats.startup_time = 100;
+            time_stats.total_format_time = 10;
+            time_stats.format_count = 1;
+        }
+        assert_eq!(pool.is_new_instance_worthwhile(5), false);
+        assert_eq!(pool.is_new_instance_worthwhile(20), true);
+
+        // dropping the plugins means there are no instances again
+        pool.drop_plugins();
+        assert_eq!(pool.is_new_instance_worthwhile(1), true);
+    }
+
+    #[test]
+    fn it_should_count_instances_that_exist() {
+        let environment = TestEnvironment::new();
+        let plugin = TestPlugin::new("test-plugin", "test", vec!["txt"], vec![]);
+        let pool = InitializedPluginPool::new(Box::new(plugin), environment);
+
+        let instance1 = pool.create_instance().unwrap();
+        let instance2 = pool.create_instance().unwrap();
+        assert_eq!(pool.instance_count.load(Ordering::SeqCst), 2);
+
+        // released instances still exist in the pool
+        pool.release(instance1);
+        assert_eq!(pool.instance_count.load(Ordering::SeqCst), 2);
+
+        // discarded instances don't
+        drop(instance2);
+        assert_eq!(pool.instance_count.load(Ordering::SeqCst), 1);
+        pool.drop_plugins();
+        assert_eq!(pool.instance_count.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn it_should_only_log_growing_once_instance_created() {
+        let environment = TestEnvironment::new();
+        environment.set_verbose(true);
+        let plugin = TestPlugin::new("test-plugin", "test", vec!["txt"], vec![]);
+        let pool = InitializedPluginPool::new(Box::new(plugin), environment.clone());
+        let _instance1 = pool.create_instance().unwrap();
+        set_time_stats(&pool);
+        environment.clear_logs();
+
+        assert_eq!(pool.is_new_instance_worthwhile(20), true);
+        assert_eq!(environment.take_logged_messages().len(), 0);
+        let _instance2 = pool.create_instance().unwrap();
+        assert_eq!(environment.take_logged_messages().last().unwrap(), "[VERBOSE]: Grew test-plugin to 2 instance(s)");
+
+        set_time_stats(&pool);
+        assert_eq!(pool.is_new_instance_worthwhile(5), false);
+        assert_eq!(environment.take_logged_messages(), vec!["[VERBOSE]: Limiting test-plugin to 2 instance(s) (startup: 100ms, average format: 10ms)"]);
+    }
+
+    fn set_time_stats(pool: &InitializedPluginPool<TestEnvironment>) {
+        let mut time_stats = pool.time_stats.write();
+        time_stats.startup_time = 100;
+        time_stats.total_format_time = 10;
+        time_stats.format_count = 1;
+    }
+
+    #[test]
+    fn it_should_get_plugin_from_language() {
+        let environment = TestEnvironment::new();
+        let pools = PluginPools::new(environment);
+        pools.set_plugins(vec![
+            Box::new(TestPlugin::new("typescript-plugin", "typescript", vec!["ts", "tsx"], vec![]).with_languages(vec!["typescript", "text/javascript", "TSX"])),
+            Box::new(TestPlugin::new("other-plugin", "other", vec!["other"], vec![]).with_languages(vec!["TypeScript", "text/css"])),
+            Box::new(TestPlugin::new("docker-plugin", "docker", vec![], vec!["Dockerfile"]).with_languages(vec!["dockerfile"])),
+        ], &HashMap::new(), DEFAULT_MAX_HOST_FORMAT_DEPTH);
+
+        assert_eq!(pools.get_plugin_from_language("typescript"), Some(get_language_plugin("typescript-plugin", "file.ts")));
+        assert_eq!(pools.get_plugin_from_language("Text/JavaScript; charset=utf-8"), Some(get_language_plugin("typescript-plugin", "file.ts")));
+        assert_eq!(pools.get_plugin_from_language("tsx"), Some(get_language_plugin("typescript-plugin", "file.tsx")));
+        assert_eq!(pools.get_plugin_from_language("text/css"), Some(get_language_plugin("other-plugin", "file.other")));
+        assert_eq!(pools.get_plugin_from_language("dockerfile"), Some(get_language_plugin("docker-plugin", "Dockerfile")));
+        assert_eq!(pools.get_plugin_from_language("ts"), None);
+        assert_eq!(pools.get_plugin_from_language("file.ts"), None);
+    }
+
+    fn get_language_plugin(plugin_name: &str, file_name: &str) -> LanguagePlugin {
+        LanguagePlugin {
+            plugin_name: plugin_name.to_string(),
+            file_name: Some(file_name.to_string()),
+        }
+    }
+}
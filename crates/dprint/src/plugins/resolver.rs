@@ -1,11 +1,15 @@
+use std::path::Path;
 use std::sync::Arc;
 use rayon::prelude::*;
 
+use dprint_core::plugins::PluginInfo;
 use dprint_core::types::ErrBox;
 
 use crate::environment::Environment;
-use crate::plugins::{Plugin, PluginSourceReference, PluginCache, PluginPools};
+use crate::plugins::{Plugin, PluginSignaturePolicy, PluginSourceReference, PluginCache, PluginPools};
+use crate::utils::PathSource;
 use super::implementations::{create_plugin};
+use super::{PluginsLockfile, PluginsLockfileEntry, read_lockfile, write_lockfile};
 
 pub struct PluginResolver<TEnvironment : Environment> {
     environment: TEnvironment,
@@ -22,17 +26,150 @@ impl<TEnvironment : Environment> PluginResolver<TEnvironment> {
         PluginResolver { environment, plugin_cache, plugin_pools }
     }
 
+    /// Sets how the signatures of plugins are verified when they're not already cached.
+    pub fn set_signature_policy(&self, signature_policy: PluginSignaturePolicy) {
+        self.plugin_cache.set_signature_policy(signature_policy);
+    }
+
     pub fn resolve_plugins(&self, plugin_references: Vec<PluginSourceReference>) -> Result<Vec<Box<dyn Plugin>>, ErrBox> {
+        self.resolve_plugins_with_locked_info(plugin_references, None)
+    }
+
+    fn resolve_plugins_with_locked_info(
+        &self,
+        plugin_references: Vec<PluginSourceReference>,
+        lockfile: Option<&PluginsLockfile>,
+    ) -> Result<Vec<Box<dyn Plugin>>, ErrBox> {
         let plugins = plugin_references
             .into_par_iter()
-            .map(|plugin_reference| self.resolve_plugin(plugin_reference))
+            .map(|plugin_reference| {
+                let locked_info = lockfile.and_then(|lockfile| get_locked_info(&plugin_reference, lockfile));
+                self.resolve_plugin(plugin_reference, locked_info)
+            })
             .collect::<Result<Vec<Box<dyn Plugin>>, ErrBox>>()?;
 
         Ok(plugins)
     }
 
-    fn resolve_plugin(&self, plugin_reference: PluginSourceReference) -> Result<Box<dyn Plugin>, ErrBox> {
-        match create_plugin(self.plugin_pools.clone(), &self.plugin_cache, self.environment.clone(), &plugin_reference) {
+    /// Resolves the plugins using the checksums recorded in the lockfile, then updates the
+    /// lockfile with the resolved plugins. When frozen, this errors instead of updating the
+    /// lockfile if the plugins would resolve differently than what it records.
+    ///
+    /// The lockfile is only used when it exists. It's created by `update_lockfile`.
+    pub fn resolve_plugins_with_lockfile(
+        &self,
+        plugin_references: Vec<PluginSourceReference>,
+        lockfile_path: &Path,
+        frozen: bool,
+    ) -> Result<Vec<Box<dyn Plugin>>, ErrBox> {
+        let lockfile = match read_lockfile(lockfile_path, &self.environment)? {
+            Some(lockfile) => lockfile,
+            None => {
+                if frozen {
+                    return err!("Could not find a lockfile at {}. Run `dprint plugins update` to create it.", lockfile_path.display());
+                }
+                return self.resolve_plugins(plugin_references);
+            }
+        };
+        if frozen {
+            verify_plugin_references_in_lockfile(&plugin_references, &lockfile, lockfile_path)?;
+        }
+
+        // plugins without a checksum in the configuration must match the locked checksum
+        let plugin_references = plugin_references.into_iter().map(|mut plugin_reference| {
+            if plugin_reference.checksum.is_none() {
+                if let Some(entry) = lockfile.get_entry(&plugin_reference.display()) {
+                    plugin_reference.checksum = Some(entry.checksum.clone());
+                }
+            }
+            plugin_reference
+        }).collect::<Vec<_>>();
+        self.resolve_and_write_lockfile(plugin_references, &lockfile, lockfile_path, frozen)
+    }
+
+    /// Downloads the remote plugins that don't have a checksum in the configuration again,
+    /// then writes the checksums of the resolved plugins to the lockfile, creating it when
+    /// it doesn't exist. This is how plugins whose files changed upstream are updated.
+    pub fn update_lockfile(
+        &self,
+        plugin_references: Vec<PluginSourceReference>,
+        lockfile_path: &Path,
+    ) -> Result<Vec<Box<dyn Plugin>>, ErrBox> {
+        // the lockfile is rewritten, so one from a different version of dprint is replaced
+        let lockfile = match read_lockfile(lockfile_path, &self.environment) {
+            Ok(lockfile) => lockfile,
+            Err(err) => {
+                log_verbose!(self.environment, "Replacing lockfile. {}", err.to_string());
+                None
+            }
+        }.unwrap_or_else(|| PluginsLockfile::new(Vec::new()));
+        for plugin_reference in plugin_references.iter() {
+            if matches!(plugin_reference.path_source, PathSource::Remote(_)) && plugin_reference.checksum.is_none() {
+                self.plugin_cache.forget(plugin_reference)?;
+            }
+        }
+        self.resolve_and_write_lockfile(plugin_references, &lockfile, lockfile_path, false)
+    }
+
+    fn resolve_and_write_lockfile(
+        &self,
+        plugin_references: Vec<PluginSourceReference>,
+        lockfile: &PluginsLockfile,
+        lockfile_path: &Path,
+        frozen: bool,
+    ) -> Result<Vec<Box<dyn Plugin>>, ErrBox> {
+        // plugins cached by older versions of the CLI may not have a checksum to record, so get them again
+        for plugin_reference in plugin_references.iter() {
+            if matches!(plugin_reference.path_source, PathSource::Remote(_)) && plugin_reference.checksum.is_none() {
+                let cache_item = self.plugin_cache.get_cached_plugin_cache_item(plugin_reference)?;
+                if cache_item.map(|cache_item| cache_item.checksum.is_none()).unwrap_or(false) {
+                    self.plugin_cache.forget(plugin_reference)?;
+                }
+            }
+        }
+
+        let plugins = self.resolve_plugins_with_locked_info(plugin_references.clone(), Some(lockfile))?;
+
+        let mut entries = Vec::new();
+        for (plugin_reference, plugin) in plugin_references.iter().zip(plugins.iter()) {
+            // local plugins are specific to the machine and are verified by their file hash instead
+            if let PathSource::Remote(_) = &plugin_reference.path_source {
+                // plugins without a checksum were resolved from the cache
+                let checksum = match &plugin_reference.checksum {
+                    Some(checksum) => Some(checksum.clone()),
+                    None => self.plugin_cache.get_cached_plugin_cache_item(plugin_reference)?.and_then(|cache_item| cache_item.checksum),
+                };
+                let checksum = match checksum {
+                    Some(checksum) => checksum,
+                    None => return err!("Expected to have the plugin checksum stored in the cache."),
+                };
+                entries.push(PluginsLockfileEntry {
+                    url: plugin_reference.display(),
+                    checksum,
+                    info: get_plugin_info(plugin.as_ref()),
+                });
+            }
+        }
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+
+        let new_lockfile = PluginsLockfile::new(entries);
+        let has_changed = *lockfile != new_lockfile || !self.environment.path_exists(lockfile_path);
+        if has_changed {
+            if frozen {
+                return err!(
+                    "The plugins resolved differently than what is recorded in {}. Run without --frozen to update it.",
+                    lockfile_path.display(),
+                );
+            }
+            log_verbose!(self.environment, "Updating lockfile {}", lockfile_path.display());
+            write_lockfile(lockfile_path, &new_lockfile, &self.environment)?;
+        }
+
+        Ok(plugins)
+    }
+
+    fn resolve_plugin(&self, plugin_reference: PluginSourceReference, locked_info: Option<PluginInfo>) -> Result<Box<dyn Plugin>, ErrBox> {
+        match create_plugin(self.plugin_pools.clone(), self.plugin_cache.clone(), self.environment.clone(), &plugin_reference, locked_info) {
             Ok(plugin) => Ok(plugin),
             Err(err) => {
                 match self.plugin_cache.forget(&plugin_reference) {
@@ -44,3 +181,60 @@ impl<TEnvironment : Environment> PluginResolver<TEnvironment> {
         }
     }
 }
+
+/// Gets the plugin's information recorded in the lockfile when it's for the expected plugin file.
+fn get_locked_info(plugin_reference: &PluginSourceReference, lockfile: &PluginsLockfile) -> Option<PluginInfo> {
+    let entry = lockfile.get_entry(&plugin_reference.display())?;
+    if plugin_reference.checksum.as_ref() == Some(&entry.checksum) {
+        Some(entry.info.clone())
+    } else {
+        None
+    }
+}
+
+fn get_plugin_info(plugin: &dyn Plugin) -> PluginInfo {
+    PluginInfo {
+        name: plugin.name().to_string(),
+        version: plugin.version().to_string(),
+        config_key: plugin.config_key().to_string(),
+        file_extensions: plugin.file_extensions().clone(),
+        file_names: plugin.file_names().clone(),
+        interpreters: plugin.interpreters().clone(),
+        languages: plugin.languages().clone(),
+        help_url: plugin.help_url().to_string(),
+        config_schema_url: plugin.config_schema_url().to_string(),
+    }
+}
+
+fn verify_plugin_references_in_lockfile(
+    plugin_references: &[PluginSourceReference],
+    lockfile: &PluginsLockfile,
+    lockfile_path: &Path,
+) -> Result<(), ErrBox> {
+    for plugin_reference in plugin_references.iter().filter(|r| matches!(r.path_source, PathSource::Remote(_))) {
+        let url = plugin_reference.display();
+        let entry = match lockfile.get_entry(&url) {
+            Some(entry) => entry,
+            None => return err!("The plugin {} is not in {}. Run without --frozen to update it.", url, lockfile_path.display()),
+        };
+        if let Some(checksum) = &plugin_reference.checksum {
+            if *checksum != entry.checksum {
+                return err!(
+                    "The checksum {} of plugin {} did not match the checksum of {} in {}. Run without --frozen to update it.",
+                    checksum,
+                    url,
+                    entry.checksum,
+                    lockfile_path.display(),
+                );
+            }
+        }
+    }
+
+    for entry in lockfile.plugins.iter() {
+        if !plugin_references.iter().any(|r| r.display() == entry.url) {
+            return err!("The plugin {} in {} is no longer used. Run without --frozen to update it.", entry.url, lockfile_path.display());
+        }
+    }
+
+    Ok(())
+}
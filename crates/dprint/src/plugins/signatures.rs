@@ -0,0 +1,195 @@
+use std::convert::TryFrom;
+use ed25519_dalek::{PublicKey, Signature};
+
+use dprint_core::types::ErrBox;
+
+use crate::utils::PathSource;
+
+/// Extension of the file beside a plugin file that contains its detached signature.
+const SIGNATURE_FILE_EXTENSION: &str = "sig";
+
+/// How the signatures of plugins should be verified.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PluginSignaturePolicy {
+    /// Hex encoded ed25519 public keys of the trusted plugin publishers.
+    pub trusted_public_keys: Vec<String>,
+    /// Only allow process and native plugins signed by a trusted public key.
+    pub require_signed_process_plugins: bool,
+}
+
+impl PluginSignaturePolicy {
+    /// Gets if the plugin's signature should be checked.
+    pub fn should_verify(&self, path_source: &PathSource) -> bool {
+        !self.trusted_public_keys.is_empty() || self.requires_signature(path_source)
+    }
+
+    /// Gets if the plugin must be signed by a trusted public key in order to be used.
+    pub fn requires_signature(&self, path_source: &PathSource) -> bool {
+        self.require_signed_process_plugins && !path_source.is_wasm_plugin()
+    }
+
+    pub fn is_trusted_public_key(&self, public_key: &str) -> bool {
+        self.trusted_public_keys.iter().any(|key| key.eq_ignore_ascii_case(public_key))
+    }
+
+    /// Verifies the hex encoded signature of the file bytes was created by one
+    /// of the trusted public keys, returning the public key that created it.
+    pub fn verify(&self, file_bytes: &[u8], signature_text: &str) -> Result<String, ErrBox> {
+        let signature = match decode_hex(signature_text.trim()).ok().and_then(|bytes| Signature::try_from(bytes.as_slice()).ok()) {
+            Some(signature) => signature,
+            None => return err!("The signature was not a hex encoded ed25519 signature."),
+        };
+
+        for public_key_text in self.trusted_public_keys.iter() {
+            let public_key = parse_public_key(public_key_text)?;
+            if public_key.verify_strict(file_bytes, &signature).is_ok() {
+                return Ok(public_key_text.to_lowercase());
+            }
+        }
+
+        err!("The signature was not created by any of the trusted public keys.")
+    }
+}
+
+/// Gets the location of the detached signature for the plugin (ex. `https://plugins.dprint.dev/plugin.wasm.sig`).
+pub fn get_signature_path_source(path_source: &PathSource) -> Result<PathSource, ErrBox> {
+    Ok(match path_source {
+        PathSource::Remote(remote_source) => {
+            PathSource::new_remote(url::Url::parse(&format!("{}.{}", remote_source.url, SIGNATURE_FILE_EXTENSION))?)
+        }
+        PathSource::Local(local_source) => {
+            let mut file_path = local_source.path.clone().into_os_string();
+            file_path.push(format!(".{}", SIGNATURE_FILE_EXTENSION));
+            PathSource::new_local(file_path.into())
+        }
+    })
+}
+
+/// Ensures the text is a hex encoded ed25519 public key.
+pub fn verify_public_key_text(public_key_text: &str) -> Result<(), ErrBox> {
+    parse_public_key(public_key_text).map(|_| ())
+}
+
+fn parse_public_key(public_key_text: &str) -> Result<PublicKey, ErrBox> {
+    match decode_hex(public_key_text).ok().and_then(|bytes| PublicKey::from_bytes(&bytes).ok()) {
+        Some(public_key) => Ok(public_key),
+        None => err!("The public key '{}' was not a hex encoded ed25519 public key.", public_key_text),
+    }
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>, ErrBox> {
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return err!("Invalid hex text.");
+    }
+    let mut bytes = Vec::with_capacity(text.len() / 2);
+    for i in (0..text.len()).step_by(2) {
+        bytes.push(u8::from_str_radix(&text[i..i + 2], 16)?);
+    }
+    Ok(bytes)
+}
+
+/// Creates the hex encoded public key and signature of the bytes for the provided secret key.
+#[cfg(test)]
+pub fn create_test_signature(secret_key_bytes: &[u8; 32], bytes: &[u8]) -> (String, String) {
+    use ed25519_dalek::{ExpandedSecretKey, SecretKey};
+    let secret_key = SecretKey::from_bytes(secret_key_bytes).unwrap();
+    let public_key = PublicKey::from(&secret_key);
+    let signature = ExpandedSecretKey::from(&secret_key).sign(bytes, &public_key);
+    (encode_hex(public_key.as_bytes()), encode_hex(&signature.to_bytes()))
+}
+
+#[cfg(test)]
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use super::*;
+
+    #[test]
+    fn it_should_verify_signature_from_trusted_key() {
+        let (public_key, signature) = create_test_signature(&[1; 32], b"plugin");
+        let (other_public_key, _) = create_test_signature(&[2; 32], b"plugin");
+        let policy = PluginSignaturePolicy {
+            trusted_public_keys: vec![other_public_key, public_key.to_uppercase()],
+            require_signed_process_plugins: false,
+        };
+        assert_eq!(policy.verify(b"plugin", &format!("{}\n", signature)).unwrap(), public_key);
+    }
+
+    #[test]
+    fn it_should_error_for_signature_of_different_bytes() {
+        let (public_key, signature) = create_test_signature(&[1; 32], b"plugin");
+        let policy = PluginSignaturePolicy {
+            trusted_public_keys: vec![public_key],
+            require_signed_process_plugins: false,
+        };
+        assert_eq!(
+            policy.verify(b"modified", &signature).err().unwrap().to_string(),
+            "The signature was not created by any of the trusted public keys."
+        );
+    }
+
+    #[test]
+    fn it_should_error_for_signature_from_untrusted_key() {
+        let (_, signature) = create_test_signature(&[1; 32], b"plugin");
+        let (other_public_key, _) = create_test_signature(&[2; 32], b"plugin");
+        let policy = PluginSignaturePolicy {
+            trusted_public_keys: vec![other_public_key],
+            require_signed_process_plugins: false,
+        };
+        assert_eq!(
+            policy.verify(b"plugin", &signature).err().unwrap().to_string(),
+            "The signature was not created by any of the trusted public keys."
+        );
+    }
+
+    #[test]
+    fn it_should_error_for_invalid_signature_text() {
+        let (public_key, _) = create_test_signature(&[1; 32], b"plugin");
+        let policy = PluginSignaturePolicy {
+            trusted_public_keys: vec![public_key],
+            require_signed_process_plugins: false,
+        };
+        assert_eq!(
+            policy.verify(b"plugin", "not hex").err().unwrap().to_string(),
+            "The signature was not a hex encoded ed25519 signature."
+        );
+    }
+
+    #[test]
+    fn it_should_verify_public_key_text() {
+        let (public_key, _) = create_test_signature(&[1; 32], b"plugin");
+        assert_eq!(verify_public_key_text(&public_key).is_ok(), true);
+        assert_eq!(
+            verify_public_key_text("abc").err().unwrap().to_string(),
+            "The public key 'abc' was not a hex encoded ed25519 public key."
+        );
+    }
+
+    #[test]
+    fn it_should_only_require_signature_for_process_plugins() {
+        let policy = PluginSignaturePolicy {
+            trusted_public_keys: Vec::new(),
+            require_signed_process_plugins: true,
+        };
+        assert_eq!(policy.requires_signature(&PathSource::new_remote_from_str("https://plugins.dprint.dev/test.wasm")), false);
+        assert_eq!(policy.requires_signature(&PathSource::new_remote_from_str("https://plugins.dprint.dev/test.exe-plugin")), true);
+        assert_eq!(policy.requires_signature(&PathSource::new_local(PathBuf::from("/test.native-plugin"))), true);
+        assert_eq!(PluginSignaturePolicy::default().should_verify(&PathSource::new_remote_from_str("https://plugins.dprint.dev/test.exe-plugin")), false);
+    }
+
+    #[test]
+    fn it_should_get_signature_path_source() {
+        assert_eq!(
+            get_signature_path_source(&PathSource::new_remote_from_str("https://plugins.dprint.dev/test.wasm")).unwrap(),
+            PathSource::new_remote_from_str("https://plugins.dprint.dev/test.wasm.sig")
+        );
+        assert_eq!(
+            get_signature_path_source(&PathSource::new_local(PathBuf::from("/plugins/test.exe-plugin"))).unwrap(),
+            PathSource::new_local(PathBuf::from("/plugins/test.exe-plugin.sig"))
+        );
+    }
+}
@@ -29,6 +29,10 @@ impl PluginSourceReference {
         self.path_source.is_process_plugin()
     }
 
+    pub fn is_native_plugin(&self) -> bool {
+        self.path_source.is_native_plugin()
+    }
+
     #[cfg(test)]
     pub fn new_local(path: std::path::PathBuf) -> PluginSourceReference {
         PluginSourceReference {
@@ -1,29 +1,41 @@
 use dprint_cli_core::types::ErrBox;
 use std::thread;
-use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use crate::environment::Environment;
+use crate::environment::{Environment, FileMetadata};
 use crate::plugins::{InitializedPlugin, InitializedPluginPool, PluginPools, TakePluginResult};
 use crate::utils::ErrorCountLogger;
 
-use super::{LongFormatCheckerThread, WorkerRegistry, Worker};
-
-pub fn do_batch_format<TEnvironment: Environment, F>(
+use super::{FileEstimate, LongFormatCheckerThread, WorkBatcher, WorkerRegistry, Worker};
+
+/// Formats the file paths provided by `discover_file_paths` while the files are still being discovered.
+///
+/// The files estimated to take the longest to format by `get_file_estimate` are formatted first
+/// in order to avoid a single slow file being formatted at the end while the other threads are idle.
+/// The file metadata in the estimate is provided to `action`. When discovering the files fails,
+/// the work that hasn't started is discarded and the error is returned once the workers finish.
+///
+/// Returns the number of discovered file paths that could be formatted by a plugin.
+pub fn do_batch_format<TEnvironment: Environment, TDiscover, F>(
     environment: &TEnvironment,
     error_logger: &ErrorCountLogger<TEnvironment>,
     plugin_pools: &Arc<PluginPools<TEnvironment>>,
-    file_paths_by_plugin: HashMap<String, Vec<PathBuf>>,
+    discover_file_paths: TDiscover,
+    get_file_estimate: &dyn Fn(&Path) -> FileEstimate,
     action: F
-) -> Result<(), ErrBox> where F: Fn(&InitializedPluginPool<TEnvironment>, &Path, &mut Box<dyn InitializedPlugin>) + Send + 'static + Clone {
-    let registry = Arc::new(WorkerRegistry::new(plugin_pools.clone(), file_paths_by_plugin));
+) -> Result<usize, ErrBox>
+    where
+        TDiscover: FnOnce(&mut dyn FnMut(PathBuf)) -> Result<(), ErrBox>,
+        F: Fn(&InitializedPluginPool<TEnvironment>, &Path, Option<FileMetadata>, &mut Box<dyn InitializedPlugin>) + Send + 'static + Clone
+{
+    let registry = Arc::new(WorkerRegistry::new(plugin_pools.clone()));
 
     // create a thread that will watch all the workers and report to the user when a file is taking a long time
     let long_format_checker_thread = LongFormatCheckerThread::new(environment, registry.clone());
 
-    // spawn a thread for 1..n workers (exclude first)
-    let thread_handles = registry.workers.iter().skip(1).map(|worker| {
+    // spawn a thread for every worker since the current thread is used for discovering files
+    let thread_handles = registry.workers.iter().map(|worker| {
         let worker = worker.clone();
         let error_logger = error_logger.clone();
         let action = action.clone();
@@ -36,11 +48,18 @@ pub fn do_batch_format<TEnvironment: Environment, F>(
     // spawn the thread to check for files that take a long time to format
     long_format_checker_thread.spawn();
 
-    // run the first worker on the current thread
-    let first_worker = registry.workers.first().unwrap().clone();
-    run_thread(error_logger, registry, &first_worker, action);
-
-    // wait for the other threads to finish
+    // discover the files on the current thread, giving them to the workers as they're found
+    let mut work_batcher = WorkBatcher::new(&registry, plugin_pools, get_file_estimate);
+    let discover_result = discover_file_paths(&mut |file_path| work_batcher.add(file_path));
+    let file_count = if discover_result.is_ok() {
+        work_batcher.finish()
+    } else {
+        // stop formatting since the error is reported instead of the result
+        work_batcher.cancel();
+        0
+    };
+
+    // wait for the threads to finish
     for handle in thread_handles {
         if let Err(_) = handle.join() {
             long_format_checker_thread.signal_exit();
@@ -53,7 +72,9 @@ pub fn do_batch_format<TEnvironment: Environment, F>(
 
     long_format_checker_thread.signal_exit();
 
-    return Ok(());
+    discover_result?;
+
+    return Ok(file_count);
 }
 
 fn run_thread<TEnvironment: Environment, F>(
@@ -61,9 +82,12 @@ fn run_thread<TEnvironment: Environment, F>(
     registry: Arc<WorkerRegistry<TEnvironment>>,
     worker: &Worker<TEnvironment>,
     action: F,
-) where F: Fn(&InitializedPluginPool<TEnvironment>, &Path, &mut Box<dyn InitializedPlugin>) + Send + 'static + Clone {
+) where F: Fn(&InitializedPluginPool<TEnvironment>, &Path, Option<FileMetadata>, &mut Box<dyn InitializedPlugin>) + Send + 'static + Clone {
     let mut current_plugin: Option<(Box<dyn InitializedPlugin>, Arc<InitializedPluginPool<TEnvironment>>)> = None;
     loop {
+        // get this before doing the work so that work added in the meantime isn't missed
+        let work_generation = registry.work_generation();
+
         if let Err(err) = do_local_work(error_logger, &registry, &worker, action.clone(), current_plugin.take()) {
             error_logger.log_error(&err.to_string());
             return;
@@ -74,8 +98,8 @@ fn run_thread<TEnvironment: Environment, F>(
                 current_plugin = Some((plugin, stolen_work.work.pool.clone()));
             }
             worker.add_work(stolen_work.work);
-        } else {
-            return; // no more work left to steal
+        } else if !registry.wait_for_work(work_generation) {
+            return; // no more work left to steal and files are no longer being discovered
         }
     }
 }
@@ -86,11 +110,11 @@ fn do_local_work<TEnvironment: Environment, F>(
     worker: &Worker<TEnvironment>,
     action: F,
     current_plugin: Option<(Box<dyn InitializedPlugin>, Arc<InitializedPluginPool<TEnvironment>>)>,
-) -> Result<(), ErrBox> where F: Fn(&InitializedPluginPool<TEnvironment>, &Path, &mut Box<dyn InitializedPlugin>) + Send + 'static + Clone {
+) -> Result<(), ErrBox> where F: Fn(&InitializedPluginPool<TEnvironment>, &Path, Option<FileMetadata>, &mut Box<dyn InitializedPlugin>) + Send + 'static + Clone {
     let mut current_plugin = current_plugin;
 
     loop {
-        let (pool, file_path) = if let Some(next_work) = worker.take_next_work() {
+        let (pool, work_item) = if let Some(next_work) = worker.take_next_work() {
             next_work
         } else {
             // release the current plugin before exiting
@@ -122,7 +146,7 @@ fn do_local_work<TEnvironment: Environment, F>(
         // now do the work using it
         let plugin_and_pool = current_plugin.as_mut().unwrap();
 
-        action(&plugin_and_pool.1, &file_path, &mut plugin_and_pool.0);
+        action(&plugin_and_pool.1, &work_item.file_path, work_item.file_metadata, &mut plugin_and_pool.0);
     }
 
     fn release_current_plugin<TEnvironment: Environment>(
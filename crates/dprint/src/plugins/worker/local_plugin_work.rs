@@ -1,29 +1,71 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use crate::environment::Environment;
+use crate::environment::{Environment, FileMetadata};
 use crate::plugins::InitializedPluginPool;
 
-use super::Deque;
-
 pub struct PluginStealInfo {
     pub has_plugin_available: bool,
     pub steal_time: u64,
 }
 
+/// What's known about a file before it's formatted.
+pub struct FileEstimate {
+    /// Estimated milliseconds the file will take to format.
+    pub estimated_time: u64,
+    /// The file's metadata, which is provided so the file doesn't need to be checked again.
+    pub file_metadata: Option<FileMetadata>,
+}
+
+/// A file path to format along with an estimate of how many milliseconds it will take.
+///
+/// Items are ordered by their estimated time so the longest is at the top of the queue.
+pub struct WorkItem {
+    pub file_path: PathBuf,
+    pub estimated_time: u64,
+    /// The file's metadata from when it was discovered, which is used to tell if it changed.
+    pub file_metadata: Option<FileMetadata>,
+}
+
+impl PartialEq for WorkItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for WorkItem {}
+
+impl PartialOrd for WorkItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for WorkItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // fall back to the file path so the order is deterministic
+        self.estimated_time.cmp(&other.estimated_time)
+            .then_with(|| other.file_path.cmp(&self.file_path))
+    }
+}
+
 pub struct LocalPluginWork<TEnvironment: Environment> {
     pub pool: Arc<InitializedPluginPool<TEnvironment>>,
-    items: Deque<PathBuf>,
+    /// Priority queue so the items estimated to take the longest are done first,
+    /// even when they're added after other items.
+    items: BinaryHeap<WorkItem>,
 }
 
 impl<TEnvironment: Environment> LocalPluginWork<TEnvironment> {
     pub fn new(
         pool: Arc<InitializedPluginPool<TEnvironment>>,
-        file_paths: Vec<PathBuf>,
+        items: Vec<WorkItem>,
     ) -> Self {
         LocalPluginWork {
             pool,
-            items: Deque::new(file_paths),
+            items: BinaryHeap::from(items),
         }
     }
 
@@ -31,23 +73,61 @@ impl<TEnvironment: Environment> LocalPluginWork<TEnvironment> {
         self.items.len()
     }
 
-    pub fn take_next_work_item(&mut self) -> PathBuf {
-        self.items.dequeue().unwrap().to_owned()
+    pub fn take_next_work_item(&mut self) -> WorkItem {
+        self.items.pop().unwrap()
     }
 
+    /// Adds the other work's items to this work's queue.
+    pub fn merge(&mut self, other: LocalPluginWork<TEnvironment>) {
+        self.items.extend(other.items);
+    }
+
+    /// Splits off about half of the estimated time. This keeps the
+    /// slow items and gives away many fast ones.
     pub fn split(&mut self) -> LocalPluginWork<TEnvironment> {
+        // longest first
+        let mut items = std::mem::take(&mut self.items).into_sorted_vec();
+        items.reverse();
+
+        let total_time: u64 = items.iter().map(|item| item.estimated_time).sum();
+        let keep_count = if total_time == 0 {
+            items.len() / 2 // nothing is known, so split by count
+        } else {
+            let mut keep_time = 0;
+            let mut keep_count = 0;
+            for item in items.iter() {
+                if keep_count > 0 && keep_time * 2 >= total_time {
+                    break;
+                }
+                keep_time += item.estimated_time;
+                keep_count += 1;
+            }
+            // always give away at least one item
+            std::cmp::min(keep_count, items.len() - 1)
+        };
+
+        let split_items = items.split_off(keep_count);
+        self.items = BinaryHeap::from(items);
         LocalPluginWork {
             pool: self.pool.clone(),
-            items: self.items.split(),
+            items: BinaryHeap::from(split_items),
         }
     }
 
+    /// Gets if another worker should take all of this work, which might require creating a new instance.
+    pub fn is_worth_taking(&self) -> bool {
+        self.pool.get_time_snapshot().has_plugin_available || self.pool.is_new_instance_worthwhile(self.items.len())
+    }
+
     pub fn calculate_worthwhile_steal_time(&self) -> Option<PluginStealInfo> {
         let remaining_len = self.items.len() as u64;
         if remaining_len <= 1 {
             return None; // don't steal, not worth it
         }
         let time_snapshot = self.pool.get_time_snapshot();
+        if !time_snapshot.has_plugin_available && !self.pool.is_new_instance_worthwhile((remaining_len / 2) as usize) {
+            return None; // don't steal, waiting for the existing instances is faster
+        }
         let actual_startup_time = if time_snapshot.has_plugin_available { 0 } else { time_snapshot.startup_time };
         let steal_time = (remaining_len / 2) * time_snapshot.average_format_time + actual_startup_time;
         let remaining_time = remaining_len * time_snapshot.average_format_time;
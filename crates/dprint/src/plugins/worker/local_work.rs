@@ -28,6 +28,8 @@ impl LocalWorkStealInfo {
 pub struct FormattingFilePathInfo {
     pub start_time: Instant,
     pub file_path: PathBuf,
+    /// Estimated milliseconds the file would take to format.
+    pub estimated_time: u64,
 }
 
 pub struct LocalWork<TEnvironment: Environment> {
@@ -49,10 +51,15 @@ impl<TEnvironment: Environment> LocalWork<TEnvironment> {
 
     pub fn calculate_worthwhile_steal_time(&self) -> Option<LocalWorkStealInfo> {
         if self.work_by_plugin.len() > 1 {
-            Some(LocalWorkStealInfo {
-                stealer_id: self.stealer_id,
-                kind: LocalWorkStealKind::Immediate
-            })
+            // the last plugin's work is what gets stolen
+            if self.work_by_plugin.last().unwrap().is_worth_taking() {
+                Some(LocalWorkStealInfo {
+                    stealer_id: self.stealer_id,
+                    kind: LocalWorkStealKind::Immediate
+                })
+            } else {
+                None
+            }
         } else {
             self.work_by_plugin.get(0)
                 .map(|plugin_work| plugin_work.calculate_worthwhile_steal_time())
@@ -68,10 +75,11 @@ impl<TEnvironment: Environment> LocalWork<TEnvironment> {
         self.current_formatting_file_path.clone()
     }
 
-    pub fn set_current_formatting_file_path(&mut self, file_path: PathBuf) {
+    pub fn set_current_formatting_file_path(&mut self, file_path: PathBuf, estimated_time: u64) {
         self.current_formatting_file_path = Some(FormattingFilePathInfo {
             start_time: Instant::now(),
             file_path,
+            estimated_time,
         });
     }
 
@@ -50,6 +50,13 @@ impl<TEnvironment: Environment> LongFormatCheckerThread<TEnvironment> {
                             // log if it hasn't been logged before
                             if !logged_file_paths.contains(&file_path_info.file_path) {
                                 environment.log_error(&format!("WARNING: Formatting is slow for {}", file_path_info.file_path.display()));
+                                log_verbose!(
+                                    environment,
+                                    "{} started formatting {}ms after the run began and was estimated to take {}ms.",
+                                    file_path_info.file_path.display(),
+                                    file_path_info.start_time.duration_since(worker_registry.start_time).as_millis(),
+                                    file_path_info.estimated_time,
+                                );
                                 logged_file_paths.insert(file_path_info.file_path.clone());
                             }
                         }
@@ -1,15 +1,16 @@
 mod do_batch_format;
-mod deque;
 mod long_format_checker_thread;
 mod local_plugin_work;
 mod local_work;
+mod work_batcher;
 mod worker;
 mod worker_registry;
 
 pub use do_batch_format::{do_batch_format};
-use deque::*;
 use local_plugin_work::*;
+pub use local_plugin_work::FileEstimate;
 use local_work::*;
+use work_batcher::*;
 use long_format_checker_thread::*;
 use worker::*;
 use worker_registry::*;
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::environment::Environment;
+use crate::plugins::PluginPools;
+
+use super::{FileEstimate, LocalPluginWork, WorkItem, WorkerRegistry};
+
+/// The most file paths to send to a worker at once.
+const MAX_BATCH_SIZE: usize = 128;
+/// Files estimated to take at least this many milliseconds are sent to
+/// the workers right away instead of waiting for the batch to fill.
+const SEND_IMMEDIATELY_ESTIMATED_TIME: u64 = 100;
+
+/// Groups discovered file paths by plugin and gives them to the workers in batches.
+///
+/// The first file path for each plugin is sent right away so that plugins get
+/// initialized while discovery continues. After that the batch size doubles
+/// each time in order to reduce the overhead of sending lots of small batches.
+/// Slow files are sent right away and the workers queue files by their estimated
+/// format time, so a slow file found late in discovery is still started early.
+pub struct WorkBatcher<'a, TEnvironment: Environment> {
+    registry: &'a WorkerRegistry<TEnvironment>,
+    plugin_pools: &'a PluginPools<TEnvironment>,
+    get_file_estimate: &'a dyn Fn(&Path) -> FileEstimate,
+    pending_by_plugin: HashMap<String, PendingBatch>,
+    file_count: usize,
+}
+
+struct PendingBatch {
+    items: Vec<WorkItem>,
+    max_size: usize,
+}
+
+impl<'a, TEnvironment: Environment> WorkBatcher<'a, TEnvironment> {
+    pub fn new(
+        registry: &'a WorkerRegistry<TEnvironment>,
+        plugin_pools: &'a PluginPools<TEnvironment>,
+        get_file_estimate: &'a dyn Fn(&Path) -> FileEstimate,
+    ) -> Self {
+        WorkBatcher {
+            registry,
+            plugin_pools,
+            get_file_estimate,
+            pending_by_plugin: HashMap::new(),
+            file_count: 0,
+        }
+    }
+
+    /// Adds the file path to the work if a plugin can format it.
+    pub fn add(&mut self, file_path: PathBuf) {
+        let plugin_name = match self.plugin_pools.get_plugin_name_from_file_name(&file_path) {
+            Some(plugin_name) => plugin_name,
+            None => return,
+        };
+        self.file_count += 1;
+
+        let FileEstimate { estimated_time, file_metadata } = (self.get_file_estimate)(&file_path);
+        let pending_batch = self.pending_by_plugin.entry(plugin_name.clone()).or_insert_with(|| PendingBatch {
+            items: Vec::new(),
+            max_size: 1,
+        });
+        pending_batch.items.push(WorkItem { file_path, estimated_time, file_metadata });
+
+        if pending_batch.items.len() >= pending_batch.max_size || estimated_time >= SEND_IMMEDIATELY_ESTIMATED_TIME {
+            let items = std::mem::take(&mut pending_batch.items);
+            pending_batch.max_size = std::cmp::min(pending_batch.max_size * 2, MAX_BATCH_SIZE);
+            self.send(&plugin_name, items);
+        }
+    }
+
+    /// Sends any pending work, marks discovery as complete, and returns the
+    /// number of file paths that will be formatted.
+    pub fn finish(mut self) -> usize {
+        for (plugin_name, pending_batch) in std::mem::take(&mut self.pending_by_plugin) {
+            if !pending_batch.items.is_empty() {
+                self.send(&plugin_name, pending_batch.items);
+            }
+        }
+        self.registry.complete_discovery();
+        self.file_count
+    }
+
+    /// Discards the pending work along with the work the workers haven't started.
+    pub fn cancel(self) {
+        self.registry.cancel_work();
+    }
+
+    fn send(&self, plugin_name: &str, items: Vec<WorkItem>) {
+        let pool = self.plugin_pools.get_pool(plugin_name).unwrap();
+        self.registry.add_work(LocalPluginWork::new(pool, items));
+    }
+}
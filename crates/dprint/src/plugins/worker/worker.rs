@@ -1,11 +1,10 @@
 use parking_lot::RwLock;
-use std::path::PathBuf;
 use std::sync::Arc;
 
 use crate::environment::Environment;
 use crate::plugins::{InitializedPlugin, InitializedPluginPool};
 
-use super::{LocalPluginWork, LocalWork, LocalWorkStealInfo, FormattingFilePathInfo};
+use super::{LocalPluginWork, LocalWork, LocalWorkStealInfo, FormattingFilePathInfo, WorkItem};
 
 pub struct StealResult<TEnvironment: Environment> {
     pub plugin: Option<Box<dyn InitializedPlugin>>,
@@ -87,26 +86,40 @@ impl<TEnvironment: Environment> Worker<TEnvironment> {
         }
     }
 
+    pub fn work_items_len(&self) -> usize {
+        self.local_work.read().work_by_plugin.iter().map(|work| work.work_items_len()).sum()
+    }
+
+    /// Adds the work to the worker's queue for the plugin, which puts the items
+    /// estimated to take longer ahead of the ones already queued.
     pub fn add_work(&self, work: LocalPluginWork<TEnvironment>) {
-        self.local_work.write().work_by_plugin.push(work);
+        let mut local_work = self.local_work.write();
+        match local_work.work_by_plugin.iter_mut().find(|existing_work| existing_work.pool.name() == work.pool.name()) {
+            Some(existing_work) => existing_work.merge(work),
+            None => local_work.work_by_plugin.push(work),
+        }
     }
 
-    pub fn take_next_work(&self) -> Option<(Arc<InitializedPluginPool<TEnvironment>>, PathBuf)> {
+    pub fn take_next_work(&self) -> Option<(Arc<InitializedPluginPool<TEnvironment>>, WorkItem)> {
         let mut local_work = self.local_work.write();
         if let Some(work_by_plugin) = local_work.work_by_plugin.get_mut(0) {
             let pool = work_by_plugin.pool.clone();
-            let file_path = work_by_plugin.take_next_work_item();
+            let work_item = work_by_plugin.take_next_work_item();
             if work_by_plugin.work_items_len() == 0 {
                 local_work.work_by_plugin.remove(0);
             }
-            local_work.set_current_formatting_file_path(file_path.clone());
-            Some((pool, file_path))
+            local_work.set_current_formatting_file_path(work_item.file_path.clone(), work_item.estimated_time);
+            Some((pool, work_item))
         } else {
             local_work.clear_current_formatting_file_path();
             None
         }
     }
 
+    pub fn clear_work(&self) {
+        self.local_work.write().work_by_plugin.clear();
+    }
+
     pub fn clear_work_for_current_plugin(&self) {
         let mut local_work = self.local_work.write();
         if !local_work.work_by_plugin.is_empty() {
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use parking_lot::{Condvar, Mutex};
 use std::sync::Arc;
+use std::time::Instant;
 
 use crate::environment::Environment;
 use crate::plugins::PluginPools;
@@ -10,45 +10,81 @@ use super::{LocalPluginWork, Worker, LocalWorkStealInfo, LocalWorkStealKind, Ste
 pub struct WorkerRegistry<TEnvironment: Environment> {
     plugin_pools: Arc<PluginPools<TEnvironment>>,
     pub workers: Vec<Arc<Worker<TEnvironment>>>,
+    pub start_time: Instant,
+    discovery_state: Mutex<DiscoveryState>,
+    discovery_state_changed: Condvar,
+}
+
+struct DiscoveryState {
+    /// Incremented each time work is added so waiting workers can tell when to check again.
+    work_generation: usize,
+    is_complete: bool,
 }
 
 impl<TEnvironment: Environment> WorkerRegistry<TEnvironment> {
-    pub fn new(plugin_pools: Arc<PluginPools<TEnvironment>>, file_paths_by_plugin: HashMap<String, Vec<PathBuf>>) -> Self {
-        let workers = get_workers(&plugin_pools, file_paths_by_plugin);
-        return WorkerRegistry {
+    pub fn new(plugin_pools: Arc<PluginPools<TEnvironment>>) -> Self {
+        let number_threads = std::cmp::max(1, num_cpus::get()); // use logical cores (same as Rayon)
+        let workers = (0..number_threads).map(|i| Arc::new(Worker::new(i, Vec::new()))).collect();
+        WorkerRegistry {
             plugin_pools,
             workers,
+            start_time: Instant::now(),
+            discovery_state: Mutex::new(DiscoveryState {
+                work_generation: 0,
+                is_complete: false,
+            }),
+            discovery_state_changed: Condvar::new(),
+        }
+    }
+
+    /// Gives newly discovered work to the worker with the least amount of queued work.
+    ///
+    /// When another instance of the plugin isn't worth creating for the work, the work is
+    /// given to a worker that already has work for the plugin instead.
+    pub fn add_work(&self, work: LocalPluginWork<TEnvironment>) {
+        let worker = if work.is_worth_taking() {
+            None
+        } else {
+            self.workers.iter()
+                .filter(|worker| worker.has_pool(work.pool.name()))
+                .min_by_key(|worker| worker.work_items_len())
         };
+        let worker = worker.unwrap_or_else(|| self.workers.iter().min_by_key(|worker| worker.work_items_len()).unwrap());
+        worker.add_work(work);
 
-        fn get_workers<TEnvironment:Environment>(
-            plugin_pools: &PluginPools<TEnvironment>,
-            file_paths_by_plugin: HashMap<String, Vec<PathBuf>>,
-        ) -> Vec<Arc<Worker<TEnvironment>>> {
-            let number_threads = std::cmp::max(1, num_cpus::get()); // use logical cores (same as Rayon)
-            let mut workers = Vec::with_capacity(number_threads);
-
-            // initially divide work by plugins
-            let mut item_stacks = Vec::with_capacity(number_threads);
-            for (i, (plugin_name, file_paths)) in file_paths_by_plugin.into_iter().enumerate() {
-                let i = i % number_threads;
-                if item_stacks.get(i).is_none() {
-                    item_stacks.push(Vec::new());
-                }
-                item_stacks.get_mut(i).unwrap().push(LocalPluginWork::new(
-                    plugin_pools.get_pool(&plugin_name).unwrap(),
-                    file_paths,
-                ));
-            }
+        let mut discovery_state = self.discovery_state.lock();
+        discovery_state.work_generation += 1;
+        self.discovery_state_changed.notify_all();
+    }
 
-            // create the workers
-            for i in 0..number_threads {
-                workers.push(Arc::new(Worker::new(i, item_stacks.pop().unwrap_or(Vec::new()))));
-            }
+    /// Signals that no more work will be added.
+    pub fn complete_discovery(&self) {
+        let mut discovery_state = self.discovery_state.lock();
+        discovery_state.is_complete = true;
+        self.discovery_state_changed.notify_all();
+    }
 
-            debug_assert!(item_stacks.is_empty());
+    /// Clears the work that hasn't been started and signals that no more work will be added.
+    pub fn cancel_work(&self) {
+        for worker in self.workers.iter() {
+            worker.clear_work();
+        }
+        self.complete_discovery();
+    }
+
+    pub fn work_generation(&self) -> usize {
+        self.discovery_state.lock().work_generation
+    }
 
-            workers
+    /// Blocks until work is added after the provided generation or discovery completes.
+    ///
+    /// Returns `false` when no more work will be added.
+    pub fn wait_for_work(&self, work_generation: usize) -> bool {
+        let mut discovery_state = self.discovery_state.lock();
+        while discovery_state.work_generation == work_generation && !discovery_state.is_complete {
+            self.discovery_state_changed.wait(&mut discovery_state);
         }
+        discovery_state.work_generation != work_generation
     }
 
     pub fn release_pool_if_no_work_in_registry(&self, asking_worker_id: usize, pool_name: &str) {
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use dprint_core::types::ErrBox;
+
+use crate::environment::Environment;
+
+/// Writes the file by writing to a temporary file first, then renaming it
+/// over the destination so other processes never read a partially written file.
+pub fn atomic_write_file(file_path: &Path, file_text: &str, environment: &impl Environment) -> Result<(), ErrBox> {
+    atomic_write_file_bytes(file_path, file_text.as_bytes(), environment)
+}
+
+/// Writes the bytes to the file the same way as `atomic_write_file`.
+pub fn atomic_write_file_bytes(file_path: &Path, bytes: &[u8], environment: &impl Environment) -> Result<(), ErrBox> {
+    let temp_file_path = get_temp_file_path(file_path);
+    environment.write_file_bytes(&temp_file_path, bytes)?;
+    if let Err(err) = environment.rename(&temp_file_path, file_path) {
+        let _ = environment.remove_file(&temp_file_path);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Gets the path of the temporary file written beside the file before being renamed over it.
+pub fn get_temp_file_path(file_path: &Path) -> PathBuf {
+    let mut file_name = file_path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    file_name.push(format!(".{}.tmp", std::process::id()));
+    file_path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::environment::TestEnvironment;
+
+    #[test]
+    fn it_should_write_file_and_remove_temp_file() {
+        let environment = TestEnvironment::new();
+        let file_path = PathBuf::from("/cache/manifest.json");
+        environment.write_file(&file_path, "old").unwrap();
+        atomic_write_file(&file_path, "new", &environment).unwrap();
+
+        assert_eq!(environment.read_file(&file_path).unwrap(), "new");
+        assert_eq!(environment.path_exists(&get_temp_file_path(&file_path)), false);
+    }
+
+    #[test]
+    fn it_should_get_temp_file_path_in_same_dir() {
+        let temp_file_path = get_temp_file_path(&PathBuf::from("/cache/manifest.json"));
+        assert_eq!(temp_file_path.parent().unwrap(), PathBuf::from("/cache"));
+        assert_eq!(temp_file_path.file_name().unwrap().to_string_lossy(), format!("manifest.json.{}.tmp", std::process::id()));
+    }
+}
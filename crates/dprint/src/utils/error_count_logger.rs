@@ -22,6 +22,11 @@ impl<TEnvironment: Environment> ErrorCountLogger<TEnvironment> {
         self.error_count.fetch_add(1, Ordering::SeqCst);
     }
 
+    /// Counts an error that was output some other way (ex. in a machine-readable format).
+    pub fn count_error(&self) {
+        self.error_count.fetch_add(1, Ordering::SeqCst);
+    }
+
     pub fn get_error_count(&self) -> usize {
         self.error_count.load(Ordering::SeqCst)
     }
@@ -1,380 +1,293 @@
-use dissimilar::*;
+use std::time::Duration;
 use crossterm::style::Stylize;
+use similar::{Algorithm, ChangeTag, TextDiff};
+
+use super::BOM_CHAR;
+
+/// Lines longer than this will have their unchanged parts collapsed.
+const MAX_LINE_WIDTH: usize = 120;
+/// Number of characters to keep on each side of a collapsed part of a line.
+const COLLAPSED_KEEP_CHARS: usize = 20;
+/// Gives up on finding the smallest difference after this amount of time.
+const DIFF_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub struct DiffOptions {
+    /// Number of unchanged lines to show before and after each change.
+    pub context_lines: usize,
+    /// Whether to output colors. When false, changed words are surrounded
+    /// with `[-` and `-]` for removals and `{+` and `+}` for additions.
+    pub use_colors: bool,
+}
 
-use dprint_core::types::ErrBox;
-
-// TODO: This file needs improvement as it is kind of buggy, but
-// does the job for now.
-
-/// Gets a string showing the difference between two strings.
-/// Note: This returns a Result because this funciton has been unstable.
-pub fn get_difference(text1: &str, text2: &str) -> Result<String, ErrBox> {
-    debug_assert!(text1 != text2);
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            context_lines: 2,
+            use_colors: true,
+        }
+    }
+}
 
-    // normalize newlines
-    let text1 = text1.replace("\r\n", "\n");
-    let text2 = text2.replace("\r\n", "\n");
+/// Gets a string showing the difference between the original and formatted text.
+pub fn get_difference(original_text: &str, formatted_text: &str) -> String {
+    get_difference_with_options(original_text, formatted_text, &DiffOptions::default())
+}
 
-    if text1 == text2 {
-        return Ok(String::from(" | Text differed by line endings."));
+/// Gets a string showing the difference between the original and formatted text using the provided options.
+pub fn get_difference_with_options(original_text: &str, formatted_text: &str, options: &DiffOptions) -> String {
+    if let Some(summary) = get_summary(original_text, formatted_text) {
+        return summary;
     }
 
-    let grouped_changes = get_grouped_changes(&text1, &text2);
-    let mut text = String::new();
-
-    for (i, grouped_change) in grouped_changes.into_iter().enumerate() {
+    let original_line_endings = LineEndings::from_text(original_text);
+    let formatted_line_endings = LineEndings::from_text(formatted_text);
+    let renderer = Renderer {
+        options,
+        show_carriage_returns: original_line_endings != formatted_line_endings,
+    };
+    let diff = TextDiff::configure()
+        .algorithm(Algorithm::Patience)
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(original_text, formatted_text);
+    let groups = diff.grouped_ops(options.context_lines);
+    let line_number_width = groups
+        .iter()
+        .filter_map(|group| group.last())
+        .map(|op| op.old_range().end.to_string().len())
+        .max()
+        .unwrap_or(1);
+    let mut lines = Vec::new();
+
+    for (i, group) in groups.iter().enumerate() {
         if i > 0 {
-            text.push_str("\n...\n");
+            lines.push(String::from("..."));
         }
 
-        let max_line_num_width = grouped_change.end_line_number.to_string().chars().count();
-        text.push_str(&format!("{:width$}| ", grouped_change.start_line_number, width = max_line_num_width));
-        text.push_str(&annotate_whitespace(get_line_start_text(&text1, grouped_change.start_index)?));
-        let mut last_index = grouped_change.start_index;
-
-        for change in grouped_change.changes {
-            for (i, line) in text1[last_index..change.start_index()].split('\n').enumerate() {
-                if i > 0 {
-                    text.push_str(&format!("\n{}| ", " ".repeat(max_line_num_width)));
-                }
-
-                text.push_str(&annotate_whitespace(line));
-            }
-
-            last_index = change.end_index();
-
-            match change {
-                Change::Addition(addition) => {
-                    for (i, line) in addition.new_text.split('\n').enumerate() {
-                        if i > 0 {
-                            text.push_str("\n");
-                            text.push_str(&get_addition_text(&format!("{}| ", " ".repeat(max_line_num_width))));
-                        }
-
-                        if !line.is_empty() {
-                            text.push_str(&get_addition_text(&annotate_whitespace(line)));
-                        }
-                    }
-                },
-                Change::Removal(removal) => {
-                    for (i, line) in removal.removed_text.split('\n').enumerate() {
-                        if i > 0 {
-                            text.push_str("\n");
-                            let line_text = format!("{}| ", " ".repeat(max_line_num_width));
-                            if line.is_empty() {
-                                text.push_str(&get_removal_text(&line_text));
-                            } else {
-                                text.push_str(&line_text);
-                            }
-                        }
-
-                        if !line.is_empty() {
-                            text.push_str(&get_removal_text(&annotate_whitespace(line)));
-                        }
-                    }
+        for op in group {
+            for change in diff.iter_inline_changes(op) {
+                let line_number = match change.tag() {
+                    ChangeTag::Insert => None,
+                    ChangeTag::Delete | ChangeTag::Equal => change.old_index().map(|index| index + 1),
+                };
+                let segments = change.iter_strings_lossy().map(|(emphasized, text)| (emphasized, text.to_string())).collect();
+                lines.push(renderer.render_line(change.tag(), line_number, line_number_width, segments));
+                if change.missing_newline() && change.tag() != ChangeTag::Equal {
+                    lines.push(renderer.render_gutter(ChangeTag::Equal, None, line_number_width) + "\\ No newline at end of file");
                 }
             }
         }
-
-        text.push_str(&annotate_whitespace(&get_line_end_text(&text1, grouped_change.end_index)));
     }
 
-    Ok(text)
+    lines.join("\n")
 }
 
-fn get_line_start_text<'a>(text: &'a str, index: usize) -> Result<&'a str, ErrBox> {
-    let new_line_byte = '\n' as u8;
-    let text_bytes = text.as_bytes();
-    let mut start_index = 0;
-    let mut length = 0;
-
-    if index > text.len() {
-        // this should never happen
-        return err!("The byte index was {}, but the text byte length is {}.", index, text.len());
+/// Gets a summary of the difference when only the BOM or line endings differ.
+fn get_summary(original_text: &str, formatted_text: &str) -> Option<String> {
+    if original_text == formatted_text {
+        return Some(String::from("The text is the same."));
     }
 
-    for i in (0..index).rev() {
-        if text_bytes[i] == new_line_byte || length > 50 {
-            start_index = i + 1;
-            break;
-        }
-        length += 1
-    }
+    let original_has_bom = original_text.starts_with(BOM_CHAR);
+    let formatted_has_bom = formatted_text.starts_with(BOM_CHAR);
+    let original_text = original_text.trim_start_matches(BOM_CHAR);
+    let formatted_text = formatted_text.trim_start_matches(BOM_CHAR);
+    let bom_differs = original_has_bom != formatted_has_bom;
+    let line_endings_differ = original_text != formatted_text;
 
-    Ok(&text[start_index..index])
-}
-
-fn get_line_end_text<'a>(text: &'a str, index: usize) -> &'a str {
-    let new_line_byte = '\n' as u8;
-    let text_bytes = text.as_bytes();
-    let mut end_index = index;
-    let mut length = 0;
-
-    for i in index..text.len() {
-        if text_bytes[i] == new_line_byte || length > 50 {
-            end_index = i;
-            break;
-        }
-        length += 1;
-    }
-
-    &text[index..end_index]
-}
-
-#[derive(Debug)]
-struct GroupedChange<'a> {
-    start_index: usize,
-    end_index: usize,
-    start_line_number: usize,
-    end_line_number: usize,
-    changes: Vec<Change<'a>>,
-}
-
-fn get_grouped_changes<'a>(text1: &'a str, text2: &'a str) -> Vec<GroupedChange<'a>> {
-    let changes = get_changes(text1, text2);
-    let mut grouped_changes: Vec<GroupedChange<'a>> = Vec::new();
-
-    for change in changes {
-        if let Some(grouped_change) = grouped_changes.last_mut() {
-            // keeps changes together if they are only separated by a single line
-            const GROUPED_LINE_COUNT: usize = 2;
-            let should_group = change.start_line_number() < GROUPED_LINE_COUNT // prevent overflow
-                || grouped_change.end_line_number >= change.start_line_number() - GROUPED_LINE_COUNT;
-            if should_group {
-                grouped_change.end_index = change.end_index();
-                grouped_change.end_line_number = change.end_line_number();
-                grouped_change.changes.push(change);
-                continue;
-            }
-        }
-
-        grouped_changes.push(GroupedChange {
-            start_index: change.start_index(),
-            end_index: change.end_index(),
-            start_line_number: change.start_line_number(),
-            end_line_number: change.end_line_number(),
-            changes: vec![change],
-        })
+    if line_endings_differ && original_text.replace("\r\n", "\n") != formatted_text.replace("\r\n", "\n") {
+        return None;
     }
 
-    grouped_changes
+    let bom_detail = if original_has_bom { "found a BOM, expected none" } else { "expected a BOM, found none" };
+    let line_endings_detail = format!(
+        "found {}, expected {}",
+        LineEndings::from_text(original_text).display(),
+        LineEndings::from_text(formatted_text).display(),
+    );
+    Some(match (bom_differs, line_endings_differ) {
+        (true, false) => format!("Only the byte order mark differs ({}).", bom_detail),
+        (false, true) => format!("Only line endings differ ({}).", line_endings_detail),
+        _ => format!("Only the byte order mark ({}) and line endings ({}) differ.", bom_detail, line_endings_detail),
+    })
 }
 
-#[derive(Debug)]
-enum Change<'a> {
-    Addition(Addition<'a>),
-    Removal(Removal<'a>),
+#[derive(Debug, PartialEq)]
+enum LineEndings {
+    None,
+    Lf,
+    CrLf,
+    Mixed,
 }
 
-impl<'a> Change<'a> {
-    /// Gets the start index in the original string.
-    fn start_index(&self) -> usize {
-        match self {
-            Change::Addition(addition) => addition.insert_index,
-            Change::Removal(removal) => removal.start_index,
-        }
-    }
-
-    /// Gets the end index in the original string.
-    fn end_index(&self) -> usize {
-        match self {
-            Change::Addition(addition) => addition.insert_index,
-            Change::Removal(removal) => removal.end_index,
-        }
-    }
-
-    /// Gets the start line number in the original string.
-    fn start_line_number(&self) -> usize {
-        match self {
-            Change::Addition(addition) => addition.insert_line_number,
-            Change::Removal(removal) => removal.start_line_number,
+impl LineEndings {
+    pub fn from_text(text: &str) -> LineEndings {
+        let newline_count = text.matches('\n').count();
+        let crlf_count = text.matches("\r\n").count();
+        if newline_count == 0 {
+            LineEndings::None
+        } else if crlf_count == 0 {
+            LineEndings::Lf
+        } else if crlf_count == newline_count {
+            LineEndings::CrLf
+        } else {
+            LineEndings::Mixed
         }
     }
 
-    /// Gets the end line number in the original string.
-    fn end_line_number(&self) -> usize {
+    pub fn display(&self) -> &'static str {
         match self {
-            Change::Addition(addition) => addition.insert_line_number,
-            Change::Removal(removal) => removal.end_line_number,
+            LineEndings::None => "no line endings",
+            LineEndings::Lf => "LF",
+            LineEndings::CrLf => "CRLF",
+            LineEndings::Mixed => "mixed CRLF and LF",
         }
     }
 }
 
-#[derive(Debug)]
-struct Addition<'a> {
-    new_text: &'a str,
-    insert_index: usize,
-    insert_line_number: usize,
-}
-
-#[derive(Debug)]
-struct Removal<'a> {
-    removed_text: &'a str,
-    start_index: usize,
-    end_index: usize,
-    start_line_number: usize,
-    end_line_number: usize,
+struct Renderer<'a> {
+    options: &'a DiffOptions,
+    /// Whether to show carriage returns at the end of changed lines. This is only
+    /// done when the line endings differ as it would be noisy otherwise.
+    show_carriage_returns: bool,
 }
 
-fn get_changes<'a>(text1: &'a str, text2: &'a str) -> Vec<Change<'a>> {
-    let chunks = get_pre_processed_chunks(text1, text2);
-    let mut changes: Vec<Change<'a>> = Vec::new();
-
-    let mut line_number = 1;
-    let mut byte_index = 0;
-
-    for i in 0..chunks.len() {
-        let chunk = chunks[i];
-        match chunk {
-            Chunk::Insert(inserted_text) => {
-                changes.push(Change::Addition(Addition {
-                    new_text: inserted_text,
-                    insert_index: byte_index,
-                    insert_line_number: line_number,
-                }));
-            },
-            Chunk::Delete(deleted_text) => {
-                let line_count = deleted_text.split('\n').count() - 1;
-
-                changes.push(Change::Removal(Removal {
-                    removed_text: deleted_text,
-                    start_index: byte_index,
-                    end_index: byte_index + deleted_text.len(),
-                    start_line_number: line_number,
-                    end_line_number: line_number + line_count,
-                }));
-
-                byte_index += deleted_text.len();
-                line_number += line_count;
-            },
-            Chunk::Equal(equal_text) => {
-                byte_index += equal_text.len();
-                line_number += equal_text.split('\n').count() - 1;
-            },
+impl<'a> Renderer<'a> {
+    pub fn render_gutter(&self, tag: ChangeTag, line_number: Option<usize>, line_number_width: usize) -> String {
+        let line_number = line_number.map(|line_number| line_number.to_string()).unwrap_or_default();
+        let sign = match tag {
+            ChangeTag::Equal => " ",
+            ChangeTag::Delete => "-",
+            ChangeTag::Insert => "+",
+        };
+        let text = format!("{}{:>width$}| ", sign, line_number, width = line_number_width);
+        if self.options.use_colors {
+            match tag {
+                ChangeTag::Equal => text,
+                ChangeTag::Delete => text.red().to_string(),
+                ChangeTag::Insert => text.green().to_string(),
+            }
+        } else {
+            text
         }
     }
 
-    changes
-}
+    pub fn render_line(&self, tag: ChangeTag, line_number: Option<usize>, line_number_width: usize, segments: Vec<(bool, String)>) -> String {
+        let mut text = self.render_gutter(tag, line_number, line_number_width);
+        let segments = self.collapse_long_line(strip_line_ending(segments, self.show_carriage_returns && tag != ChangeTag::Equal));
+        let has_emphasis = segments.iter().any(|(emphasized, _)| *emphasized);
 
-fn get_pre_processed_chunks<'a>(text1: &'a str, text2: &'a str) -> Vec<dissimilar::Chunk<'a>> {
-    // What we want to do here is take a collection of chunks like this:
-    //   [Equal("class Test"), Delete("\n"), Insert(" "), Equal("{\n"), Delete("\n"), Equal("}"), Insert("\n")]
-    // And transform them so the parts that proceed the newline delete end up as inserts on the previous line and deletes on the next:
-    //   [Equal("class Test"), Insert(" "), Insert("{"), Equal("\n"), Delete("{"), Delete("\n"), Delete("\n"), Equal("}"), Insert("\n")]
-    // Note: It would probably be nice to group like chunks together here... for the future...
-    let chunks = dissimilar::diff(text1, text2);
-
-    let mut final_chunks = Vec::new();
-    let mut i = 0;
-    while i < chunks.len() {
-        let chunk = chunks[i];
-
-        let mut was_deleted_with_newline = false;
-        if let Chunk::Delete(delete_text) = chunk {
-            if let Some(Chunk::Equal(last_change)) = final_chunks.last() {
-                was_deleted_with_newline = delete_text.find('\n').is_some() && !last_change.ends_with('\n');
+        for (emphasized, segment_text) in segments {
+            if tag == ChangeTag::Equal {
+                text.push_str(&segment_text);
+                continue;
             }
-        }
 
-        if was_deleted_with_newline {
-            let add_delete_at_end = if let Some(Chunk::Insert(insert_text)) = chunks.get(i + 1) {
-                // Do not add a delete newline at the end if the next chunk is inserting a newline.
-                // TBH: This doesn't seem exactly right and I did this to fix a bug.
-                !insert_text.contains('\n')
+            let segment_text = annotate_whitespace(&segment_text);
+            if self.options.use_colors {
+                // when nothing is emphasized, the entire line was changed
+                let emphasized = emphasized || !has_emphasis;
+                text.push_str(&match (tag, emphasized) {
+                    (ChangeTag::Delete, true) => segment_text.white().on_red().to_string(),
+                    (ChangeTag::Delete, false) => segment_text.red().to_string(),
+                    (ChangeTag::Insert, true) => segment_text.white().on_green().to_string(),
+                    (ChangeTag::Insert, false) => segment_text.green().to_string(),
+                    (ChangeTag::Equal, _) => unreachable!(),
+                });
+            } else if emphasized {
+                text.push_str(&match tag {
+                    ChangeTag::Delete => format!("[-{}-]", segment_text),
+                    _ => format!("{{+{}+}}", segment_text),
+                });
             } else {
-                true
-            };
-
-            let delete_text = if let Chunk::Delete(delete_text) = chunk { delete_text } else { unreachable!() };
-
-            // push delete text previous line
-            let delete_text_new_line_index = delete_text.find('\n').unwrap();
-            if delete_text_new_line_index > 0 {
-                final_chunks.push(dissimilar::Chunk::Delete(&delete_text[0..delete_text_new_line_index]));
+                text.push_str(&segment_text);
             }
+        }
 
-            // push delete text next line for next line chunks
-            let mut next_line_chunks = Vec::new();
-            if delete_text_new_line_index + 1 < delete_text.len() {
-                next_line_chunks.push(dissimilar::Chunk::Delete(&delete_text[delete_text_new_line_index + 1..]));
-            }
+        text
+    }
 
-            i += 1;
-
-            // move the next equal or insert chunks to be inserts on the previous line and deletes on the current
-            while i < chunks.len() {
-                let chunk = chunks[i];
-                match chunk {
-                    Chunk::Equal(equal_text) => {
-                        let new_line_index = equal_text.find('\n');
-                        if let Some(new_line_index) = new_line_index {
-                            // for previous line
-                            final_chunks.push(dissimilar::Chunk::Insert(&equal_text[0..new_line_index]));
-
-                            // for next line
-                            next_line_chunks.push(dissimilar::Chunk::Delete(&equal_text[0..new_line_index]));
-
-                            // for remainder of next line equal text
-                            let remainder_equal_text = &equal_text[new_line_index + 1..];
-                            if remainder_equal_text.len() > 0 {
-                                next_line_chunks.push(dissimilar::Chunk::Equal(remainder_equal_text));
-                            }
-                            break;
-                        } else {
-                            final_chunks.push(dissimilar::Chunk::Insert(equal_text));
-                            next_line_chunks.push(dissimilar::Chunk::Delete(equal_text));
-                        }
-                    },
-                    Chunk::Insert(insert_text) => {
-                        let new_line_index = insert_text.find('\n');
-                        if let Some(new_line_index) = new_line_index {
-                            // for previous line
-                            final_chunks.push(dissimilar::Chunk::Insert(&insert_text[0..new_line_index]));
-                            // for next line
-                            let remainder_text = &insert_text[new_line_index + 1..];
-                            if remainder_text.len() > 0 {
-                                next_line_chunks.push(dissimilar::Chunk::Insert(remainder_text));
-                            }
-                            break;
-                        } else {
-                            final_chunks.push(dissimilar::Chunk::Insert(insert_text));
-                        }
-                    }
-                    Chunk::Delete(delete_text) => {
-                        next_line_chunks.push(dissimilar::Chunk::Delete(delete_text));
-                    }
-                }
-                i += 1;
-            }
+    fn collapse_long_line(&self, segments: Vec<(bool, String)>) -> Vec<(bool, String)> {
+        let line_width = segments.iter().map(|(_, text)| text.chars().count()).sum::<usize>();
+        if line_width <= MAX_LINE_WIDTH {
+            return segments;
+        }
 
-            final_chunks.push(dissimilar::Chunk::Equal(&delete_text[delete_text_new_line_index..delete_text_new_line_index+1]));
-            final_chunks.extend(next_line_chunks);
-            if add_delete_at_end {
-                final_chunks.push(dissimilar::Chunk::Delete(&delete_text[delete_text_new_line_index..delete_text_new_line_index+1]));
+        let last_index = segments.len().saturating_sub(1);
+        segments
+            .into_iter()
+            .enumerate()
+            .map(|(i, (emphasized, text))| {
+                let text = if emphasized {
+                    collapse_middle(&text, MAX_LINE_WIDTH / 2)
+                } else if last_index == 0 {
+                    collapse_end(&text, MAX_LINE_WIDTH)
+                } else if i == 0 {
+                    collapse_start(&text, COLLAPSED_KEEP_CHARS)
+                } else if i == last_index {
+                    collapse_end(&text, COLLAPSED_KEEP_CHARS)
+                } else {
+                    collapse_middle(&text, COLLAPSED_KEEP_CHARS)
+                };
+                (emphasized, text)
+            })
+            .collect()
+    }
+}
+
+/// Removes the newline from the end of the last segment. The carriage return
+/// is only kept when specified.
+fn strip_line_ending(mut segments: Vec<(bool, String)>, keep_carriage_return: bool) -> Vec<(bool, String)> {
+    if let Some((_, text)) = segments.last_mut() {
+        if text.ends_with('\n') {
+            text.pop();
+            if text.ends_with('\r') && !keep_carriage_return {
+                text.pop();
             }
-        } else {
-            final_chunks.push(chunk);
         }
-
-        i += 1;
     }
+    segments.retain(|(_, text)| !text.is_empty());
+    segments
+}
 
-    final_chunks
+/// Keeps the last characters of the text.
+fn collapse_start(text: &str, keep_chars: usize) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    if chars.len() <= keep_chars + 1 {
+        text.to_string()
+    } else {
+        format!("\u{2026}{}", chars[chars.len() - keep_chars..].iter().collect::<String>())
+    }
 }
 
-fn get_addition_text(text: &str) -> String {
-    text.white().on_green().to_string()
+/// Keeps the first characters of the text.
+fn collapse_end(text: &str, keep_chars: usize) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    if chars.len() <= keep_chars + 1 {
+        text.to_string()
+    } else {
+        format!("{}\u{2026}", chars[..keep_chars].iter().collect::<String>())
+    }
 }
 
-fn get_removal_text(text: &str) -> String {
-    let text = text.replace("\t", "\u{21E5}");
-    text.white().on_red().to_string()
+/// Keeps the first and last characters of the text.
+fn collapse_middle(text: &str, keep_chars: usize) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    if chars.len() <= keep_chars * 2 + 1 {
+        text.to_string()
+    } else {
+        format!(
+            "{}\u{2026}{}",
+            chars[..keep_chars].iter().collect::<String>(),
+            chars[chars.len() - keep_chars..].iter().collect::<String>()
+        )
+    }
 }
 
 fn annotate_whitespace(text: &str) -> String {
     text.replace("\t", "\u{2192}")
         .replace(" ", "\u{00B7}")
+        .replace("\r", "\u{240D}")
 }
 
 #[cfg(test)]
@@ -382,90 +295,196 @@ mod test {
     use pretty_assertions::assert_eq;
     use super::*;
 
+    fn get_plain_difference(original_text: &str, formatted_text: &str) -> String {
+        get_plain_difference_with_context(original_text, formatted_text, 2)
+    }
+
+    fn get_plain_difference_with_context(original_text: &str, formatted_text: &str, context_lines: usize) -> String {
+        get_difference_with_options(original_text, formatted_text, &DiffOptions {
+            context_lines,
+            use_colors: false,
+        })
+    }
+
+    /// Asserts the difference matches the snapshot in `tests/snapshots/difference`.
+    /// Run with `DPRINT_UPDATE_SNAPSHOTS=1` to overwrite the snapshot with the actual result.
+    fn assert_difference_snapshot(name: &str, original_text: &str, formatted_text: &str) {
+        let snapshot_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/snapshots/difference")
+            .join(format!("{}.txt", name));
+        let actual = format!("{}\n", get_plain_difference(original_text, formatted_text));
+        if std::env::var("DPRINT_UPDATE_SNAPSHOTS").is_ok() {
+            std::fs::write(&snapshot_path, &actual).expect("Expected to write the snapshot.");
+            return;
+        }
+        let expected = std::fs::read_to_string(&snapshot_path)
+            .unwrap_or_else(|err| panic!("Could not read snapshot {}: {}", snapshot_path.display(), err.to_string()));
+        assert_eq!(actual, expected.replace("\r\n", "\n"));
+    }
+
     #[test]
-    fn it_should_get_when_differs_by_line_endings() {
-        assert_eq!(get_difference("test\r\n", "test\n").unwrap(), " | Text differed by line endings.");
+    fn it_should_get_when_only_line_endings_differ() {
+        assert_eq!(get_plain_difference("test\r\ntest\r\n", "test\ntest\n"), "Only line endings differ (found CRLF, expected LF).");
+        assert_eq!(get_plain_difference("test\ntest\r\n", "test\r\ntest\r\n"), "Only line endings differ (found mixed CRLF and LF, expected CRLF).");
+    }
+
+    #[test]
+    fn it_should_get_when_only_bom_differs() {
+        assert_eq!(get_plain_difference("\u{FEFF}test\n", "test\n"), "Only the byte order mark differs (found a BOM, expected none).");
+        assert_eq!(get_plain_difference("test\n", "\u{FEFF}test\n"), "Only the byte order mark differs (expected a BOM, found none).");
+    }
+
+    #[test]
+    fn it_should_get_when_bom_and_line_endings_differ() {
+        assert_eq!(
+            get_plain_difference("\u{FEFF}test\r\n", "test\n"),
+            "Only the byte order mark (found a BOM, expected none) and line endings (found CRLF, expected LF) differ."
+        );
     }
 
     #[test]
     fn it_should_get_difference_on_one_line() {
-        assert_eq!(get_difference("test1\n", "test2\n").unwrap(), format!("1| test{}{}", get_removal_text("1"), get_addition_text("2")));
+        assert_eq!(get_plain_difference("let a = 1;\n", "let a = 2;\n"), "-1| let·a·=·[-1;-]\n+ | let·a·=·{+2;+}");
     }
 
     #[test]
-    fn it_should_show_the_addition_of_last_line() {
+    fn it_should_color_difference() {
         assert_eq!(
-            get_difference("testing\ntesting", "testing\ntesting\n").unwrap(),
+            get_difference("let a = 1;\n", "let a = 2;\n"),
             format!(
-                "{}\n{}",
-                "2| testing",
-                get_addition_text(&format!(" | "))
+                "{}{}{}\n{}{}{}",
+                "-1| ".red(),
+                "let·a·=·".red(),
+                "1;".white().on_red(),
+                "+ | ".green(),
+                "let·a·=·".green(),
+                "2;".white().on_green(),
             )
         );
     }
 
     #[test]
-    fn it_should_get_difference_for_removed_line() {
+    fn it_should_show_context_lines() {
+        let original_text = "a\nb\nc\nd\ne\nf\ng\n";
+        let formatted_text = "a\nb\nc\nD\ne\nf\ng\n";
+        assert_eq!(get_plain_difference_with_context(original_text, formatted_text, 0), "-4| [-d-]\n+ | {+D+}");
+        assert_eq!(get_plain_difference_with_context(original_text, formatted_text, 1), " 3| c\n-4| [-d-]\n+ | {+D+}\n 5| e");
         assert_eq!(
-            get_difference("class Test\n{\n\n}", "class Test {\n}\n").unwrap(),
-            format!(
-                "{}\n{}\n{}\n{}\n{}",
-                format!("1| class\u{00B7}Test{}{}", get_addition_text("\u{00B7}"), get_addition_text("{")),
-                format!(" | {}", get_removal_text("{")),
-                get_removal_text(" | "),
-                format!("{}{}", get_removal_text(" | "), "}"),
-                get_addition_text(" | "),
-            )
+            get_plain_difference_with_context(original_text, formatted_text, 5),
+            " 1| a\n 2| b\n 3| c\n-4| [-d-]\n+ | {+D+}\n 5| e\n 6| f\n 7| g"
         );
     }
 
     #[test]
-    fn it_should_show_multiple_removals_on_different_lines() {
+    fn it_should_separate_distant_changes() {
         assert_eq!(
-            get_difference("let t ;\n\n\nlet u ;\n", "let t;\n\n\nlet u;\n").unwrap(),
-            format!(
-                "{}\n...\n{}",
-                format!("1| let\u{00B7}t{};", get_removal_text("\u{00B7}")),
-                format!("4| let\u{00B7}u{};", get_removal_text("\u{00B7}")),
-            )
+            get_plain_difference_with_context("let t ;\n\n\n\nlet u ;\n", "let t;\n\n\n\nlet u;\n", 1),
+            "-1| let·[-t·;-]\n+ | let·{+t;+}\n 2| \n...\n 4| \n-5| let·[-u·;-]\n+ | let·{+u;+}",
         );
     }
 
     #[test]
-    fn it_should_keep_grouped_when_changes_only_separated_by_one_line() {
+    fn it_should_keep_changes_together_when_within_context() {
         assert_eq!(
-            get_difference("let t ;\ntest;\nlet u ;\n", "let t;\ntest;\nlet u;\n").unwrap(),
-            format!(
-                "{}\n{}\n{}",
-                format!("1| let\u{00B7}t{};", get_removal_text("\u{00B7}")),
-                " | test;",
-                format!(" | let\u{00B7}u{};", get_removal_text("\u{00B7}")),
-            )
+            get_plain_difference_with_context("let t ;\ntest;\nlet u ;\n", "let t;\ntest;\nlet u;\n", 1),
+            "-1| let·[-t·;-]\n+ | let·{+t;+}\n 2| test;\n-3| let·[-u·;-]\n+ | let·{+u;+}",
+        );
+    }
+
+    #[test]
+    fn it_should_show_visible_whitespace_and_tabs() {
+        assert_eq!(get_plain_difference("\tcall( a );\n", "  call(a);\n"), "-1| →call(·a·);\n+ | ··call(a);");
+    }
+
+    #[test]
+    fn it_should_handle_file_without_trailing_newline() {
+        assert_eq!(
+            get_plain_difference("testing\ntesting", "testing\ntesting\n"),
+            " 1| testing\n-2| testing\n  | \\ No newline at end of file\n+ | testing",
+        );
+        assert_eq!(
+            get_plain_difference("a\nb\n", "a\nc"),
+            " 1| a\n-2| b\n+ | c\n  | \\ No newline at end of file",
         );
     }
 
     #[test]
-    fn it_should_annotate_whitespace_end_line_text() {
+    fn it_should_show_carriage_returns_when_line_endings_differ() {
         assert_eq!(
-            get_difference("t t t\n", "tt t\n").unwrap(),
+            get_plain_difference("a\r\nb \r\nc\r\n", "a\nb\nc\n"),
+            "-1| a␍\n-2| b[-·-]␍\n-3| c␍\n+ | a\n+ | b\n+ | c",
+        );
+    }
+
+    #[test]
+    fn it_should_not_show_carriage_returns_when_line_endings_same() {
+        assert_eq!(get_plain_difference("a\r\nb \r\n", "a\r\nb\r\n"), " 1| a\n-2| b[-·-]\n+ | b");
+    }
+
+    #[test]
+    fn it_should_collapse_unchanged_parts_of_long_lines() {
+        let original_text = format!("{} middle {}\n", "a".repeat(200), "b".repeat(200));
+        let formatted_text = format!("{} changed {}\n", "a".repeat(200), "b".repeat(200));
+        assert_eq!(
+            get_plain_difference(&original_text, &formatted_text),
             format!(
-                "1| t{}t\u{00B7}t",
-                get_removal_text("\u{00B7}")
-            )
+                "-1| …{}·[-middle-]·{}…\n+ | …{}·{{+changed+}}·{}…",
+                "a".repeat(19),
+                "b".repeat(19),
+                "a".repeat(19),
+                "b".repeat(19),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_truncate_long_changed_lines() {
+        let original_text = format!("{}\n", "a".repeat(500));
+        let formatted_text = format!("{}\n", "b".repeat(500));
+        assert_eq!(
+            get_plain_difference(&original_text, &formatted_text),
+            format!("-1| [-{0}…{0}-]\n+ | {{+{1}…{1}+}}", "a".repeat(60), "b".repeat(60)),
+        );
+    }
+
+    #[test]
+    fn it_should_handle_many_changes() {
+        let original_text = (0..2000).map(|i| format!("line {}\n", i)).collect::<String>();
+        let formatted_text = (0..2000).map(|i| if i % 10 == 0 { format!("changed {}\n", i) } else { format!("line {}\n", i) }).collect::<String>();
+        let text = get_plain_difference_with_context(&original_text, &formatted_text, 0);
+        assert_eq!(text.lines().filter(|line| *line == "...").count(), 199);
+        assert_eq!(text.lines().last().unwrap(), "+    | {+changed+}·1990");
+    }
+
+    #[test]
+    fn it_should_snapshot_long_lines() {
+        assert_difference_snapshot(
+            "long_lines",
+            &format!("short\n{} middle {}\n{}\nend\n", "a".repeat(200), "b".repeat(200), "c".repeat(500)),
+            &format!("short\n{} changed {}\n{}\nend\n", "a".repeat(200), "b".repeat(200), "d".repeat(500)),
         );
     }
 
+    #[test]
+    fn it_should_snapshot_original_without_trailing_newline() {
+        assert_difference_snapshot("original_without_trailing_newline", "a\nb\nc", "a\nb\nc\n");
+    }
+
+    #[test]
+    fn it_should_snapshot_formatted_without_trailing_newline() {
+        assert_difference_snapshot("formatted_without_trailing_newline", "a\nb\nc\n", "a\nB\nc");
+    }
+
+    #[test]
+    fn it_should_snapshot_mixed_line_endings() {
+        assert_difference_snapshot("mixed_line_endings", "a\r\nb\nc \r\nd\n", "a\r\nb\r\nc\r\nd\r\n");
+    }
+
     #[test]
     fn it_should_handle_replacements() {
         assert_eq!(
-            get_difference("use::asdf\nuse::test", "use::other\nsomething").unwrap(),
-            format!(
-                "1| use::{}{}\n | {}{}",
-                get_removal_text("asdf"),
-                get_addition_text("other"),
-                get_removal_text("use::test"),
-                get_addition_text("something")
-            )
+            get_plain_difference("use::asdf\nuse::test", "use::other\nsomething"),
+            "-1| use::asdf\n-2| use::test\n  | \\ No newline at end of file\n+ | use::other\n+ | something\n  | \\ No newline at end of file",
         );
     }
 }
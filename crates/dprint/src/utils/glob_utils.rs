@@ -1,5 +1,9 @@
 use std::borrow::Cow;
 
+/// File name of the ignore files that dprint will always respect when collecting
+/// files. These use the same syntax as `.gitignore` files.
+pub const DPRINT_IGNORE_FILE_NAME: &str = ".dprintignore";
+
 // Adapted from https://github.com/dsherret/ts-morph/blob/0f8a77a9fa9d74e32f88f36992d527a2f059c6ac/packages/common/src/fileSystem/FileUtils.ts#L272
 
 pub fn to_absolute_glob(pattern: &str, dir: &str) -> String {
@@ -1,3 +1,4 @@
+mod atomic_write_file;
 mod error_count_logger;
 mod extract_zip;
 mod file_path_utils;
@@ -8,10 +9,13 @@ mod resolve_url_or_file_path;
 mod pretty_print_json_text;
 mod path_source;
 mod reset_events;
+mod shebang;
 mod table_text;
 mod glob_utils;
 mod thread_exit_signal;
+mod version_utils;
 
+pub use atomic_write_file::*;
 pub use error_count_logger::*;
 pub use extract_zip::*;
 pub use file_path_utils::*;
@@ -21,7 +25,9 @@ pub use get_bytes_hash::*;
 pub use resolve_url_or_file_path::*;
 pub use pretty_print_json_text::*;
 pub use path_source::*;
+pub use shebang::*;
 pub use table_text::*;
 pub use glob_utils::*;
 pub use reset_events::*;
 pub use thread_exit_signal::*;
+pub use version_utils::*;
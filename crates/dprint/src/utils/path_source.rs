@@ -75,6 +75,10 @@ impl PathSource {
     pub fn is_process_plugin(&self) -> bool {
         self.display().to_lowercase().ends_with(".exe-plugin")
     }
+
+    pub fn is_native_plugin(&self) -> bool {
+        self.display().to_lowercase().ends_with(".native-plugin")
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -69,7 +69,7 @@ fn resolve_url<TEnvironment : Environment>(
     cache: &Cache<TEnvironment>,
     environment: &TEnvironment,
 ) -> Result<ResolvedPath, ErrBox> {
-    let cache_key = format!("url:{}", url.as_str());
+    let cache_key = get_url_cache_key(url);
     let mut is_first_download = false;
 
     let cache_item = if let Some(cache_item) = cache.get_cache_item(&cache_key) {
@@ -89,6 +89,11 @@ fn resolve_url<TEnvironment : Environment>(
     Ok(ResolvedPath::remote(cache.resolve_cache_item_file_path(&cache_item), url.clone(), is_first_download))
 }
 
+/// Gets the key a downloaded file is stored with in the cache.
+pub fn get_url_cache_key(url: &Url) -> String {
+    format!("url:{}", url.as_str())
+}
+
 pub fn fetch_file_or_url_bytes(
     url_or_file_path: &PathSource,
     environment: &impl Environment
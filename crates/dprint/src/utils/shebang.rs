@@ -0,0 +1,89 @@
+/// Interpreters mapped to the file extension of the language they run. These are
+/// used for files without an extension when no plugin or configuration says otherwise.
+pub const DEFAULT_INTERPRETER_EXTENSIONS: [(&str, &str); 17] = [
+    ("node", "js"),
+    ("nodejs", "js"),
+    ("zx", "mjs"),
+    ("deno", "ts"),
+    ("ts-node", "ts"),
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("dash", "sh"),
+    ("ksh", "sh"),
+    ("zsh", "sh"),
+    ("python", "py"),
+    ("ruby", "rb"),
+    ("perl", "pl"),
+    ("php", "php"),
+    ("lua", "lua"),
+    ("pwsh", "ps1"),
+    ("fish", "fish"),
+];
+
+/// Gets the lowercase interpreter name from the shebang of a file's first line.
+///
+/// For example, `node` is returned for both `#!/usr/local/bin/node` and `#!/usr/bin/env -S node --flag`.
+pub fn get_shebang_interpreter(first_line: &str) -> Option<String> {
+    let first_line = first_line.trim_start_matches('\u{FEFF}');
+    if !first_line.starts_with("#!") {
+        return None;
+    }
+
+    let mut parts = first_line[2..].split_whitespace();
+    let program = get_path_file_name(parts.next()?);
+    let interpreter = if program == "env" {
+        // skip over any flags (ex. `-S`) or environment variable assignments
+        get_path_file_name(parts.find(|part| !part.starts_with('-') && !part.contains('='))?)
+    } else {
+        program
+    };
+
+    if interpreter.is_empty() {
+        None
+    } else {
+        Some(interpreter.to_lowercase())
+    }
+}
+
+/// Removes any version from the end of an interpreter name (ex. `python3.9` to `python`).
+pub fn strip_interpreter_version(interpreter: &str) -> &str {
+    interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+}
+
+fn get_path_file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_get_interpreter_from_direct_path() {
+        assert_eq!(get_shebang_interpreter("#!/bin/sh"), Some("sh".to_string()));
+        assert_eq!(get_shebang_interpreter("#! /usr/local/bin/node --harmony"), Some("node".to_string()));
+    }
+
+    #[test]
+    fn it_should_get_interpreter_from_env() {
+        assert_eq!(get_shebang_interpreter("#!/usr/bin/env node"), Some("node".to_string()));
+        assert_eq!(get_shebang_interpreter("#!/usr/bin/env -S deno run --allow-read"), Some("deno".to_string()));
+        assert_eq!(get_shebang_interpreter("#!/usr/bin/env NODE_ENV=production node"), Some("node".to_string()));
+        assert_eq!(get_shebang_interpreter("\u{FEFF}#!/usr/bin/env Python3"), Some("python3".to_string()));
+    }
+
+    #[test]
+    fn it_should_not_get_interpreter_when_not_shebang() {
+        assert_eq!(get_shebang_interpreter(""), None);
+        assert_eq!(get_shebang_interpreter("# comment"), None);
+        assert_eq!(get_shebang_interpreter("#!"), None);
+        assert_eq!(get_shebang_interpreter("#!/usr/bin/env"), None);
+        assert_eq!(get_shebang_interpreter("#!/usr/bin/env -S"), None);
+    }
+
+    #[test]
+    fn it_should_strip_interpreter_version() {
+        assert_eq!(strip_interpreter_version("python3.9"), "python");
+        assert_eq!(strip_interpreter_version("node"), "node");
+    }
+}
@@ -0,0 +1,49 @@
+use std::cmp::Ordering;
+
+/// Compares two versions (ex. "0.15.0") by their numeric components.
+///
+/// Missing or non-numeric components are treated as zero and any
+/// pre-release or build metadata (ex. "-alpha") is ignored.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let a = get_version_components(a);
+    let b = get_version_components(b);
+    for i in 0..std::cmp::max(a.len(), b.len()) {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+fn get_version_components(version: &str) -> Vec<u64> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(|c| c == '-' || c == '+').next().unwrap();
+    version.split('.').map(|part| part.parse::<u64>().unwrap_or(0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_compare_versions() {
+        assert_eq!(compare_versions("0.15.0", "0.15.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.15.0", "0.16.0"), Ordering::Less);
+        assert_eq!(compare_versions("0.15.1", "0.15.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.0", "0.99.99"), Ordering::Greater);
+        assert_eq!(compare_versions("0.9.0", "0.10.0"), Ordering::Less);
+    }
+
+    #[test]
+    fn it_should_treat_missing_components_as_zero() {
+        assert_eq!(compare_versions("1.2", "1.2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1", "1.0.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn it_should_ignore_pre_release_and_build_metadata() {
+        assert_eq!(compare_versions("v1.2.3-alpha.1", "1.2.3"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2.3+build", "1.2.3"), Ordering::Equal);
+    }
+}
@@ -7,7 +7,7 @@ use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 use dprint_core::generate_plugin_code;
 use dprint_core::types::ErrBox;
-use dprint_core::plugins::{PluginHandler, PluginInfo};
+use dprint_core::plugins::{PluginHandler, PluginInfo, FormatTextResult, FormatDiagnostic, FormatDiagnosticRange, FormatDiagnosticSeverity};
 use dprint_core::configuration::{GlobalConfiguration, ResolveConfigurationResult, get_unknown_property_diagnostics, ConfigKeyMap, get_value};
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -51,6 +51,8 @@ impl PluginHandler<Configuration> for TestWasmPlugin {
             config_key: "test-plugin".to_string(),
             file_extensions: vec!["txt".to_string()],
             file_names: vec![],
+            interpreters: vec![],
+            languages: vec!["test-plugin-language".to_string()],
             help_url: "https://dprint.dev/plugins/test".to_string(),
             config_schema_url: "https://plugins.dprint.dev/schemas/test.json".to_string()
         }
@@ -65,16 +67,20 @@ impl PluginHandler<Configuration> for TestWasmPlugin {
         _: &Path,
         file_text: &str,
         config: &Configuration,
-        mut format_with_host: impl FnMut(&Path, String, &ConfigKeyMap) -> Result<String, ErrBox>,
+        mut format_with_host: impl FnMut(&Path, Option<&str>, String, &ConfigKeyMap) -> Result<String, ErrBox>,
     ) -> Result<String, ErrBox> {
         if self.has_panicked {
             panic!("Previously panicked. Plugin should not have been used by the CLI again.")
         } else if file_text.starts_with("plugin: ") {
-            format_with_host(&PathBuf::from("./test.txt_ps"), file_text.replace("plugin: ", ""), &HashMap::new())
+            format_with_host(&PathBuf::from("./test.txt_ps"), None, file_text.replace("plugin: ", ""), &HashMap::new())
+        } else if file_text.starts_with("plugin-language: ") {
+            format_with_host(&PathBuf::from("./test.md"), Some("test-process-plugin-language"), file_text.replace("plugin-language: ", ""), &HashMap::new())
+        } else if file_text.starts_with("plugin-unknown-language: ") {
+            format_with_host(&PathBuf::from("./test.txt_ps"), Some("unknown-language"), file_text.replace("plugin-unknown-language: ", ""), &HashMap::new())
         } else if file_text.starts_with("plugin-config: ") {
             let mut config_map = HashMap::new();
             config_map.insert("ending".to_string(), "custom_config".into());
-            format_with_host(&PathBuf::from("./test.txt_ps"), file_text.replace("plugin-config: ", ""), &config_map)
+            format_with_host(&PathBuf::from("./test.txt_ps"), None, file_text.replace("plugin-config: ", ""), &config_map)
         } else if file_text == "should_error" {
             err!("Did error.")
         } else if file_text == "should_panic" {
@@ -86,6 +92,32 @@ impl PluginHandler<Configuration> for TestWasmPlugin {
             Ok(format!("{}_{}", file_text, config.ending))
         }
     }
+
+    fn format_text_with_diagnostics(
+        &mut self,
+        file_path: &Path,
+        file_text: &str,
+        config: &Configuration,
+        format_with_host: impl FnMut(&Path, Option<&str>, String, &ConfigKeyMap) -> Result<String, ErrBox>,
+    ) -> Result<FormatTextResult, ErrBox> {
+        let text = self.format_text(file_path, file_text, config, format_with_host)?;
+        let mut diagnostics = Vec::new();
+        if let Some(start) = file_text.find("diagnostic_warning") {
+            diagnostics.push(FormatDiagnostic {
+                severity: FormatDiagnosticSeverity::Warning,
+                message: "Found a warning.".to_string(),
+                range: Some(FormatDiagnosticRange { start, end: start + "diagnostic_warning".len() }),
+            });
+        }
+        if file_text.contains("diagnostic_error") {
+            diagnostics.push(FormatDiagnostic {
+                severity: FormatDiagnosticSeverity::Error,
+                message: "Found an error.".to_string(),
+                range: None,
+            });
+        }
+        Ok(FormatTextResult { text, diagnostics })
+    }
 }
 
 generate_plugin_code!(TestWasmPlugin, TestWasmPlugin::new());
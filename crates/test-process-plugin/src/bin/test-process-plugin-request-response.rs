@@ -0,0 +1,13 @@
+use dprint_core::types::ErrBox;
+use dprint_core::plugins::process::{get_parent_process_id_from_cli_args, handle_process_stdio_messages, start_parent_process_checker_thread};
+use test_process_plugin::TestProcessPluginHandler;
+
+/// The test plugin, but only supporting the request/response mode of the protocol.
+fn main() -> Result<(), ErrBox> {
+    let name = "test-process-plugin-request-response";
+    if let Some(parent_process_id) = get_parent_process_id_from_cli_args() {
+        start_parent_process_checker_thread(String::from(name), parent_process_id);
+    }
+
+    handle_process_stdio_messages(TestProcessPluginHandler::with_name(name))
+}
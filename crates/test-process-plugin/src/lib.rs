@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Serialize, Deserialize};
+
+use dprint_core::configuration::{GlobalConfiguration, ResolveConfigurationResult, get_unknown_property_diagnostics, ConfigKeyMap, get_value};
+use dprint_core::{err_obj, err};
+use dprint_core::types::ErrBox;
+use dprint_core::plugins::{PluginHandler, PluginInfo, FormatTextResult, FormatDiagnostic, FormatDiagnosticRange, FormatDiagnosticSeverity, CancellationToken};
+
+/// The number of "plugin-concurrent: " requests currently being formatted by the process.
+static CONCURRENT_REQUEST_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Configuration {
+    ending: String,
+    line_width: u32,
+}
+
+pub struct TestProcessPluginHandler {
+    name: &'static str,
+    cancellation_token: CancellationToken,
+}
+
+impl TestProcessPluginHandler {
+    pub fn new() -> Self {
+        TestProcessPluginHandler::with_name(env!("CARGO_PKG_NAME"))
+    }
+
+    /// Creates a handler whose plugin has the provided name, which is also the executable's file name.
+    pub fn with_name(name: &'static str) -> Self {
+        TestProcessPluginHandler {
+            name,
+            cancellation_token: CancellationToken::new(),
+        }
+    }
+}
+
+impl PluginHandler<Configuration> for TestProcessPluginHandler {
+    fn get_plugin_info(&mut self) -> PluginInfo {
+        PluginInfo {
+            name: String::from(self.name),
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            config_key: "testProcessPlugin".to_string(),
+            file_extensions: vec!["txt_ps".to_string()],
+            file_names: vec!["test-process-plugin-exact-file".to_string()],
+            interpreters: vec!["dprint-test-process".to_string()],
+            languages: vec!["test-process-plugin-language".to_string()],
+            help_url: "https://dprint.dev/plugins/test-process".to_string(),
+            config_schema_url: "".to_string()
+        }
+    }
+
+    fn get_license_text(&mut self) -> String {
+        "License text.".to_string()
+    }
+
+    fn resolve_config(&mut self, config: ConfigKeyMap, global_config: &GlobalConfiguration) -> ResolveConfigurationResult<Configuration> {
+        let mut config = config;
+        let mut diagnostics = Vec::new();
+        let ending = get_value(&mut config, "ending", String::from("formatted_process"), &mut diagnostics);
+        let line_width = get_value(&mut config, "line_width", global_config.line_width.unwrap_or(120), &mut diagnostics);
+
+        diagnostics.extend(get_unknown_property_diagnostics(config));
+
+        ResolveConfigurationResult {
+            config: Configuration { ending, line_width },
+            diagnostics,
+        }
+    }
+
+    fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation_token = token;
+    }
+
+    fn format_text(
+        &mut self,
+        _: &Path,
+        file_text: &str,
+        config: &Configuration,
+        mut format_with_host: impl FnMut(&Path, Option<&str>, String, &ConfigKeyMap) -> Result<String, ErrBox>,
+    ) -> Result<String, ErrBox> {
+        if file_text.starts_with("plugin: ") {
+            format_with_host(&PathBuf::from("./test.txt"), None, file_text.replace("plugin: ", ""), &HashMap::new())
+        } else if file_text.starts_with("plugin-language: ") {
+            format_with_host(&PathBuf::from("./test.md"), Some("test-plugin-language"), file_text.replace("plugin-language: ", ""), &HashMap::new())
+        } else if file_text.starts_with("plugin-config: ") {
+            let mut config_map = HashMap::new();
+            config_map.insert("ending".to_string(), "custom_config".into());
+            format_with_host(&PathBuf::from("./test.txt"), None, file_text.replace("plugin-config: ", ""), &config_map)
+        } else if file_text.starts_with("plugin-concurrent: ") {
+            // wait for another request to be in flight so the host format requests overlap
+            CONCURRENT_REQUEST_COUNT.fetch_add(1, Ordering::SeqCst);
+            for _ in 0..100 {
+                if CONCURRENT_REQUEST_COUNT.load(Ordering::SeqCst) > 1 {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            let result = format_with_host(&PathBuf::from("./test.txt"), None, file_text.replace("plugin-concurrent: ", ""), &HashMap::new());
+            CONCURRENT_REQUEST_COUNT.fetch_sub(1, Ordering::SeqCst);
+            Ok(format!("{}_{}", result?, std::process::id()))
+        } else if file_text == "should_error" {
+            err!("Did error.")
+        } else if file_text == "wait_cancellation" {
+            for _ in 0..1000 {
+                if self.cancellation_token.is_cancelled() {
+                    return err!("Cancelled.");
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            err!("Was not cancelled.")
+        } else if file_text.ends_with(&config.ending) {
+            Ok(String::from(file_text))
+        } else {
+            Ok(format!("{}_{}", file_text, config.ending))
+        }
+    }
+
+    fn format_text_with_diagnostics(
+        &mut self,
+        file_path: &Path,
+        file_text: &str,
+        config: &Configuration,
+        format_with_host: impl FnMut(&Path, Option<&str>, String, &ConfigKeyMap) -> Result<String, ErrBox>,
+    ) -> Result<FormatTextResult, ErrBox> {
+        let text = self.format_text(file_path, file_text, config, format_with_host)?;
+        let mut diagnostics = Vec::new();
+        if let Some(start) = file_text.find("diagnostic_warning") {
+            diagnostics.push(FormatDiagnostic {
+                severity: FormatDiagnosticSeverity::Warning,
+                message: "Found a warning.".to_string(),
+                range: Some(FormatDiagnosticRange { start, end: start + "diagnostic_warning".len() }),
+            });
+        }
+        if file_text.contains("diagnostic_error") {
+            diagnostics.push(FormatDiagnostic {
+                severity: FormatDiagnosticSeverity::Error,
+                message: "Found an error.".to_string(),
+                range: None,
+            });
+        }
+        Ok(FormatTextResult { text, diagnostics })
+    }
+}
@@ -0,0 +1,2 @@
+#!/bin/bash
+cargo build --release --target=wasm32-wasi
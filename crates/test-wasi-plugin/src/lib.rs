@@ -0,0 +1,137 @@
+#[macro_use(err_obj)]
+#[macro_use(err)]
+extern crate dprint_core;
+
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use dprint_core::generate_plugin_code;
+use dprint_core::types::ErrBox;
+use dprint_core::plugins::{PluginHandler, PluginInfo};
+use dprint_core::configuration::{GlobalConfiguration, ResolveConfigurationResult, get_unknown_property_diagnostics, ConfigKeyMap, get_value};
+
+// The WASI functions are declared here rather than going through std so that this builds
+// for wasm32-unknown-unknown when the wasm32-wasi target isn't installed. The resulting
+// module imports these from `wasi_snapshot_preview1` the same as a wasm32-wasi module does.
+mod wasi {
+    #[repr(C)]
+    pub struct Ciovec {
+        pub buf: *const u8,
+        pub buf_len: usize,
+    }
+
+    pub const ERRNO_BADF: u16 = 8;
+
+    #[link(wasm_import_module = "wasi_snapshot_preview1")]
+    extern "C" {
+        pub fn fd_write(fd: u32, iovs: *const Ciovec, iovs_len: usize, nwritten: *mut usize) -> u16;
+        pub fn fd_prestat_get(fd: u32, prestat: *mut u8) -> u16;
+        pub fn environ_sizes_get(count: *mut usize, buf_size: *mut usize) -> u16;
+        pub fn args_sizes_get(count: *mut usize, buf_size: *mut usize) -> u16;
+        pub fn random_get(buf: *mut u8, buf_len: usize) -> u16;
+        pub fn sock_accept(fd: u32, flags: u16, result_fd: *mut u32) -> u16;
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Configuration {
+    ending: String,
+    line_width: u32,
+}
+
+struct TestWasiPlugin;
+
+impl PluginHandler<Configuration> for TestWasiPlugin {
+    fn resolve_config(&mut self, config: ConfigKeyMap, global_config: &GlobalConfiguration) -> ResolveConfigurationResult<Configuration> {
+        let mut config = config;
+        let mut diagnostics = Vec::new();
+        let ending = get_value(&mut config, "ending", String::from("formatted_wasi"), &mut diagnostics);
+        let line_width = get_value(&mut config, "line_width", global_config.line_width.unwrap_or(120), &mut diagnostics);
+
+        diagnostics.extend(get_unknown_property_diagnostics(config));
+
+        ResolveConfigurationResult {
+            config: Configuration { ending, line_width },
+            diagnostics,
+        }
+    }
+
+    fn get_plugin_info(&mut self) -> PluginInfo {
+        PluginInfo {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config_key: "testWasiPlugin".to_string(),
+            file_extensions: vec!["txt_wasi".to_string()],
+            file_names: vec![],
+            interpreters: vec![],
+            languages: vec![],
+            help_url: "https://dprint.dev/plugins/test-wasi".to_string(),
+            config_schema_url: "".to_string()
+        }
+    }
+
+    fn get_license_text(&mut self) -> String {
+        std::str::from_utf8(include_bytes!("../LICENSE")).unwrap().into()
+    }
+
+    fn format_text(
+        &mut self,
+        _: &Path,
+        file_text: &str,
+        config: &Configuration,
+        _: impl FnMut(&Path, Option<&str>, String, &ConfigKeyMap) -> Result<String, ErrBox>,
+    ) -> Result<String, ErrBox> {
+        if let Some(text) = file_text.strip_prefix("stdout: ") {
+            write_fd(1, &format!("{}\n", text))?;
+            Ok(text.to_string())
+        } else if let Some(text) = file_text.strip_prefix("stderr: ") {
+            // written in two parts to ensure the host buffers until the end of the line
+            write_fd(2, text)?;
+            write_fd(2, "\n")?;
+            Ok(text.to_string())
+        } else if file_text == "sandbox" {
+            Ok(get_sandbox_description())
+        } else if file_text.ends_with(&config.ending) {
+            Ok(String::from(file_text))
+        } else {
+            Ok(format!("{}_{}", file_text, config.ending))
+        }
+    }
+}
+
+fn write_fd(fd: u32, text: &str) -> Result<(), ErrBox> {
+    let iovec = wasi::Ciovec {
+        buf: text.as_ptr(),
+        buf_len: text.len(),
+    };
+    let mut written = 0;
+    let errno = unsafe { wasi::fd_write(fd, &iovec, 1, &mut written) };
+    if errno != 0 {
+        return err!("Error writing to {}: {}", fd, errno);
+    }
+    Ok(())
+}
+
+fn get_sandbox_description() -> String {
+    let mut prestat = [0u8; 8];
+    let preopen_errno = unsafe { wasi::fd_prestat_get(3, prestat.as_mut_ptr()) };
+    let (mut env_count, mut env_size) = (0, 0);
+    unsafe { wasi::environ_sizes_get(&mut env_count, &mut env_size) };
+    let (mut args_count, mut args_size) = (0, 0);
+    unsafe { wasi::args_sizes_get(&mut args_count, &mut args_size) };
+    let mut random_bytes = [0u8; 8];
+    let random_errno = unsafe { wasi::random_get(random_bytes.as_mut_ptr(), random_bytes.len()) };
+    let mut socket_fd = 0;
+    let socket_errno = unsafe { wasi::sock_accept(3, 0, &mut socket_fd) };
+
+    format!(
+        "preopens: {}, env vars: {}, args: {}, random: {}, sockets: {}",
+        if preopen_errno == wasi::ERRNO_BADF { "none" } else { "some" },
+        env_count,
+        args_count,
+        if random_errno == 0 { "ok" } else { "error" },
+        if socket_errno == 0 { "some" } else { "none" },
+    )
+}
+
+generate_plugin_code!(TestWasiPlugin, TestWasiPlugin);
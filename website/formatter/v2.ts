@@ -78,6 +78,7 @@ export function createImportObject(): any /*: WebAssembly.Imports*/ {
             "host_read_buffer": () => {},
             "host_write_buffer": () => {},
             "host_take_file_path": () => {},
+            "host_take_language": () => {},
             "host_take_override_config": () => {},
             "host_format": () => 0, // no change
             "host_get_formatted_text": () => 0, // zero length